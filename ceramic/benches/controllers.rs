@@ -0,0 +1,21 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use dataverse_ceramic::StreamState;
+use serde_json::json;
+
+fn state() -> StreamState {
+	let mut state = StreamState::default();
+	state.set_metadata(json!({
+		"controllers": ["did:pkh:eip155:1:0x312eA852726E3A9f633A0377c0ea882086d66666"],
+		"model": "kjzl6hvfrbw6c86gt9j415yw2x8stmkotcrzpeutrbkp42i4z90gp5ibptz4sso"
+	}));
+	state
+}
+
+fn controllers(c: &mut Criterion) {
+	let state = state();
+	c.bench_function("account", |b| b.iter(|| black_box(state.account())));
+	c.bench_function("controllers", |b| b.iter(|| black_box(state.controllers())));
+}
+
+criterion_group!(benches, controllers);
+criterion_main!(benches);
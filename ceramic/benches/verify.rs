@@ -0,0 +1,34 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use dataverse_ceramic::commit::example;
+use dataverse_ceramic::Event;
+
+fn events(n: usize) -> Vec<Event> {
+	let event: Event = example::genesis()
+		.genesis
+		.try_into()
+		.expect("example genesis is an event");
+	vec![event; n]
+}
+
+/// sequential and batch verification of 10, 50 and 200 events,
+/// run with `cargo bench -p dataverse-ceramic --bench verify`
+fn verify(c: &mut Criterion) {
+	let mut group = c.benchmark_group("verify signatures");
+	for n in [10, 50, 200] {
+		let events = events(n);
+		group.bench_with_input(BenchmarkId::new("sequential", n), &events, |b, events| {
+			b.iter(|| {
+				for event in events {
+					black_box(event.verify_signature(vec![])).ok();
+				}
+			})
+		});
+		group.bench_with_input(BenchmarkId::new("batch", n), &events, |b, events| {
+			b.iter(|| black_box(Event::verify_signatures_batch(events, vec![])))
+		});
+	}
+	group.finish();
+}
+
+criterion_group!(benches, verify);
+criterion_main!(benches);
@@ -0,0 +1,47 @@
+//! Export spans of stream loading to an OTLP collector.
+//!
+//! ```sh
+//! cargo run -p dataverse-ceramic --example otlp --features opentelemetry -- \
+//!     https://gateway.ceramic.network <stream_id> http://localhost:4317
+//! ```
+use dataverse_ceramic::{http, Ceramic, StreamId, StreamLoader};
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::{propagation::TraceContextPropagator, runtime, trace, Resource};
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+	let mut args = std::env::args().skip(1);
+	let endpoint = args.next().unwrap_or("http://localhost:7007".into());
+	let stream_id: StreamId = args.next().expect("stream_id is required").parse()?;
+	let collector = args.next().unwrap_or("http://localhost:4317".into());
+
+	opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+	let tracer = opentelemetry_otlp::new_pipeline()
+		.tracing()
+		.with_exporter(
+			opentelemetry_otlp::new_exporter()
+				.tonic()
+				.with_endpoint(collector),
+		)
+		.with_trace_config(
+			trace::config().with_resource(Resource::new(vec![KeyValue::new(
+				"service.name",
+				"dataverse-ceramic-example",
+			)])),
+		)
+		.install_batch(runtime::Tokio)?;
+	tracing_subscriber::registry()
+		.with(tracing_opentelemetry::layer().with_tracer(tracer))
+		.init();
+
+	let ceramic = Ceramic::new(&endpoint).await?;
+	let state = http::Client::new()
+		.load_stream_state(&ceramic, &stream_id, None)
+		.await?;
+	println!("{}", state.content);
+
+	opentelemetry::global::shutdown_tracer_provider();
+	Ok(())
+}
@@ -0,0 +1,461 @@
+use std::collections::hash_map::RandomState;
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// consecutive errors after which an endpoint is skipped for `unhealthy_timeout`
+pub const MAX_CONSECUTIVE_ERRORS: u32 = 3;
+
+/// Whether `err` is a failure of the endpoint rather than an answer to the request:
+/// transport errors, timeouts and 5xx responses. Only those count against the health of an
+/// endpoint and are retried on the others, a 404 or another 4xx is the same on every node.
+pub fn is_endpoint_failure(err: &anyhow::Error) -> bool {
+	err.chain().any(|cause| {
+		if let Some(err) = cause.downcast_ref::<reqwest::Error>() {
+			return match err.status() {
+				Some(status) => status.is_server_error(),
+				None => err.is_connect() || err.is_timeout() || err.is_request() || err.is_body(),
+			};
+		}
+		cause.is::<std::io::Error>() || cause.is::<tokio::time::error::Elapsed>()
+	})
+}
+
+/// how the next endpoint of a pool is picked
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LbStrategy {
+	RoundRobin,
+	/// endpoint with the fewest calls in flight
+	LeastConnections,
+	Random,
+}
+
+/// health of an endpoint, updated after every call through the pool
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EndpointHealth {
+	pub endpoint: String,
+	pub last_response_time: Option<Duration>,
+	pub error_count: u64,
+	pub consecutive_errors: u32,
+	pub unhealthy_until: Option<Instant>,
+	pub in_flight: usize,
+}
+
+impl EndpointHealth {
+	pub fn is_healthy(&self, now: Instant) -> bool {
+		self.unhealthy_until.map_or(true, |until| now >= until)
+	}
+}
+
+/// Ceramic nodes serving the same network, calls go to a healthy node picked by `strategy`.
+///
+/// A failed call is retried on the other endpoints before its error is returned.
+#[derive(Debug)]
+pub struct EndpointPool {
+	strategy: LbStrategy,
+	unhealthy_timeout: Duration,
+	health: Mutex<Vec<EndpointHealth>>,
+	next: AtomicUsize,
+	/// held shared by calls and exclusively by `switch_endpoint`
+	switch: tokio::sync::RwLock<()>,
+}
+
+impl EndpointPool {
+	pub fn new(endpoints: Vec<String>, strategy: LbStrategy) -> Self {
+		let health = endpoints
+			.into_iter()
+			.map(|endpoint| EndpointHealth {
+				endpoint,
+				..Default::default()
+			})
+			.collect();
+		Self {
+			strategy,
+			unhealthy_timeout: Duration::from_secs(30),
+			health: Mutex::new(health),
+			next: AtomicUsize::new(0),
+			switch: tokio::sync::RwLock::new(()),
+		}
+	}
+
+	pub fn with_unhealthy_timeout(mut self, unhealthy_timeout: Duration) -> Self {
+		self.unhealthy_timeout = unhealthy_timeout;
+		self
+	}
+
+	pub fn health(&self) -> Vec<EndpointHealth> {
+		self.health.lock().unwrap().clone()
+	}
+
+	/// Replace endpoints of pool with `endpoint`, for rolling upgrades of nodes.
+	///
+	/// Waits for calls in flight to finish, calls made meanwhile wait for the switch. `check`
+	/// is run on `endpoint` before it's used, the pool is unchanged if the check fails.
+	pub async fn switch_endpoint<F, Fut>(&self, endpoint: String, check: F) -> anyhow::Result<()>
+	where
+		F: FnOnce(String) -> Fut,
+		Fut: Future<Output = anyhow::Result<()>>,
+	{
+		let _switch = self.switch.write().await;
+		check(endpoint.clone()).await?;
+		*self.health.lock().unwrap() = vec![EndpointHealth {
+			endpoint,
+			..Default::default()
+		}];
+		Ok(())
+	}
+
+	/// pick an endpoint not in `tried`, unhealthy ones only when no healthy one is left
+	fn pick(&self, tried: &[usize]) -> Option<(usize, String)> {
+		let mut health = self.health.lock().unwrap();
+		let now = Instant::now();
+		let untried: Vec<usize> = (0..health.len())
+			.filter(|idx| !tried.contains(idx))
+			.collect();
+		let healthy: Vec<usize> = untried
+			.iter()
+			.copied()
+			.filter(|idx| health[*idx].is_healthy(now))
+			.collect();
+		let candidates = match healthy.is_empty() {
+			true => untried,
+			false => healthy,
+		};
+		if candidates.is_empty() {
+			return None;
+		}
+		let idx = match self.strategy {
+			LbStrategy::RoundRobin => {
+				let next = self.next.fetch_add(1, Ordering::Relaxed);
+				candidates[next % candidates.len()]
+			}
+			LbStrategy::LeastConnections => *candidates
+				.iter()
+				.min_by_key(|idx| health[**idx].in_flight)
+				.expect("candidates not empty"),
+			LbStrategy::Random => {
+				let random = RandomState::new().build_hasher().finish() as usize;
+				candidates[random % candidates.len()]
+			}
+		};
+		health[idx].in_flight += 1;
+		Some((idx, health[idx].endpoint.clone()))
+	}
+
+	fn record(&self, idx: usize, elapsed: Duration, ok: bool) {
+		let mut health = self.health.lock().unwrap();
+		let endpoint = &mut health[idx];
+		endpoint.last_response_time = Some(elapsed);
+		if ok {
+			endpoint.consecutive_errors = 0;
+			endpoint.unhealthy_until = None;
+			return;
+		}
+		endpoint.error_count += 1;
+		endpoint.consecutive_errors += 1;
+		if endpoint.consecutive_errors >= MAX_CONSECUTIVE_ERRORS {
+			tracing::warn!(
+				endpoint = endpoint.endpoint,
+				errors = endpoint.consecutive_errors,
+				"mark ceramic endpoint unhealthy"
+			);
+			endpoint.unhealthy_until = Some(Instant::now() + self.unhealthy_timeout);
+		}
+	}
+
+	/// Call with endpoints of pool until one answers, see [`is_endpoint_failure`]. Other
+	/// errors are returned right away, the last endpoint failure if no endpoint answers.
+	pub async fn call<R, F, Fut>(&self, call: F) -> anyhow::Result<R>
+	where
+		F: Fn(String) -> Fut,
+		Fut: Future<Output = anyhow::Result<R>>,
+	{
+		let _switch = self.switch.read().await;
+		let mut tried = vec![];
+		let mut last_err = None;
+		while let Some((idx, endpoint)) = self.pick(&tried) {
+			tried.push(idx);
+			match self.call_endpoint(idx, endpoint, &call).await {
+				Err(err) if is_endpoint_failure(&err) => last_err = Some(err),
+				result => return result,
+			}
+		}
+		Err(last_err.unwrap_or_else(|| anyhow::anyhow!("no ceramic endpoint in pool")))
+	}
+
+	/// call with one endpoint of pool, for requests not safe to replay on another endpoint
+	pub async fn call_once<R, F, Fut>(&self, call: F) -> anyhow::Result<R>
+	where
+		F: Fn(String) -> Fut,
+		Fut: Future<Output = anyhow::Result<R>>,
+	{
+		let _switch = self.switch.read().await;
+		match self.pick(&[]) {
+			Some((idx, endpoint)) => self.call_endpoint(idx, endpoint, &call).await,
+			None => anyhow::bail!("no ceramic endpoint in pool"),
+		}
+	}
+
+	async fn call_endpoint<R, F, Fut>(
+		&self,
+		idx: usize,
+		endpoint: String,
+		call: &F,
+	) -> anyhow::Result<R>
+	where
+		F: Fn(String) -> Fut,
+		Fut: Future<Output = anyhow::Result<R>>,
+	{
+		let start = Instant::now();
+		let in_flight = InFlight { pool: self, idx };
+		let result = call(endpoint).await;
+		drop(in_flight);
+		let failed = matches!(&result, Err(err) if is_endpoint_failure(err));
+		self.record(idx, start.elapsed(), !failed);
+		result
+	}
+}
+
+/// serialized form of a pool, health of endpoints starts over once deserialized
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PoolConfig {
+	endpoints: Vec<String>,
+	strategy: LbStrategy,
+	unhealthy_timeout: Duration,
+}
+
+impl Serialize for EndpointPool {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		let config = PoolConfig {
+			endpoints: self
+				.health()
+				.into_iter()
+				.map(|health| health.endpoint)
+				.collect(),
+			strategy: self.strategy,
+			unhealthy_timeout: self.unhealthy_timeout,
+		};
+		config.serialize(serializer)
+	}
+}
+
+impl<'de> Deserialize<'de> for EndpointPool {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		let config = PoolConfig::deserialize(deserializer)?;
+		Ok(EndpointPool::new(config.endpoints, config.strategy)
+			.with_unhealthy_timeout(config.unhealthy_timeout))
+	}
+}
+
+/// serde of `Ceramic::pool`, so tasks carrying a `Ceramic` keep balancing once deserialized
+pub(crate) mod shared_pool {
+	use super::*;
+
+	pub fn serialize<S: Serializer>(
+		pool: &Option<Arc<EndpointPool>>,
+		serializer: S,
+	) -> Result<S::Ok, S::Error> {
+		pool.as_deref().serialize(serializer)
+	}
+
+	pub fn deserialize<'de, D: Deserializer<'de>>(
+		deserializer: D,
+	) -> Result<Option<Arc<EndpointPool>>, D::Error> {
+		Ok(Option::<EndpointPool>::deserialize(deserializer)?.map(Arc::new))
+	}
+}
+
+/// call counted in `in_flight` of endpoint until dropped, cancelled calls included
+struct InFlight<'a> {
+	pool: &'a EndpointPool,
+	idx: usize,
+}
+
+impl Drop for InFlight<'_> {
+	fn drop(&mut self) {
+		if let Ok(mut health) = self.pool.health.lock() {
+			health[self.idx].in_flight -= 1;
+		}
+	}
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+	use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+	use super::*;
+
+	fn pool(strategy: LbStrategy) -> EndpointPool {
+		let endpoints = vec!["http://bad".to_string(), "http://good".to_string()];
+		EndpointPool::new(endpoints, strategy)
+	}
+
+	async fn request(endpoint: String) -> anyhow::Result<String> {
+		if endpoint == "http://bad" {
+			return Err(std::io::Error::from(std::io::ErrorKind::ConnectionRefused).into());
+		}
+		Ok(endpoint)
+	}
+
+	#[tokio::test]
+	async fn requests_skip_failing_endpoint() -> anyhow::Result<()> {
+		for strategy in [
+			LbStrategy::RoundRobin,
+			LbStrategy::LeastConnections,
+			LbStrategy::Random,
+		] {
+			let pool = pool(strategy);
+			for _ in 0..10 {
+				assert_eq!(pool.call(request).await?, "http://good");
+			}
+			let health = pool.health();
+			assert_eq!(health[1].error_count, 0);
+			assert!(health[1].last_response_time.is_some());
+			assert!(health[0].error_count <= MAX_CONSECUTIVE_ERRORS as u64);
+			assert!(health.iter().all(|endpoint| endpoint.in_flight == 0));
+		}
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn unhealthy_endpoint_skipped_until_timeout() -> anyhow::Result<()> {
+		let pool = pool(LbStrategy::RoundRobin).with_unhealthy_timeout(Duration::ZERO);
+		for _ in 0..10 {
+			pool.call(request).await?;
+		}
+		// zero timeout makes the endpoint healthy again right away
+		assert!(pool.health()[0].error_count > MAX_CONSECUTIVE_ERRORS as u64);
+
+		let pool = EndpointPool::new(vec!["http://bad".to_string()], LbStrategy::RoundRobin);
+		assert!(pool.call(request).await.is_err());
+		Ok(())
+	}
+
+	/// http server answering every request with `status`, returns its url
+	pub(crate) async fn serve(status: u16) -> anyhow::Result<String> {
+		let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+		let url = format!("http://{}", listener.local_addr()?);
+		tokio::spawn(async move {
+			while let Ok((mut socket, _)) = listener.accept().await {
+				let mut request = [0; 1024];
+				let _ = socket.read(&mut request).await;
+				let response = format!(
+					"HTTP/1.1 {} Mock\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+					status
+				);
+				let _ = socket.write_all(response.as_bytes()).await;
+			}
+		});
+		Ok(url)
+	}
+
+	/// url of a port nothing listens on
+	pub(crate) async fn down() -> anyhow::Result<String> {
+		let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+		Ok(format!("http://{}", listener.local_addr()?))
+	}
+
+	async fn get(endpoint: String, calls: &AtomicUsize) -> anyhow::Result<String> {
+		calls.fetch_add(1, Ordering::SeqCst);
+		reqwest::get(&endpoint).await?.error_for_status()?;
+		Ok(endpoint)
+	}
+
+	#[tokio::test]
+	async fn retry_endpoint_failures_of_http_pool() -> anyhow::Result<()> {
+		let ok = serve(200).await?;
+		let endpoints = vec![down().await?, serve(503).await?, ok.clone()];
+		let pool = EndpointPool::new(endpoints, LbStrategy::RoundRobin);
+		let calls = AtomicUsize::new(0);
+		for _ in 0..3 {
+			assert_eq!(pool.call(|endpoint| get(endpoint, &calls)).await?, ok);
+		}
+		let health = pool.health();
+		assert!(health[0].error_count > 0);
+		assert!(health[1].error_count > 0);
+		assert_eq!(health[2].error_count, 0);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn not_retry_application_errors() -> anyhow::Result<()> {
+		let endpoints = vec![serve(404).await?, serve(404).await?];
+		let pool = EndpointPool::new(endpoints, LbStrategy::RoundRobin);
+		let calls = AtomicUsize::new(0);
+		for _ in 0..MAX_CONSECUTIVE_ERRORS + 1 {
+			assert!(pool.call(|endpoint| get(endpoint, &calls)).await.is_err());
+		}
+		assert_eq!(
+			calls.load(Ordering::SeqCst),
+			MAX_CONSECUTIVE_ERRORS as usize + 1
+		);
+		assert!(pool
+			.health()
+			.iter()
+			.all(|endpoint| endpoint.error_count == 0 && endpoint.unhealthy_until.is_none()));
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn call_once_not_replayed() -> anyhow::Result<()> {
+		let endpoints = vec![down().await?, serve(200).await?];
+		let pool = EndpointPool::new(endpoints, LbStrategy::RoundRobin);
+		let calls = AtomicUsize::new(0);
+		assert!(pool
+			.call_once(|endpoint| get(endpoint, &calls))
+			.await
+			.is_err());
+		assert_eq!(calls.load(Ordering::SeqCst), 1);
+		assert_eq!(pool.health()[0].error_count, 1);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn switch_endpoint_after_calls_in_flight() -> anyhow::Result<()> {
+		let pool = Arc::new(pool(LbStrategy::RoundRobin));
+		let (release, released) = tokio::sync::oneshot::channel::<()>();
+		let in_flight = tokio::spawn({
+			let pool = pool.clone();
+			async move {
+				pool.call_once(|endpoint| async move {
+					let _ = released.await;
+					Ok(endpoint)
+				})
+				.await
+			}
+		});
+		while pool.health().iter().all(|endpoint| endpoint.in_flight == 0) {
+			tokio::task::yield_now().await;
+		}
+
+		let switch = tokio::spawn({
+			let pool = pool.clone();
+			async move {
+				pool.switch_endpoint("http://new".into(), |_| async { Ok(()) })
+					.await
+			}
+		});
+		tokio::time::sleep(Duration::from_millis(50)).await;
+		assert!(!switch.is_finished());
+		assert_eq!(pool.health().len(), 2);
+
+		release.send(()).ok();
+		in_flight.await??;
+		switch.await??;
+		assert_eq!(pool.call(request).await?, "http://new");
+
+		let failed = pool
+			.switch_endpoint("http://bad".into(), |endpoint| async move {
+				anyhow::bail!("{} is down", endpoint)
+			})
+			.await;
+		assert!(failed.is_err());
+		assert_eq!(pool.health()[0].endpoint, "http://new");
+		Ok(())
+	}
+}
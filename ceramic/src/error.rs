@@ -0,0 +1,103 @@
+use std::time::Duration;
+
+use ceramic_core::{Cid, StreamId};
+
+/// Errors callers may tell apart, returned inside `anyhow::Error`.
+///
+/// anyhow converts them with `?`, match on the kind with
+/// `err.downcast_ref::<DataverseError>()`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DataverseError {
+	StreamNotFound(StreamId),
+	/// model of stream does not belong to the dapp
+	ModelMismatch {
+		stream_id: StreamId,
+		model_id: StreamId,
+	},
+	SignatureVerification(String),
+	DappNotFound(uuid::Uuid),
+	/// anchor commits are created by ceramic nodes, they cannot be saved in a batch
+	AnchorNotSupported,
+	/// event is not in the chain of stream
+	EventNotFound {
+		stream_id: StreamId,
+		cid: Cid,
+	},
+	/// stream id computed from genesis is not the expected one
+	GenesisMismatch {
+		stream_id: StreamId,
+		genesis_stream_id: StreamId,
+	},
+	/// event cid not belongs to any known stream
+	UnknownCid(Cid),
+	/// stream was not anchored before timeout, the anchor may still land later
+	AnchorTimeout {
+		stream_id: StreamId,
+		timeout: Duration,
+	},
+}
+
+impl std::fmt::Display for DataverseError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Self::StreamNotFound(stream_id) => write!(f, "stream {} not found", stream_id),
+			Self::ModelMismatch {
+				stream_id,
+				model_id,
+			} => write!(
+				f,
+				"stream {} with model {} not belong to dapp",
+				stream_id, model_id
+			),
+			Self::SignatureVerification(err) => write!(f, "invalid signature: {}", err),
+			Self::DappNotFound(dapp_id) => write!(f, "dapp {} not found", dapp_id),
+			Self::AnchorNotSupported => write!(f, "anchor commit not supported"),
+			Self::EventNotFound { stream_id, cid } => {
+				write!(f, "event {} not found in stream {}", cid, stream_id)
+			}
+			Self::GenesisMismatch {
+				stream_id,
+				genesis_stream_id,
+			} => write!(
+				f,
+				"genesis stream_id {} not match {}",
+				genesis_stream_id, stream_id
+			),
+			Self::UnknownCid(cid) => write!(f, "unknown cid {}, no stream found", cid),
+			Self::AnchorTimeout { stream_id, timeout } => write!(
+				f,
+				"anchor timeout: stream {} not anchored in {:?}",
+				stream_id, timeout
+			),
+		}
+	}
+}
+
+impl std::error::Error for DataverseError {}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn downcast_from_anyhow() -> anyhow::Result<()> {
+		let stream_id: StreamId =
+			"kjzl6kcym7w8y5fhg4cl0xi8npfke3jmaaeeic9dwx64bgunnqa6amortdgdsym".parse()?;
+		let err: anyhow::Error = DataverseError::StreamNotFound(stream_id.clone()).into();
+		assert_eq!(
+			err.downcast_ref::<DataverseError>(),
+			Some(&DataverseError::StreamNotFound(stream_id.clone()))
+		);
+		assert_eq!(err.to_string(), format!("stream {} not found", stream_id));
+
+		let err = anyhow::anyhow!("connection reset").context(DataverseError::AnchorNotSupported);
+		assert_eq!(
+			err.downcast_ref::<DataverseError>(),
+			Some(&DataverseError::AnchorNotSupported)
+		);
+		assert!(anyhow::anyhow!("stream not found")
+			.downcast_ref::<DataverseError>()
+			.is_none());
+		Ok(())
+	}
+}
@@ -1,6 +1,8 @@
 use std::str::FromStr;
 
+use anyhow::Context;
 use libipld::cid::Cid;
+use libipld::multihash::{Code, MultihashDigest};
 use libipld::{cbor::DagCborCodec, codec::Codec};
 use libipld::{ipld, Ipld};
 use primitive_types::H256;
@@ -33,6 +35,25 @@ impl AnchorValue {
 		}
 	}
 
+	/// check the proof block is the block linked by `proof`, names a known chain and the path
+	/// is a merkle path, the transaction and the tree from root to `prev` are not checked
+	pub fn verify_proof(&self) -> anyhow::Result<AnchorProof> {
+		let proof_block = self
+			.proof_block
+			.as_ref()
+			.context("anchor proof block is none")?;
+		let cid = Cid::new_v1(0x71, Code::Sha2_256.digest(proof_block));
+		if cid != self.proof {
+			anyhow::bail!("anchor proof block {} not match proof {}", cid, self.proof);
+		}
+		let proof = self.proof()?.context("anchor proof block is none")?;
+		proof.chain()?;
+		if !self.path.is_empty() && self.path.split('/').any(|idx| idx != "0" && idx != "1") {
+			anyhow::bail!("invalid anchor path {}", self.path);
+		}
+		Ok(proof)
+	}
+
 	pub fn to_vec(&self) -> anyhow::Result<Vec<u8>> {
 		let data: Ipld = self.clone().into();
 		DagCborCodec.encode(&data)
@@ -122,6 +143,39 @@ impl Into<crate::stream::AnchorProof> for AnchorProof {
 	}
 }
 
+pub mod example {
+	use super::*;
+
+	/// dag-cbor block of an anchor proof on ethereum mainnet
+	pub fn proof_block() -> Vec<u8> {
+		vec![
+			164, 100, 114, 111, 111, 116, 216, 42, 88, 37, 0, 1, 113, 18, 32, 207, 168, 82, 146,
+			21, 182, 223, 25, 66, 200, 254, 64, 1, 34, 102, 17, 253, 203, 63, 115, 212, 223, 233,
+			78, 130, 165, 11, 117, 233, 247, 127, 170, 102, 116, 120, 72, 97, 115, 104, 216, 42,
+			88, 38, 0, 1, 147, 1, 27, 32, 141, 27, 16, 141, 128, 187, 139, 165, 10, 133, 142, 28,
+			12, 216, 162, 223, 178, 117, 205, 144, 225, 105, 253, 183, 130, 98, 241, 48, 253, 83,
+			212, 212, 102, 116, 120, 84, 121, 112, 101, 106, 102, 40, 98, 121, 116, 101, 115, 51,
+			50, 41, 103, 99, 104, 97, 105, 110, 73, 100, 104, 101, 105, 112, 49, 53, 53, 58, 49,
+		]
+	}
+
+	/// anchor event of `prev` in the stream of genesis `id`, the proof is `proof_block`
+	pub fn anchor(id: Cid, prev: Cid) -> anyhow::Result<crate::Event> {
+		let proof_block = proof_block();
+		let anchor = AnchorValue {
+			id,
+			prev,
+			proof: Cid::new_v1(0x71, Code::Sha2_256.digest(&proof_block)),
+			path: "0".to_string(),
+			proof_block: Some(proof_block),
+		};
+		Ok(crate::Event {
+			cid: Cid::new_v1(0x71, Code::Sha2_256.digest(&anchor.to_vec()?)),
+			value: anchor.into(),
+		})
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	use libipld::Ipld;
@@ -152,17 +206,33 @@ mod tests {
 		assert_eq!(encoded, data);
 	}
 
+	#[test]
+	fn verify_anchor_proof() -> anyhow::Result<()> {
+		let proof_block = example::proof_block();
+		let mut anchor = AnchorValue {
+			proof: Cid::new_v1(0x71, Code::Sha2_256.digest(&proof_block)),
+			path: "0/1/0".to_string(),
+			proof_block: Some(proof_block),
+			..Default::default()
+		};
+		assert_eq!(anchor.verify_proof()?.chain_id, "eip155:1");
+
+		anchor.path = "0/2".to_string();
+		assert!(anchor.verify_proof().is_err());
+
+		anchor.path = "0".to_string();
+		anchor.proof =
+			Cid::from_str("bafyreidtdpcjnltl7enswtp4s4xbsweb5zndvzihiyczl3t6ppqvbcgjpu")?;
+		assert!(anchor.verify_proof().is_err());
+
+		anchor.proof_block = None;
+		assert!(anchor.verify_proof().is_err());
+		Ok(())
+	}
+
 	#[test]
 	fn decode_anchor_proof() {
-		let data = vec![
-			164, 100, 114, 111, 111, 116, 216, 42, 88, 37, 0, 1, 113, 18, 32, 207, 168, 82, 146,
-			21, 182, 223, 25, 66, 200, 254, 64, 1, 34, 102, 17, 253, 203, 63, 115, 212, 223, 233,
-			78, 130, 165, 11, 117, 233, 247, 127, 170, 102, 116, 120, 72, 97, 115, 104, 216, 42,
-			88, 38, 0, 1, 147, 1, 27, 32, 141, 27, 16, 141, 128, 187, 139, 165, 10, 133, 142, 28,
-			12, 216, 162, 223, 178, 117, 205, 144, 225, 105, 253, 183, 130, 98, 241, 48, 253, 83,
-			212, 212, 102, 116, 120, 84, 121, 112, 101, 106, 102, 40, 98, 121, 116, 101, 115, 51,
-			50, 41, 103, 99, 104, 97, 105, 110, 73, 100, 104, 101, 105, 112, 49, 53, 53, 58, 49,
-		];
+		let data = example::proof_block();
 		let node: Ipld = DagCborCodec.decode(&data).unwrap();
 		let proof = libipld::serde::from_ipld::<AnchorProof>(node);
 		assert!(proof.is_ok());
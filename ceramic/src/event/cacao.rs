@@ -67,6 +67,33 @@ impl Payload {
         }
         Ok(result)
     }
+
+    /// caveats restricting events signed with the capability
+    pub fn caveats(&self) -> anyhow::Result<Vec<Caveat>> {
+        let models = self.resource_models()?;
+        if models.is_empty() {
+            return Ok(vec![]);
+        }
+        Ok(vec![Caveat::Models(models)])
+    }
+}
+
+/// restriction a capability puts on the events it signs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Caveat {
+    /// events can only create streams of the models
+    Models(Vec<StreamId>),
+}
+
+impl std::fmt::Display for Caveat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Caveat::Models(models) => {
+                let models: Vec<String> = models.iter().map(|model| model.to_string()).collect();
+                write!(f, "model in [{}]", models.join(", "))
+            }
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
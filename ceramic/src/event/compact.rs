@@ -0,0 +1,79 @@
+use ceramic_core::Base64String;
+use libipld::cid::Cid;
+use serde::{Deserialize, Serialize};
+
+use super::{AnchorValue, Event, EventValue, SignedValue, ToCid};
+
+/// Compact json form of event, null fields are omitted and blocks are base64url encoded.
+///
+/// Field aliases:
+/// - `c`: cid of event, codec 0x85 for signed event and 0x71 for anchor event
+/// - `j`: jws block of signed event, or block of anchor event
+/// - `l`: linked block of signed event, or proof block of anchor event
+/// - `k`: cacao block of signed event
+#[derive(Debug, Serialize, Deserialize)]
+struct CompactEvent {
+	c: String,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	j: Option<Base64String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	l: Option<Base64String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	k: Option<Base64String>,
+}
+
+impl Event {
+	pub fn to_compact_json(&self) -> anyhow::Result<serde_json::Value> {
+		let (block, linked_block, cacao_block) = match &self.value {
+			EventValue::Signed(signed) => (
+				signed.jws.to_vec()?,
+				signed.linked_block.clone(),
+				signed.cacao_block.clone(),
+			),
+			EventValue::Anchor(anchor) => (anchor.to_vec()?, anchor.proof_block.clone(), None),
+		};
+		let compact = CompactEvent {
+			c: self.cid.to_string(),
+			j: Some(Base64String::from(block)),
+			l: linked_block.map(Base64String::from),
+			k: cacao_block.map(Base64String::from),
+		};
+		Ok(serde_json::to_value(compact)?)
+	}
+
+	pub fn from_compact_json(value: serde_json::Value) -> anyhow::Result<Self> {
+		let compact: CompactEvent = serde_json::from_value(value)?;
+		let cid = Cid::try_from(compact.c)?;
+		let block = match compact.j {
+			Some(block) => block.to_vec()?,
+			None => anyhow::bail!("missing block of event {}", cid),
+		};
+		let linked_block = compact.l.map(|block| block.to_vec()).transpose()?;
+		let cacao_block = compact.k.map(|block| block.to_vec()).transpose()?;
+		let value = match cid.codec() {
+			0x71 => AnchorValue::try_from((block, linked_block))?.into(),
+			0x85 => SignedValue::try_from((block, linked_block, cacao_block))?.into(),
+			_ => anyhow::bail!("unsupported codec {}", cid.codec()),
+		};
+		Ok(Event { cid, value })
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn compact_json_round_trip() -> anyhow::Result<()> {
+		let event: Event = crate::commit::example::genesis().genesis.try_into()?;
+		let compact = event.to_compact_json()?;
+		let full = serde_json::to_value(&event)?;
+		assert!(compact.to_string().len() * 10 < full.to_string().len() * 7);
+
+		let decoded = Event::from_compact_json(compact)?;
+		assert_eq!(decoded.cid, event.cid);
+		assert_eq!(decoded.prev()?, event.prev()?);
+		assert_eq!(decoded.to_compact_json()?, event.to_compact_json()?);
+		Ok(())
+	}
+}
@@ -1,6 +1,7 @@
 pub mod anchor;
 pub mod cacao;
 pub mod commit;
+mod compact;
 pub mod ipld;
 pub mod jws;
 pub mod operator;
@@ -106,6 +107,35 @@ impl Event {
 		Ok(())
 	}
 
+	/// DAG-CBOR bytes of the event content before CID computation, without any framing:
+	/// the linked payload block of a signed event, or the block of an anchor event
+	#[allow(clippy::wrong_self_convention)]
+	pub fn into_raw_payload(&self) -> anyhow::Result<Vec<u8>> {
+		match &self.value {
+			EventValue::Signed(signed) => signed
+				.linked_block
+				.clone()
+				.context("missing linked block in signed event"),
+			EventValue::Anchor(anchor) => anchor.to_vec(),
+		}
+	}
+
+	pub fn payload_hex(&self) -> anyhow::Result<String> {
+		Ok(hex::encode(self.into_raw_payload()?))
+	}
+
+	/// size in bytes of the raw payload, 0 when the payload is unavailable
+	pub fn payload_size(&self) -> usize {
+		self.into_raw_payload()
+			.map(|payload| payload.len())
+			.unwrap_or_default()
+	}
+
+	/// estimate the per-byte part of anchor fee, see [`crate::FeeSchedule`]
+	pub fn estimated_anchor_cost(&self, fee_per_byte: f64) -> f64 {
+		self.payload_size() as f64 * fee_per_byte
+	}
+
 	pub fn decode(cid: Cid, data: Vec<u8>) -> anyhow::Result<Self> {
 		let codec = cid.codec();
 		let value = EventValue::decode(codec, data)?;
@@ -200,6 +230,23 @@ mod tests {
 		Ok(())
 	}
 
+	#[test]
+	fn raw_payload_matches_payload_link() -> anyhow::Result<()> {
+		use libipld::multihash::{Code, MultihashDigest};
+
+		let genesis: Event = crate::commit::example::genesis().genesis.try_into()?;
+		let raw = genesis.into_raw_payload()?;
+		let payload_link = match &genesis.value {
+			EventValue::Signed(signed) => signed.payload_link()?,
+			_ => anyhow::bail!("genesis should be signed"),
+		};
+		assert_eq!(Cid::new_v1(0x71, Code::Sha2_256.digest(&raw)), payload_link);
+		assert_eq!(genesis.payload_hex()?, hex::encode(&raw));
+		assert_eq!(genesis.payload_size(), raw.len());
+		assert_eq!(genesis.estimated_anchor_cost(0.5), raw.len() as f64 * 0.5);
+		Ok(())
+	}
+
 	#[test]
 	fn test_decode_anchor_event() {
 		// Test data
@@ -1,6 +1,16 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use ceramic_core::{Cid, StreamId};
+use ceramic_event::{DidDocument, JwkSigner, Signer};
+use dag_jose::JsonWebSignature;
+use futures::stream::{self, BoxStream, StreamExt, TryStreamExt};
+use int_enum::IntEnum;
+use libipld::multihash::{Code, MultihashDigest};
 
-use crate::{event::Event, Ceramic};
+use crate::{
+	did::generate_did_str,
+	event::{jws::Jws, Event, EventValue, Payload, SignedValue, ToCid},
+	Ceramic,
+};
 
 #[async_trait::async_trait]
 pub trait EventsLoader: Sync + Send {
@@ -10,6 +20,40 @@ pub trait EventsLoader: Sync + Send {
 		stream_id: &StreamId,
 		tip: Option<Cid>,
 	) -> anyhow::Result<Vec<Event>>;
+
+	/// Events of stream after `since`, for callers already holding events up to `since`.
+	///
+	/// Loads the whole stream and skips events up to and including `since` by default,
+	/// loaders able to filter on the server side should override it. Fails when `since`
+	/// is not an event of the stream.
+	async fn load_events_since(
+		&self,
+		ceramic: &Ceramic,
+		stream_id: &StreamId,
+		since: Cid,
+	) -> anyhow::Result<Vec<Event>> {
+		let mut events = self.load_events(ceramic, stream_id, None).await?;
+		match events.iter().position(|event| event.cid == since) {
+			Some(idx) => Ok(events.split_off(idx + 1)),
+			None => anyhow::bail!("event {} not found in stream {}", since, stream_id),
+		}
+	}
+
+	/// Events of stream from genesis to `tip`, yielded one at a time.
+	///
+	/// Wraps `load_events` by default, loaders fetching events one by one should override it
+	/// to yield events before the whole stream is fetched.
+	fn load_events_stream<'a>(
+		&'a self,
+		ceramic: &'a Ceramic,
+		stream_id: &'a StreamId,
+		tip: Option<Cid>,
+	) -> BoxStream<'a, anyhow::Result<Event>> {
+		stream::once(self.load_events(ceramic, stream_id, tip))
+			.map_ok(|events| stream::iter(events.into_iter().map(Ok)))
+			.try_flatten()
+			.boxed()
+	}
 }
 
 #[async_trait::async_trait]
@@ -32,4 +76,238 @@ pub trait EventsUploader {
 		}
 		Ok(())
 	}
+
+	/// publish the genesis event of a new stream, the stream id is computed from genesis cid
+	async fn publish_genesis(
+		&self,
+		ceramic: &Ceramic,
+		stream_type: u64,
+		event: &Event,
+	) -> anyhow::Result<StreamId> {
+		if let Some(prev) = event.prev()? {
+			anyhow::bail!("event {} is not genesis, has prev {}", event.cid, prev);
+		}
+		let stream_id = StreamId {
+			r#type: IntEnum::from_int(stream_type)?,
+			cid: event.cid,
+		};
+		self.upload_event(ceramic, &stream_id, event.clone())
+			.await?;
+		Ok(stream_id)
+	}
+}
+
+#[async_trait::async_trait]
+pub trait EventSigner: Sync + Send {
+	/// did of the signer, used as controller of streams it creates
+	fn controller(&self) -> String;
+
+	/// sign payload as a jws event, linked block and cacao block are attached to the event
+	async fn sign(&self, payload: Payload) -> anyhow::Result<Event>;
+}
+
+/// signer of jws events with an ed25519 key, the controller is the did:key of the key,
+/// events carry no cacao
+pub struct KeySigner {
+	signer: JwkSigner,
+}
+
+impl KeySigner {
+	/// `pk` is the hex encoded seed of the ed25519 key
+	pub async fn new(pk: &str) -> anyhow::Result<Self> {
+		let did = DidDocument::new(&generate_did_str(pk)?);
+		Ok(Self {
+			signer: JwkSigner::new(did, pk).await?,
+		})
+	}
+}
+
+#[async_trait::async_trait]
+impl EventSigner for KeySigner {
+	fn controller(&self) -> String {
+		self.signer.id().id.clone()
+	}
+
+	async fn sign(&self, payload: Payload) -> anyhow::Result<Event> {
+		let linked_block = payload.encode()?;
+		let link = Cid::new_v1(0x71, Code::Sha2_256.digest(&linked_block));
+		let did = self.controller();
+		let kid = format!("{}#{}", did, did.trim_start_matches("did:key:"));
+		let protected = serde_json::json!({ "alg": "EdDSA", "kid": kid });
+		let protected = URL_SAFE_NO_PAD.encode(serde_json::to_vec(&protected)?);
+		let payload = URL_SAFE_NO_PAD.encode(link.to_bytes());
+		let signing_input = format!("{}.{}", protected, payload);
+		let signature = self.signer.sign(signing_input.as_bytes()).await?;
+
+		let jws = JsonWebSignature {
+			payload,
+			signatures: vec![dag_jose::Signature {
+				header: Default::default(),
+				protected: Some(protected),
+				signature: signature.to_string(),
+			}],
+			link,
+		};
+		let cid = jws.cid()?;
+		let Jws(jws) = jws.try_into()?;
+		Ok(Event {
+			cid,
+			value: EventValue::Signed(SignedValue {
+				jws,
+				linked_block: Some(linked_block),
+				cacao_block: None,
+			}),
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use tokio::sync::Mutex;
+
+	use super::*;
+	use crate::network::Network;
+
+	struct MemoryUploader {
+		uploaded: Mutex<Vec<(StreamId, Cid)>>,
+	}
+
+	#[async_trait::async_trait]
+	impl EventsUploader for MemoryUploader {
+		async fn upload_event(
+			&self,
+			_ceramic: &Ceramic,
+			stream_id: &StreamId,
+			event: Event,
+		) -> anyhow::Result<()> {
+			self.uploaded
+				.lock()
+				.await
+				.push((stream_id.clone(), event.cid));
+			Ok(())
+		}
+	}
+
+	struct MemoryLoader(Vec<Event>);
+
+	#[async_trait::async_trait]
+	impl EventsLoader for MemoryLoader {
+		async fn load_events(
+			&self,
+			_ceramic: &Ceramic,
+			_stream_id: &StreamId,
+			_tip: Option<Cid>,
+		) -> anyhow::Result<Vec<Event>> {
+			Ok(self.0.clone())
+		}
+	}
+
+	fn ceramic() -> Ceramic {
+		Ceramic {
+			endpoint: "http://localhost:7007".into(),
+			network: Network::InMemory,
+			pool: None,
+		}
+	}
+
+	#[tokio::test]
+	async fn publish_genesis() -> anyhow::Result<()> {
+		let uploader = MemoryUploader {
+			uploaded: Mutex::new(vec![]),
+		};
+		let genesis = crate::commit::example::genesis();
+		let expected = genesis.stream_id()?;
+		let event: Event = genesis.genesis.try_into()?;
+
+		let stream_id = uploader
+			.publish_genesis(&ceramic(), genesis.r#type, &event)
+			.await?;
+		assert_eq!(stream_id, expected);
+		assert_eq!(uploader.uploaded.lock().await.len(), 1);
+
+		let data: Event = crate::commit::example::data().commit.try_into()?;
+		let res = uploader
+			.publish_genesis(&ceramic(), genesis.r#type, &data)
+			.await;
+		assert!(res.is_err());
+		assert_eq!(uploader.uploaded.lock().await.len(), 1);
+		Ok(())
+	}
+	#[tokio::test]
+	async fn key_signer_signs_payload() -> anyhow::Result<()> {
+		let signer =
+			KeySigner::new("d160c4553ba7547cd5d66993d99329379a0c299a1bb1058abc5b874e0ba56375")
+				.await?;
+		assert_eq!(
+			signer.controller(),
+			"did:key:z6MkuBcU2NW8Yfd1pJKA8HeFxeojzujcNyhmTNkuhDEfpqKT"
+		);
+		let genesis: Event = crate::commit::example::genesis().genesis.try_into()?;
+		let payload = match &genesis.value {
+			EventValue::Signed(signed) => signed.payload()?,
+			_ => anyhow::bail!("genesis is not signed"),
+		};
+
+		let event = signer.sign(payload.clone()).await?;
+		let signed = match &event.value {
+			EventValue::Signed(signed) => signed,
+			_ => anyhow::bail!("signed event expected"),
+		};
+		assert_eq!(signed.payload()?, payload);
+		assert!(signed.is_gensis());
+		// decodes from its block like events loaded from kubo
+		let decoded = Event::decode(event.cid, signed.jws.to_vec()?)?;
+		match decoded.value {
+			EventValue::Signed(decoded) => {
+				assert_eq!(decoded.payload_link()?, signed.payload_link()?)
+			}
+			_ => anyhow::bail!("signed event expected"),
+		}
+		assert!(event.verify_signature(vec![]).is_ok());
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn load_events_since() -> anyhow::Result<()> {
+		let genesis = crate::commit::example::genesis();
+		let stream_id = genesis.stream_id()?;
+		let genesis: Event = genesis.genesis.try_into()?;
+		let data: Event = crate::commit::example::data().commit.try_into()?;
+		let loader = MemoryLoader(vec![genesis.clone(), data.clone()]);
+
+		let events = loader
+			.load_events_since(&ceramic(), &stream_id, genesis.cid)
+			.await?;
+		assert_eq!(events.len(), 1);
+		assert_eq!(events[0].cid, data.cid);
+		assert!(loader
+			.load_events_since(&ceramic(), &stream_id, data.cid)
+			.await?
+			.is_empty());
+
+		let missing = MemoryLoader(vec![data]);
+		assert!(missing
+			.load_events_since(&ceramic(), &stream_id, genesis.cid)
+			.await
+			.is_err());
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn load_events_stream() -> anyhow::Result<()> {
+		let genesis = crate::commit::example::genesis();
+		let stream_id = genesis.stream_id()?;
+		let genesis: Event = genesis.genesis.try_into()?;
+		let data: Event = crate::commit::example::data().commit.try_into()?;
+		let loader = MemoryLoader(vec![genesis.clone(), data.clone()]);
+
+		let ceramic = ceramic();
+		let events: Vec<Event> = loader
+			.load_events_stream(&ceramic, &stream_id, None)
+			.try_collect()
+			.await?;
+		let cids: Vec<Cid> = events.iter().map(|event| event.cid).collect();
+		assert_eq!(cids, vec![genesis.cid, data.cid]);
+		Ok(())
+	}
 }
@@ -1,3 +1,5 @@
+use std::collections::BTreeMap;
+
 use crate::stream::StreamState;
 use crate::EventValue;
 
@@ -154,7 +156,7 @@ impl StreamStateApplyer for SignedValue {
 						stream_state.content = data.clone();
 					}
 					if let Some(header) = &payload.header {
-						stream_state.metadata = header.to_metadata();
+						stream_state.set_metadata(header.to_metadata());
 					}
 				}
 				// data commit
@@ -188,6 +190,33 @@ pub struct Payload {
 	pub id: Option<Cid>,
 }
 
+impl Payload {
+	/// encode as the dag-cbor block linked by the jws of an event
+	pub fn encode(&self) -> anyhow::Result<Vec<u8>> {
+		let mut node = BTreeMap::new();
+		if let Some(data) = &self.data {
+			let data: Ipld = DagJsonCodec.decode(&serde_json::to_vec(data)?)?;
+			node.insert("data".to_string(), data);
+		}
+		if let Some(header) = &self.header {
+			let controllers = header.controllers.iter().cloned().map(Ipld::String);
+			let header = BTreeMap::from([
+				("model".to_string(), Ipld::Bytes(header.model.to_vec()?)),
+				("controllers".to_string(), Ipld::List(controllers.collect())),
+				("unique".to_string(), Ipld::Bytes(header.unique.clone())),
+			]);
+			node.insert("header".to_string(), Ipld::Map(header));
+		}
+		if let Some(prev) = self.prev {
+			node.insert("prev".to_string(), Ipld::Link(prev));
+		}
+		if let Some(id) = self.id {
+			node.insert("id".to_string(), Ipld::Link(id));
+		}
+		Ok(DagCborCodec.encode(&Ipld::Map(node))?)
+	}
+}
+
 impl TryFrom<Vec<u8>> for Payload {
 	type Error = anyhow::Error;
 
@@ -1,17 +1,96 @@
 use ceramic_core::StreamId;
 use chrono::{DateTime, Utc};
+use rayon::prelude::*;
 
-use super::{Event, EventValue};
+use super::{cacao::Caveat, Event, EventValue};
 
+/// hex chars of payload kept in verification errors
+const PAYLOAD_PREFIX_LEN: usize = 64;
+
+#[derive(Debug, Clone)]
 pub enum VerifyOption {
     ResourceModelsContain(StreamId),
     ExpirationTimeBefore(DateTime<Utc>),
+    /// reject events issued before the time, prevents replaying stale signed events
+    ExpirationTimeAfter(DateTime<Utc>),
+    /// parse events in the compact json of `Event::to_compact_json` in `verify_json`
+    AcceptCompactJson,
+}
+
+/// Errors of event verification callers may tell apart, returned inside `anyhow::Error`,
+/// match on them with `err.downcast_ref::<VerifyError>()`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum VerifyError {
+    /// event does not satisfy a caveat of its capability, `rule` describes the caveat
+    CaveatViolation { rule: String },
 }
 
+impl std::fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::CaveatViolation { rule } => write!(f, "caveat violation: {}", rule),
+        }
+    }
+}
+
+impl std::error::Error for VerifyError {}
+
 impl Event {
+    /// verify the event against options, the error includes the start of the hex payload and
+    /// still downcasts to `VerifyError`, the whole payload is logged at debug level
     pub fn verify_signature(
         &self,
         opts: Vec<VerifyOption>,
+    ) -> anyhow::Result<Option<DateTime<Utc>>> {
+        self.verify_signature_with(opts)
+            .map_err(|err| match self.payload_hex() {
+                Ok(payload) => {
+                    let cid = self.cid.to_string();
+                    tracing::debug!(cid, payload, "failed to verify event: {}", err);
+                    let payload = match payload.len() > PAYLOAD_PREFIX_LEN {
+                        true => format!("{}...", &payload[..PAYLOAD_PREFIX_LEN]),
+                        false => payload,
+                    };
+                    let context = format!("{} (event {}, payload {})", err, cid, payload);
+                    err.context(context)
+                }
+                Err(_) => err,
+            })
+    }
+
+    /// parse an event from json and verify it against options, the compact json of
+    /// `Event::to_compact_json` is only parsed with `VerifyOption::AcceptCompactJson`
+    pub fn verify_json(
+        value: serde_json::Value,
+        opts: Vec<VerifyOption>,
+    ) -> anyhow::Result<(Event, Option<DateTime<Utc>>)> {
+        let accept_compact = opts
+            .iter()
+            .any(|opt| matches!(opt, VerifyOption::AcceptCompactJson));
+        let event = match serde_json::from_value::<Event>(value.clone()) {
+            Ok(event) => event,
+            Err(_) if accept_compact => Event::from_compact_json(value)?,
+            Err(err) => anyhow::bail!("invalid event json: {}", err),
+        };
+        let expiration_time = event.verify_signature(opts)?;
+        Ok((event, expiration_time))
+    }
+
+    /// verify events in parallel on the current rayon pool, results are in the order of events,
+    /// run it inside `ThreadPool::install` to use another pool than the global one
+    pub fn verify_signatures_batch(
+        events: &[Event],
+        opts: Vec<VerifyOption>,
+    ) -> Vec<anyhow::Result<()>> {
+        events
+            .par_iter()
+            .map(|event| event.verify_signature(opts.clone()).map(|_| ()))
+            .collect()
+    }
+
+    fn verify_signature_with(
+        &self,
+        opts: Vec<VerifyOption>,
     ) -> anyhow::Result<Option<DateTime<Utc>>> {
         let mut expiration_time = None;
         if let EventValue::Signed(signed) = &self.value {
@@ -26,6 +105,9 @@ impl Event {
                             if !resource_models.contains(&model) {
                                 anyhow::bail!("invalid resource model");
                             }
+                            for caveat in cacao.p.caveats()? {
+                                self.apply_capability_caveat(&caveat)?;
+                            }
                         }
                         VerifyOption::ExpirationTimeBefore(before) => {
                             expiration_time = cacao.p.expiration_time()?;
@@ -35,10 +117,137 @@ impl Event {
                                 }
                             }
                         }
+                        VerifyOption::ExpirationTimeAfter(after) => {
+                            if cacao.p.issued_at()? < after {
+                                anyhow::bail!("jws commit issued before {}", after);
+                            }
+                        }
+                        // only changes how `verify_json` parses events
+                        VerifyOption::AcceptCompactJson => {}
                     }
                 }
             };
         };
         Ok(expiration_time)
     }
+
+    /// check the event content against a capability caveat,
+    /// only genesis events carry the model, other events always pass
+    pub fn apply_capability_caveat(&self, caveat: &Caveat) -> anyhow::Result<()> {
+        let header = match &self.value {
+            EventValue::Signed(signed) => signed.payload()?.header,
+            EventValue::Anchor(_) => None,
+        };
+        if let Some(header) = header {
+            match caveat {
+                Caveat::Models(models) => {
+                    if !models.contains(&header.model) {
+                        return Err(VerifyError::CaveatViolation {
+                            rule: caveat.to_string(),
+                        }
+                        .into());
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+
+    fn genesis() -> anyhow::Result<(Event, DateTime<Utc>)> {
+        let event: Event = crate::commit::example::genesis().genesis.try_into()?;
+        let issued_at = match &event.value {
+            EventValue::Signed(signed) => signed.cacao()?.unwrap().p.issued_at()?,
+            _ => anyhow::bail!("genesis should be signed"),
+        };
+        Ok((event, issued_at))
+    }
+
+    #[test]
+    fn apply_model_caveat() -> anyhow::Result<()> {
+        let (event, _) = genesis()?;
+        let model = match &event.value {
+            EventValue::Signed(signed) => signed.payload()?.header.unwrap().model,
+            _ => anyhow::bail!("genesis should be signed"),
+        };
+        let allowed = Caveat::Models(vec![model]);
+        assert!(event.apply_capability_caveat(&allowed).is_ok());
+
+        let other: StreamId =
+            "kjzl6hvfrbw6c763ubdhowzao0m4yp84cxzbfnlh4hdi5alqo4yrebmc0qpjdi5".parse()?;
+        let denied = Caveat::Models(vec![other]);
+        let err = event.apply_capability_caveat(&denied).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<VerifyError>(),
+            Some(&VerifyError::CaveatViolation {
+                rule: denied.to_string()
+            })
+        );
+        assert!(err.to_string().starts_with("caveat violation"));
+        Ok(())
+    }
+
+    #[test]
+    fn verify_issued_at_threshold() -> anyhow::Result<()> {
+        let (event, issued_at) = genesis()?;
+        let opts = vec![VerifyOption::ExpirationTimeAfter(issued_at)];
+        assert!(event.verify_signature(opts).is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn verify_issued_before_threshold() -> anyhow::Result<()> {
+        let (event, issued_at) = genesis()?;
+        let after = issued_at + Duration::seconds(1);
+        let opts = vec![VerifyOption::ExpirationTimeAfter(after)];
+        let err = event.verify_signature(opts).unwrap_err().to_string();
+        assert!(err.contains(&event.cid.to_string()));
+        let payload = event.payload_hex()?;
+        assert!(payload.len() > PAYLOAD_PREFIX_LEN);
+        assert!(err.ends_with(&format!("payload {}...)", &payload[..PAYLOAD_PREFIX_LEN])));
+        Ok(())
+    }
+
+    #[test]
+    fn verify_issued_after_threshold() -> anyhow::Result<()> {
+        let (event, issued_at) = genesis()?;
+        let after = issued_at - Duration::seconds(1);
+        let opts = vec![VerifyOption::ExpirationTimeAfter(after)];
+        assert!(event.verify_signature(opts).is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn verify_compact_json_only_when_accepted() -> anyhow::Result<()> {
+        let (event, _) = genesis()?;
+        let compact = event.to_compact_json()?;
+        assert!(Event::verify_json(compact.clone(), vec![]).is_err());
+
+        let (parsed, _) = Event::verify_json(compact, vec![VerifyOption::AcceptCompactJson])?;
+        assert_eq!(parsed.cid, event.cid);
+        Ok(())
+    }
+
+    #[test]
+    fn verify_batch() -> anyhow::Result<()> {
+        let (event, issued_at) = genesis()?;
+        let after = issued_at + Duration::seconds(1);
+        let events = vec![event.clone(), event];
+
+        let results = Event::verify_signatures_batch(&events, vec![]);
+        assert!(results.iter().all(|result| result.is_ok()));
+
+        let opts = vec![VerifyOption::ExpirationTimeAfter(after)];
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(2).build()?;
+        let results = pool.install(|| Event::verify_signatures_batch(&events, opts));
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|result| result.is_err()));
+        Ok(())
+    }
 }
@@ -12,10 +12,11 @@ use ssi::jwk::Algorithm;
 
 use crate::{
 	did::generate_did_str,
-	event::{Event, EventsLoader, EventsUploader},
+	event::{Event, EventValue, EventsLoader, EventsUploader},
 	network::{Chain, Network},
 	stream::StreamState,
-	AnchorStatus, Ceramic, LogType, StreamAnchorRequester, StreamLoader, StreamsLoader,
+	AnchorStatus, Ceramic, DataverseError, LogType, StreamAnchorRequester, StreamLoader,
+	StreamsLoader,
 };
 
 pub struct Client {}
@@ -37,8 +38,15 @@ impl Client {
 		model_id: &StreamId,
 		query: Option<FilterQuery>,
 	) -> anyhow::Result<Vec<StreamState>> {
-		let http_client = Self::init(&ceramic.endpoint)?;
-		let edges = http_client.query_all(account, model_id, query).await?;
+		let edges = ceramic
+			.call(|endpoint| {
+				let (account, query) = (account.clone(), query.clone());
+				async move {
+					let http_client = Self::init(&endpoint)?;
+					Ok(http_client.query_all(account, model_id, query).await?)
+				}
+			})
+			.await?;
 		let mut streams = Vec::new();
 		for edge in edges {
 			if let Some(node) = edge.node {
@@ -67,22 +75,92 @@ impl Client {
 			.parse::<Chain>()?;
 		Ok(chain.network())
 	}
+
+	/// Status of the latest anchor request of stream, read from its commits.
+	///
+	/// Stream state is loaded as well when the latest commit is not an anchor commit.
+	pub async fn anchor_request_status(
+		&self,
+		ceramic: &Ceramic,
+		stream_id: &StreamId,
+	) -> anyhow::Result<AnchorRequestStatus> {
+		let events = self.load_events(ceramic, stream_id, None).await?;
+		let status = match events.last().map(|event| &event.value) {
+			Some(EventValue::Anchor(_)) => AnchorStatus::Anchored,
+			_ => {
+				self.load_stream_state(ceramic, stream_id, None)
+					.await?
+					.anchor_status
+			}
+		};
+		Ok(AnchorRequestStatus::from_events(&events, status))
+	}
+}
+
+/// Progress of an anchor request, unlike `crate::AnchorStatus` of stream state
+/// an anchored request carries its anchor commit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AnchorRequestStatus {
+	Pending,
+	Processing,
+	Anchored { anchor_cid: Cid, anchor_proof: Cid },
+	Failed(String),
+}
+
+impl AnchorRequestStatus {
+	/// status of the latest event in `events`, `status` is the anchor status of stream state
+	pub fn from_events(events: &[Event], status: crate::AnchorStatus) -> Self {
+		let tip = match events.last() {
+			Some(tip) => tip,
+			None => return Self::Pending,
+		};
+		if let EventValue::Anchor(anchor) = &tip.value {
+			return Self::Anchored {
+				anchor_cid: tip.cid,
+				anchor_proof: anchor.proof,
+			};
+		}
+		match status {
+			crate::AnchorStatus::Processing => Self::Processing,
+			crate::AnchorStatus::Failed => {
+				Self::Failed(format!("anchor request of commit {} failed", tip.cid))
+			}
+			_ => Self::Pending,
+		}
+	}
 }
 
 #[async_trait::async_trait]
 impl EventsLoader for Client {
+	#[cfg_attr(
+		feature = "opentelemetry",
+		tracing::instrument(
+			skip_all,
+			fields(
+				ceramic.url = %ceramic.endpoint,
+				stream_id = %stream_id,
+				event_count = tracing::field::Empty
+			)
+		)
+	)]
 	async fn load_events(
 		&self,
 		ceramic: &Ceramic,
 		stream_id: &StreamId,
 		_tip: Option<Cid>,
 	) -> anyhow::Result<Vec<Event>> {
-		let http_client = Self::init(&ceramic.endpoint)?;
-		let commits = http_client.commits(stream_id).await?.commits;
+		let commits = ceramic
+			.call(|endpoint| async move {
+				let http_client = Self::init(&endpoint)?;
+				Ok(http_client.commits(stream_id).await?.commits)
+			})
+			.await?;
 		let mut events = vec![];
 		for commit in commits {
 			events.push(commit.try_into()?)
 		}
+		#[cfg(feature = "opentelemetry")]
+		tracing::Span::current().record("event_count", events.len());
 		Ok(events)
 	}
 }
@@ -132,21 +210,42 @@ impl EventsUploader for Client {
 
 #[async_trait::async_trait]
 impl StreamLoader for Client {
+	#[cfg_attr(
+		feature = "opentelemetry",
+		tracing::instrument(
+			skip_all,
+			fields(ceramic.url = %ceramic.endpoint, stream_id = %stream_id)
+		)
+	)]
 	async fn load_stream_state(
 		&self,
 		ceramic: &Ceramic,
 		stream_id: &StreamId,
 		_tip: Option<Cid>,
 	) -> anyhow::Result<StreamState> {
-		let ceramic = Self::init(&ceramic.endpoint)?;
-		let stream = ceramic.get(stream_id).await?;
-		let state = stream.state.context("Failed to load stream")?.try_into()?;
+		let stream = ceramic
+			.call(|endpoint| async move {
+				let http_client = Self::init(&endpoint)?;
+				Ok(http_client.get(stream_id).await?)
+			})
+			.await?;
+		let state = stream
+			.state
+			.ok_or_else(|| DataverseError::StreamNotFound(stream_id.clone()))?
+			.try_into()?;
 		Ok(state)
 	}
 }
 
 #[async_trait::async_trait]
 impl StreamsLoader for Client {
+	#[cfg_attr(
+		feature = "opentelemetry",
+		tracing::instrument(
+			skip_all,
+			fields(ceramic.url = %ceramic.endpoint, model_id = %model_id)
+		)
+	)]
 	async fn load_stream_states(
 		&self,
 		ceramic: &Ceramic,
@@ -155,6 +254,36 @@ impl StreamsLoader for Client {
 	) -> anyhow::Result<Vec<StreamState>> {
 		self.query_model(ceramic, account, model_id, None).await
 	}
+
+	/// count with the collection count api of ceramic, stream states are not transferred
+	async fn count_streams(
+		&self,
+		ceramic: &Ceramic,
+		account: Option<String>,
+		model_id: &StreamId,
+	) -> anyhow::Result<u64> {
+		let mut query = serde_json::json!({ "model": model_id.to_string() });
+		if let Some(account) = account {
+			query["account"] = account.into();
+		}
+		let query = &query;
+		let response: CollectionCount = ceramic
+			.call(|endpoint| async move {
+				let url = url::Url::parse(&endpoint)?.join("api/v0/collection/count")?;
+				let request = reqwest::Client::new().post(url).json(query);
+				#[cfg(feature = "opentelemetry")]
+				let request = request.headers(crate::telemetry::trace_header_map());
+				let response = request.send().await?.error_for_status()?.json().await?;
+				Ok(response)
+			})
+			.await?;
+		Ok(response.count)
+	}
+}
+
+#[derive(serde::Deserialize)]
+struct CollectionCount {
+	count: u64,
 }
 
 #[async_trait::async_trait]
@@ -164,8 +293,13 @@ impl StreamAnchorRequester for Client {
 		ceramic: &Ceramic,
 		stream_id: &StreamId,
 	) -> anyhow::Result<AnchorStatus> {
-		let http_client = Self::init(&ceramic.endpoint)?;
-		let status = http_client.request_anchor(stream_id).await?;
+		// requesting anchor is not idempotent, it is sent to one endpoint only
+		let status = ceramic
+			.call_once(|endpoint| async move {
+				let http_client = Self::init(&endpoint)?;
+				Ok(http_client.request_anchor(stream_id).await?)
+			})
+			.await?;
 		Ok(AnchorStatus::from_int(status.anchor_status)?)
 	}
 }
@@ -267,6 +401,50 @@ mod tests {
 		);
 	}
 
+	#[test]
+	fn anchor_request_status_from_events() -> anyhow::Result<()> {
+		let genesis: Event = crate::commit::example::genesis().genesis.try_into()?;
+		let proof = Cid::from_str("bafyreidtdpcjnltl7enswtp4s4xbsweb5zndvzihiyczl3t6ppqvbcgjpu")?;
+		let anchor = Event {
+			cid: Cid::from_str("bafyreiaxfjkme33rujt5wfajbl7r6pcdhjw4gfzwmxqe7xs4wf3dwvxdpy")?,
+			value: EventValue::Anchor(crate::event::AnchorValue {
+				id: genesis.cid,
+				prev: genesis.cid,
+				proof,
+				..Default::default()
+			}),
+		};
+
+		assert_eq!(
+			AnchorRequestStatus::from_events(
+				&[genesis.clone(), anchor.clone()],
+				crate::AnchorStatus::Anchored
+			),
+			AnchorRequestStatus::Anchored {
+				anchor_cid: anchor.cid,
+				anchor_proof: proof,
+			}
+		);
+		let events = [genesis];
+		assert_eq!(
+			AnchorRequestStatus::from_events(&events, crate::AnchorStatus::Processing),
+			AnchorRequestStatus::Processing
+		);
+		assert!(matches!(
+			AnchorRequestStatus::from_events(&events, crate::AnchorStatus::Failed),
+			AnchorRequestStatus::Failed(_)
+		));
+		assert_eq!(
+			AnchorRequestStatus::from_events(&events, crate::AnchorStatus::Anchored),
+			AnchorRequestStatus::Pending
+		);
+		assert_eq!(
+			AnchorRequestStatus::from_events(&[], crate::AnchorStatus::Pending),
+			AnchorRequestStatus::Pending
+		);
+		Ok(())
+	}
+
 	#[tokio::test]
 	async fn load_events() {
 		let client = Client::new();
@@ -46,3 +46,130 @@ impl AsyncRunnable for EventUploadHandler {
 		true
 	}
 }
+
+/// request anchors of streams in one background task,
+/// ceramic http api has no batch endpoint so events are uploaded sequentially.
+///
+/// events failed to upload are queued again in a new task, so a retry never
+/// uploads events which already succeeded
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "fang::serde")]
+pub struct BatchAnchorTask {
+	pub ceramic: Ceramic,
+	pub requests: Vec<(StreamId, Event)>,
+}
+
+impl BatchAnchorTask {
+	/// upload requests in order, returns the requests failed to upload
+	async fn upload<U: EventsUploader + Sync>(&self, uploader: &U) -> Vec<(StreamId, Event)> {
+		let mut failed = vec![];
+		for (stream_id, commit) in &self.requests {
+			let result = uploader
+				.upload_event(&self.ceramic, stream_id, commit.clone())
+				.await;
+			let (id, cid) = (stream_id.to_string(), commit.cid.to_string());
+			match result {
+				Err(err) => {
+					tracing::warn!(stream_id = id, cid, ?err, "failed to upload event via http");
+					failed.push((stream_id.clone(), commit.clone()));
+				}
+				Ok(_) => tracing::info!(stream_id = id, cid, "upload event via http"),
+			}
+		}
+		failed
+	}
+}
+
+#[async_trait]
+#[typetag::serde]
+impl AsyncRunnable for BatchAnchorTask {
+	async fn run(&self, client: &mut dyn AsyncQueueable) -> Result<(), FangError> {
+		let http_operator = super::Client::new();
+
+		let failed = self.upload(&http_operator).await;
+		if failed.is_empty() {
+			return Ok(());
+		}
+		// nothing uploaded, retrying this task is safe
+		if failed.len() == self.requests.len() {
+			return Err(FangError {
+				description: format!("failed to upload {} events", failed.len()),
+			});
+		}
+		let remaining = BatchAnchorTask {
+			ceramic: self.ceramic.clone(),
+			requests: failed,
+		};
+		client.insert_task(&remaining).await.map_err(|err| FangError {
+			description: format!(
+				"failed to queue {} events not uploaded: {:?}",
+				remaining.requests.len(),
+				err
+			),
+		})?;
+		Ok(())
+	}
+
+	fn uniq(&self) -> bool {
+		true
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use ceramic_core::Cid;
+	use tokio::sync::Mutex;
+
+	use super::*;
+	use crate::network::Network;
+
+	struct FlakyUploader {
+		failing: Vec<Cid>,
+		uploaded: Mutex<Vec<Cid>>,
+	}
+
+	#[async_trait::async_trait]
+	impl EventsUploader for FlakyUploader {
+		async fn upload_event(
+			&self,
+			_ceramic: &Ceramic,
+			_stream_id: &StreamId,
+			event: Event,
+		) -> anyhow::Result<()> {
+			if self.failing.contains(&event.cid) {
+				anyhow::bail!("connection reset");
+			}
+			self.uploaded.lock().await.push(event.cid);
+			Ok(())
+		}
+	}
+
+	#[tokio::test]
+	async fn batch_anchor_keeps_only_failed_requests() -> anyhow::Result<()> {
+		let genesis = crate::commit::example::genesis();
+		let stream_id = genesis.stream_id()?;
+		let genesis: Event = genesis.genesis.try_into()?;
+		let data: Event = crate::commit::example::data().commit.try_into()?;
+		let task = BatchAnchorTask {
+			ceramic: Ceramic {
+				endpoint: "http://localhost:7007".into(),
+				network: Network::InMemory,
+				pool: None,
+			},
+			requests: vec![
+				(stream_id.clone(), genesis.clone()),
+				(stream_id, data.clone()),
+			],
+		};
+		let uploader = FlakyUploader {
+			failing: vec![data.cid],
+			uploaded: Mutex::new(vec![]),
+		};
+
+		let failed = task.upload(&uploader).await;
+		assert_eq!(uploader.uploaded.lock().await.clone(), vec![genesis.cid]);
+		assert_eq!(failed.len(), 1);
+		assert_eq!(failed[0].1.cid, data.cid);
+		Ok(())
+	}
+}
@@ -1,24 +1,87 @@
 extern crate lru;
 
+use anyhow::Context;
 use ceramic_core::{Cid, StreamId};
-use fang::{AsyncQueue, AsyncQueueable};
+use fang::{AsyncQueue, AsyncQueueable, AsyncRunnable};
+use futures::future::join_all;
+use futures::StreamExt;
+use libipld::{cbor::DagCborCodec, codec::Codec, Ipld};
 use lru::LruCache;
 use postgres_openssl::MakeTlsConnector;
-use std::{num::NonZeroUsize, sync::Arc};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use std::{collections::HashSet, num::NonZeroUsize, sync::Arc};
 use tokio::sync::Mutex;
 
-use crate::{http, Ceramic, Event, EventValue, StreamLoader};
+use crate::stream::operator::MAX_CONCURRENT_LOADS;
+use crate::{http, Ceramic, Event, EventValue, StreamLoader, StreamState};
 
 use super::{
+	dead_letter::DeadLetterStore,
 	message::MessagePublisher,
-	task::{BlockUploadHandler, UpdateMessagePublishHandler},
-	AnchorRuester, BlockUploader, CidLoader, Client,
+	task::{
+		BatchBlockUploadHandler, BlockUploadHandler, UpdateMessagePublishHandler,
+		DEFAULT_MAX_RETRIES, DEFAULT_RETRY_BACKOFF_MS,
+	},
+	AnchorRequester, BlockUploader, CidLoader, CidPinner, Client,
 };
 
+/// run `f` on the dead letter store in the blocking pool, stores may do file io
+async fn run_blocking<T, F>(store: &Arc<dyn DeadLetterStore>, f: F) -> anyhow::Result<T>
+where
+	T: Send + 'static,
+	F: FnOnce(&dyn DeadLetterStore) -> anyhow::Result<T> + Send + 'static,
+{
+	let store = store.clone();
+	tokio::task::spawn_blocking(move || f(store.as_ref())).await?
+}
+
 pub struct Cached {
 	pub client: Arc<Client>,
 	pub queue: Arc<Mutex<AsyncQueue<MakeTlsConnector>>>,
 	pub cache: Arc<Mutex<LruCache<Cid, Vec<u8>>>>,
+	/// cids of cached blocks known to be stored in kubo
+	pub persisted: Arc<Mutex<HashSet<Cid>>>,
+	pub standby: Option<Arc<Cached>>,
+	standby_status: Arc<Mutex<StandbyStatus>>,
+	#[cfg(feature = "metrics")]
+	pub metrics: Option<super::metrics::CacheMetrics>,
+	hits: Arc<AtomicU64>,
+	misses: Arc<AtomicU64>,
+	evictions: Arc<AtomicU64>,
+	/// blocks larger than this are not written by `save_to_disk`
+	pub max_persist_bytes: usize,
+	/// tasks the queue failed to insert, replayed by `drain_dead_letters`
+	pub dead_letter: Option<Arc<dyn DeadLetterStore>>,
+	/// retries of queued tasks, see `with_upload_retries`
+	max_retries: u32,
+	retry_backoff: Duration,
+	/// loader of latest states, tips of batch loads are taken from them
+	tip_loader: Arc<dyn StreamLoader>,
+}
+
+/// default max size of a block written by `save_to_disk`
+const DEFAULT_MAX_PERSIST_BYTES: usize = 1024 * 1024;
+
+/// counters of block cache since start, an under-sized cache shows many evictions and misses
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+	pub hits: u64,
+	pub misses: u64,
+	pub evictions: u64,
+	/// number of blocks in cache
+	pub current_size: usize,
+}
+
+/// which instance is serving when a hot standby is configured
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StandbyStatus {
+	/// the primary is serving
+	Active,
+	/// the primary failed and the standby is serving
+	TakingOver,
 }
 
 impl Cached {
@@ -35,62 +98,595 @@ impl Cached {
 			client,
 			queue,
 			cache: Arc::new(Mutex::new(LruCache::new(cap))),
+			persisted: Default::default(),
+			standby: None,
+			standby_status: Arc::new(Mutex::new(StandbyStatus::Active)),
+			#[cfg(feature = "metrics")]
+			metrics: None,
+			hits: Default::default(),
+			misses: Default::default(),
+			evictions: Default::default(),
+			max_persist_bytes: DEFAULT_MAX_PERSIST_BYTES,
+			dead_letter: None,
+			max_retries: DEFAULT_MAX_RETRIES,
+			retry_backoff: Duration::from_millis(DEFAULT_RETRY_BACKOFF_MS),
+			tip_loader: Arc::new(http::Client::new()),
 		})
 	}
-}
 
-impl StreamLoader for Cached {}
+	/// Retry a failed upload or publish task up to `max_retries` times, waiting `backoff`
+	/// doubled on each attempt. The settings are stored in the tasks queued afterwards.
+	pub fn with_upload_retries(mut self, max_retries: u32, backoff: Duration) -> Self {
+		self.max_retries = max_retries;
+		self.retry_backoff = backoff;
+		self
+	}
 
-#[async_trait::async_trait]
-impl CidLoader for Cached {
-	async fn load_cid(&self, cid: &Cid) -> anyhow::Result<Vec<u8>> {
+	fn retry_backoff_ms(&self) -> u64 {
+		self.retry_backoff.as_millis().min(u64::MAX as u128) as u64
+	}
+
+	/// Resolve tips of streams in batch loads with `tip_loader`, kubo cannot find the latest
+	/// tip of a stream. The state api of ceramic is used by default.
+	pub fn with_tip_loader(mut self, tip_loader: Arc<dyn StreamLoader>) -> Self {
+		self.tip_loader = tip_loader;
+		self
+	}
+
+	/// latest tip of stream, from the log of its state loaded by `tip_loader`
+	async fn resolve_tip(&self, ceramic: &Ceramic, stream_id: &StreamId) -> anyhow::Result<Cid> {
+		let state = self
+			.tip_loader
+			.load_stream_state(ceramic, stream_id, None)
+			.await?;
+		let tip = state.log.last().context("missing last log")?;
+		Ok(tip.cid.parse()?)
+	}
+
+	pub fn with_dead_letter(mut self, dead_letter: Arc<dyn DeadLetterStore>) -> Self {
+		self.dead_letter = Some(dead_letter);
+		self
+	}
+
+	/// insert task into queue, a task the queue fails to insert is kept as a dead letter
+	async fn queue_task(&self, task: &dyn AsyncRunnable) -> anyhow::Result<()> {
+		let err = match self.queue.lock().await.insert_task(task).await {
+			Ok(_) => return Ok(()),
+			Err(err) => anyhow::Error::from(err),
+		};
+		if let Some(dead_letter) = &self.dead_letter {
+			let recorded = match serde_json::to_string(task) {
+				Ok(task) => {
+					let error = err.to_string();
+					run_blocking(dead_letter, move |store| {
+						store.record_failed_task(&task, &error)
+					})
+					.await
+				}
+				Err(err) => Err(err.into()),
+			};
+			match recorded {
+				Ok(_) => tracing::warn!(?err, "failed to insert task, kept as dead letter"),
+				Err(dead_letter_err) => {
+					tracing::error!(?err, ?dead_letter_err, "failed to keep task as dead letter")
+				}
+			}
+		}
+		Err(err)
+	}
+
+	/// Insert tasks kept as dead letters back into the queue, returns the number of tasks
+	/// replayed. Stops at the first task the queue fails to insert, it is kept again as the
+	/// last dead letter for the next drain.
+	///
+	/// A dead letter is removed before its task is inserted, so a failed removal never
+	/// replays the task twice.
+	pub async fn drain_dead_letters(&self) -> anyhow::Result<usize> {
+		let dead_letter = match &self.dead_letter {
+			Some(dead_letter) => dead_letter,
+			None => return Ok(0),
+		};
+		let mut replayed = 0;
+		for (id, task_json) in run_blocking(dead_letter, |store| store.failed_tasks()).await? {
+			let task: Box<dyn AsyncRunnable> = match serde_json::from_str(&task_json) {
+				Ok(task) => task,
+				Err(err) => {
+					tracing::error!(id, ?err, "skip invalid dead letter");
+					continue;
+				}
+			};
+			run_blocking(dead_letter, move |store| store.remove_failed_task(id)).await?;
+			if let Err(err) = self.queue.lock().await.insert_task(task.as_ref()).await {
+				let error = err.to_string();
+				if let Err(dead_letter_err) = run_blocking(dead_letter, move |store| {
+					store.record_failed_task(&task_json, &error)
+				})
+				.await
+				{
+					tracing::error!(id, ?dead_letter_err, "failed to keep task as dead letter");
+				}
+				anyhow::bail!(
+					"replayed {} dead letters, then queue failed: {}",
+					replayed,
+					err
+				);
+			}
+			replayed += 1;
+		}
+		Ok(replayed)
+	}
+
+	pub fn with_max_persist_bytes(mut self, max_persist_bytes: usize) -> Self {
+		self.max_persist_bytes = max_persist_bytes;
+		self
+	}
+
+	/// Write cached blocks to `path`, blocks larger than `max_persist_bytes` are skipped.
+	///
+	/// The file is the sha256 checksum of its body followed by the body, a dag-cbor list of
+	/// `[cid, block, persisted]` from least to most recently used.
+	pub async fn save_to_disk(&self, path: &Path) -> anyhow::Result<()> {
+		let entries: Vec<Ipld> = {
+			let cache = self.cache.lock().await;
+			let persisted = self.persisted.lock().await;
+			cache
+				.iter()
+				.rev()
+				.filter(|(_, block)| block.len() <= self.max_persist_bytes)
+				.map(|(cid, block)| {
+					Ipld::List(vec![
+						Ipld::Bytes(cid.to_bytes()),
+						Ipld::Bytes(block.clone()),
+						Ipld::Bool(persisted.contains(cid)),
+					])
+				})
+				.collect()
+		};
+		let body = DagCborCodec.encode(&Ipld::List(entries))?;
+		let mut data = Sha256::digest(&body).to_vec();
+		data.extend(body);
+		tokio::fs::write(path, data).await?;
+		Ok(())
+	}
+
+	/// Create client with blocks written by `save_to_disk`,
+	/// a missing or corrupted file is discarded and the cache starts empty.
+	pub async fn load_from_disk(
+		path: &Path,
+		client: Arc<Client>,
+		queue: Arc<Mutex<AsyncQueue<MakeTlsConnector>>>,
+		cache_size: usize,
+	) -> anyhow::Result<Self> {
+		let cached = Self::new(client, queue, cache_size)?;
+		let data = match tokio::fs::read(path).await {
+			Ok(data) => data,
+			Err(err) => {
+				tracing::info!(?path, "no block cache file loaded: {}", err);
+				return Ok(cached);
+			}
+		};
+		match decode_cache_file(&data) {
+			Ok(entries) => {
+				for (cid, block, persisted) in entries {
+					cached.cache_block(cid, block, persisted).await;
+				}
+			}
+			Err(err) => tracing::warn!(?path, "discard corrupted block cache file: {}", err),
+		}
+		Ok(cached)
+	}
+
+	pub async fn stats(&self) -> CacheStats {
+		CacheStats {
+			hits: self.hits.load(Ordering::Relaxed),
+			misses: self.misses.load(Ordering::Relaxed),
+			evictions: self.evictions.load(Ordering::Relaxed),
+			current_size: self.cache.lock().await.len(),
+		}
+	}
+
+	/// Upload cached blocks not known to be stored in kubo and clear them from cache,
+	/// returns the number of uploaded blocks. Blocks failed to upload are kept in cache.
+	///
+	/// Upload tasks queued by `block_upload` may not have run before shutdown, drain the
+	/// cache on exit to keep those blocks:
+	///
+	/// ```ignore
+	/// tokio::signal::ctrl_c().await?;
+	/// let flushed = cached.drain_cache().await?;
+	/// tracing::info!(flushed, "cache drained");
+	/// ```
+	pub async fn drain_cache(&self) -> anyhow::Result<usize> {
+		let pending: Vec<(Cid, Vec<u8>)> = {
+			let cache = self.cache.lock().await;
+			let persisted = self.persisted.lock().await;
+			cache
+				.iter()
+				.filter(|(cid, _)| !persisted.contains(cid))
+				.map(|(cid, block)| (*cid, block.clone()))
+				.collect()
+		};
+
+		let mut flushed = 0;
+		let mut failed = 0;
+		for (cid, block) in pending {
+			match self.client.block_upload(cid, block).await {
+				Ok(_) => {
+					self.persisted.lock().await.insert(cid);
+					flushed += 1;
+				}
+				Err(err) => {
+					tracing::error!(cid = cid.to_string(), ?err, "failed to flush block");
+					failed += 1;
+				}
+			}
+		}
+
+		let mut cache = self.cache.lock().await;
+		let mut persisted = self.persisted.lock().await;
+		let drained: Vec<Cid> = cache
+			.iter()
+			.filter(|(cid, _)| persisted.contains(cid))
+			.map(|(cid, _)| *cid)
+			.collect();
+		for cid in drained {
+			cache.pop(&cid);
+			persisted.remove(&cid);
+		}
+		if failed > 0 {
+			anyhow::bail!("flushed {} blocks, {} blocks failed", flushed, failed);
+		}
+		Ok(flushed)
+	}
+
+	/// export hits and misses of `stats` as prometheus counters, counts before are dropped
+	#[cfg(feature = "metrics")]
+	pub fn with_metrics(mut self, metrics: super::metrics::CacheMetrics) -> Self {
+		self.hits = metrics.hits.clone();
+		self.misses = metrics.misses.clone();
+		self.metrics = Some(metrics);
+		self
+	}
+
+	/// current values of cache metrics in prometheus text format,
+	/// all zero when metrics are not configured
+	#[cfg(feature = "metrics")]
+	pub fn metrics_snapshot(&self) -> anyhow::Result<String> {
+		self.metrics.clone().unwrap_or_default().encode()
+	}
+
+	/// Set a secondary instance which receives all writes and serves reads when the primary fails.
+	pub fn with_hot_standby(mut self, standby: Arc<Cached>) -> Self {
+		self.standby = Some(standby);
+		self
+	}
+
+	pub async fn standby_status(&self) -> StandbyStatus {
+		*self.standby_status.lock().await
+	}
+
+	async fn set_standby_status(&self, status: StandbyStatus) {
+		let mut current = self.standby_status.lock().await;
+		if *current != status {
+			tracing::info!(?status, "hot standby status changed");
+			*current = status;
+		}
+	}
+
+	/// Settle a write sent to both instances, it succeeds when either of them succeeds.
+	///
+	/// The standby is written through its queue directly, so its errors reach here instead of
+	/// being logged and dropped like writes without standby.
+	async fn settle_write(
+		&self,
+		primary: anyhow::Result<()>,
+		standby: anyhow::Result<()>,
+	) -> anyhow::Result<()> {
+		match (primary, standby) {
+			(Ok(_), _) => {
+				self.set_standby_status(StandbyStatus::Active).await;
+				Ok(())
+			}
+			(Err(err), Ok(_)) => {
+				tracing::warn!(?err, "primary write failed, standby taking over");
+				self.set_standby_status(StandbyStatus::TakingOver).await;
+				Ok(())
+			}
+			(Err(err), Err(standby_err)) => {
+				anyhow::bail!(
+					"primary write failed: {}, standby write failed: {}",
+					err,
+					standby_err
+				)
+			}
+		}
+	}
+
+	async fn load_cid_primary(&self, cid: &Cid) -> anyhow::Result<Vec<u8>> {
 		let data_opt;
 		{
 			let mut cache = self.cache.lock().await;
 			data_opt = cache.get(&cid).map(|data| data.to_vec());
 		}
 		if let Some(data) = data_opt {
+			self.hits.fetch_add(1, Ordering::Relaxed);
+			tracing::debug!(cid = cid.to_string(), "block cache hit");
 			return Ok(data);
 		}
+		self.misses.fetch_add(1, Ordering::Relaxed);
+		tracing::debug!(cid = cid.to_string(), "block cache miss");
 		match self.client.load_cid(cid).await {
 			Ok(data) => {
-				let mut cache = self.cache.lock().await;
-				cache.put(cid.clone(), data.to_vec());
+				self.cache_block(*cid, data.to_vec(), true).await;
+				// kubo of the primary answered, it serves again after a takeover
+				self.set_standby_status(StandbyStatus::Active).await;
 				Ok(data)
 			}
 			Err(err) => Err(err),
 		}
 	}
+
+	/// Put block into cache, cids evicted from cache are no longer tracked as persisted.
+	///
+	/// A cid known to be stored in kubo stays persisted when its block is cached again with
+	/// `persisted` false, blocks are content addressed so kubo already has it.
+	async fn cache_block(&self, cid: Cid, block: Vec<u8>, persisted: bool) {
+		self.cache_blocks(vec![(cid, block)], persisted).await
+	}
+
+	/// put blocks into cache under one lock, like `cache_block`
+	async fn cache_blocks(&self, blocks: Vec<(Cid, Vec<u8>)>, persisted: bool) {
+		let mut cache = self.cache.lock().await;
+		let mut persisted_cids = self.persisted.lock().await;
+		for (cid, block) in blocks {
+			if let Some((evicted, _)) = cache.push(cid, block) {
+				if evicted != cid {
+					persisted_cids.remove(&evicted);
+					self.evictions.fetch_add(1, Ordering::Relaxed);
+				}
+			}
+			if persisted {
+				persisted_cids.insert(cid);
+			}
+		}
+	}
+
+	async fn enqueue_block_upload(&self, cid: Cid, block: Vec<u8>) -> anyhow::Result<()> {
+		self.cache_block(cid, block.clone(), false).await;
+		let task = BlockUploadHandler {
+			cid,
+			block,
+			max_retries: self.max_retries,
+			retry_backoff_ms: self.retry_backoff_ms(),
+		};
+		self.queue_task(&task).await
+	}
+
+	async fn enqueue_block_upload_many(&self, blocks: Vec<(Cid, Vec<u8>)>) -> anyhow::Result<()> {
+		self.cache_blocks(blocks.clone(), false).await;
+		let task = BatchBlockUploadHandler {
+			blocks,
+			max_retries: self.max_retries,
+			retry_backoff_ms: self.retry_backoff_ms(),
+		};
+		self.queue_task(&task).await
+	}
+
+	async fn enqueue_message(&self, topic: &String, msg: Vec<u8>) -> anyhow::Result<()> {
+		let task = UpdateMessagePublishHandler {
+			topic: topic.clone(),
+			msg,
+			max_retries: self.max_retries,
+			retry_backoff_ms: self.retry_backoff_ms(),
+		};
+		self.queue_task(&task).await
+	}
+
+	/// Fetch blocks of cids into cache, `max_concurrency` blocks are requested at a time.
+	///
+	/// The kubo rpc client has no `dag/export`, so every block is its own `block/get` request,
+	/// this only bounds how many run at once.
+	/// The cache keeps its capacity, prefetching more blocks than it can hold evicts the least
+	/// recently used entries, including blocks prefetched earlier in the same call.
+	/// Returns the number of blocks newly cached, failed blocks are skipped.
+	pub async fn prefetch_cids_concurrently(
+		&self,
+		cids: &[Cid],
+		max_concurrency: usize,
+	) -> anyhow::Result<usize> {
+		if max_concurrency == 0 {
+			anyhow::bail!("{} is not a valid concurrency", max_concurrency);
+		}
+		let missing: Vec<Cid> = {
+			let cache = self.cache.lock().await;
+			cids.iter()
+				.filter(|cid| !cache.contains(cid))
+				.cloned()
+				.collect()
+		};
+
+		let mut count = 0;
+		for chunk in missing.chunks(max_concurrency) {
+			let blocks = join_all(chunk.iter().map(|cid| self.client.load_cid(cid))).await;
+			for (cid, block) in chunk.iter().zip(blocks) {
+				match block {
+					Ok(block) => {
+						self.cache_block(*cid, block, true).await;
+						count += 1;
+					}
+					Err(err) => {
+						tracing::warn!(cid = cid.to_string(), ?err, "failed to prefetch block")
+					}
+				}
+			}
+		}
+		Ok(count)
+	}
+}
+
+fn decode_cache_file(data: &[u8]) -> anyhow::Result<Vec<(Cid, Vec<u8>, bool)>> {
+	if data.len() < 32 {
+		anyhow::bail!("file too short");
+	}
+	let (checksum, body) = data.split_at(32);
+	if Sha256::digest(body).as_slice() != checksum {
+		anyhow::bail!("checksum mismatch");
+	}
+	let entries = match DagCborCodec.decode::<Ipld>(body)? {
+		Ipld::List(entries) => entries,
+		_ => anyhow::bail!("expected a list of entries"),
+	};
+	let mut result = Vec::with_capacity(entries.len());
+	for entry in entries {
+		match entry {
+			Ipld::List(fields) => match fields.as_slice() {
+				[Ipld::Bytes(cid), Ipld::Bytes(block), Ipld::Bool(persisted)] => {
+					result.push((Cid::try_from(cid.as_slice())?, block.clone(), *persisted))
+				}
+				_ => anyhow::bail!("invalid entry fields"),
+			},
+			_ => anyhow::bail!("invalid entry"),
+		}
+	}
+	Ok(result)
+}
+
+#[async_trait::async_trait]
+impl StreamLoader for Cached {
+	/// Resolve tips of streams with `tip_loader`, prefetch the tip blocks, then load states
+	/// from kubo at the tips, `MAX_CONCURRENT_LOADS` at a time.
+	/// A stream whose tip is not resolved fails alone.
+	async fn load_stream_states_batch(
+		&self,
+		ceramic: &Ceramic,
+		ids: &[StreamId],
+	) -> anyhow::Result<Vec<(StreamId, anyhow::Result<StreamState>)>> {
+		let tips: Vec<_> = futures::stream::iter(
+			ids.iter()
+				.map(|stream_id| self.resolve_tip(ceramic, stream_id)),
+		)
+		.buffered(MAX_CONCURRENT_LOADS)
+		.collect()
+		.await;
+		let resolved: Vec<Cid> = tips
+			.iter()
+			.filter_map(|tip| tip.as_ref().ok())
+			.copied()
+			.collect();
+		self.prefetch_cids_concurrently(&resolved, MAX_CONCURRENT_LOADS)
+			.await?;
+
+		let states: Vec<_> =
+			futures::stream::iter(ids.iter().zip(tips).map(|(stream_id, tip)| async move {
+				self.load_stream_state(ceramic, stream_id, Some(tip?)).await
+			}))
+			.buffered(MAX_CONCURRENT_LOADS)
+			.collect()
+			.await;
+		Ok(ids.iter().cloned().zip(states).collect())
+	}
+}
+
+#[async_trait::async_trait]
+impl CidLoader for Cached {
+	async fn load_cid(&self, cid: &Cid) -> anyhow::Result<Vec<u8>> {
+		let err = match self.load_cid_primary(cid).await {
+			Ok(data) => return Ok(data),
+			Err(err) => err,
+		};
+		match &self.standby {
+			Some(standby) => {
+				tracing::warn!(
+					cid = cid.to_string(),
+					?err,
+					"primary load failed, trying standby"
+				);
+				let data = standby.load_cid(cid).await?;
+				self.set_standby_status(StandbyStatus::TakingOver).await;
+				Ok(data)
+			}
+			None => Err(err),
+		}
+	}
+}
+
+#[async_trait::async_trait]
+impl CidPinner for Cached {
+	async fn pin_cid(&self, cid: &Cid) -> anyhow::Result<()> {
+		self.client.pin_cid(cid).await
+	}
+
+	async fn unpin_cid(&self, cid: &Cid) -> anyhow::Result<()> {
+		self.client.unpin_cid(cid).await
+	}
 }
 
 #[async_trait::async_trait]
 impl BlockUploader for Cached {
 	async fn block_upload(&self, cid: Cid, block: Vec<u8>) -> anyhow::Result<()> {
-		self.cache.lock().await.put(cid, block.clone());
-		let task = BlockUploadHandler { cid, block };
-		if let Err(err) = self.queue.lock().await.insert_task(&task).await {
-			log::error!("failed to insert task: {}", err);
-		};
-		Ok(())
+		match &self.standby {
+			Some(standby) => {
+				let (primary, secondary) = futures::join!(
+					self.enqueue_block_upload(cid, block.clone()),
+					standby.enqueue_block_upload(cid, block)
+				);
+				self.settle_write(primary, secondary).await
+			}
+			None => {
+				if let Err(err) = self.enqueue_block_upload(cid, block).await {
+					log::error!("failed to insert task: {}", err);
+				};
+				Ok(())
+			}
+		}
+	}
+
+	/// cache all blocks at once and queue a single task uploading them
+	async fn block_upload_many(&self, blocks: Vec<(Cid, Vec<u8>)>) -> anyhow::Result<()> {
+		if blocks.is_empty() {
+			return Ok(());
+		}
+		match &self.standby {
+			Some(standby) => {
+				let (primary, secondary) = futures::join!(
+					self.enqueue_block_upload_many(blocks.clone()),
+					standby.enqueue_block_upload_many(blocks)
+				);
+				self.settle_write(primary, secondary).await
+			}
+			None => {
+				if let Err(err) = self.enqueue_block_upload_many(blocks).await {
+					log::error!("failed to insert task: {}", err);
+				};
+				Ok(())
+			}
+		}
 	}
 }
 
 #[async_trait::async_trait]
 impl MessagePublisher for Cached {
 	async fn publish_message(&self, topic: &String, msg: Vec<u8>) -> anyhow::Result<()> {
-		let task = UpdateMessagePublishHandler {
-			topic: topic.clone(),
-			msg,
-		};
-		if let Err(err) = self.queue.lock().await.insert_task(&task).await {
-			log::error!("failed to insert task: {}", err);
-		};
-		Ok(())
+		match &self.standby {
+			Some(standby) => {
+				let (primary, secondary) = futures::join!(
+					self.enqueue_message(topic, msg.clone()),
+					standby.enqueue_message(topic, msg)
+				);
+				self.settle_write(primary, secondary).await
+			}
+			None => {
+				if let Err(err) = self.enqueue_message(topic, msg).await {
+					log::error!("failed to insert task: {}", err);
+				};
+				Ok(())
+			}
+		}
 	}
 }
 
 #[async_trait::async_trait]
-impl AnchorRuester for Cached {
+impl AnchorRequester for Cached {
 	async fn request_anchor(
 		&self,
 		ceramic: &Ceramic,
@@ -103,10 +699,385 @@ impl AnchorRuester for Cached {
 				stream_id: stream_id.clone(),
 				commit: event,
 			};
-			if let Err(err) = self.queue.lock().await.insert_task(&task).await {
+			if let Err(err) = self.queue_task(&task).await {
 				log::error!("failed to insert task: {}", err);
 			};
 		}
 		Ok(())
 	}
+
+	async fn batch_request_anchor(
+		&self,
+		ceramic: &Ceramic,
+		requests: Vec<(StreamId, Event)>,
+	) -> anyhow::Result<Vec<anyhow::Result<()>>> {
+		let len = requests.len();
+		let task = http::BatchAnchorTask {
+			ceramic: ceramic.clone(),
+			requests: requests
+				.into_iter()
+				.filter(|(_, event)| matches!(event.value, EventValue::Signed(_)))
+				.collect(),
+		};
+		if task.requests.is_empty() {
+			return Ok((0..len).map(|_| Ok(())).collect());
+		}
+		let result = self.queue_task(&task).await;
+		Ok((0..len)
+			.map(|_| match &result {
+				Ok(_) => Ok(()),
+				Err(err) => Err(anyhow::anyhow!("failed to insert task: {}", err)),
+			})
+			.collect())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::str::FromStr;
+	use std::sync::atomic::AtomicBool;
+
+	use super::*;
+	use crate::event::ToCid;
+
+	fn cached(cache_size: usize) -> anyhow::Result<Cached> {
+		let client = Arc::new(crate::kubo::new("http://127.0.0.1:1"));
+		let queue = AsyncQueue::builder()
+			.uri("postgres://127.0.0.1:1/fang")
+			.max_pool_size(1_u32)
+			.build();
+		Cached::new(client, Arc::new(Mutex::new(queue)), cache_size)
+	}
+
+	/// ids are never reused, as with the autoincrement ids of sqlite
+	#[derive(Default)]
+	struct MemoryDeadLetters {
+		tasks: std::sync::Mutex<Vec<(i64, String)>>,
+		next_id: std::sync::atomic::AtomicI64,
+	}
+
+	impl DeadLetterStore for MemoryDeadLetters {
+		fn record_failed_task(&self, task_json: &str, _error: &str) -> anyhow::Result<()> {
+			let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+			self.tasks.lock().unwrap().push((id, task_json.to_string()));
+			Ok(())
+		}
+
+		fn failed_tasks(&self) -> anyhow::Result<Vec<(i64, String)>> {
+			Ok(self.tasks.lock().unwrap().clone())
+		}
+
+		fn remove_failed_task(&self, id: i64) -> anyhow::Result<()> {
+			self.tasks
+				.lock()
+				.unwrap()
+				.retain(|(task_id, _)| *task_id != id);
+			Ok(())
+		}
+	}
+
+	#[tokio::test]
+	async fn failed_tasks_kept_as_dead_letters() -> anyhow::Result<()> {
+		let dead_letters = Arc::new(MemoryDeadLetters::default());
+		let cached = cached(10)?.with_dead_letter(dead_letters.clone());
+		let cid = Cid::from_str("bafyreid43i4yornrup5nuiiu5bavu3k5se4z7wrokwd2oznvanp27eo7xe")?;
+
+		cached.block_upload(cid, vec![1, 2, 3]).await?;
+		cached
+			.publish_message(&"topic".to_string(), vec![4])
+			.await?;
+		let tasks = dead_letters.failed_tasks()?;
+		assert_eq!(tasks.len(), 2);
+		let task: Box<dyn AsyncRunnable> = serde_json::from_str(&tasks[0].1)?;
+		assert!(task.uniq());
+
+		// queue still unavailable, dead letters are kept, the first one as the last
+		assert!(cached.drain_dead_letters().await.is_err());
+		let kept = dead_letters.failed_tasks()?;
+		assert_eq!(kept.len(), 2);
+		assert_eq!(kept[0], tasks[1]);
+		assert_eq!(kept[1].1, tasks[0].1);
+		assert!(kept[1].0 > tasks[1].0);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn standby_takes_over_failed_writes() -> anyhow::Result<()> {
+		let cached = cached(10)?;
+		let failed = || Err(anyhow::anyhow!("queue unavailable"));
+
+		cached.settle_write(failed(), Ok(())).await?;
+		assert_eq!(cached.standby_status().await, StandbyStatus::TakingOver);
+		cached.settle_write(Ok(()), failed()).await?;
+		assert_eq!(cached.standby_status().await, StandbyStatus::Active);
+		assert!(cached.settle_write(failed(), failed()).await.is_err());
+		Ok(())
+	}
+
+	/// url of a kubo answering every request with `block` while `up`, connections are closed
+	/// without answer otherwise
+	async fn serve_block(block: &'static [u8], up: Arc<AtomicBool>) -> anyhow::Result<String> {
+		use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+		let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+		let url = format!("http://{}", listener.local_addr()?);
+		tokio::spawn(async move {
+			while let Ok((mut socket, _)) = listener.accept().await {
+				if !up.load(Ordering::SeqCst) {
+					continue;
+				}
+				let mut request = [0; 1024];
+				let _ = socket.read(&mut request).await;
+				let head = format!(
+					"HTTP/1.1 200 OK\r\ncontent-type: application/octet-stream\r\n\
+					 content-length: {}\r\nconnection: close\r\n\r\n",
+					block.len()
+				);
+				let _ = socket.write_all(head.as_bytes()).await;
+				let _ = socket.write_all(block).await;
+			}
+		});
+		Ok(url)
+	}
+
+	#[tokio::test]
+	async fn primary_serves_again_after_recovery() -> anyhow::Result<()> {
+		let cid = Cid::from_str("bafyreid43i4yornrup5nuiiu5bavu3k5se4z7wrokwd2oznvanp27eo7xe")?;
+		let standby = cached(10)?;
+		standby.cache_block(cid, vec![1, 2, 3], true).await;
+		let up = Arc::new(AtomicBool::new(false));
+		let url = serve_block(b"block", up.clone()).await?;
+		let cached = Cached::new(Arc::new(crate::kubo::new(&url)), standby.queue.clone(), 10)?
+			.with_hot_standby(Arc::new(standby));
+
+		// primary kubo is down, the block is served by the standby
+		assert_eq!(cached.load_cid(&cid).await?, vec![1, 2, 3]);
+		assert_eq!(cached.standby_status().await, StandbyStatus::TakingOver);
+
+		up.store(true, Ordering::SeqCst);
+		assert_eq!(cached.load_cid(&cid).await?, b"block".to_vec());
+		assert_eq!(cached.standby_status().await, StandbyStatus::Active);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn writes_fail_when_primary_and_standby_fail() -> anyhow::Result<()> {
+		let cached = cached(10)?.with_hot_standby(Arc::new(cached(10)?));
+		let cid = Cid::from_str("bafyreid43i4yornrup5nuiiu5bavu3k5se4z7wrokwd2oznvanp27eo7xe")?;
+
+		let err = cached.block_upload(cid, vec![1, 2, 3]).await.unwrap_err();
+		assert!(err.to_string().contains("standby write failed"));
+		assert!(cached
+			.block_upload_many(vec![(cid, vec![1, 2, 3])])
+			.await
+			.is_err());
+		assert!(cached
+			.publish_message(&"topic".to_string(), vec![4])
+			.await
+			.is_err());
+		assert_eq!(cached.standby_status().await, StandbyStatus::Active);
+		Ok(())
+	}
+
+	#[cfg(feature = "metrics")]
+	fn counter(snapshot: &str, name: &str) -> Option<u64> {
+		snapshot
+			.lines()
+			.find_map(|line| line.strip_prefix(&format!("{} ", name)))
+			.and_then(|value| value.parse().ok())
+	}
+
+	#[cfg(feature = "metrics")]
+	#[tokio::test]
+	async fn metrics_snapshot_counts_hits_and_misses() -> anyhow::Result<()> {
+		let cached = cached(10)?.with_metrics(crate::kubo::metrics::CacheMetrics::new()?);
+		let cached_cid =
+			Cid::from_str("bafyreid43i4yornrup5nuiiu5bavu3k5se4z7wrokwd2oznvanp27eo7xe")?;
+		let missing_cid =
+			Cid::from_str("bafyreihtmj5y6lbm23uulkwddp2hdiw4frhe6ofiunoqqjkcxasvuxlbrq")?;
+		cached.cache.lock().await.put(cached_cid, vec![1, 2, 3]);
+
+		for _ in 0..3 {
+			assert_eq!(cached.load_cid(&cached_cid).await?, vec![1, 2, 3]);
+		}
+		for _ in 0..2 {
+			assert!(cached.load_cid(&missing_cid).await.is_err());
+		}
+
+		let snapshot = cached.metrics_snapshot()?;
+		assert_eq!(counter(&snapshot, "kubo_cache_hits_total"), Some(3));
+		assert_eq!(counter(&snapshot, "kubo_cache_misses_total"), Some(2));
+		let stats = cached.stats().await;
+		assert_eq!((stats.hits, stats.misses), (3, 2));
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn stats_count_hits_misses_and_evictions() -> anyhow::Result<()> {
+		let cached = cached(2)?;
+		let cids = [
+			"bafyreid43i4yornrup5nuiiu5bavu3k5se4z7wrokwd2oznvanp27eo7xe",
+			"bafyreihtmj5y6lbm23uulkwddp2hdiw4frhe6ofiunoqqjkcxasvuxlbrq",
+			"bafyreidnbzsaplrdpjx3schac4fjhwqjzv3kbvdswi52npq3kpdzpbv5qa",
+		]
+		.map(Cid::from_str)
+		.into_iter()
+		.collect::<Result<Vec<_>, _>>()?;
+		for cid in &cids {
+			cached.cache_block(*cid, vec![1], true).await;
+		}
+
+		assert!(cached.load_cid(&cids[2]).await.is_ok());
+		assert!(cached.load_cid(&cids[0]).await.is_err());
+		assert_eq!(
+			cached.stats().await,
+			CacheStats {
+				hits: 1,
+				misses: 1,
+				evictions: 1,
+				current_size: 2,
+			}
+		);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn cache_blocks_in_batch() -> anyhow::Result<()> {
+		let cached = cached(2)?;
+		let cids = [
+			"bafyreid43i4yornrup5nuiiu5bavu3k5se4z7wrokwd2oznvanp27eo7xe",
+			"bafyreihtmj5y6lbm23uulkwddp2hdiw4frhe6ofiunoqqjkcxasvuxlbrq",
+			"bafyreidnbzsaplrdpjx3schac4fjhwqjzv3kbvdswi52npq3kpdzpbv5qa",
+		]
+		.map(Cid::from_str)
+		.into_iter()
+		.collect::<Result<Vec<_>, _>>()?;
+		cached.cache_block(cids[0], vec![0], true).await;
+
+		let blocks = cids[1..].iter().map(|cid| (*cid, vec![1])).collect();
+		cached.cache_blocks(blocks, false).await;
+		let cache = cached.cache.lock().await;
+		assert!(!cache.contains(&cids[0]));
+		assert_eq!(cache.peek(&cids[2]), Some(&vec![1]));
+		assert!(cached.persisted.lock().await.is_empty());
+		assert_eq!(cached.evictions.load(Ordering::Relaxed), 1);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn drain_cache_skips_persisted_blocks() -> anyhow::Result<()> {
+		let cached = cached(10)?;
+		let persisted =
+			Cid::from_str("bafyreid43i4yornrup5nuiiu5bavu3k5se4z7wrokwd2oznvanp27eo7xe")?;
+		let pending = Cid::from_str("bafyreihtmj5y6lbm23uulkwddp2hdiw4frhe6ofiunoqqjkcxasvuxlbrq")?;
+		cached.cache_block(persisted, vec![0], true).await;
+		// uploaded again, as `block_upload` of a block loaded from kubo
+		cached.cache_block(persisted, vec![0], false).await;
+		cached.cache_block(pending, vec![1], false).await;
+
+		// kubo is unreachable, only the pending block is tried
+		let err = cached.drain_cache().await.unwrap_err();
+		assert_eq!(err.to_string(), "flushed 0 blocks, 1 blocks failed");
+		let cache = cached.cache.lock().await;
+		assert!(!cache.contains(&persisted));
+		assert!(cache.contains(&pending));
+		Ok(())
+	}
+
+	/// state api of ceramic knowing only the example genesis stream
+	struct ExampleStateLoader;
+
+	#[async_trait::async_trait]
+	impl crate::EventsLoader for ExampleStateLoader {
+		async fn load_events(
+			&self,
+			_ceramic: &Ceramic,
+			stream_id: &StreamId,
+			_tip: Option<Cid>,
+		) -> anyhow::Result<Vec<Event>> {
+			let genesis = crate::commit::example::genesis();
+			if *stream_id != genesis.stream_id()? {
+				anyhow::bail!("stream {} not found", stream_id);
+			}
+			Ok(vec![genesis.genesis.try_into()?])
+		}
+	}
+
+	impl StreamLoader for ExampleStateLoader {}
+
+	#[tokio::test]
+	async fn batch_load_states_at_resolved_tips() -> anyhow::Result<()> {
+		let cached = cached(10)?.with_tip_loader(Arc::new(ExampleStateLoader));
+		let genesis = crate::commit::example::genesis();
+		let known = genesis.stream_id()?;
+		let event: Event = genesis.genesis.try_into()?;
+		// blocks of the genesis are cached, the kubo client is never reached
+		let signed = match &event.value {
+			EventValue::Signed(signed) => signed,
+			EventValue::Anchor(_) => anyhow::bail!("example genesis is not signed"),
+		};
+		let blocks = vec![
+			(event.cid, signed.jws.to_vec()?),
+			(
+				signed.payload_link()?,
+				signed.linked_block.clone().unwrap_or_default(),
+			),
+			(
+				signed.cacao_link()?,
+				signed.cacao_block.clone().unwrap_or_default(),
+			),
+		];
+		cached.cache_blocks(blocks, true).await;
+		let unknown: StreamId =
+			"kjzl6kcym7w8y5fhg4cl0xi8npfke3jmaaeeic9dwx64bgunnqa6amortdgdsym".parse()?;
+
+		let ceramic = Ceramic {
+			endpoint: "http://localhost:7007".into(),
+			network: crate::network::Network::InMemory,
+			pool: None,
+		};
+		let states = cached
+			.load_stream_states_batch(&ceramic, &[known.clone(), unknown.clone()])
+			.await?;
+		assert_eq!(states.len(), 2);
+		assert_eq!(states[0].0, known);
+		let state = states[0]
+			.1
+			.as_ref()
+			.map_err(|err| anyhow::anyhow!("{}", err))?;
+		assert_eq!(state.stream_id()?, known);
+		assert_eq!(state.log.len(), 1);
+		assert_eq!(states[1].0, unknown);
+		assert!(states[1].1.is_err());
+		assert_eq!(cached.stats().await.misses, 0);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn save_and_load_from_disk() -> anyhow::Result<()> {
+		let path = std::env::temp_dir().join(format!("kubo-cache-{}", std::process::id()));
+		let small = Cid::from_str("bafyreid43i4yornrup5nuiiu5bavu3k5se4z7wrokwd2oznvanp27eo7xe")?;
+		let large = Cid::from_str("bafyreihtmj5y6lbm23uulkwddp2hdiw4frhe6ofiunoqqjkcxasvuxlbrq")?;
+		let cached = cached(10)?.with_max_persist_bytes(4);
+		cached.cache_block(small, vec![1, 2, 3], true).await;
+		cached.cache_block(large, vec![0; 16], false).await;
+		cached.save_to_disk(&path).await?;
+
+		let loaded =
+			Cached::load_from_disk(&path, cached.client.clone(), cached.queue.clone(), 10).await?;
+		assert_eq!(loaded.cache.lock().await.peek(&small), Some(&vec![1, 2, 3]));
+		assert!(!loaded.cache.lock().await.contains(&large));
+		assert!(loaded.persisted.lock().await.contains(&small));
+
+		let mut data = tokio::fs::read(&path).await?;
+		let last = data.len() - 1;
+		data[last] ^= 0xff;
+		tokio::fs::write(&path, data).await?;
+		let corrupted =
+			Cached::load_from_disk(&path, cached.client.clone(), cached.queue.clone(), 10).await?;
+		assert_eq!(corrupted.cache.lock().await.len(), 0);
+
+		tokio::fs::remove_file(&path).await?;
+		Ok(())
+	}
 }
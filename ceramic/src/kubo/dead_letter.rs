@@ -0,0 +1,94 @@
+/// Tasks which failed to be inserted into the queue, kept to be replayed once it's back.
+pub trait DeadLetterStore: Send + Sync {
+	/// `task_json` is the task serialized as fang stores it in the queue
+	fn record_failed_task(&self, task_json: &str, error: &str) -> anyhow::Result<()>;
+
+	/// recorded tasks with their ids, oldest first
+	fn failed_tasks(&self) -> anyhow::Result<Vec<(i64, String)>>;
+
+	fn remove_failed_task(&self, id: i64) -> anyhow::Result<()>;
+}
+
+/// dead letters in a local sqlite file, which stays writable while postgres is unreachable
+#[cfg(feature = "sqlite")]
+pub struct SqliteDeadLetterStore {
+	conn: std::sync::Mutex<rusqlite::Connection>,
+}
+
+#[cfg(feature = "sqlite")]
+impl SqliteDeadLetterStore {
+	pub fn open(path: impl AsRef<std::path::Path>) -> anyhow::Result<Self> {
+		Self::new(rusqlite::Connection::open(path)?)
+	}
+
+	pub fn open_in_memory() -> anyhow::Result<Self> {
+		Self::new(rusqlite::Connection::open_in_memory()?)
+	}
+
+	fn new(conn: rusqlite::Connection) -> anyhow::Result<Self> {
+		conn.execute(
+			"CREATE TABLE IF NOT EXISTS dead_letters (
+				id INTEGER PRIMARY KEY AUTOINCREMENT,
+				task TEXT NOT NULL,
+				error TEXT NOT NULL,
+				created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+			)",
+			(),
+		)?;
+		Ok(Self {
+			conn: std::sync::Mutex::new(conn),
+		})
+	}
+
+	fn conn(&self) -> anyhow::Result<std::sync::MutexGuard<'_, rusqlite::Connection>> {
+		self.conn
+			.lock()
+			.map_err(|_| anyhow::anyhow!("dead letter store poisoned"))
+	}
+}
+
+#[cfg(feature = "sqlite")]
+impl DeadLetterStore for SqliteDeadLetterStore {
+	fn record_failed_task(&self, task_json: &str, error: &str) -> anyhow::Result<()> {
+		self.conn()?.execute(
+			"INSERT INTO dead_letters (task, error) VALUES (?1, ?2)",
+			(task_json, error),
+		)?;
+		Ok(())
+	}
+
+	fn failed_tasks(&self) -> anyhow::Result<Vec<(i64, String)>> {
+		let conn = self.conn()?;
+		let mut stmt = conn.prepare("SELECT id, task FROM dead_letters ORDER BY id")?;
+		let rows = stmt.query_map((), |row| Ok((row.get(0)?, row.get(1)?)))?;
+		Ok(rows.collect::<Result<_, _>>()?)
+	}
+
+	fn remove_failed_task(&self, id: i64) -> anyhow::Result<()> {
+		self.conn()?
+			.execute("DELETE FROM dead_letters WHERE id = ?1", (id,))?;
+		Ok(())
+	}
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn sqlite_dead_letters() -> anyhow::Result<()> {
+		let store = SqliteDeadLetterStore::open_in_memory()?;
+		store.record_failed_task(r#"{"a":1}"#, "not connected")?;
+		store.record_failed_task(r#"{"b":2}"#, "not connected")?;
+
+		let tasks = store.failed_tasks()?;
+		assert_eq!(tasks.len(), 2);
+		assert_eq!(tasks[0].1, r#"{"a":1}"#);
+
+		store.remove_failed_task(tasks[0].0)?;
+		let tasks = store.failed_tasks()?;
+		assert_eq!(tasks.len(), 1);
+		assert_eq!(tasks[0].1, r#"{"b":2}"#);
+		Ok(())
+	}
+}
@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use prometheus::core::{Collector, Desc};
+use prometheus::proto::{Counter, Metric, MetricFamily, MetricType};
+use prometheus::{Encoder, Registry, TextEncoder};
+
+/// prometheus metrics of block cache, read from the counters behind `Cached::stats`
+#[derive(Debug, Clone)]
+pub struct CacheMetrics {
+	pub registry: Registry,
+	pub(crate) hits: Arc<AtomicU64>,
+	pub(crate) misses: Arc<AtomicU64>,
+}
+
+impl CacheMetrics {
+	pub fn new() -> anyhow::Result<Self> {
+		let registry = Registry::new();
+		let hits = Arc::new(AtomicU64::new(0));
+		let misses = Arc::new(AtomicU64::new(0));
+		registry.register(Box::new(PullingCounter::new(
+			"kubo_cache_hits_total",
+			"blocks loaded from cache",
+			hits.clone(),
+		)?))?;
+		registry.register(Box::new(PullingCounter::new(
+			"kubo_cache_misses_total",
+			"blocks missing in cache",
+			misses.clone(),
+		)?))?;
+		Ok(Self {
+			registry,
+			hits,
+			misses,
+		})
+	}
+
+	/// encode current values of metrics in prometheus text format
+	pub fn encode(&self) -> anyhow::Result<String> {
+		Ok(TextEncoder::new().encode_to_string(&self.registry.gather())?)
+	}
+}
+
+impl Default for CacheMetrics {
+	fn default() -> Self {
+		Self::new().expect("cache metrics should be valid")
+	}
+}
+
+/// counter collecting its value from an atomic counted elsewhere
+struct PullingCounter {
+	desc: Desc,
+	value: Arc<AtomicU64>,
+}
+
+impl PullingCounter {
+	fn new(name: &str, help: &str, value: Arc<AtomicU64>) -> anyhow::Result<Self> {
+		let desc = Desc::new(name.into(), help.into(), vec![], HashMap::new())?;
+		Ok(Self { desc, value })
+	}
+}
+
+impl Collector for PullingCounter {
+	fn desc(&self) -> Vec<&Desc> {
+		vec![&self.desc]
+	}
+
+	fn collect(&self) -> Vec<MetricFamily> {
+		let mut counter = Counter::default();
+		counter.set_value(self.value.load(Ordering::Relaxed) as f64);
+		let mut metric = Metric::default();
+		metric.set_counter(counter);
+
+		let mut family = MetricFamily::default();
+		family.set_name(self.desc.fq_name.clone());
+		family.set_help(self.desc.help.clone());
+		family.set_field_type(MetricType::COUNTER);
+		family.set_metric(vec![metric].into());
+		vec![family]
+	}
+}
@@ -1,17 +1,26 @@
 pub mod cache;
+pub mod dead_letter;
 pub mod message;
+#[cfg(feature = "metrics")]
+pub mod metrics;
 pub mod pubsub;
 pub mod store;
 pub mod task;
 
-pub use cache::Cached;
+pub use cache::{CacheStats, Cached, StandbyStatus};
+pub use dead_letter::DeadLetterStore;
+#[cfg(feature = "sqlite")]
+pub use dead_letter::SqliteDeadLetterStore;
 pub use store::Store;
 
 use ceramic_core::{Cid, StreamId};
 use ceramic_kubo_rpc_server::models;
 use ceramic_kubo_rpc_server::{ApiNoContext, ContextWrapperExt};
 use ceramic_kubo_rpc_server::{BlockGetPostResponse, BlockPutPostResponse};
+use ceramic_kubo_rpc_server::{PinAddPostResponse, PinRmPostResponse};
 use int_enum::IntEnum;
+use libipld::{cbor::DagCborCodec, json::DagJsonCodec, prelude::Codec, Ipld};
+use serde::de::DeserializeOwned;
 use swagger::{AuthData, ByteArray, ContextBuilder, EmptyContext, Push, XSpanIdString};
 
 use crate::event::{self, Event, EventsLoader, EventsUploader, ToCid};
@@ -37,10 +46,17 @@ pub fn new(base_path: &str) -> Client {
 	);
 
 	// Using HTTP
-	let client = Box::new(
-		ceramic_kubo_rpc_server::Client::try_new_http(&base_path)
-			.expect("Failed to create HTTP client"),
+	#[cfg(not(feature = "opentelemetry"))]
+	let client = ceramic_kubo_rpc_server::Client::try_new_http(&base_path);
+	// requests carry the trace headers of the calling span
+	#[cfg(feature = "opentelemetry")]
+	let client = ceramic_kubo_rpc_server::Client::try_new_with_client_service(
+		crate::telemetry::TraceContextService::new(swagger::DropContextService::new(
+			hyper::Client::new(),
+		)),
+		&base_path,
 	);
+	let client = Box::new(client.expect("Failed to create HTTP client"));
 	Box::new(client.with_context(context))
 }
 
@@ -73,6 +89,17 @@ pub trait CidLoader {
 			}
 		}
 	}
+
+	/// load block of cid and decode it by the codec of cid, dag-cbor or dag-json
+	async fn load_cid_with_codec<T: DeserializeOwned>(&self, cid: &Cid) -> anyhow::Result<T> {
+		let block = self.load_cid(cid).await?;
+		let node: Ipld = match cid.codec() {
+			0x71 => DagCborCodec.decode(&block)?,
+			0x0129 => DagJsonCodec.decode(&block)?,
+			codec => anyhow::bail!("unsupported codec {} of cid {}", codec, cid),
+		};
+		Ok(libipld::serde::from_ipld(node)?)
+	}
 }
 
 #[async_trait::async_trait]
@@ -104,6 +131,17 @@ impl CidLoader for Client {
 #[async_trait::async_trait]
 pub trait BlockUploader {
 	async fn block_upload(&self, cid: Cid, block: Vec<u8>) -> anyhow::Result<()>;
+
+	/// upload blocks one by one, stops at the first failed block
+	async fn block_upload_many(&self, blocks: Vec<(Cid, Vec<u8>)>) -> anyhow::Result<()>
+	where
+		Self: Sync,
+	{
+		for (cid, block) in blocks {
+			self.block_upload(cid, block).await?;
+		}
+		Ok(())
+	}
 }
 
 #[async_trait::async_trait]
@@ -127,29 +165,82 @@ impl BlockUploader for Client {
 }
 
 #[async_trait::async_trait]
-impl<T: BlockUploader + AnchorRuester + MessageUpdatePublisher + Send + Sync> EventsUploader for T {
+pub trait CidPinner: Send + Sync {
+	async fn pin_cid(&self, cid: &Cid) -> anyhow::Result<()>;
+
+	async fn unpin_cid(&self, cid: &Cid) -> anyhow::Result<()>;
+}
+
+#[async_trait::async_trait]
+impl CidPinner for Client {
+	async fn pin_cid(&self, cid: &Cid) -> anyhow::Result<()> {
+		let res = self.pin_add_post(cid.to_string(), Some(true), None).await?;
+		match res {
+			PinAddPostResponse::Success(_) => {
+				tracing::info!(cid = cid.to_string(), "cid pinned");
+				Ok(())
+			}
+			PinAddPostResponse::BadRequest(err) => {
+				tracing::warn!(?err, cid = cid.to_string(), "bad request");
+				anyhow::bail!("failed to pin {}: {:?}", cid, err)
+			}
+		}
+	}
+
+	async fn unpin_cid(&self, cid: &Cid) -> anyhow::Result<()> {
+		let res = self.pin_rm_post(cid.to_string()).await?;
+		match res {
+			PinRmPostResponse::Success(_) => {
+				tracing::info!(cid = cid.to_string(), "cid unpinned");
+				Ok(())
+			}
+			PinRmPostResponse::BadRequest(err) => {
+				tracing::warn!(?err, cid = cid.to_string(), "bad request");
+				anyhow::bail!("failed to unpin {}: {:?}", cid, err)
+			}
+		}
+	}
+}
+
+/// upload blocks of a signed event, with its cacao and payload blocks
+pub async fn upload_blocks<T: BlockUploader + Sync + ?Sized>(
+	uploader: &T,
+	commit: &Event,
+) -> anyhow::Result<()> {
+	match &commit.value {
+		event::EventValue::Signed(signed) => {
+			if let Some(cacao_block) = &signed.cacao_block {
+				uploader
+					.block_upload(signed.cacao_link()?, cacao_block.clone())
+					.await?;
+			}
+			if let Some(linked_block) = &signed.linked_block {
+				uploader
+					.block_upload(signed.payload_link()?, linked_block.clone())
+					.await?;
+			}
+			uploader
+				.block_upload(commit.cid, signed.jws.to_vec()?)
+				.await?;
+		}
+		// anchor commit generate by ceramic node default
+		// don't need to upload it
+		event::EventValue::Anchor(_) => {}
+	}
+	Ok(())
+}
+
+#[async_trait::async_trait]
+impl<T: BlockUploader + AnchorRequester + MessageUpdatePublisher + Send + Sync> EventsUploader
+	for T
+{
 	async fn upload_event(
 		&self,
 		ceramic: &Ceramic,
 		stream_id: &StreamId,
 		commit: Event,
 	) -> anyhow::Result<()> {
-		match &commit.value {
-			event::EventValue::Signed(signed) => {
-				if let Some(cacao_block) = &signed.cacao_block {
-					self.block_upload(signed.cacao_link()?, cacao_block.clone())
-						.await?;
-				}
-				if let Some(linked_block) = &signed.linked_block {
-					self.block_upload(signed.payload_link()?, linked_block.clone())
-						.await?;
-				}
-				self.block_upload(commit.cid, signed.jws.to_vec()?).await?;
-			}
-			// anchor commit generate by ceramic node default
-			// don't need to upload it
-			event::EventValue::Anchor(_) => {}
-		}
+		upload_blocks(self, &commit).await?;
 		self.request_anchor(&ceramic, &stream_id, commit).await?;
 		Ok(())
 	}
@@ -185,16 +276,14 @@ impl<T: CidLoader + Send + Sync> EventsLoader for T {
 	async fn load_events(
 		&self,
 		_ceramic: &Ceramic,
-		_stream_id: &StreamId,
+		stream_id: &StreamId,
 		tip: Option<Cid>,
 	) -> anyhow::Result<Vec<Event>> {
 		let mut commits = Vec::new();
+		// kubo cannot query the latest tip of stream
 		let mut cid = match tip {
 			Some(tip) => tip,
-			None => {
-				log::warn!("kubo not support query tip, should input tip");
-				todo!("query latest tip of stream")
-			}
+			None => anyhow::bail!("kubo cannot load events of {} without the tip", stream_id),
 		};
 		loop {
 			let bytes = self.load_cid_retry_3_times(&cid).await?;
@@ -217,20 +306,58 @@ impl<T: CidLoader + Send + Sync> EventsLoader for T {
 		}
 		Ok(commits)
 	}
+
+	/// kubo cannot find the latest tip of stream, so events after `since` are unknown
+	async fn load_events_since(
+		&self,
+		_ceramic: &Ceramic,
+		stream_id: &StreamId,
+		since: Cid,
+	) -> anyhow::Result<Vec<Event>> {
+		anyhow::bail!(
+			"kubo cannot load events of {} after {} without the tip",
+			stream_id,
+			since
+		)
+	}
 }
 
 #[async_trait::async_trait]
-pub trait AnchorRuester {
+pub trait AnchorRequester {
 	async fn request_anchor(
 		&self,
 		ceramic: &Ceramic,
 		stream_id: &StreamId,
 		event: Event,
 	) -> anyhow::Result<()>;
+
+	/// request anchors of many streams, returns one result for each request in the same order.
+	/// requesters without batch support fall back to sequential requests
+	async fn batch_request_anchor(
+		&self,
+		ceramic: &Ceramic,
+		requests: Vec<(StreamId, Event)>,
+	) -> anyhow::Result<Vec<anyhow::Result<()>>> {
+		let mut results = Vec::with_capacity(requests.len());
+		for (stream_id, event) in requests {
+			results.push(self.request_anchor(ceramic, &stream_id, event).await);
+		}
+		Ok(results)
+	}
 }
 
+/// Former name of `AnchorRequester`, every requester implements it.
+///
+/// Traits cannot be aliased, bounds and trait objects of the old name keep working but
+/// requesters should implement `AnchorRequester` instead.
+#[deprecated(since = "0.1.0", note = "use AnchorRequester")]
+pub trait AnchorRuester: AnchorRequester {}
+
+#[allow(deprecated)]
+impl<T: AnchorRequester + ?Sized> AnchorRuester for T {}
+
 #[async_trait::async_trait]
-impl AnchorRuester for Client {
+impl AnchorRequester for Client {
 	async fn request_anchor(
 		&self,
 		ceramic: &Ceramic,
@@ -249,3 +376,74 @@ impl AnchorRuester for Client {
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use std::str::FromStr;
+
+	use super::*;
+
+	struct BlockLoader;
+
+	#[async_trait::async_trait]
+	impl CidLoader for BlockLoader {
+		async fn load_cid(&self, cid: &Cid) -> anyhow::Result<Vec<u8>> {
+			let node = libipld::ipld!({ "name": "dataverse", "version": 1 });
+			match cid.codec() {
+				0x71 => DagCborCodec.encode(&node),
+				0x0129 => DagJsonCodec.encode(&node),
+				_ => Ok(vec![0x01]),
+			}
+		}
+	}
+
+	#[tokio::test]
+	async fn load_cid_with_codec() -> anyhow::Result<()> {
+		#[derive(serde::Deserialize, Debug, PartialEq)]
+		struct Block {
+			name: String,
+			version: u64,
+		}
+		let expected = Block {
+			name: "dataverse".to_string(),
+			version: 1,
+		};
+		let cid = Cid::from_str("bafyreiaxfjkme33rujt5wfajbl7r6pcdhjw4gfzwmxqe7xs4wf3dwvxdpy")?;
+		let block: Block = BlockLoader.load_cid_with_codec(&cid).await?;
+		assert_eq!(block, expected);
+
+		let cid = Cid::new_v1(0x0129, *cid.hash());
+		let block: Block = BlockLoader.load_cid_with_codec(&cid).await?;
+		assert_eq!(block, expected);
+
+		let cid = Cid::new_v1(0x55, *cid.hash());
+		assert!(BlockLoader
+			.load_cid_with_codec::<Block>(&cid)
+			.await
+			.is_err());
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn load_state_without_tip() -> anyhow::Result<()> {
+		let ceramic = Ceramic {
+			endpoint: "http://localhost:7007".into(),
+			network: crate::network::Network::InMemory,
+			pool: None,
+		};
+		let stream_id = crate::commit::example::genesis().stream_id()?;
+		let err = new("http://127.0.0.1:1")
+			.load_stream_state_no_cache(&ceramic, &stream_id)
+			.await
+			.unwrap_err();
+		assert!(err.to_string().contains("without the tip"));
+		Ok(())
+	}
+
+	#[test]
+	#[allow(deprecated)]
+	fn deprecated_anchor_requester_name() {
+		fn requester<T: AnchorRuester + ?Sized>(_: &T) {}
+		requester(&new("http://127.0.0.1:1"));
+	}
+}
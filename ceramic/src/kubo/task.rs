@@ -1,3 +1,17 @@
+//! Background tasks of kubo writes, queued by `Cached`.
+//!
+//! A task failing its run is retried by fang, the task is rescheduled `backoff(attempt)`
+//! seconds later until it failed `max_retries` times, then it stays failed in the queue.
+//! Tasks carry `max_retries` and `retry_backoff_ms` in their body, the backoff of an attempt
+//! is `retry_backoff_ms * 2^attempt` rounded up to whole seconds, fang schedules in seconds.
+//! Tasks queued before the fields existed use fang's defaults, 20 retries and 2^attempt
+//! seconds.
+//!
+//! Retry fields are part of the body fang hashes for `uniq` tasks, they are only serialized
+//! when they differ from the defaults. Tasks with default retries hash as they did before the
+//! fields existed and dedup with tasks queued by older versions, the same task queued with
+//! other retry settings is not deduplicated.
+
 use ceramic_core::Cid;
 use fang::async_trait;
 use fang::asynk::async_queue::AsyncQueueable;
@@ -12,6 +26,35 @@ use super::{BlockUploader, Client};
 
 static KUBO: OnceLock<Client> = OnceLock::new();
 
+pub const DEFAULT_MAX_RETRIES: u32 = 20;
+pub const DEFAULT_RETRY_BACKOFF_MS: u64 = 1000;
+
+fn default_max_retries() -> u32 {
+	DEFAULT_MAX_RETRIES
+}
+
+fn default_retry_backoff_ms() -> u64 {
+	DEFAULT_RETRY_BACKOFF_MS
+}
+
+fn is_default_max_retries(max_retries: &u32) -> bool {
+	*max_retries == DEFAULT_MAX_RETRIES
+}
+
+fn is_default_retry_backoff_ms(retry_backoff_ms: &u64) -> bool {
+	*retry_backoff_ms == DEFAULT_RETRY_BACKOFF_MS
+}
+
+fn max_retries(max_retries: u32) -> i32 {
+	max_retries.min(i32::MAX as u32) as i32
+}
+
+/// `retry_backoff_ms * 2^attempt` in seconds, rounded up
+fn backoff_secs(retry_backoff_ms: u64, attempt: u32) -> u32 {
+	let backoff_ms = retry_backoff_ms.saturating_mul(1_u64 << attempt.min(32));
+	(backoff_ms.saturating_add(999) / 1000).min(u32::MAX as u64) as u32
+}
+
 pub fn init_kubo(base_path: &str) {
 	KUBO.get_or_init(|| super::new(base_path));
 }
@@ -28,11 +71,25 @@ async fn get_kubo() -> Result<&'static Client, FangError> {
 	}
 }
 
+/// Upload a block in a background task.
+///
+/// `uniq` makes fang skip inserting a task whose serialized metadata hashes the same as a
+/// queued one, the block is addressed by its cid so the cid works as the idempotency key.
 #[derive(Serialize, Deserialize)]
 #[serde(crate = "fang::serde")]
 pub struct BlockUploadHandler {
 	pub cid: Cid,
 	pub block: Vec<u8>,
+	#[serde(
+		default = "default_max_retries",
+		skip_serializing_if = "is_default_max_retries"
+	)]
+	pub max_retries: u32,
+	#[serde(
+		default = "default_retry_backoff_ms",
+		skip_serializing_if = "is_default_retry_backoff_ms"
+	)]
+	pub retry_backoff_ms: u64,
 }
 
 #[async_trait]
@@ -58,6 +115,73 @@ impl AsyncRunnable for BlockUploadHandler {
 	fn uniq(&self) -> bool {
 		true
 	}
+
+	fn max_retries(&self) -> i32 {
+		max_retries(self.max_retries)
+	}
+
+	fn backoff(&self, attempt: u32) -> u32 {
+		backoff_secs(self.retry_backoff_ms, attempt)
+	}
+}
+
+/// upload blocks in one background task, a failed block fails the task after the others
+/// are uploaded
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "fang::serde")]
+pub struct BatchBlockUploadHandler {
+	pub blocks: Vec<(Cid, Vec<u8>)>,
+	#[serde(
+		default = "default_max_retries",
+		skip_serializing_if = "is_default_max_retries"
+	)]
+	pub max_retries: u32,
+	#[serde(
+		default = "default_retry_backoff_ms",
+		skip_serializing_if = "is_default_retry_backoff_ms"
+	)]
+	pub retry_backoff_ms: u64,
+}
+
+#[async_trait]
+#[typetag::serde]
+impl AsyncRunnable for BatchBlockUploadHandler {
+	async fn run(&self, _queue: &mut dyn AsyncQueueable) -> Result<(), FangError> {
+		let kubo = get_kubo().await?;
+
+		let mut failed = 0;
+		for (cid, block) in &self.blocks {
+			match kubo.block_upload(*cid, block.clone()).await {
+				Ok(_) => tracing::info!(cid = cid.to_string(), "uploading block"),
+				Err(err) => {
+					tracing::warn!(cid = cid.to_string(), ?err, "uploading block");
+					failed += 1;
+				}
+			}
+		}
+		if failed > 0 {
+			return Err(FangError {
+				description: format!(
+					"Failed to upload {} of {} blocks",
+					failed,
+					self.blocks.len()
+				),
+			});
+		}
+		Ok(())
+	}
+
+	fn uniq(&self) -> bool {
+		true
+	}
+
+	fn max_retries(&self) -> i32 {
+		max_retries(self.max_retries)
+	}
+
+	fn backoff(&self, attempt: u32) -> u32 {
+		backoff_secs(self.retry_backoff_ms, attempt)
+	}
 }
 
 #[derive(Serialize, Deserialize)]
@@ -65,6 +189,16 @@ impl AsyncRunnable for BlockUploadHandler {
 pub struct UpdateMessagePublishHandler {
 	pub topic: String,
 	pub msg: Vec<u8>,
+	#[serde(
+		default = "default_max_retries",
+		skip_serializing_if = "is_default_max_retries"
+	)]
+	pub max_retries: u32,
+	#[serde(
+		default = "default_retry_backoff_ms",
+		skip_serializing_if = "is_default_retry_backoff_ms"
+	)]
+	pub retry_backoff_ms: u64,
 }
 
 #[async_trait]
@@ -91,4 +225,95 @@ impl AsyncRunnable for UpdateMessagePublishHandler {
 	fn uniq(&self) -> bool {
 		true
 	}
+
+	fn max_retries(&self) -> i32 {
+		max_retries(self.max_retries)
+	}
+
+	fn backoff(&self, attempt: u32) -> u32 {
+		backoff_secs(self.retry_backoff_ms, attempt)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::str::FromStr;
+
+	use super::*;
+
+	/// body fang hashes to find a queued `uniq` task, the metadata column of the task
+	fn metadata(task: &dyn AsyncRunnable) -> anyhow::Result<String> {
+		Ok(serde_json::to_value(task)?.to_string())
+	}
+
+	#[test]
+	fn block_upload_uniq_by_cid() -> anyhow::Result<()> {
+		let cid = Cid::from_str("bafyreiaxfjkme33rujt5wfajbl7r6pcdhjw4gfzwmxqe7xs4wf3dwvxdpy")?;
+		let task = BlockUploadHandler {
+			cid,
+			block: vec![1, 2, 3],
+			max_retries: DEFAULT_MAX_RETRIES,
+			retry_backoff_ms: DEFAULT_RETRY_BACKOFF_MS,
+		};
+		let retried = BlockUploadHandler {
+			cid,
+			block: vec![1, 2, 3],
+			max_retries: DEFAULT_MAX_RETRIES,
+			retry_backoff_ms: DEFAULT_RETRY_BACKOFF_MS,
+		};
+		assert!(task.uniq());
+		// fang hashes the metadata to find the queued task with the same content
+		assert_eq!(metadata(&task)?, metadata(&retried)?);
+
+		let other = BlockUploadHandler {
+			cid: Cid::from_str("bagcqcerayswtqarydm2rgeh37yir45ccvfkj3qhwhfmu4vdjjrtny5l4rpia")?,
+			block: vec![4, 5, 6],
+			max_retries: DEFAULT_MAX_RETRIES,
+			retry_backoff_ms: DEFAULT_RETRY_BACKOFF_MS,
+		};
+		assert_ne!(metadata(&task)?, metadata(&other)?);
+
+		// hashes as the task queued before retry fields existed
+		let queued = serde_json::json!({
+			"type": "BlockUploadHandler",
+			"cid": cid,
+			"block": [1, 2, 3],
+		});
+		assert_eq!(metadata(&task)?, queued.to_string());
+
+		let custom = BlockUploadHandler {
+			cid,
+			block: vec![1, 2, 3],
+			max_retries: 3,
+			retry_backoff_ms: DEFAULT_RETRY_BACKOFF_MS,
+		};
+		assert_ne!(metadata(&task)?, metadata(&custom)?);
+		let custom: Box<dyn AsyncRunnable> = serde_json::from_str(&metadata(&custom)?)?;
+		assert_eq!(custom.max_retries(), 3);
+		Ok(())
+	}
+
+	#[test]
+	fn retry_settings() -> anyhow::Result<()> {
+		let task = UpdateMessagePublishHandler {
+			topic: "topic".to_string(),
+			msg: vec![1],
+			max_retries: 3,
+			retry_backoff_ms: 1500,
+		};
+		assert_eq!(task.max_retries(), 3);
+		assert_eq!(task.backoff(0), 2);
+		assert_eq!(task.backoff(2), 6);
+		assert_eq!(task.backoff(64), u32::MAX);
+
+		// tasks queued without the fields keep fang's defaults
+		let queued: Box<dyn AsyncRunnable> = serde_json::from_value(serde_json::json!({
+			"type": "UpdateMessagePublishHandler",
+			"topic": "topic",
+			"msg": [1],
+		}))?;
+		assert_eq!(queued.max_retries(), 20);
+		assert_eq!(queued.backoff(3), 8);
+		Ok(())
+	}
 }
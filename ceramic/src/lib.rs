@@ -1,26 +1,386 @@
+pub mod balancer;
 pub mod did;
+pub mod error;
 pub mod event;
 pub mod http;
 pub mod kubo;
 pub mod network;
 pub mod stream;
+#[cfg(feature = "opentelemetry")]
+pub mod telemetry;
 
 pub use ceramic_core::StreamId;
+pub use error::DataverseError;
 pub use event::commit;
 pub use event::{Event, EventValue, EventsLoader, EventsUploader};
 use serde::{Deserialize, Serialize};
+use std::sync::{Arc, OnceLock};
 pub use stream::*;
 
+/// fees charged by anchor service for anchoring an event, ceramic anchor service exposes
+/// no fee configuration so users set the fees they are charged
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FeeSchedule {
+	pub per_byte: f64,
+	pub flat_fee: f64,
+	pub currency: String,
+}
+
+impl FeeSchedule {
+	pub fn cost(&self, event: &Event) -> f64 {
+		self.flat_fee + event.estimated_anchor_cost(self.per_byte)
+	}
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Ceramic {
 	pub endpoint: String,
 	pub network: network::Network,
+	/// nodes balanced across instead of `endpoint`, see `with_endpoints`
+	#[serde(
+		default,
+		skip_serializing_if = "Option::is_none",
+		with = "balancer::shared_pool"
+	)]
+	pub pool: Option<Arc<balancer::EndpointPool>>,
 }
 
 impl Ceramic {
 	pub async fn new(endpoint: &str) -> anyhow::Result<Self> {
 		let network = http::Client::network(endpoint).await?;
 		let endpoint = endpoint.into();
-		Ok(Self { endpoint, network })
+		Ok(Self {
+			endpoint,
+			network,
+			pool: None,
+		})
+	}
+
+	/// Balance calls across `urls` instead of `endpoint`, the nodes should be on the same network.
+	pub fn with_endpoints(mut self, urls: Vec<String>, strategy: balancer::LbStrategy) -> Self {
+		self.pool = Some(Arc::new(balancer::EndpointPool::new(urls, strategy)));
+		self
+	}
+
+	/// call with the endpoint, or with endpoints of the pool until one answers,
+	/// see [`balancer::EndpointPool::call`]
+	pub async fn call<R, F, Fut>(&self, call: F) -> anyhow::Result<R>
+	where
+		F: Fn(String) -> Fut,
+		Fut: std::future::Future<Output = anyhow::Result<R>>,
+	{
+		match &self.pool {
+			Some(pool) => pool.call(call).await,
+			None => call(self.endpoint.clone()).await,
+		}
+	}
+
+	/// call with the endpoint, or with one endpoint of the pool, for requests not safe to replay
+	pub async fn call_once<R, F, Fut>(&self, call: F) -> anyhow::Result<R>
+	where
+		F: Fn(String) -> Fut,
+		Fut: std::future::Future<Output = anyhow::Result<R>>,
+	{
+		match &self.pool {
+			Some(pool) => pool.call_once(call).await,
+			None => call(self.endpoint.clone()).await,
+		}
+	}
+
+	/// Point the shared pool to a new node once calls in flight are done, the node must pass
+	/// `health_check` first. Clones of this instance share the pool and switch with it, see
+	/// [`balancer::EndpointPool::switch_endpoint`].
+	pub async fn switch_endpoint(&self, new_url: &str) -> anyhow::Result<()> {
+		let pool = match &self.pool {
+			Some(pool) => pool,
+			None => anyhow::bail!("switching endpoint needs a pool, see `with_endpoints`"),
+		};
+		let network = self.network;
+		pool.switch_endpoint(new_url.into(), |endpoint| async move {
+			let ceramic = Ceramic {
+				endpoint,
+				network,
+				pool: None,
+			};
+			if !ceramic.health_check().await?.reachable {
+				anyhow::bail!("health check of {} failed", ceramic.endpoint);
+			}
+			Ok(())
+		})
+		.await?;
+		tracing::info!(
+			old = self.endpoint,
+			new = new_url,
+			"switch ceramic endpoint"
+		);
+		Ok(())
+	}
+
+	/// Probe the node at `endpoint`, or the nodes of the pool, with the healthcheck api of
+	/// ceramic. With a pool, the health of the first reachable node is returned, see
+	/// [`Ceramic::health_check_endpoints`] for each node.
+	///
+	/// An unreachable node is not an error, only an invalid endpoint is. `version` and
+	/// `peer_count` are read from the ipfs compatible api when the node serves it, `registry`
+	/// from the check set with [`set_registry_health_check`].
+	pub async fn health_check(&self) -> anyhow::Result<CeramicHealth> {
+		let mut healths = self.health_check_endpoints().await?;
+		let idx = healths
+			.iter()
+			.position(|(_, health)| health.reachable)
+			.unwrap_or(0);
+		let mut health = healths.swap_remove(idx).1;
+		if let Some(check) = REGISTRY_HEALTH_CHECK.get() {
+			health.registry = Some(check().await);
+		}
+		Ok(health)
+	}
+
+	/// health of `endpoint`, or of each endpoint of the pool in the pool order
+	pub async fn health_check_endpoints(&self) -> anyhow::Result<Vec<(String, CeramicHealth)>> {
+		let mut endpoints: Vec<String> = match &self.pool {
+			Some(pool) => pool
+				.health()
+				.into_iter()
+				.map(|health| health.endpoint)
+				.collect(),
+			None => vec![],
+		};
+		if endpoints.is_empty() {
+			endpoints.push(self.endpoint.clone());
+		}
+		let healths =
+			futures::future::try_join_all(endpoints.iter().map(|endpoint| probe(endpoint))).await?;
+		Ok(endpoints.into_iter().zip(healths).collect())
+	}
+
+	/// whether the node answers its healthcheck, for liveness and readiness probes
+	pub async fn is_reachable(&self) -> bool {
+		self.health_check()
+			.await
+			.map_or(false, |health| health.reachable)
+	}
+}
+
+const HEALTH_CHECK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// probe the node at `endpoint` with the healthcheck api of ceramic
+async fn probe(endpoint: &str) -> anyhow::Result<CeramicHealth> {
+	let base = url::Url::parse(endpoint)?;
+	let client = reqwest::Client::builder()
+		.timeout(HEALTH_CHECK_TIMEOUT)
+		.build()?;
+
+	let start = std::time::Instant::now();
+	let request = client.get(base.join("api/v0/node/healthcheck")?);
+	#[cfg(feature = "opentelemetry")]
+	let request = request.headers(telemetry::trace_header_map());
+	let healthcheck = request.send().await;
+	let latency_ms = start.elapsed().as_millis() as u64;
+	let reachable = matches!(healthcheck, Ok(res) if res.status().is_success());
+	if !reachable {
+		return Ok(CeramicHealth {
+			reachable,
+			version: None,
+			peer_count: None,
+			latency_ms,
+			registry: None,
+		});
+	}
+
+	let ipfs = |path: &str| {
+		let url = base.join(path);
+		let client = client.clone();
+		async move {
+			let request = client.post(url?);
+			#[cfg(feature = "opentelemetry")]
+			let request = request.headers(telemetry::trace_header_map());
+			let res = request.send().await?.error_for_status()?;
+			anyhow::Ok(res.json::<serde_json::Value>().await?)
+		}
+	};
+	let version = ipfs("api/v0/version").await.ok().and_then(|res| {
+		res.get("Version")
+			.and_then(|version| version.as_str())
+			.map(str::to_string)
+	});
+	let peer_count = ipfs("api/v0/swarm/peers").await.ok().and_then(|res| {
+		res.get("Peers")
+			.and_then(|peers| peers.as_array())
+			.map(|peers| peers.len() as u32)
+	});
+	Ok(CeramicHealth {
+		reachable,
+		version,
+		peer_count,
+		latency_ms,
+		registry: None,
+	})
+}
+
+/// result of `Ceramic::health_check`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CeramicHealth {
+	pub reachable: bool,
+	pub version: Option<String>,
+	pub peer_count: Option<u32>,
+	pub latency_ms: u64,
+	/// dapp registry, when a check is set with `set_registry_health_check`
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub registry: Option<DappHealth>,
+}
+
+/// state of the dapp registry, see `dataverse_core::store::dapp::check_health`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DappHealth {
+	pub registry_reachable: bool,
+	pub cached_dapps: usize,
+	pub cached_models: usize,
+	pub last_error: Option<String>,
+}
+
+pub type RegistryHealthCheck = fn() -> futures::future::BoxFuture<'static, DappHealth>;
+
+static REGISTRY_HEALTH_CHECK: OnceLock<RegistryHealthCheck> = OnceLock::new();
+
+/// Report the dapp registry in `Ceramic::health_check`, only the first check set is kept.
+///
+/// The registry is kept by dataverse-core, which depends on this crate, so it sets the check
+/// here instead of being called directly.
+pub fn set_registry_health_check(check: RegistryHealthCheck) {
+	let _ = REGISTRY_HEALTH_CHECK.set(check);
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[tokio::test]
+	async fn unreachable_node() -> anyhow::Result<()> {
+		let ceramic = Ceramic {
+			endpoint: "http://127.0.0.1:1".into(),
+			network: network::Network::InMemory,
+			pool: None,
+		};
+		let health = ceramic.health_check().await?;
+		assert!(!health.reachable);
+		assert_eq!(health.version, None);
+		assert!(!ceramic.is_reachable().await);
+
+		let invalid = Ceramic {
+			endpoint: "not a url".into(),
+			..ceramic
+		};
+		assert!(invalid.health_check().await.is_err());
+		assert!(!invalid.is_reachable().await);
+		Ok(())
+	}
+
+	#[test]
+	fn pool_kept_through_serde() -> anyhow::Result<()> {
+		let ceramic = Ceramic {
+			endpoint: "http://127.0.0.1:1".into(),
+			network: network::Network::InMemory,
+			pool: None,
+		};
+		let value = serde_json::to_value(&ceramic)?;
+		assert!(value.get("pool").is_none());
+		assert!(serde_json::from_value::<Ceramic>(value)?.pool.is_none());
+
+		let endpoints = vec![
+			"http://127.0.0.1:1".to_string(),
+			"http://127.0.0.1:2".into(),
+		];
+		let ceramic = ceramic.with_endpoints(endpoints.clone(), balancer::LbStrategy::Random);
+		let ceramic: Ceramic = serde_json::from_value(serde_json::to_value(&ceramic)?)?;
+		let pool = ceramic.pool.expect("pool should be deserialized");
+		let health = pool.health();
+		assert_eq!(
+			health
+				.into_iter()
+				.map(|health| health.endpoint)
+				.collect::<Vec<_>>(),
+			endpoints
+		);
+		assert_eq!(serde_json::to_value(pool.as_ref())?["strategy"], "Random");
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn switch_endpoint_of_shared_pool() -> anyhow::Result<()> {
+		let ceramic = Ceramic {
+			endpoint: "http://127.0.0.1:1".into(),
+			network: network::Network::InMemory,
+			pool: None,
+		};
+		assert!(ceramic.switch_endpoint("http://127.0.0.1:2").await.is_err());
+
+		let ceramic = ceramic.with_endpoints(
+			vec![balancer::tests::down().await?],
+			balancer::LbStrategy::RoundRobin,
+		);
+		let clone = ceramic.clone();
+		let down = balancer::tests::down().await?;
+		assert!(ceramic.switch_endpoint(&down).await.is_err());
+		assert!(!clone.health_check().await?.reachable);
+
+		let up = balancer::tests::serve(200).await?;
+		ceramic.switch_endpoint(&up).await?;
+		let healths = clone.health_check_endpoints().await?;
+		assert_eq!(healths.len(), 1);
+		assert_eq!(healths[0].0, up);
+		assert!(healths[0].1.reachable);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn registry_in_health_check() -> anyhow::Result<()> {
+		fn registry() -> futures::future::BoxFuture<'static, DappHealth> {
+			Box::pin(async {
+				DappHealth {
+					cached_dapps: 1,
+					..Default::default()
+				}
+			})
+		}
+		set_registry_health_check(registry);
+
+		let ceramic = Ceramic {
+			endpoint: "http://127.0.0.1:1".into(),
+			network: network::Network::InMemory,
+			pool: None,
+		};
+		let health = ceramic.health_check().await?;
+		assert_eq!(
+			health.registry.map(|registry| registry.cached_dapps),
+			Some(1)
+		);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn probe_pool_endpoints() -> anyhow::Result<()> {
+		let endpoints = vec![
+			"http://127.0.0.1:1".to_string(),
+			"http://127.0.0.1:2".into(),
+		];
+		let pool = balancer::EndpointPool::new(endpoints.clone(), balancer::LbStrategy::RoundRobin);
+		let ceramic = Ceramic {
+			endpoint: "not a url".into(),
+			network: network::Network::InMemory,
+			pool: Some(Arc::new(pool)),
+		};
+		let healths = ceramic.health_check_endpoints().await?;
+		let probed: Vec<_> = healths
+			.iter()
+			.map(|(endpoint, _)| endpoint.clone())
+			.collect();
+		assert_eq!(probed, endpoints);
+		assert!(healths.iter().all(|(_, health)| !health.reachable));
+		assert!(!ceramic.health_check().await?.reachable);
+		Ok(())
 	}
 }
@@ -1,9 +1,11 @@
 pub mod commit_id;
 pub mod operator;
 pub mod patch;
+pub mod publisher;
 pub mod stream;
 pub mod stream_id;
 
 pub use operator::*;
+pub use publisher::*;
 pub use stream::*;
 pub use stream_id::*;
@@ -1,13 +1,92 @@
 use crate::event::{Event, EventsLoader, EventsUploader};
-use crate::{AnchorStatus, Ceramic, StreamState};
+use crate::{AnchorStatus, Ceramic, DataverseError, StreamState};
+use base64::Engine;
 use ceramic_core::{Cid, StreamId};
+use futures::StreamExt;
 use int_enum::IntEnum;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{watch, Mutex};
 
 #[async_trait::async_trait]
 pub trait StreamOperator: StreamLoader + EventsUploader + Send + Sync {}
 
 impl<T: StreamLoader + EventsUploader> StreamOperator for T {}
 
+#[async_trait::async_trait]
+pub trait StreamPublisher {
+	/// Publish new commits of stream, `state` is the stream before them, `None` for a new stream.
+	///
+	/// Commits may come in any order, each one must follow the state or another commit.
+	async fn publish_events(
+		&self,
+		ceramic: &Ceramic,
+		stream_id: &StreamId,
+		state: Option<&StreamState>,
+		commits: &[Event],
+	) -> anyhow::Result<()>;
+}
+
+/// page of streams ordered by stream id, `after` is the cursor returned with the previous page
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageOptions {
+	pub after: Option<String>,
+	pub limit: usize,
+}
+
+impl PageOptions {
+	/// opaque cursor of a page ending at the stream
+	pub fn cursor(stream_id: &StreamId) -> String {
+		Self::encode_cursor(&stream_id.to_string())
+	}
+
+	fn encode_cursor(stream_id: &str) -> String {
+		base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(stream_id)
+	}
+
+	fn after_stream_id(&self) -> anyhow::Result<Option<String>> {
+		match &self.after {
+			Some(cursor) => {
+				let bytes = base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(cursor)?;
+				Ok(Some(String::from_utf8(bytes)?))
+			}
+			None => Ok(None),
+		}
+	}
+
+	/// take the page from items keyed by stream id, with the cursor of next page if any
+	fn paginate<T>(&self, mut items: Vec<(String, T)>) -> anyhow::Result<(Vec<T>, Option<String>)> {
+		if self.limit == 0 {
+			anyhow::bail!("page limit must be positive");
+		}
+		let after = self.after_stream_id()?;
+		items.sort_by(|(a, _), (b, _)| a.cmp(b));
+		let mut items = items
+			.into_iter()
+			.filter(|(id, _)| after.as_ref().map_or(true, |after| id > after))
+			.peekable();
+
+		let mut page = Vec::new();
+		let mut last = None;
+		while page.len() < self.limit {
+			match items.next() {
+				Some((id, item)) => {
+					page.push(item);
+					last = Some(id);
+				}
+				None => break,
+			}
+		}
+		let next = match (items.peek(), last) {
+			(Some(_), Some(last)) => Some(Self::encode_cursor(&last)),
+			_ => None,
+		};
+		Ok((page, next))
+	}
+}
+
 #[async_trait::async_trait]
 pub trait StreamsLoader: StreamLoader {
 	async fn load_stream_states(
@@ -16,10 +95,72 @@ pub trait StreamsLoader: StreamLoader {
 		account: Option<String>,
 		model_id: &StreamId,
 	) -> anyhow::Result<Vec<StreamState>>;
+
+	/// load a page of stream states ordered by stream id, with the cursor of next page if any
+	async fn load_stream_states_paged(
+		&self,
+		ceramic: &Ceramic,
+		account: Option<String>,
+		model_id: &StreamId,
+		page: &PageOptions,
+	) -> anyhow::Result<(Vec<StreamState>, Option<String>)> {
+		let states = self.load_stream_states(ceramic, account, model_id).await?;
+		let mut items = Vec::with_capacity(states.len());
+		for state in states {
+			items.push((state.stream_id()?.to_string(), state));
+		}
+		page.paginate(items)
+	}
+
+	/// number of streams of model, loads all the stream states by default
+	async fn count_streams(
+		&self,
+		ceramic: &Ceramic,
+		account: Option<String>,
+		model_id: &StreamId,
+	) -> anyhow::Result<u64> {
+		let states = self.load_stream_states(ceramic, account, model_id).await?;
+		Ok(states.len() as u64)
+	}
+
+	/// load stream states of model for each account, `MAX_CONCURRENT_LOADS` at a time,
+	/// a failed account does not fail the others
+	async fn load_stream_states_for_accounts(
+		&self,
+		ceramic: &Ceramic,
+		accounts: &[String],
+		model_id: &StreamId,
+	) -> anyhow::Result<HashMap<String, anyhow::Result<Vec<StreamState>>>> {
+		Ok(load_accounts_concurrently(self, ceramic, accounts, model_id).await)
+	}
+}
+
+async fn load_accounts_concurrently<L: StreamsLoader + ?Sized>(
+	loader: &L,
+	ceramic: &Ceramic,
+	accounts: &[String],
+	model_id: &StreamId,
+) -> HashMap<String, anyhow::Result<Vec<StreamState>>> {
+	let states: Vec<_> = futures::stream::iter(
+		accounts
+			.iter()
+			.map(|account| loader.load_stream_states(ceramic, Some(account.clone()), model_id)),
+	)
+	.buffered(MAX_CONCURRENT_LOADS)
+	.collect()
+	.await;
+	accounts.iter().cloned().zip(states).collect()
 }
 
 #[async_trait::async_trait]
 pub trait StreamLoader: EventsLoader + Sync + Send {
+	#[cfg_attr(
+		feature = "opentelemetry",
+		tracing::instrument(
+			skip_all,
+			fields(ceramic.url = %ceramic.endpoint, stream_id = %stream_id)
+		)
+	)]
 	async fn load_stream_state(
 		&self,
 		ceramic: &Ceramic,
@@ -27,8 +168,49 @@ pub trait StreamLoader: EventsLoader + Sync + Send {
 		tip: Option<Cid>,
 	) -> anyhow::Result<StreamState> {
 		let events = self.load_events(ceramic, stream_id, tip).await?;
+		if events.is_empty() {
+			return Err(DataverseError::StreamNotFound(stream_id.clone()).into());
+		}
+		StreamState::make(stream_id.r#type.int_value(), events).await
+	}
+
+	/// load stream state from its events, bypassing any cache
+	async fn load_stream_state_no_cache(
+		&self,
+		ceramic: &Ceramic,
+		stream_id: &StreamId,
+	) -> anyhow::Result<StreamState> {
+		let events = self.load_events(ceramic, stream_id, None).await?;
 		StreamState::make(stream_id.r#type.int_value(), events).await
 	}
+
+	/// load states of streams at latest tip, `MAX_CONCURRENT_LOADS` at a time,
+	/// a failed stream does not fail the batch
+	async fn load_stream_states_batch(
+		&self,
+		ceramic: &Ceramic,
+		ids: &[StreamId],
+	) -> anyhow::Result<Vec<(StreamId, anyhow::Result<StreamState>)>> {
+		Ok(load_stream_states_concurrently(self, ceramic, ids).await)
+	}
+}
+
+/// number of loads running at the same time in batch loads
+pub const MAX_CONCURRENT_LOADS: usize = 16;
+
+pub(crate) async fn load_stream_states_concurrently<L: StreamLoader + ?Sized>(
+	loader: &L,
+	ceramic: &Ceramic,
+	ids: &[StreamId],
+) -> Vec<(StreamId, anyhow::Result<StreamState>)> {
+	let states: Vec<_> = futures::stream::iter(
+		ids.iter()
+			.map(|stream_id| loader.load_stream_state(ceramic, stream_id, None)),
+	)
+	.buffered(MAX_CONCURRENT_LOADS)
+	.collect()
+	.await;
+	ids.iter().cloned().zip(states).collect()
 }
 
 #[async_trait::async_trait]
@@ -45,20 +227,142 @@ pub trait StreamAnchorRequester {
 	) -> anyhow::Result<AnchorStatus>;
 }
 
+/// result of a load shared with callers waiting on it, errors are formatted to be cloneable
+type InFlight = watch::Receiver<Option<Result<StreamState, String>>>;
+
+/// Cache stream states loaded at latest tip, loads with an explicit tip bypass the cache.
+///
+/// Concurrent loads of the same uncached stream are coalesced into one load of the inner loader.
 pub struct CachedStreamLoader<T: StreamLoader> {
 	loader: T,
-	cache: std::collections::HashMap<String, StreamState>,
+	cache: Arc<Mutex<HashMap<String, (StreamState, Instant)>>>,
+	/// stream states of model per account, keyed by `model_id/account`
+	accounts: Arc<Mutex<HashMap<String, (Vec<StreamState>, Instant)>>>,
+	/// stream counts of model per account, kept for at most `COUNT_TTL`
+	counts: Arc<Mutex<HashMap<String, (u64, Instant)>>>,
+	in_flight: Arc<Mutex<HashMap<String, InFlight>>>,
+	ttl: Duration,
 }
 
 impl<T: StreamLoader> CachedStreamLoader<T> {
 	pub fn new(loader: T) -> Self {
 		Self {
 			loader,
-			cache: std::collections::HashMap::new(),
+			cache: Arc::new(Mutex::new(HashMap::new())),
+			accounts: Arc::new(Mutex::new(HashMap::new())),
+			counts: Arc::new(Mutex::new(HashMap::new())),
+			in_flight: Arc::new(Mutex::new(HashMap::new())),
+			ttl: Duration::MAX,
+		}
+	}
+
+	/// expire cached states after `ttl`, `Duration::ZERO` disables caching
+	pub fn with_ttl(mut self, ttl: Duration) -> Self {
+		self.ttl = ttl;
+		self
+	}
+
+	pub fn inner(&self) -> &T {
+		&self.loader
+	}
+
+	async fn cached(&self, stream_id: &StreamId) -> Option<StreamState> {
+		let cache = self.cache.lock().await;
+		let (state, inserted) = cache.get(&stream_id.to_string())?;
+		is_fresh(*inserted, Instant::now(), self.ttl).then(|| state.clone())
+	}
+
+	async fn insert(&self, stream_id: &StreamId, state: &StreamState) {
+		if self.ttl.is_zero() {
+			return;
+		}
+		self.cache
+			.lock()
+			.await
+			.insert(stream_id.to_string(), (state.clone(), Instant::now()));
+	}
+
+	/// load stream at latest tip, joining a load of the same stream already in flight
+	async fn load_coalesced(
+		&self,
+		ceramic: &Ceramic,
+		stream_id: &StreamId,
+	) -> anyhow::Result<StreamState> {
+		let key = stream_id.to_string();
+		let (sender, mut receiver) = {
+			let mut in_flight = self.in_flight.lock().await;
+			match in_flight.get(&key) {
+				// channel is closed when its loader was cancelled before sending
+				Some(receiver) if receiver.has_changed().is_ok() => (None, receiver.clone()),
+				_ => {
+					let (sender, receiver) = watch::channel(None);
+					in_flight.insert(key.clone(), receiver.clone());
+					(Some(sender), receiver)
+				}
+			}
+		};
+
+		let sender = match sender {
+			Some(sender) => sender,
+			None => {
+				if let Ok(result) = receiver.wait_for(Option::is_some).await {
+					if let Some(result) = result.clone() {
+						return result.map_err(anyhow::Error::msg);
+					}
+				}
+				return self
+					.loader
+					.load_stream_state(ceramic, stream_id, None)
+					.await;
+			}
+		};
+
+		let result = self
+			.loader
+			.load_stream_state(ceramic, stream_id, None)
+			.await;
+		if let Ok(stream) = &result {
+			self.insert(stream_id, stream).await;
+		}
+		self.in_flight.lock().await.remove(&key);
+		let shared = match &result {
+			Ok(stream) => Ok(stream.clone()),
+			Err(err) => Err(format!("{:#}", err)),
+		};
+		// no one waiting when all followers are gone
+		let _ = sender.send(Some(shared));
+		result
+	}
+
+	/// drop cached account states of model, or of any model when `None`
+	async fn invalidate_accounts(&self, model_id: Option<&StreamId>) {
+		let mut accounts = self.accounts.lock().await;
+		let mut counts = self.counts.lock().await;
+		match model_id {
+			Some(model_id) => {
+				let prefix = format!("{}/", model_id);
+				accounts.retain(|key, _| !key.starts_with(&prefix));
+				counts.retain(|key, _| !key.starts_with(&prefix));
+			}
+			None => {
+				accounts.clear();
+				counts.clear();
+			}
 		}
 	}
 }
 
+/// counts go stale as soon as a stream of model is created elsewhere, cache them briefly
+const COUNT_TTL: Duration = Duration::from_secs(5);
+
+fn account_key(model_id: &StreamId, account: &str) -> String {
+	format!("{}/{}", model_id, account)
+}
+
+fn is_fresh(inserted: Instant, now: Instant, ttl: Duration) -> bool {
+	now.saturating_duration_since(inserted) < ttl
+}
+
 #[async_trait::async_trait]
 impl<T: StreamLoader + Send + Sync> EventsLoader for CachedStreamLoader<T> {
 	async fn load_events(
@@ -69,6 +373,17 @@ impl<T: StreamLoader + Send + Sync> EventsLoader for CachedStreamLoader<T> {
 	) -> anyhow::Result<Vec<Event>> {
 		self.loader.load_events(ceramic, stream_id, tip).await
 	}
+
+	async fn load_events_since(
+		&self,
+		ceramic: &Ceramic,
+		stream_id: &StreamId,
+		since: Cid,
+	) -> anyhow::Result<Vec<Event>> {
+		self.loader
+			.load_events_since(ceramic, stream_id, since)
+			.await
+	}
 }
 
 #[async_trait::async_trait]
@@ -79,15 +394,25 @@ impl<T: StreamLoader + Send + Sync> StreamLoader for CachedStreamLoader<T> {
 		stream_id: &StreamId,
 		tip: Option<Cid>,
 	) -> anyhow::Result<StreamState> {
-		if let Some(stream) = self.cache.get(&stream_id.to_string()) {
-			return Ok(stream.clone());
+		if tip.is_some() {
+			return self.loader.load_stream_state(ceramic, stream_id, tip).await;
 		}
+		if let Some(stream) = self.cached(stream_id).await {
+			return Ok(stream);
+		}
+		self.load_coalesced(ceramic, stream_id).await
+	}
 
+	async fn load_stream_state_no_cache(
+		&self,
+		ceramic: &Ceramic,
+		stream_id: &StreamId,
+	) -> anyhow::Result<StreamState> {
 		let stream = self
 			.loader
-			.load_stream_state(ceramic, stream_id, tip)
+			.load_stream_state_no_cache(ceramic, stream_id)
 			.await?;
-		// TODO: insert data into cache
+		self.insert(stream_id, &stream).await;
 		Ok(stream)
 	}
 }
@@ -104,4 +429,1164 @@ impl<T: StreamsLoader + Send + Sync> StreamsLoader for CachedStreamLoader<T> {
 			.load_stream_states(ceramic, account, model_id)
 			.await
 	}
+
+	async fn count_streams(
+		&self,
+		ceramic: &Ceramic,
+		account: Option<String>,
+		model_id: &StreamId,
+	) -> anyhow::Result<u64> {
+		let key = account_key(model_id, account.as_deref().unwrap_or_default());
+		let ttl = self.ttl.min(COUNT_TTL);
+		if let Some((count, inserted)) = self.counts.lock().await.get(&key) {
+			if is_fresh(*inserted, Instant::now(), ttl) {
+				return Ok(*count);
+			}
+		}
+		let count = self
+			.loader
+			.count_streams(ceramic, account, model_id)
+			.await?;
+		if !ttl.is_zero() {
+			self.counts
+				.lock()
+				.await
+				.insert(key, (count, Instant::now()));
+		}
+		Ok(count)
+	}
+
+	async fn load_stream_states_for_accounts(
+		&self,
+		ceramic: &Ceramic,
+		accounts: &[String],
+		model_id: &StreamId,
+	) -> anyhow::Result<HashMap<String, anyhow::Result<Vec<StreamState>>>> {
+		let mut result = HashMap::new();
+		let mut missing = vec![];
+		{
+			let cache = self.accounts.lock().await;
+			let now = Instant::now();
+			for account in accounts {
+				match cache.get(&account_key(model_id, account)) {
+					Some((states, inserted)) if is_fresh(*inserted, now, self.ttl) => {
+						result.insert(account.clone(), Ok(states.clone()));
+					}
+					_ => missing.push(account.clone()),
+				}
+			}
+		}
+
+		let loaded = load_accounts_concurrently(&self.loader, ceramic, &missing, model_id).await;
+		for (account, states) in loaded {
+			if let Ok(states) = &states {
+				for state in states {
+					if let Ok(stream_id) = state.stream_id() {
+						self.insert(&stream_id, state).await;
+					}
+				}
+				if !self.ttl.is_zero() {
+					self.accounts.lock().await.insert(
+						account_key(model_id, &account),
+						(states.clone(), Instant::now()),
+					);
+				}
+			}
+			result.insert(account, states);
+		}
+		Ok(result)
+	}
+}
+
+#[async_trait::async_trait]
+impl<T: StreamLoader + EventsUploader + Send + Sync> EventsUploader for CachedStreamLoader<T> {
+	async fn upload_event(
+		&self,
+		ceramic: &Ceramic,
+		stream_id: &StreamId,
+		event: Event,
+	) -> anyhow::Result<()> {
+		self.loader.upload_event(ceramic, stream_id, event).await
+	}
+}
+
+/// keep cached stream states in sync with writes
+#[async_trait::async_trait]
+pub trait CacheInvalidator: Send + Sync {
+	/// drop the cached state of stream, the next load reads from the inner loader
+	async fn invalidate(&self, stream_id: &StreamId);
+
+	/// replace the cached state of stream with the state after a write
+	async fn populate(&self, stream_id: &StreamId, state: StreamState);
+}
+
+#[async_trait::async_trait]
+impl<T: StreamLoader + Send + Sync> CacheInvalidator for CachedStreamLoader<T> {
+	async fn invalidate(&self, stream_id: &StreamId) {
+		let removed = self.cache.lock().await.remove(&stream_id.to_string());
+		let model_id = removed.and_then(|(state, _)| state.model().ok().flatten());
+		self.invalidate_accounts(model_id.as_ref()).await;
+	}
+
+	async fn populate(&self, stream_id: &StreamId, state: StreamState) {
+		let model_id = state.model().ok().flatten();
+		self.insert(stream_id, &state).await;
+		self.invalidate_accounts(model_id.as_ref()).await;
+	}
+}
+
+/// Error worth retrying, such as a reset connection or an unavailable node.
+///
+/// Wrap an error with it to have `RetryingStreamLoader` retry the call.
+#[derive(Debug)]
+pub struct TransientError(pub anyhow::Error);
+
+impl std::fmt::Display for TransientError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "transient error: {}", self.0)
+	}
+}
+
+impl std::error::Error for TransientError {}
+
+impl TransientError {
+	/// whether the error is marked transient or is a failure of the endpoint, see
+	/// [`crate::balancer::is_endpoint_failure`], messages are not looked at
+	pub fn is_transient(err: &anyhow::Error) -> bool {
+		err.chain().any(|cause| cause.is::<TransientError>())
+			|| crate::balancer::is_endpoint_failure(err)
+	}
+}
+
+/// retry transient failures of the inner loader with exponential backoff
+pub struct RetryingStreamLoader<T: StreamLoader> {
+	loader: T,
+	max_retries: u32,
+	initial_delay: Duration,
+}
+
+impl<T: StreamLoader> RetryingStreamLoader<T> {
+	pub fn new(loader: T, max_retries: u32, initial_delay: Duration) -> Self {
+		Self {
+			loader,
+			max_retries,
+			initial_delay,
+		}
+	}
+
+	async fn retry<R, F, Fut>(&self, stream_id: &str, call: F) -> anyhow::Result<R>
+	where
+		F: Fn() -> Fut + Send,
+		Fut: Future<Output = anyhow::Result<R>> + Send,
+	{
+		let mut delay = self.initial_delay;
+		let mut attempt = 0;
+		loop {
+			match call().await {
+				Err(err) if attempt < self.max_retries && TransientError::is_transient(&err) => {
+					attempt += 1;
+					tracing::warn!(
+						stream_id,
+						attempt,
+						"retry in {:?} after transient error: {}",
+						delay,
+						err
+					);
+					tokio::time::sleep(delay).await;
+					delay = delay.saturating_mul(2);
+				}
+				result => return result,
+			}
+		}
+	}
+}
+
+#[async_trait::async_trait]
+impl<T: StreamLoader + Send + Sync> EventsLoader for RetryingStreamLoader<T> {
+	async fn load_events(
+		&self,
+		ceramic: &Ceramic,
+		stream_id: &StreamId,
+		tip: Option<Cid>,
+	) -> anyhow::Result<Vec<Event>> {
+		self.retry(&stream_id.to_string(), || {
+			self.loader.load_events(ceramic, stream_id, tip)
+		})
+		.await
+	}
+
+	async fn load_events_since(
+		&self,
+		ceramic: &Ceramic,
+		stream_id: &StreamId,
+		since: Cid,
+	) -> anyhow::Result<Vec<Event>> {
+		self.retry(&stream_id.to_string(), || {
+			self.loader.load_events_since(ceramic, stream_id, since)
+		})
+		.await
+	}
+}
+
+#[async_trait::async_trait]
+impl<T: StreamLoader + Send + Sync> StreamLoader for RetryingStreamLoader<T> {
+	async fn load_stream_state(
+		&self,
+		ceramic: &Ceramic,
+		stream_id: &StreamId,
+		tip: Option<Cid>,
+	) -> anyhow::Result<StreamState> {
+		self.retry(&stream_id.to_string(), || {
+			self.loader.load_stream_state(ceramic, stream_id, tip)
+		})
+		.await
+	}
+
+	async fn load_stream_state_no_cache(
+		&self,
+		ceramic: &Ceramic,
+		stream_id: &StreamId,
+	) -> anyhow::Result<StreamState> {
+		self.retry(&stream_id.to_string(), || {
+			self.loader.load_stream_state_no_cache(ceramic, stream_id)
+		})
+		.await
+	}
+}
+
+#[async_trait::async_trait]
+impl<T: StreamsLoader + Send + Sync> StreamsLoader for RetryingStreamLoader<T> {
+	async fn load_stream_states(
+		&self,
+		ceramic: &Ceramic,
+		account: Option<String>,
+		model_id: &StreamId,
+	) -> anyhow::Result<Vec<StreamState>> {
+		self.retry(&model_id.to_string(), || {
+			self.loader
+				.load_stream_states(ceramic, account.clone(), model_id)
+		})
+		.await
+	}
+
+	async fn count_streams(
+		&self,
+		ceramic: &Ceramic,
+		account: Option<String>,
+		model_id: &StreamId,
+	) -> anyhow::Result<u64> {
+		self.retry(&model_id.to_string(), || {
+			self.loader
+				.count_streams(ceramic, account.clone(), model_id)
+		})
+		.await
+	}
+}
+
+/// fail loads of the inner loader taking longer than `timeout`
+pub struct TimeoutStreamLoader<T: StreamLoader> {
+	loader: T,
+	timeout: Duration,
+}
+
+impl<T: StreamLoader> TimeoutStreamLoader<T> {
+	pub fn new(loader: T, timeout: Duration) -> Self {
+		Self { loader, timeout }
+	}
+
+	async fn with_timeout<R>(
+		&self,
+		call: &str,
+		stream_id: &StreamId,
+		future: impl Future<Output = anyhow::Result<R>> + Send,
+	) -> anyhow::Result<R> {
+		match tokio::time::timeout(self.timeout, future).await {
+			Ok(result) => result,
+			Err(_) => Err(TransientError(anyhow::anyhow!(
+				"{} of stream {} timed out after {:?}",
+				call,
+				stream_id,
+				self.timeout
+			))
+			.into()),
+		}
+	}
+}
+
+#[async_trait::async_trait]
+impl<T: StreamLoader + Send + Sync> EventsLoader for TimeoutStreamLoader<T> {
+	async fn load_events(
+		&self,
+		ceramic: &Ceramic,
+		stream_id: &StreamId,
+		tip: Option<Cid>,
+	) -> anyhow::Result<Vec<Event>> {
+		let future = self.loader.load_events(ceramic, stream_id, tip);
+		self.with_timeout("load events", stream_id, future).await
+	}
+
+	async fn load_events_since(
+		&self,
+		ceramic: &Ceramic,
+		stream_id: &StreamId,
+		since: Cid,
+	) -> anyhow::Result<Vec<Event>> {
+		let future = self.loader.load_events_since(ceramic, stream_id, since);
+		self.with_timeout("load events", stream_id, future).await
+	}
+}
+
+#[async_trait::async_trait]
+impl<T: StreamLoader + Send + Sync> StreamLoader for TimeoutStreamLoader<T> {
+	async fn load_stream_state(
+		&self,
+		ceramic: &Ceramic,
+		stream_id: &StreamId,
+		tip: Option<Cid>,
+	) -> anyhow::Result<StreamState> {
+		let future = self.loader.load_stream_state(ceramic, stream_id, tip);
+		self.with_timeout("load state", stream_id, future).await
+	}
+
+	async fn load_stream_state_no_cache(
+		&self,
+		ceramic: &Ceramic,
+		stream_id: &StreamId,
+	) -> anyhow::Result<StreamState> {
+		let future = self.loader.load_stream_state_no_cache(ceramic, stream_id);
+		self.with_timeout("load state", stream_id, future).await
+	}
+}
+
+#[async_trait::async_trait]
+impl<T: StreamsLoader + Send + Sync> StreamsLoader for TimeoutStreamLoader<T> {
+	async fn load_stream_states(
+		&self,
+		ceramic: &Ceramic,
+		account: Option<String>,
+		model_id: &StreamId,
+	) -> anyhow::Result<Vec<StreamState>> {
+		let future = self.loader.load_stream_states(ceramic, account, model_id);
+		self.with_timeout("load states", model_id, future).await
+	}
+
+	async fn count_streams(
+		&self,
+		ceramic: &Ceramic,
+		account: Option<String>,
+		model_id: &StreamId,
+	) -> anyhow::Result<u64> {
+		let future = self.loader.count_streams(ceramic, account, model_id);
+		self.with_timeout("count streams", model_id, future).await
+	}
+}
+
+/// state of a circuit breaker
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+	/// calls pass through, transient failures are counted
+	Closed,
+	/// calls fail fast without reaching the inner loader
+	Open,
+	/// reset timeout elapsed, calls pass through to probe the endpoint
+	HalfOpen,
+}
+
+#[derive(Debug)]
+struct Breaker {
+	state: CircuitState,
+	failures: u32,
+	successes: u32,
+	/// tokio instant, so tests can drive the reset timeout with paused time
+	opened_at: tokio::time::Instant,
+}
+
+/// Fail fast while the endpoint of the inner loader is down.
+///
+/// The circuit opens after `failure_threshold` consecutive transient failures, see
+/// [`TransientError::is_transient`], half-opens after `reset_timeout`, and closes again after
+/// `success_threshold` consecutive successes, a failure while half-open reopens it.
+pub struct CircuitBreakerStreamLoader<T: StreamLoader> {
+	loader: T,
+	failure_threshold: u32,
+	success_threshold: u32,
+	reset_timeout: Duration,
+	breaker: Arc<std::sync::Mutex<Breaker>>,
+}
+
+impl<T: StreamLoader> CircuitBreakerStreamLoader<T> {
+	pub fn new(
+		loader: T,
+		failure_threshold: u32,
+		success_threshold: u32,
+		reset_timeout: Duration,
+	) -> Self {
+		Self {
+			loader,
+			failure_threshold: failure_threshold.max(1),
+			success_threshold: success_threshold.max(1),
+			reset_timeout,
+			breaker: Arc::new(std::sync::Mutex::new(Breaker {
+				state: CircuitState::Closed,
+				failures: 0,
+				successes: 0,
+				opened_at: tokio::time::Instant::now(),
+			})),
+		}
+	}
+
+	pub fn state(&self) -> CircuitState {
+		let mut breaker = self.breaker();
+		self.refresh(&mut breaker);
+		breaker.state
+	}
+
+	/// breaker fields are consistent after any panic, a poisoned lock is recovered
+	fn breaker(&self) -> std::sync::MutexGuard<'_, Breaker> {
+		self.breaker
+			.lock()
+			.unwrap_or_else(std::sync::PoisonError::into_inner)
+	}
+
+	/// half-open the circuit once reset timeout elapsed
+	fn refresh(&self, breaker: &mut Breaker) {
+		if breaker.state == CircuitState::Open && breaker.opened_at.elapsed() >= self.reset_timeout
+		{
+			breaker.state = CircuitState::HalfOpen;
+			breaker.successes = 0;
+		}
+	}
+
+	fn open(&self, breaker: &mut Breaker) {
+		tracing::warn!(failures = breaker.failures, "circuit open");
+		breaker.state = CircuitState::Open;
+		breaker.opened_at = tokio::time::Instant::now();
+		breaker.successes = 0;
+	}
+
+	fn record<R>(&self, result: &anyhow::Result<R>) {
+		let failed = matches!(result, Err(err) if TransientError::is_transient(err));
+		let mut breaker = self.breaker();
+		match (breaker.state, failed) {
+			(CircuitState::Closed, false) => breaker.failures = 0,
+			(CircuitState::Closed, true) => {
+				breaker.failures += 1;
+				if breaker.failures >= self.failure_threshold {
+					self.open(&mut breaker);
+				}
+			}
+			(CircuitState::HalfOpen, false) => {
+				breaker.successes += 1;
+				if breaker.successes >= self.success_threshold {
+					tracing::info!("circuit closed");
+					breaker.state = CircuitState::Closed;
+					breaker.failures = 0;
+				}
+			}
+			(CircuitState::HalfOpen, true) => self.open(&mut breaker),
+			// calls started before the circuit opened
+			(CircuitState::Open, _) => {}
+		}
+	}
+
+	async fn call<R>(
+		&self,
+		future: impl Future<Output = anyhow::Result<R>> + Send,
+	) -> anyhow::Result<R> {
+		if self.state() == CircuitState::Open {
+			anyhow::bail!("circuit open");
+		}
+		let result = future.await;
+		self.record(&result);
+		result
+	}
+}
+
+#[async_trait::async_trait]
+impl<T: StreamLoader + Send + Sync> EventsLoader for CircuitBreakerStreamLoader<T> {
+	async fn load_events(
+		&self,
+		ceramic: &Ceramic,
+		stream_id: &StreamId,
+		tip: Option<Cid>,
+	) -> anyhow::Result<Vec<Event>> {
+		self.call(self.loader.load_events(ceramic, stream_id, tip))
+			.await
+	}
+
+	async fn load_events_since(
+		&self,
+		ceramic: &Ceramic,
+		stream_id: &StreamId,
+		since: Cid,
+	) -> anyhow::Result<Vec<Event>> {
+		self.call(self.loader.load_events_since(ceramic, stream_id, since))
+			.await
+	}
+}
+
+#[async_trait::async_trait]
+impl<T: StreamLoader + Send + Sync> StreamLoader for CircuitBreakerStreamLoader<T> {
+	async fn load_stream_state(
+		&self,
+		ceramic: &Ceramic,
+		stream_id: &StreamId,
+		tip: Option<Cid>,
+	) -> anyhow::Result<StreamState> {
+		self.call(self.loader.load_stream_state(ceramic, stream_id, tip))
+			.await
+	}
+
+	async fn load_stream_state_no_cache(
+		&self,
+		ceramic: &Ceramic,
+		stream_id: &StreamId,
+	) -> anyhow::Result<StreamState> {
+		self.call(self.loader.load_stream_state_no_cache(ceramic, stream_id))
+			.await
+	}
+}
+
+#[async_trait::async_trait]
+impl<T: StreamsLoader + Send + Sync> StreamsLoader for CircuitBreakerStreamLoader<T> {
+	async fn load_stream_states(
+		&self,
+		ceramic: &Ceramic,
+		account: Option<String>,
+		model_id: &StreamId,
+	) -> anyhow::Result<Vec<StreamState>> {
+		self.call(self.loader.load_stream_states(ceramic, account, model_id))
+			.await
+	}
+
+	async fn count_streams(
+		&self,
+		ceramic: &Ceramic,
+		account: Option<String>,
+		model_id: &StreamId,
+	) -> anyhow::Result<u64> {
+		self.call(self.loader.count_streams(ceramic, account, model_id))
+			.await
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::atomic::{AtomicUsize, Ordering};
+
+	use super::*;
+	use crate::network::Network;
+
+	struct CountingLoader {
+		calls: AtomicUsize,
+	}
+
+	#[async_trait::async_trait]
+	impl EventsLoader for CountingLoader {
+		async fn load_events(
+			&self,
+			_ceramic: &Ceramic,
+			_stream_id: &StreamId,
+			_tip: Option<Cid>,
+		) -> anyhow::Result<Vec<Event>> {
+			self.calls.fetch_add(1, Ordering::SeqCst);
+			let genesis: Event = crate::commit::example::genesis().genesis.try_into()?;
+			Ok(vec![genesis])
+		}
+	}
+
+	impl StreamLoader for CountingLoader {}
+
+	fn ceramic() -> Ceramic {
+		Ceramic {
+			endpoint: "http://localhost:7007".into(),
+			network: Network::InMemory,
+			pool: None,
+		}
+	}
+
+	#[tokio::test]
+	async fn cached_stream_loader_hits_cache() -> anyhow::Result<()> {
+		let loader = CachedStreamLoader::new(CountingLoader {
+			calls: AtomicUsize::new(0),
+		});
+		let stream_id = crate::commit::example::genesis().stream_id()?;
+
+		let first = loader
+			.load_stream_state(&ceramic(), &stream_id, None)
+			.await?;
+		let second = loader
+			.load_stream_state(&ceramic(), &stream_id, None)
+			.await?;
+		assert_eq!(loader.loader.calls.load(Ordering::SeqCst), 1);
+		assert_eq!(first.content, second.content);
+		Ok(())
+	}
+
+	/// loader blocking loads until the gate has permits
+	struct GatedLoader {
+		calls: AtomicUsize,
+		gate: tokio::sync::Semaphore,
+	}
+
+	#[async_trait::async_trait]
+	impl EventsLoader for GatedLoader {
+		async fn load_events(
+			&self,
+			_ceramic: &Ceramic,
+			_stream_id: &StreamId,
+			_tip: Option<Cid>,
+		) -> anyhow::Result<Vec<Event>> {
+			self.calls.fetch_add(1, Ordering::SeqCst);
+			let _permit = self.gate.acquire().await?;
+			let genesis: Event = crate::commit::example::genesis().genesis.try_into()?;
+			Ok(vec![genesis])
+		}
+	}
+
+	impl StreamLoader for GatedLoader {}
+
+	#[tokio::test]
+	async fn coalesce_concurrent_loads() -> anyhow::Result<()> {
+		let loader = Arc::new(CachedStreamLoader::new(GatedLoader {
+			calls: AtomicUsize::new(0),
+			gate: tokio::sync::Semaphore::new(0),
+		}));
+		let stream_id = crate::commit::example::genesis().stream_id()?;
+
+		let tasks: Vec<_> = (0..20)
+			.map(|_| {
+				let loader = loader.clone();
+				let stream_id = stream_id.clone();
+				tokio::spawn(
+					async move { loader.load_stream_state(&ceramic(), &stream_id, None).await },
+				)
+			})
+			.collect();
+		while loader.in_flight.lock().await.is_empty() {
+			tokio::task::yield_now().await;
+		}
+		tokio::time::sleep(Duration::from_millis(10)).await;
+		loader.loader.gate.add_permits(1);
+
+		for task in tasks {
+			assert_eq!(task.await??.stream_id()?, stream_id);
+		}
+		assert_eq!(loader.loader.calls.load(Ordering::SeqCst), 1);
+		assert!(loader.in_flight.lock().await.is_empty());
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn populate_and_invalidate_cache() -> anyhow::Result<()> {
+		let loader = CachedStreamLoader::new(CountingLoader {
+			calls: AtomicUsize::new(0),
+		});
+		let stream_id = crate::commit::example::genesis().stream_id()?;
+		let genesis: Event = crate::commit::example::genesis().genesis.try_into()?;
+		let state = StreamState::make(stream_id.r#type.int_value(), vec![genesis]).await?;
+
+		loader.populate(&stream_id, state).await;
+		loader
+			.load_stream_state(&ceramic(), &stream_id, None)
+			.await?;
+		assert_eq!(loader.loader.calls.load(Ordering::SeqCst), 0);
+
+		loader.invalidate(&stream_id).await;
+		loader
+			.load_stream_state(&ceramic(), &stream_id, None)
+			.await?;
+		assert_eq!(loader.loader.calls.load(Ordering::SeqCst), 1);
+		Ok(())
+	}
+
+	/// loader only knowing the example genesis stream
+	struct GenesisLoader;
+
+	#[async_trait::async_trait]
+	impl EventsLoader for GenesisLoader {
+		async fn load_events(
+			&self,
+			_ceramic: &Ceramic,
+			stream_id: &StreamId,
+			_tip: Option<Cid>,
+		) -> anyhow::Result<Vec<Event>> {
+			let genesis = crate::commit::example::genesis();
+			if *stream_id != genesis.stream_id()? {
+				anyhow::bail!("stream {} not found", stream_id);
+			}
+			Ok(vec![genesis.genesis.try_into()?])
+		}
+	}
+
+	impl StreamLoader for GenesisLoader {}
+
+	#[tokio::test]
+	async fn batch_load_partial_failure() -> anyhow::Result<()> {
+		let known = crate::commit::example::genesis().stream_id()?;
+		let unknown: StreamId =
+			"kjzl6kcym7w8y5fhg4cl0xi8npfke3jmaaeeic9dwx64bgunnqa6amortdgdsym".parse()?;
+		let ids = vec![unknown.clone(), known.clone()];
+
+		let states = GenesisLoader
+			.load_stream_states_batch(&ceramic(), &ids)
+			.await?;
+		assert_eq!(states.len(), 2);
+		assert_eq!(states[0].0, unknown);
+		assert!(states[0].1.is_err());
+		assert_eq!(states[1].0, known);
+		assert_eq!(states[1].1.as_ref().unwrap().stream_id()?, known);
+		Ok(())
+	}
+
+	/// loader of the example genesis stream counting loads running at the same time
+	#[derive(Default)]
+	struct InFlightLoader {
+		in_flight: AtomicUsize,
+		max_in_flight: AtomicUsize,
+	}
+
+	impl InFlightLoader {
+		async fn track(&self) {
+			let in_flight = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+			self.max_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+			tokio::task::yield_now().await;
+			self.in_flight.fetch_sub(1, Ordering::SeqCst);
+		}
+	}
+
+	#[async_trait::async_trait]
+	impl EventsLoader for InFlightLoader {
+		async fn load_events(
+			&self,
+			ceramic: &Ceramic,
+			stream_id: &StreamId,
+			tip: Option<Cid>,
+		) -> anyhow::Result<Vec<Event>> {
+			self.track().await;
+			GenesisLoader.load_events(ceramic, stream_id, tip).await
+		}
+	}
+
+	impl StreamLoader for InFlightLoader {}
+
+	#[async_trait::async_trait]
+	impl StreamsLoader for InFlightLoader {
+		async fn load_stream_states(
+			&self,
+			_ceramic: &Ceramic,
+			_account: Option<String>,
+			_model_id: &StreamId,
+		) -> anyhow::Result<Vec<StreamState>> {
+			self.track().await;
+			Ok(vec![])
+		}
+	}
+
+	#[tokio::test]
+	async fn batch_load_is_bounded() -> anyhow::Result<()> {
+		let stream_id = crate::commit::example::genesis().stream_id()?;
+		let ids = vec![stream_id; MAX_CONCURRENT_LOADS * 2];
+		let loader = InFlightLoader::default();
+
+		let states = loader.load_stream_states_batch(&ceramic(), &ids).await?;
+		assert_eq!(states.len(), ids.len());
+		assert!(states.iter().all(|(_, state)| state.is_ok()));
+		assert_eq!(
+			loader.max_in_flight.load(Ordering::SeqCst),
+			MAX_CONCURRENT_LOADS
+		);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn account_loads_are_bounded() -> anyhow::Result<()> {
+		let model_id = crate::commit::example::genesis().model_id()?;
+		let accounts: Vec<_> = (0..MAX_CONCURRENT_LOADS * 2)
+			.map(|idx| format!("did:key:{}", idx))
+			.collect();
+		let loader = InFlightLoader::default();
+
+		let states = loader
+			.load_stream_states_for_accounts(&ceramic(), &accounts, &model_id)
+			.await?;
+		assert_eq!(states.len(), accounts.len());
+		assert_eq!(
+			loader.max_in_flight.load(Ordering::SeqCst),
+			MAX_CONCURRENT_LOADS
+		);
+		Ok(())
+	}
+
+	#[async_trait::async_trait]
+	impl StreamsLoader for CountingLoader {
+		async fn load_stream_states(
+			&self,
+			ceramic: &Ceramic,
+			account: Option<String>,
+			_model_id: &StreamId,
+		) -> anyhow::Result<Vec<StreamState>> {
+			if account.as_deref() == Some("bad") {
+				anyhow::bail!("invalid account");
+			}
+			let stream_id = crate::commit::example::genesis().stream_id()?;
+			Ok(vec![
+				self.load_stream_state(ceramic, &stream_id, None).await?,
+			])
+		}
+	}
+
+	#[tokio::test]
+	async fn load_stream_states_for_accounts() -> anyhow::Result<()> {
+		let loader = CachedStreamLoader::new(CountingLoader {
+			calls: AtomicUsize::new(0),
+		});
+		let model_id: StreamId =
+			"kjzl6hvfrbw6c86gt9j415yw2x8stmkotcrzpeutrbkp42i4z90gp5ibptz4sso".parse()?;
+		let accounts = vec!["alice".to_string(), "bad".to_string()];
+
+		let states = loader
+			.load_stream_states_for_accounts(&ceramic(), &accounts, &model_id)
+			.await?;
+		assert_eq!(states.len(), 2);
+		assert_eq!(states["alice"].as_ref().unwrap().len(), 1);
+		assert!(states["bad"].is_err());
+		assert_eq!(loader.loader.calls.load(Ordering::SeqCst), 1);
+
+		let states = loader
+			.load_stream_states_for_accounts(&ceramic(), &accounts, &model_id)
+			.await?;
+		assert!(states["alice"].is_ok());
+		assert_eq!(loader.loader.calls.load(Ordering::SeqCst), 1);
+
+		let stream_id = crate::commit::example::genesis().stream_id()?;
+		loader.invalidate(&stream_id).await;
+		loader
+			.load_stream_states_for_accounts(&ceramic(), &accounts, &model_id)
+			.await?;
+		assert_eq!(loader.loader.calls.load(Ordering::SeqCst), 2);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn count_streams_cached() -> anyhow::Result<()> {
+		let loader = CachedStreamLoader::new(CountingLoader {
+			calls: AtomicUsize::new(0),
+		});
+		let model_id: StreamId =
+			"kjzl6hvfrbw6c86gt9j415yw2x8stmkotcrzpeutrbkp42i4z90gp5ibptz4sso".parse()?;
+
+		assert_eq!(loader.count_streams(&ceramic(), None, &model_id).await?, 1);
+		assert_eq!(loader.count_streams(&ceramic(), None, &model_id).await?, 1);
+		assert_eq!(loader.loader.calls.load(Ordering::SeqCst), 1);
+
+		let stream_id = crate::commit::example::genesis().stream_id()?;
+		loader.invalidate(&stream_id).await;
+		loader.count_streams(&ceramic(), None, &model_id).await?;
+		assert_eq!(loader.loader.calls.load(Ordering::SeqCst), 2);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn zero_ttl_passes_through() -> anyhow::Result<()> {
+		let loader = CachedStreamLoader::new(CountingLoader {
+			calls: AtomicUsize::new(0),
+		})
+		.with_ttl(Duration::ZERO);
+		let stream_id = crate::commit::example::genesis().stream_id()?;
+
+		loader
+			.load_stream_state(&ceramic(), &stream_id, None)
+			.await?;
+		loader
+			.load_stream_state(&ceramic(), &stream_id, None)
+			.await?;
+		assert_eq!(loader.loader.calls.load(Ordering::SeqCst), 2);
+		assert!(loader.cache.lock().await.is_empty());
+		Ok(())
+	}
+
+	#[test]
+	fn paginate_with_cursor() -> anyhow::Result<()> {
+		let items = || {
+			["c", "a", "d", "b", "e"]
+				.into_iter()
+				.map(|id| (id.to_string(), id))
+				.collect::<Vec<_>>()
+		};
+		let mut page = PageOptions {
+			after: None,
+			limit: 2,
+		};
+		let mut pages = vec![];
+		loop {
+			let (items, next) = page.paginate(items())?;
+			pages.push(items);
+			match next {
+				Some(cursor) => page.after = Some(cursor),
+				None => break,
+			}
+		}
+		assert_eq!(pages, vec![vec!["a", "b"], vec!["c", "d"], vec!["e"]]);
+
+		page.limit = 0;
+		assert!(page.paginate(items()).is_err());
+		Ok(())
+	}
+
+	/// loader failing the first `failures` loads with the error
+	struct FlakyLoader {
+		calls: AtomicUsize,
+		failures: usize,
+		error: fn() -> anyhow::Error,
+	}
+
+	#[async_trait::async_trait]
+	impl EventsLoader for FlakyLoader {
+		async fn load_events(
+			&self,
+			_ceramic: &Ceramic,
+			_stream_id: &StreamId,
+			_tip: Option<Cid>,
+		) -> anyhow::Result<Vec<Event>> {
+			if self.calls.fetch_add(1, Ordering::SeqCst) < self.failures {
+				return Err((self.error)());
+			}
+			let genesis: Event = crate::commit::example::genesis().genesis.try_into()?;
+			Ok(vec![genesis])
+		}
+	}
+
+	impl StreamLoader for FlakyLoader {}
+
+	fn retrying(
+		failures: usize,
+		error: fn() -> anyhow::Error,
+	) -> RetryingStreamLoader<FlakyLoader> {
+		let loader = FlakyLoader {
+			calls: AtomicUsize::new(0),
+			failures,
+			error,
+		};
+		RetryingStreamLoader::new(loader, 3, Duration::from_millis(1))
+	}
+
+	#[tokio::test]
+	async fn retry_transient_errors() -> anyhow::Result<()> {
+		let stream_id = crate::commit::example::genesis().stream_id()?;
+		let transient = || -> anyhow::Error { TransientError(anyhow::anyhow!("node down")).into() };
+
+		let loader = retrying(3, transient);
+		assert!(loader
+			.load_events(&ceramic(), &stream_id, None)
+			.await
+			.is_ok());
+		assert_eq!(loader.loader.calls.load(Ordering::SeqCst), 4);
+
+		let loader = retrying(4, transient);
+		assert!(loader
+			.load_events(&ceramic(), &stream_id, None)
+			.await
+			.is_err());
+		assert_eq!(loader.loader.calls.load(Ordering::SeqCst), 4);
+
+		let reset = || -> anyhow::Error {
+			std::io::Error::from(std::io::ErrorKind::ConnectionReset).into()
+		};
+		let loader = retrying(1, reset);
+		assert!(loader
+			.load_events(&ceramic(), &stream_id, None)
+			.await
+			.is_ok());
+		assert_eq!(loader.loader.calls.load(Ordering::SeqCst), 2);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn not_retry_permanent_errors() -> anyhow::Result<()> {
+		let stream_id = crate::commit::example::genesis().stream_id()?;
+		let loader = retrying(1, || anyhow::anyhow!("invalid genesis event"));
+		assert!(loader
+			.load_events(&ceramic(), &stream_id, None)
+			.await
+			.is_err());
+		assert_eq!(loader.loader.calls.load(Ordering::SeqCst), 1);
+
+		// messages reading like a network failure are not retried
+		let loader = retrying(1, || {
+			anyhow::anyhow!("event bafy503 timed out: service unavailable")
+		});
+		assert!(loader
+			.load_events(&ceramic(), &stream_id, None)
+			.await
+			.is_err());
+		assert_eq!(loader.loader.calls.load(Ordering::SeqCst), 1);
+		Ok(())
+	}
+
+	struct SlowLoader {
+		delay: Duration,
+	}
+
+	#[async_trait::async_trait]
+	impl EventsLoader for SlowLoader {
+		async fn load_events(
+			&self,
+			_ceramic: &Ceramic,
+			_stream_id: &StreamId,
+			_tip: Option<Cid>,
+		) -> anyhow::Result<Vec<Event>> {
+			tokio::time::sleep(self.delay).await;
+			let genesis: Event = crate::commit::example::genesis().genesis.try_into()?;
+			Ok(vec![genesis])
+		}
+	}
+
+	impl StreamLoader for SlowLoader {}
+
+	#[tokio::test]
+	async fn timeout_slow_loads() -> anyhow::Result<()> {
+		let stream_id = crate::commit::example::genesis().stream_id()?;
+		let slow = SlowLoader {
+			delay: Duration::from_millis(200),
+		};
+		let loader = TimeoutStreamLoader::new(slow, Duration::from_millis(10));
+		let err = loader
+			.load_events(&ceramic(), &stream_id, None)
+			.await
+			.unwrap_err();
+		assert!(err.to_string().contains("timed out"));
+
+		let fast = SlowLoader {
+			delay: Duration::ZERO,
+		};
+		let loader = TimeoutStreamLoader::new(fast, Duration::from_secs(5));
+		assert!(loader
+			.load_events(&ceramic(), &stream_id, None)
+			.await
+			.is_ok());
+		Ok(())
+	}
+
+	#[tokio::test(start_paused = true)]
+	async fn circuit_breaker_opens_and_recovers() -> anyhow::Result<()> {
+		let stream_id = crate::commit::example::genesis().stream_id()?;
+		let transient = || -> anyhow::Error { TransientError(anyhow::anyhow!("node down")).into() };
+		let flaky = FlakyLoader {
+			calls: AtomicUsize::new(0),
+			failures: 3,
+			error: transient,
+		};
+		let loader = CircuitBreakerStreamLoader::new(flaky, 2, 1, Duration::from_millis(20));
+
+		for _ in 0..2 {
+			assert!(loader
+				.load_events(&ceramic(), &stream_id, None)
+				.await
+				.is_err());
+		}
+		assert_eq!(loader.state(), CircuitState::Open);
+		let err = loader
+			.load_events(&ceramic(), &stream_id, None)
+			.await
+			.unwrap_err();
+		assert_eq!(err.to_string(), "circuit open");
+		assert_eq!(loader.loader.calls.load(Ordering::SeqCst), 2);
+
+		// failed probe reopens the circuit
+		tokio::time::sleep(Duration::from_millis(30)).await;
+		assert_eq!(loader.state(), CircuitState::HalfOpen);
+		assert!(loader
+			.load_events(&ceramic(), &stream_id, None)
+			.await
+			.is_err());
+		assert_eq!(loader.state(), CircuitState::Open);
+
+		tokio::time::sleep(Duration::from_millis(30)).await;
+		assert!(loader
+			.load_events(&ceramic(), &stream_id, None)
+			.await
+			.is_ok());
+		assert_eq!(loader.state(), CircuitState::Closed);
+		assert_eq!(loader.loader.calls.load(Ordering::SeqCst), 4);
+		Ok(())
+	}
+
+	/// loader answering `load_events_since` only, like a loader unable to load the full stream
+	struct SinceLoader;
+
+	#[async_trait::async_trait]
+	impl EventsLoader for SinceLoader {
+		async fn load_events(
+			&self,
+			_ceramic: &Ceramic,
+			_stream_id: &StreamId,
+			_tip: Option<Cid>,
+		) -> anyhow::Result<Vec<Event>> {
+			anyhow::bail!("full stream load")
+		}
+
+		async fn load_events_since(
+			&self,
+			_ceramic: &Ceramic,
+			_stream_id: &StreamId,
+			_since: Cid,
+		) -> anyhow::Result<Vec<Event>> {
+			let genesis: Event = crate::commit::example::genesis().genesis.try_into()?;
+			Ok(vec![genesis])
+		}
+	}
+
+	impl StreamLoader for SinceLoader {}
+
+	#[tokio::test]
+	async fn wrappers_forward_load_events_since() -> anyhow::Result<()> {
+		let stream_id = crate::commit::example::genesis().stream_id()?;
+		let since = stream_id.cid;
+		let ceramic = ceramic();
+
+		let cached = CachedStreamLoader::new(SinceLoader);
+		assert_eq!(
+			cached
+				.load_events_since(&ceramic, &stream_id, since)
+				.await?
+				.len(),
+			1
+		);
+		let retrying = RetryingStreamLoader::new(SinceLoader, 3, Duration::from_millis(1));
+		assert_eq!(
+			retrying
+				.load_events_since(&ceramic, &stream_id, since)
+				.await?
+				.len(),
+			1
+		);
+		let timeout = TimeoutStreamLoader::new(SinceLoader, Duration::from_secs(5));
+		assert_eq!(
+			timeout
+				.load_events_since(&ceramic, &stream_id, since)
+				.await?
+				.len(),
+			1
+		);
+		let breaker = CircuitBreakerStreamLoader::new(SinceLoader, 2, 1, Duration::from_secs(1));
+		assert_eq!(
+			breaker
+				.load_events_since(&ceramic, &stream_id, since)
+				.await?
+				.len(),
+			1
+		);
+		Ok(())
+	}
+
+	#[test]
+	fn ttl_boundary() {
+		let ttl = Duration::from_secs(60);
+		let inserted = Instant::now();
+		let almost = inserted + ttl - Duration::from_nanos(1);
+		assert!(is_fresh(inserted, almost, ttl));
+		assert!(!is_fresh(inserted, inserted + ttl, ttl));
+		assert!(is_fresh(inserted, inserted + ttl, Duration::MAX));
+	}
 }
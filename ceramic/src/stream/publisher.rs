@@ -0,0 +1,217 @@
+use std::collections::HashSet;
+
+use ceramic_core::{Cid, StreamId};
+use int_enum::IntEnum;
+
+use crate::event::Event;
+use crate::kubo::{self, message::MessageUpdatePublisher, AnchorRequester, BlockUploader};
+use crate::{Ceramic, StreamState};
+
+use super::StreamPublisher;
+
+/// Publish commits through kubo: blocks are uploaded, genesis commits are sent for anchoring
+/// and the new tip is announced over pubsub.
+pub struct ConcreteStreamPublisher<T> {
+	client: T,
+}
+
+impl<T> ConcreteStreamPublisher<T> {
+	pub fn new(client: T) -> Self {
+		Self { client }
+	}
+
+	pub fn inner(&self) -> &T {
+		&self.client
+	}
+}
+
+/// order commits so that each one follows its prev, which is either in `known` or an
+/// earlier commit
+fn sort_commits(commits: &[Event], known: &HashSet<Cid>) -> anyhow::Result<Vec<Event>> {
+	let mut known = known.clone();
+	let mut remaining: Vec<&Event> = commits.iter().collect();
+	let mut sorted = Vec::with_capacity(commits.len());
+	while !remaining.is_empty() {
+		let mut next = None;
+		for (idx, commit) in remaining.iter().enumerate() {
+			match commit.prev()? {
+				Some(prev) if !known.contains(&prev) => continue,
+				_ => {
+					next = Some(idx);
+					break;
+				}
+			}
+		}
+		match next {
+			Some(idx) => {
+				let commit = remaining.remove(idx);
+				known.insert(commit.cid);
+				sorted.push(commit.clone());
+			}
+			None => {
+				let commit = remaining[0];
+				anyhow::bail!(
+					"prev {:?} of commit {} not found in stream or commits",
+					commit.prev()?,
+					commit.cid
+				);
+			}
+		}
+	}
+	Ok(sorted)
+}
+
+#[async_trait::async_trait]
+impl<T> StreamPublisher for ConcreteStreamPublisher<T>
+where
+	T: BlockUploader + AnchorRequester + MessageUpdatePublisher + Send + Sync,
+{
+	async fn publish_events(
+		&self,
+		ceramic: &Ceramic,
+		stream_id: &StreamId,
+		state: Option<&StreamState>,
+		commits: &[Event],
+	) -> anyhow::Result<()> {
+		let known: HashSet<Cid> = match state {
+			Some(state) => state.history().iter().map(|commit| commit.cid).collect(),
+			None => HashSet::new(),
+		};
+		let commits = sort_commits(commits, &known)?;
+		let tip = match commits.last() {
+			Some(commit) => commit.cid,
+			None => anyhow::bail!("input commits of {} is empty", stream_id),
+		};
+		let model = match state {
+			Some(state) => state.must_model()?,
+			None => StreamState::make(stream_id.r#type.int_value(), commits.clone())
+				.await?
+				.must_model()?,
+		};
+
+		for commit in commits {
+			kubo::upload_blocks(&self.client, &commit).await?;
+			if commit.prev()?.is_none() {
+				self.client
+					.request_anchor(ceramic, stream_id, commit)
+					.await?;
+			}
+		}
+		self.client
+			.publish_update(ceramic, stream_id, &tip, &model)
+			.await
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::str::FromStr;
+
+	use tokio::sync::Mutex;
+
+	use super::*;
+	use crate::event::{AnchorValue, EventValue};
+	use crate::kubo::message::MessagePublisher;
+	use crate::network::Network;
+
+	#[derive(Default)]
+	struct MemoryClient {
+		blocks: Mutex<Vec<Cid>>,
+		anchors: Mutex<Vec<Cid>>,
+		messages: Mutex<Vec<Vec<u8>>>,
+	}
+
+	#[async_trait::async_trait]
+	impl BlockUploader for MemoryClient {
+		async fn block_upload(&self, cid: Cid, _block: Vec<u8>) -> anyhow::Result<()> {
+			self.blocks.lock().await.push(cid);
+			Ok(())
+		}
+	}
+
+	#[async_trait::async_trait]
+	impl AnchorRequester for MemoryClient {
+		async fn request_anchor(
+			&self,
+			_ceramic: &Ceramic,
+			_stream_id: &StreamId,
+			event: Event,
+		) -> anyhow::Result<()> {
+			self.anchors.lock().await.push(event.cid);
+			Ok(())
+		}
+	}
+
+	#[async_trait::async_trait]
+	impl MessagePublisher for MemoryClient {
+		async fn publish_message(&self, _topic: &String, msg: Vec<u8>) -> anyhow::Result<()> {
+			self.messages.lock().await.push(msg);
+			Ok(())
+		}
+	}
+
+	fn ceramic() -> Ceramic {
+		Ceramic {
+			endpoint: "http://localhost:7007".into(),
+			network: Network::InMemory,
+			pool: None,
+		}
+	}
+
+	#[tokio::test]
+	async fn publish_events() -> anyhow::Result<()> {
+		let genesis = crate::commit::example::genesis();
+		let stream_id = genesis.stream_id()?;
+		let genesis: Event = genesis.genesis.try_into()?;
+		let anchor = Event {
+			cid: Cid::from_str("bafyreiaxfjkme33rujt5wfajbl7r6pcdhjw4gfzwmxqe7xs4wf3dwvxdpy")?,
+			value: EventValue::Anchor(AnchorValue {
+				id: genesis.cid,
+				prev: genesis.cid,
+				..Default::default()
+			}),
+		};
+
+		let publisher = ConcreteStreamPublisher::new(MemoryClient::default());
+		publisher
+			.publish_events(
+				&ceramic(),
+				&stream_id,
+				None,
+				&[anchor.clone(), genesis.clone()],
+			)
+			.await?;
+		let client = publisher.inner();
+		assert!(client.blocks.lock().await.contains(&genesis.cid));
+		assert_eq!(*client.anchors.lock().await, vec![genesis.cid]);
+		let messages = client.messages.lock().await;
+		assert_eq!(messages.len(), 1);
+		let msg: serde_json::Value = serde_json::from_slice(&messages[0])?;
+		assert_eq!(msg["tip"], anchor.cid.to_string());
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn publish_events_missing_prev() -> anyhow::Result<()> {
+		let genesis = crate::commit::example::genesis();
+		let stream_id = genesis.stream_id()?;
+		let genesis: Event = genesis.genesis.try_into()?;
+		let anchor = Event {
+			cid: Cid::from_str("bafyreiaxfjkme33rujt5wfajbl7r6pcdhjw4gfzwmxqe7xs4wf3dwvxdpy")?,
+			value: EventValue::Anchor(AnchorValue {
+				id: genesis.cid,
+				prev: genesis.cid,
+				..Default::default()
+			}),
+		};
+
+		let publisher = ConcreteStreamPublisher::new(MemoryClient::default());
+		let res = publisher
+			.publish_events(&ceramic(), &stream_id, None, &[anchor])
+			.await;
+		assert!(res.is_err());
+		assert!(publisher.inner().blocks.lock().await.is_empty());
+		assert!(publisher.inner().messages.lock().await.is_empty());
+		Ok(())
+	}
+}
@@ -3,11 +3,14 @@ use std::str::FromStr;
 
 use ceramic_core::{Cid, MultiBase32String, StreamId};
 use ceramic_http_client::api::StateLog;
+use chrono::{DateTime, Utc};
+use futures::{Stream, StreamExt};
 use int_enum::IntEnum;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
-use crate::event::{Event, VerifyOption};
+use crate::event::{Event, EventValue, VerifyOption};
+use crate::DataverseError;
 
 use super::commit_id::CommitId;
 use super::stream_id::StreamIdType;
@@ -73,23 +76,78 @@ pub struct AnchorProof {
 	pub tx_type: Option<String>,
 }
 
+/// Signing authority of a stream, delegated through the CACAO capability of an event
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ControllerEntry {
+	/// DID which signs the events
+	pub did: String,
+	/// DID which delegated signing rights to `did`
+	pub delegated_by: Option<String>,
+	/// first event signed by this authority
+	pub since_event: Cid,
+}
+
+/// Kind of a commit in stream log, a signed event is genesis or data
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum CommitKind {
+	Genesis,
+	Data,
+	Anchor,
+}
+
+impl From<LogType> for CommitKind {
+	fn from(log_type: LogType) -> Self {
+		match log_type {
+			LogType::Genesis => CommitKind::Genesis,
+			LogType::Signed => CommitKind::Data,
+			LogType::Anchor => CommitKind::Anchor,
+		}
+	}
+}
+
+/// Commit of stream log, read from state without loading the event
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommitSummary {
+	pub cid: Cid,
+	pub kind: CommitKind,
+	/// anchor time of commit, `None` until it's anchored
+	pub timestamp: Option<DateTime<Utc>>,
+}
+
 impl StreamState {
 	pub async fn make(r#type: u64, events: Vec<Event>) -> anyhow::Result<Self> {
+		Self::from_stream(r#type, futures::stream::iter(events.into_iter().map(Ok))).await
+	}
+
+	/// state of events applied as they are yielded, see `EventsLoader::load_events_stream`
+	pub async fn from_stream(
+		r#type: u64,
+		events: impl Stream<Item = anyhow::Result<Event>>,
+	) -> anyhow::Result<Self> {
 		let mut state = StreamState {
 			r#type,
 			..Default::default()
 		};
 
-		for event in events {
+		let mut chain = vec![];
+		futures::pin_mut!(events);
+		while let Some(event) = events.next().await {
+			let event = event?;
 			event.apply_to(&mut state).await?;
+			push_controller_entry(&mut chain, &event);
 			let model = state.must_model()?;
 			let opts = vec![
 				VerifyOption::ResourceModelsContain(model.clone()),
 				// cannot get anchor time (should get time of txHash from rpc)
 				// VerifyOption::ExpirationTimeBefore(Utc::now()),
 			];
-			event.verify_signature(opts)?;
+			event
+				.verify_signature(opts)
+				.map_err(|err| DataverseError::SignatureVerification(err.to_string()))?;
 		}
+		state
+			.audit_controller_chain(&chain)
+			.map_err(|err| DataverseError::SignatureVerification(err.to_string()))?;
 		Ok(state)
 	}
 
@@ -102,6 +160,7 @@ impl StreamState {
 			r#type: stream_id.r#type.int_value(),
 			..Default::default()
 		};
+		let mut chain = vec![];
 		let mut tip = tip;
 		loop {
 			let event = match event_map.get(&tip) {
@@ -109,6 +168,7 @@ impl StreamState {
 				None => anyhow::bail!("event {} not found", tip),
 			};
 			event.apply_to(&mut state).await?;
+			push_controller_entry(&mut chain, event);
 			match event.prev()? {
 				Some(prev) => tip = prev,
 				None => break,
@@ -121,22 +181,88 @@ impl StreamState {
 			];
 			event.verify_signature(opts)?;
 		}
+		state.audit_controller_chain(&chain)?;
 		Ok(state)
 	}
 
 	/// Get controllers for stream
 	pub fn controllers(&self) -> Vec<String> {
-		let mut controllers = vec![];
-		if let Some(controllers_json) = self.metadata.get("controllers") {
-			if let Some(controllers_vec) = controllers_json.as_array() {
-				for controller in controllers_vec {
-					if let Some(controller_str) = controller.as_str() {
-						controllers.push(controller_str.to_string());
-					}
+		self.controller_values()
+			.filter_map(Value::as_str)
+			.map(String::from)
+			.collect()
+	}
+
+	/// first controller of stream, the account owning it, borrowed from metadata
+	pub fn account(&self) -> Option<&str> {
+		self.controller_values().find_map(Value::as_str)
+	}
+
+	/// replace metadata of stream
+	pub fn set_metadata(&mut self, metadata: Value) {
+		self.metadata = metadata;
+	}
+
+	fn controller_values(&self) -> impl Iterator<Item = &Value> {
+		self.metadata
+			.get("controllers")
+			.and_then(Value::as_array)
+			.into_iter()
+			.flatten()
+	}
+
+	/// Get delegated signing authorities from events of stream, in event order.
+	///
+	/// StreamState only keeps cids of its log, so events must be provided by the caller.
+	/// Events without capability are skipped, so streams without delegation get an empty chain.
+	pub fn controller_chain(&self, events: &[Event]) -> Vec<ControllerEntry> {
+		let mut chain: Vec<ControllerEntry> = vec![];
+		for event in events {
+			push_controller_entry(&mut chain, event);
+		}
+		chain
+	}
+
+	/// Audit signing authorities of events, every delegation must be issued by a controller of stream
+	pub fn verify_controller_chain(
+		&self,
+		events: &[Event],
+	) -> anyhow::Result<Vec<ControllerEntry>> {
+		let chain = self.controller_chain(events);
+		self.audit_controller_chain(&chain)?;
+		Ok(chain)
+	}
+
+	fn audit_controller_chain(&self, chain: &[ControllerEntry]) -> anyhow::Result<()> {
+		let controllers = self.controllers();
+		for entry in chain {
+			if let Some(delegated_by) = &entry.delegated_by {
+				let is_controller = controllers
+					.iter()
+					.any(|controller| controller.eq_ignore_ascii_case(delegated_by));
+				if !is_controller {
+					anyhow::bail!(
+						"event {} is signed by {} delegated by {}, which is not a controller",
+						entry.since_event,
+						entry.did,
+						delegated_by
+					);
 				}
 			}
 		}
-		controllers
+		Ok(())
+	}
+
+	/// byte size of content serialized as compact json
+	pub fn compact_size(&self) -> usize {
+		serde_json::to_vec(&self.content)
+			.map(|content| content.len())
+			.unwrap_or_default()
+	}
+
+	/// total byte size of event payloads, the storage cost of whole stream history
+	pub fn full_size(events: &[Event]) -> usize {
+		events.iter().map(Event::payload_size).sum()
 	}
 
 	/// Get model id for stream
@@ -164,6 +290,66 @@ impl StreamState {
 		})
 	}
 
+	/// creation time of stream, the anchor timestamp of genesis log,
+	/// or `createdAt` of content when genesis is not anchored yet
+	pub fn created_at(&self) -> Option<DateTime<Utc>> {
+		if let Some(timestamp) = self.log.first().and_then(|log| log.timestamp) {
+			return DateTime::from_timestamp(timestamp, 0);
+		}
+		self.content.get("createdAt")?.as_str()?.parse().ok()
+	}
+
+	/// update time of stream, the anchor timestamp of latest log,
+	/// or `updatedAt` of content when latest event is not anchored yet
+	pub fn updated_at(&self) -> Option<DateTime<Utc>> {
+		if let Some(timestamp) = self.log.last().and_then(|log| log.timestamp) {
+			return DateTime::from_timestamp(timestamp, 0);
+		}
+		let updated_at = self
+			.content
+			.get("updatedAt")
+			.and_then(|value| value.as_str());
+		match updated_at.and_then(|value| value.parse().ok()) {
+			Some(updated_at) => Some(updated_at),
+			None => self.created_at(),
+		}
+	}
+
+	/// State of stream of `r#type` at commit `to`, replaying `events` from genesis up to and
+	/// including `to`.
+	///
+	/// `events` are the events of the stream in chronological order, fails when `to` is not
+	/// one of them.
+	pub async fn rollback(r#type: u64, to: Cid, events: &[Event]) -> anyhow::Result<StreamState> {
+		let position = events
+			.iter()
+			.position(|event| event.cid == to)
+			.ok_or_else(|| anyhow::anyhow!("commit {} not found in events", to))?;
+		Self::make(r#type, events[..=position].to_vec()).await
+	}
+
+	/// number of events in stream log
+	pub fn commit_count(&self) -> usize {
+		self.log.len()
+	}
+
+	/// commits of stream log from genesis to tip, log entries of unknown type or
+	/// invalid cid are skipped
+	pub fn history(&self) -> Vec<CommitSummary> {
+		self.log
+			.iter()
+			.filter_map(|log| {
+				Some(CommitSummary {
+					cid: Cid::from_str(log.cid.as_ref()).ok()?,
+					kind: LogType::from_int(log.r#type).ok()?.into(),
+					timestamp: log
+						.timestamp
+						.and_then(|timestamp| DateTime::from_timestamp(timestamp, 0)),
+				})
+			})
+			.collect()
+	}
+
 	pub fn commit_ids(&self) -> anyhow::Result<Vec<CommitId>> {
 		let mut commit_ids = vec![];
 		let stream_id = self.stream_id()?;
@@ -178,6 +364,32 @@ impl StreamState {
 	}
 }
 
+/// append signing authority of event to chain, unless it's unchanged since the last entry
+fn push_controller_entry(chain: &mut Vec<ControllerEntry>, event: &Event) {
+	let cacao = match &event.value {
+		EventValue::Signed(signed) => match signed.cacao() {
+			Ok(Some(cacao)) => cacao,
+			Ok(None) => return,
+			Err(err) => {
+				tracing::warn!(cid = event.cid.to_string(), ?err, "failed to decode cacao");
+				return;
+			}
+		},
+		EventValue::Anchor(_) => return,
+	};
+	let delegated_by = Some(cacao.p.iss.clone());
+	let unchanged = chain.last().map_or(false, |last| {
+		last.did == cacao.p.aud && last.delegated_by == delegated_by
+	});
+	if !unchanged {
+		chain.push(ControllerEntry {
+			did: cacao.p.aud.clone(),
+			delegated_by,
+			since_event: event.cid,
+		});
+	}
+}
+
 impl Default for StreamState {
 	fn default() -> Self {
 		Self {
@@ -227,6 +439,198 @@ mod tests {
 		assert_eq!(status, AnchorStatus::Anchored);
 	}
 
+	#[test]
+	fn controller_chain() -> anyhow::Result<()> {
+		let genesis: Event = crate::commit::example::genesis().genesis.try_into()?;
+		let cacao = match &genesis.value {
+			EventValue::Signed(signed) => signed.cacao()?.unwrap(),
+			_ => anyhow::bail!("genesis should be signed"),
+		};
+
+		let mut state = StreamState::default();
+		let chain = state.controller_chain(&[genesis.clone(), genesis.clone()]);
+		assert_eq!(
+			chain,
+			vec![ControllerEntry {
+				did: cacao.p.aud.clone(),
+				delegated_by: Some(cacao.p.iss.clone()),
+				since_event: genesis.cid,
+			}]
+		);
+		assert!(state.verify_controller_chain(&[genesis.clone()]).is_err());
+
+		assert_eq!(state.account(), None);
+		state.set_metadata(json!({ "controllers": [cacao.p.iss] }));
+		assert_eq!(state.account(), Some(cacao.p.iss.as_str()));
+		assert_eq!(state.verify_controller_chain(&[genesis])?, chain);
+		assert!(state.controller_chain(&[]).is_empty());
+		Ok(())
+	}
+
+	#[test]
+	fn stream_sizes() -> anyhow::Result<()> {
+		let state = StreamState {
+			content: json!({"title": "hello"}),
+			..Default::default()
+		};
+		assert_eq!(state.compact_size(), r#"{"title":"hello"}"#.len());
+
+		let genesis: Event = crate::commit::example::genesis().genesis.try_into()?;
+		let size = genesis.payload_size();
+		assert_eq!(
+			StreamState::full_size(&[genesis.clone(), genesis]),
+			size * 2
+		);
+		assert_eq!(StreamState::full_size(&[]), 0);
+		Ok(())
+	}
+
+	#[test]
+	fn stream_history() -> anyhow::Result<()> {
+		let genesis = "bagcqcerayswtqarydm2rgeh37yir45ccvfkj3qhwhfmu4vdjjrtny5l4rpia";
+		let anchor = "bafyreiaxfjkme33rujt5wfajbl7r6pcdhjw4gfzwmxqe7xs4wf3dwvxdpy";
+		let log = |cid: &str, r#type, timestamp| StateLog {
+			cid: cid.to_string(),
+			r#type,
+			timestamp,
+			expiration_time: None,
+		};
+		let state = StreamState {
+			log: vec![
+				log(genesis, 0, None),
+				log(genesis, 1, None),
+				log(anchor, 2, Some(1693551803)),
+				log("not a cid", 1, None),
+			],
+			..Default::default()
+		};
+		assert_eq!(state.commit_count(), 4);
+
+		let history = state.history();
+		let kinds: Vec<_> = history.iter().map(|commit| commit.kind).collect();
+		assert_eq!(
+			kinds,
+			vec![CommitKind::Genesis, CommitKind::Data, CommitKind::Anchor]
+		);
+		assert_eq!(history[0].cid, Cid::from_str(genesis)?);
+		assert_eq!(history[0].timestamp, None);
+		assert_eq!(
+			history[2].timestamp,
+			DateTime::from_timestamp(1693551803, 0)
+		);
+		assert_eq!(StreamState::default().commit_count(), 0);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn rollback() -> anyhow::Result<()> {
+		let genesis = crate::commit::example::genesis();
+		let stream_id = genesis.stream_id()?;
+		let genesis: Event = genesis.genesis.try_into()?;
+		// unsigned patch of genesis, verification only checks events with cacao
+		let mut signed = match &genesis.value {
+			EventValue::Signed(signed) => signed.clone(),
+			_ => anyhow::bail!("genesis is not signed"),
+		};
+		let payload = libipld::ipld!({
+			"data": [{ "op": "replace", "path": "/text", "value": "updated" }],
+			"prev": genesis.cid,
+			"id": genesis.cid
+		});
+		signed.linked_block = Some(libipld::prelude::Codec::encode(
+			&libipld::cbor::DagCborCodec,
+			&payload,
+		)?);
+		signed.cacao_block = None;
+		let data = Event {
+			cid: Cid::from_str("bafyreidtdpcjnltl7enswtp4s4xbsweb5zndvzihiyczl3t6ppqvbcgjpu")?,
+			value: EventValue::Signed(signed),
+		};
+		let anchor = Event {
+			cid: Cid::from_str("bafyreiaxfjkme33rujt5wfajbl7r6pcdhjw4gfzwmxqe7xs4wf3dwvxdpy")?,
+			value: EventValue::Anchor(crate::event::AnchorValue {
+				id: genesis.cid,
+				prev: data.cid,
+				..Default::default()
+			}),
+		};
+		let events = vec![genesis.clone(), data.clone(), anchor];
+
+		let r#type = stream_id.r#type.int_value();
+		let state = StreamState::make(r#type, events.clone()).await?;
+		assert_eq!(state.commit_count(), 3);
+		let expected = StreamState::make(r#type, vec![genesis.clone()]).await?;
+		assert_ne!(state.content, expected.content);
+
+		let rolled_back = StreamState::rollback(r#type, genesis.cid, &events).await?;
+		assert_eq!(rolled_back.content, expected.content);
+		assert_eq!(rolled_back.commit_count(), 1);
+		assert_eq!(rolled_back.stream_id()?, stream_id);
+
+		let rolled_back = StreamState::rollback(r#type, data.cid, &events).await?;
+		assert_eq!(rolled_back.content, state.content);
+		assert_eq!(rolled_back.content["text"], "updated");
+		assert_eq!(rolled_back.commit_count(), 2);
+
+		let missing =
+			Cid::from_str("bagcqcerafrbuvb252ortgwwdpequn6i3bn67qxiholbff2irmqgqp6bmtxuq")?;
+		assert!(StreamState::rollback(r#type, missing, &events)
+			.await
+			.is_err());
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn from_stream() -> anyhow::Result<()> {
+		let genesis = crate::commit::example::genesis();
+		let r#type = genesis.r#type;
+		let genesis: Event = genesis.genesis.try_into()?;
+
+		let expected = StreamState::make(r#type, vec![genesis.clone()]).await?;
+		let events = futures::stream::iter(vec![Ok(genesis.clone())]);
+		let state = StreamState::from_stream(r#type, events).await?;
+		assert_eq!(state.content, expected.content);
+		assert_eq!(state.commit_count(), 1);
+
+		let events = futures::stream::iter(vec![Ok(genesis), Err(anyhow::anyhow!("failed"))]);
+		assert!(StreamState::from_stream(r#type, events).await.is_err());
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn make_audits_controller_chain() -> anyhow::Result<()> {
+		let genesis = crate::commit::example::genesis();
+		let r#type = genesis.r#type;
+		let genesis: Event = genesis.genesis.try_into()?;
+
+		// unsigned event handing the stream to another controller, the cacao of genesis
+		// is then no longer issued by a controller
+		let mut signed = match &genesis.value {
+			EventValue::Signed(signed) => signed.clone(),
+			_ => anyhow::bail!("genesis is not signed"),
+		};
+		let mut payload = signed.payload()?;
+		if let Some(header) = payload.header.as_mut() {
+			header.controllers =
+				vec!["did:key:z6Mkj9M6QgzPP3zPdHoCHEojiZSTd4kS53z2x9Hi8L9jgBM1".to_string()];
+		}
+		signed.linked_block = Some(payload.encode()?);
+		signed.cacao_block = None;
+		let handover = Event {
+			cid: Cid::from_str("bafyreidtdpcjnltl7enswtp4s4xbsweb5zndvzihiyczl3t6ppqvbcgjpu")?,
+			value: EventValue::Signed(signed),
+		};
+
+		assert!(StreamState::make(r#type, vec![genesis.clone()])
+			.await
+			.is_ok());
+		let err = StreamState::make(r#type, vec![genesis, handover])
+			.await
+			.unwrap_err();
+		assert!(err.to_string().contains("not a controller"));
+		Ok(())
+	}
+
 	#[test]
 	fn decode_anchor_proof() {
 		let data = json!({
@@ -294,6 +698,12 @@ mod tests {
 			"kjzl6hvfrbw6c763ubdhowzao0m4yp84cxzbfnlh4hdi5alqo4yrebmc0qpjdi5"
 		);
 
+		assert_eq!(
+			data.created_at(),
+			"2023-04-04T17:27:35Z".parse::<DateTime<Utc>>().ok()
+		);
+		assert_eq!(data.updated_at(), data.created_at());
+
 		let stream_id = data.stream_id().unwrap().to_string();
 		assert_eq!(
 			stream_id,
@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+use std::task::{Context, Poll};
+
+use hyper::service::Service;
+use hyper::{Body, Request};
+use opentelemetry::propagation::TextMapPropagator;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+/// headers propagating the current span, injected by the global text map propagator
+pub fn trace_headers() -> HashMap<String, String> {
+	let context = tracing::Span::current().context();
+	let mut carrier = HashMap::new();
+	opentelemetry::global::get_text_map_propagator(|propagator| {
+		propagator.inject_context(&context, &mut carrier)
+	});
+	carrier
+}
+
+/// W3C `traceparent` of the current span.
+///
+/// `None` when the span is not exported to opentelemetry or the global propagator is not
+/// `TraceContextPropagator`.
+pub fn traceparent() -> Option<String> {
+	trace_headers().remove("traceparent")
+}
+
+/// `trace_headers` as http headers, merged into requests to ceramic and kubo.
+///
+/// `ceramic_http_client` does not take extra headers, so requests made through it are not
+/// linked to the current span.
+pub fn trace_header_map() -> HeaderMap {
+	trace_headers()
+		.into_iter()
+		.filter_map(|(name, value)| {
+			Some((
+				HeaderName::try_from(name).ok()?,
+				HeaderValue::try_from(value).ok()?,
+			))
+		})
+		.collect()
+}
+
+/// client service of the kubo rpc client, adding trace headers to every request
+#[derive(Clone)]
+pub struct TraceContextService<S> {
+	inner: S,
+}
+
+impl<S> TraceContextService<S> {
+	pub fn new(inner: S) -> Self {
+		Self { inner }
+	}
+}
+
+impl<S, C> Service<(Request<Body>, C)> for TraceContextService<S>
+where
+	S: Service<(Request<Body>, C)>,
+{
+	type Response = S::Response;
+	type Error = S::Error;
+	type Future = S::Future;
+
+	fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+		self.inner.poll_ready(cx)
+	}
+
+	fn call(&mut self, (mut request, context): (Request<Body>, C)) -> Self::Future {
+		request.headers_mut().extend(trace_header_map());
+		self.inner.call((request, context))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use hyper::Response;
+	use opentelemetry::trace::TracerProvider as _;
+	use opentelemetry_sdk::propagation::TraceContextPropagator;
+	use std::sync::{Arc, Mutex};
+	use tracing_subscriber::layer::SubscriberExt;
+
+	/// inner service keeping headers of the last request
+	#[derive(Clone, Default)]
+	struct Recorder(Arc<Mutex<Option<HeaderMap>>>);
+
+	impl Service<(Request<Body>, ())> for Recorder {
+		type Response = Response<Body>;
+		type Error = hyper::Error;
+		type Future = futures::future::Ready<Result<Response<Body>, hyper::Error>>;
+
+		fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+			Poll::Ready(Ok(()))
+		}
+
+		fn call(&mut self, (request, _): (Request<Body>, ())) -> Self::Future {
+			*self.0.lock().unwrap() = Some(request.headers().clone());
+			futures::future::ready(Ok(Response::new(Body::empty())))
+		}
+	}
+
+	#[test]
+	fn traceparent_injected_into_requests() -> anyhow::Result<()> {
+		opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+		let provider = opentelemetry_sdk::trace::TracerProvider::builder().build();
+		let subscriber = tracing_subscriber::registry()
+			.with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+
+		tracing::subscriber::with_default(subscriber, || {
+			assert!(traceparent().is_none());
+
+			let span = tracing::info_span!("load_stream_state");
+			let _guard = span.enter();
+			let traceparent = traceparent().expect("span should be exported");
+
+			let request = reqwest::Client::new()
+				.post("http://localhost:7007/api/v0/collection/count")
+				.headers(trace_header_map())
+				.build()?;
+			assert_eq!(request.headers()["traceparent"], traceparent.as_str());
+
+			let recorder = Recorder::default();
+			let mut service = TraceContextService::new(recorder.clone());
+			let _ = service.call((Request::new(Body::empty()), ()));
+			let headers = recorder.0.lock().unwrap().take().expect("request not sent");
+			assert_eq!(headers["traceparent"], traceparent.as_str());
+			Ok(())
+		})
+	}
+}
@@ -1,8 +1,10 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 
 use anyhow::Context;
 use ceramic_core::StreamId;
-use dataverse_ceramic::Ceramic;
+pub use dataverse_ceramic::DappHealth;
+use dataverse_ceramic::{Ceramic, DataverseError};
 use once_cell::sync::Lazy;
 use tokio::sync::Mutex;
 
@@ -14,6 +16,7 @@ pub struct Model {
 	pub encryptable: Vec<String>,
 	pub version: i32,
 	pub latest: bool,
+	pub is_deprecated: bool,
 }
 
 impl Model {
@@ -25,10 +28,34 @@ impl Model {
 static MODEL_STORE: Lazy<Mutex<ModelStore>> = Lazy::new(|| Mutex::new(ModelStore::new()));
 
 pub struct ModelStore {
-	client: dapp_table_client::Client,
+	client: Arc<dapp_table_client::Client>,
 	models: HashMap<String, Model>,
 	ceramic: HashMap<String, Ceramic>,
 	dapp_ceramic: HashMap<uuid::Uuid, String>,
+	deprecated: HashSet<String>,
+	last_error: Option<String>,
+}
+
+/// report the state of dapp registry, `detailed` queries the registry instead of
+/// reporting the result of last lookup
+pub async fn check_health(detailed: bool) -> DappHealth {
+	if detailed {
+		// query registry without holding the store lock
+		let client = MODEL_STORE.lock().await.client.clone();
+		let result = client.lookup_dapps().await;
+		let _ = MODEL_STORE.lock().await.record_lookup(result);
+	}
+	MODEL_STORE.lock().await.health()
+}
+
+/// report the dapp registry in `Ceramic::health_check`, with the state of last lookup.
+/// done when the store is first used, call it at startup to report before that
+pub fn register_health_check() {
+	dataverse_ceramic::set_registry_health_check(registry_health);
+}
+
+fn registry_health() -> std::pin::Pin<Box<dyn std::future::Future<Output = DappHealth> + Send>> {
+	Box::pin(check_health(false))
 }
 
 pub async fn get_dapp_ceramic(dapp_id: &uuid::Uuid) -> anyhow::Result<Ceramic> {
@@ -53,15 +80,143 @@ pub async fn get_models(dapp_id: &uuid::Uuid, offline: bool) -> anyhow::Result<V
 	MODEL_STORE.lock().await.get_models(dapp_id, offline).await
 }
 
+/// cache ceramic and models of dapp, lookups of them no longer query the registry
+pub async fn cache_dapp(dapp_id: &uuid::Uuid, ceramic: Ceramic, models: Vec<Model>) {
+	MODEL_STORE
+		.lock()
+		.await
+		.cache_dapp(dapp_id, ceramic, models)
+}
+
+/// mark model as deprecated, streams of the model are still loadable.
+///
+/// The registry has no deprecation field, so it's kept in process memory only. It
+/// survives refreshing models from registry but not a restart, save `deprecated_models`
+/// and mark them again on startup to keep it.
+pub async fn mark_deprecated(model_id: &StreamId) -> anyhow::Result<()> {
+	MODEL_STORE.lock().await.mark_deprecated(model_id).await
+}
+
+/// models marked deprecated since the process started
+pub async fn deprecated_models() -> anyhow::Result<Vec<StreamId>> {
+	MODEL_STORE.lock().await.deprecated_models()
+}
+
+/// list deduplicated ceramic endpoints of all dapps in registry
+pub async fn list_ceramic_nodes() -> anyhow::Result<Vec<String>> {
+	// query registry without holding the store lock
+	let client = MODEL_STORE.lock().await.client.clone();
+	let dapps = client.lookup_dapps().await;
+	let dapps = MODEL_STORE.lock().await.record_lookup(dapps)?;
+	let mut nodes: Vec<String> = dapps.into_iter().map(|dapp| dapp.ceramic).collect();
+	nodes.sort();
+	nodes.dedup();
+	Ok(nodes)
+}
+
+/// check if any dapp uses the ceramic node, failing to query registry is treated as in use
+pub async fn is_node_in_use(ceramic_url: &str) -> bool {
+	match list_ceramic_nodes().await {
+		Ok(nodes) => nodes.iter().any(|node| node == ceramic_url),
+		Err(err) => {
+			log::warn!("failed to list ceramic nodes: {}", err);
+			true
+		}
+	}
+}
+
+/// Point dapps cached with `old_url` to `new_url`, returns the number of repointed dapps.
+///
+/// The registry has no api to update dapps, so only the cache of this process is changed.
+/// Dapps loaded from the registry again, as after a restart, point to `old_url` until
+/// repointed again.
+pub async fn repoint_node(old_url: &str, new_url: &str) -> anyhow::Result<usize> {
+	// make sure the new node is reachable before repointing, without holding the store lock
+	let cached = MODEL_STORE.lock().await.ceramic.get(new_url).cloned();
+	let ceramic = match cached {
+		Some(ceramic) => ceramic,
+		None => connect_ceramic(new_url).await?,
+	};
+	Ok(MODEL_STORE.lock().await.repoint_node(old_url, ceramic))
+}
+
+/// ceramic at `endpoint` on the network of its first chain
+async fn connect_ceramic(endpoint: &str) -> anyhow::Result<Ceramic> {
+	let chains = dataverse_ceramic::http::Client::chains(endpoint).await?;
+	Ok(Ceramic {
+		endpoint: endpoint.to_string(),
+		network: chains.first().context("ceramic not in networks")?.network(),
+		pool: None,
+	})
+}
+
 impl ModelStore {
 	fn new() -> Self {
+		register_health_check();
 		let backend = std::env::var("DAPP_TABLE_BACKEND").ok();
 		ModelStore {
 			models: Default::default(),
 			dapp_ceramic: Default::default(),
 			ceramic: Default::default(),
-			client: dapp_table_client::Client::new(backend),
+			deprecated: Default::default(),
+			client: Arc::new(dapp_table_client::Client::new(backend)),
+			last_error: None,
+		}
+	}
+
+	fn health(&self) -> DappHealth {
+		DappHealth {
+			registry_reachable: self.last_error.is_none(),
+			cached_dapps: self.dapp_ceramic.len(),
+			cached_models: self.models.len(),
+			last_error: self.last_error.clone(),
+		}
+	}
+
+	fn repoint_node(&mut self, old_url: &str, ceramic: Ceramic) -> usize {
+		let mut count = 0;
+		for endpoint in self.dapp_ceramic.values_mut() {
+			if endpoint == old_url {
+				*endpoint = ceramic.endpoint.clone();
+				count += 1;
+			}
 		}
+		self.ceramic.insert(ceramic.endpoint.clone(), ceramic);
+		count
+	}
+
+	fn cache_dapp(&mut self, dapp_id: &uuid::Uuid, ceramic: Ceramic, models: Vec<Model>) {
+		self.dapp_ceramic.insert(*dapp_id, ceramic.endpoint.clone());
+		self.ceramic.insert(ceramic.endpoint.clone(), ceramic);
+		for model in models {
+			self.models.insert(model.id.to_string(), model);
+		}
+	}
+
+	fn deprecated_models(&self) -> anyhow::Result<Vec<StreamId>> {
+		let mut model_ids: Vec<&String> = self.deprecated.iter().collect();
+		model_ids.sort();
+		model_ids
+			.into_iter()
+			.map(|model_id| Ok(model_id.parse()?))
+			.collect()
+	}
+
+	async fn mark_deprecated(&mut self, model_id: &StreamId) -> anyhow::Result<()> {
+		self.get_model(model_id).await?;
+		self.deprecated.insert(model_id.to_string());
+		if let Some(model) = self.models.get_mut(&model_id.to_string()) {
+			model.is_deprecated = true;
+		}
+		Ok(())
+	}
+
+	fn record_lookup<T>(&mut self, result: anyhow::Result<T>) -> anyhow::Result<T> {
+		match &result {
+			Ok(_) => self.last_error = None,
+			Err(err) => self.last_error = Some(err.to_string()),
+		}
+		result
 	}
 
 	async fn get_dapp_ceramic(
@@ -79,7 +234,7 @@ impl ModelStore {
 			};
 		}
 
-		anyhow::bail!("dapp {} not found", dapp_id)
+		Err(DataverseError::DappNotFound(*dapp_id).into())
 	}
 
 	async fn get_ceramic(&mut self, ceramic_str: &String) -> anyhow::Result<Ceramic> {
@@ -87,11 +242,7 @@ impl ModelStore {
 			return Ok(ceramic.clone());
 		}
 
-		let chains = dataverse_ceramic::http::Client::chains(&ceramic_str).await?;
-		let ceramic = Ceramic {
-			endpoint: ceramic_str.clone(),
-			network: chains.first().context("ceramic not in networks")?.network(),
-		};
+		let ceramic = connect_ceramic(ceramic_str).await?;
 		self.ceramic.insert(ceramic_str.clone(), ceramic.clone());
 		Ok(ceramic)
 	}
@@ -119,7 +270,8 @@ impl ModelStore {
 		let dapp = self
 			.client
 			.lookup_dapp_by_dapp_id(&dapp_id.to_string())
-			.await?;
+			.await;
+		let dapp = self.record_lookup(dapp)?;
 		self.dapp_ceramic
 			.insert(dapp_id.clone(), dapp.ceramic.clone());
 		let ceramic = self.get_ceramic(&dapp.ceramic).await?;
@@ -141,6 +293,7 @@ impl ModelStore {
 					name: model.model_name.clone(),
 					version: idx as i32,
 					latest: ele.latest,
+					is_deprecated: self.deprecated.contains(&ele.model_id),
 				};
 				self.models.insert(model.id.to_string(), model.clone());
 				result.push(model)
@@ -187,7 +340,8 @@ impl ModelStore {
 			model_id: Some(model_id.to_string()),
 		};
 		log::info!("lookup dapp with model_id: {}", model_id);
-		let dapp = self.client.lookup_dapp(variables).await?;
+		let dapp = self.client.lookup_dapp(variables).await;
+		let dapp = self.record_lookup(dapp)?;
 
 		let models = self.store_dapp_models(dapp)?;
 		for model in models {
@@ -198,3 +352,98 @@ impl ModelStore {
 		anyhow::bail!("model with id `{}` not found in dapp table", model_id)
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[tokio::test]
+	async fn check_health_counts() -> anyhow::Result<()> {
+		let mut store = ModelStore::new();
+		let dapp_id = uuid::Uuid::new_v4();
+		let model = Model {
+			id: "kjzl6hvfrbw6c5qdzwi9esxvt1v5mtt7od7hb2947624mn4u0rmq1rh9anjcnxx".parse()?,
+			name: "indexFile".into(),
+			dapp_id,
+			encryptable: vec![],
+			version: 0,
+			latest: true,
+			is_deprecated: false,
+		};
+		store.models.insert(model.id.to_string(), model);
+		store
+			.dapp_ceramic
+			.insert(dapp_id, "http://localhost:7007".into());
+
+		let health = store.health();
+		assert_eq!(
+			health,
+			DappHealth {
+				registry_reachable: true,
+				cached_dapps: 1,
+				cached_models: 1,
+				last_error: None,
+			}
+		);
+
+		let _ = store.record_lookup::<()>(Err(anyhow::anyhow!("registry down")));
+		let health = store.health();
+		assert!(!health.registry_reachable);
+		assert_eq!(health.last_error, Some("registry down".to_string()));
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn registry_in_ceramic_health() -> anyhow::Result<()> {
+		register_health_check();
+		let ceramic = Ceramic {
+			endpoint: "http://127.0.0.1:1".into(),
+			network: dataverse_ceramic::network::Network::InMemory,
+			pool: None,
+		};
+		let health = ceramic.health_check().await?;
+		assert!(!health.reachable);
+		assert!(health.registry.is_some());
+		Ok(())
+	}
+
+	#[test]
+	fn repoint_cached_dapps() {
+		let mut store = ModelStore::new();
+		let old_url = "http://old:7007";
+		let (moved, other) = (uuid::Uuid::new_v4(), uuid::Uuid::new_v4());
+		store.dapp_ceramic.insert(moved, old_url.into());
+		store.dapp_ceramic.insert(other, "http://other:7007".into());
+		let ceramic = Ceramic {
+			endpoint: "http://new:7007".into(),
+			network: dataverse_ceramic::network::Network::InMemory,
+			pool: None,
+		};
+
+		assert_eq!(store.repoint_node(old_url, ceramic), 1);
+		assert_eq!(store.dapp_ceramic[&moved], "http://new:7007");
+		assert_eq!(store.dapp_ceramic[&other], "http://other:7007");
+		assert!(store.ceramic.contains_key("http://new:7007"));
+	}
+
+	#[tokio::test]
+	async fn mark_cached_model_deprecated() -> anyhow::Result<()> {
+		let mut store = ModelStore::new();
+		let model = Model {
+			id: "kjzl6hvfrbw6c5qdzwi9esxvt1v5mtt7od7hb2947624mn4u0rmq1rh9anjcnxx".parse()?,
+			name: "indexFile".into(),
+			dapp_id: uuid::Uuid::new_v4(),
+			encryptable: vec![],
+			version: 0,
+			latest: true,
+			is_deprecated: false,
+		};
+		store.models.insert(model.id.to_string(), model.clone());
+
+		store.mark_deprecated(&model.id).await?;
+		assert!(store.get_model(&model.id).await?.is_deprecated);
+		assert!(store.deprecated.contains(&model.id.to_string()));
+		assert_eq!(store.deprecated_models()?, vec![model.id]);
+		Ok(())
+	}
+}
@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use ceramic_core::{Cid, StreamId};
+use dataverse_ceramic::event::Event;
+use tokio::sync::RwLock;
+
+use crate::stream::{Stream, StreamStore};
+
+/// stream store keeping streams in memory, for tests without a database
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStreamStore {
+	streams: Arc<RwLock<HashMap<StreamId, Stream>>>,
+	genesis_events: Arc<RwLock<HashMap<Cid, Event>>>,
+	/// stream of every event cid indexed, genesis and tip are indexed when saving a stream
+	event_index: Arc<RwLock<HashMap<Cid, StreamId>>>,
+}
+
+impl MemoryStreamStore {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// keep genesis event of a stream, served by `load_genesis_event`
+	pub async fn save_genesis_event(&self, event: Event) {
+		self.genesis_events.write().await.insert(event.cid, event);
+	}
+
+	pub async fn remove_stream(&self, stream_id: &StreamId) -> Option<Stream> {
+		self.event_index
+			.write()
+			.await
+			.retain(|_, indexed| indexed != stream_id);
+		self.streams.write().await.remove(stream_id)
+	}
+
+	pub async fn len(&self) -> usize {
+		self.streams.read().await.len()
+	}
+
+	pub async fn is_empty(&self) -> bool {
+		self.streams.read().await.is_empty()
+	}
+}
+
+#[async_trait::async_trait]
+impl StreamStore for MemoryStreamStore {
+	async fn save_stream(&self, stream: &Stream) -> anyhow::Result<()> {
+		let stream_id = stream.stream_id()?;
+		let mut event_index = self.event_index.write().await;
+		event_index.insert(stream.genesis, stream_id.clone());
+		event_index.insert(stream.tip, stream_id.clone());
+		self.streams.write().await.insert(stream_id, stream.clone());
+		Ok(())
+	}
+
+	async fn load_stream(&self, stream_id: &StreamId) -> anyhow::Result<Option<Stream>> {
+		Ok(self.streams.read().await.get(stream_id).cloned())
+	}
+
+	async fn list_all_streams(&self) -> anyhow::Result<Vec<Stream>> {
+		Ok(self.streams.read().await.values().cloned().collect())
+	}
+
+	async fn index_events(&self, stream_id: &StreamId, cids: &[Cid]) -> anyhow::Result<()> {
+		let mut event_index = self.event_index.write().await;
+		for cid in cids {
+			event_index.insert(*cid, stream_id.clone());
+		}
+		Ok(())
+	}
+
+	async fn find_stream_by_event(&self, cid: &Cid) -> anyhow::Result<Option<StreamId>> {
+		Ok(self.event_index.read().await.get(cid).cloned())
+	}
+
+	async fn load_genesis_event(&self, stream: &Stream) -> anyhow::Result<Option<Event>> {
+		Ok(self
+			.genesis_events
+			.read()
+			.await
+			.get(&stream.genesis)
+			.cloned())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use dataverse_ceramic::commit::example;
+
+	use super::*;
+
+	#[tokio::test]
+	async fn save_load_and_remove_stream() -> anyhow::Result<()> {
+		let store = MemoryStreamStore::new();
+		let genesis = example::genesis();
+		let r#type = genesis.r#type;
+		let commit: Event = genesis.genesis.try_into()?;
+		let dapp_id = uuid::Uuid::new_v4();
+		let mut stream = Stream::new(&dapp_id, r#type, &commit, None)?;
+		let stream_id = stream.stream_id()?;
+		assert!(store.load_stream(&stream_id).await?.is_none());
+
+		store.save_stream(&stream).await?;
+		let data: Event = example::data().commit.try_into()?;
+		stream.tip = data.cid;
+		store.save_stream(&stream).await?;
+		assert_eq!(store.len().await, 1);
+		let loaded = store.load_stream(&stream_id).await?.unwrap();
+		assert_eq!(loaded.tip, data.cid);
+		assert_eq!(store.list_all_streams().await?.len(), 1);
+		assert_eq!(
+			store.find_stream_by_event(&commit.cid).await?,
+			Some(stream_id.clone())
+		);
+		assert_eq!(
+			store.find_stream_by_event(&data.cid).await?,
+			Some(stream_id.clone())
+		);
+
+		let exported = store.export_to_json().await?;
+		let copy = MemoryStreamStore::new();
+		assert_eq!(copy.import_from_json(exported).await?.inserted, 1);
+		assert!(copy.load_stream(&stream_id).await?.is_some());
+
+		assert!(store.remove_stream(&stream_id).await.is_some());
+		assert!(store.is_empty().await);
+		Ok(())
+	}
+}
@@ -1 +1,2 @@
 pub mod dapp;
+pub mod memory;
@@ -1,5 +1,9 @@
+use std::sync::Arc;
+
+use anyhow::Context;
 use ceramic_core::{Cid, StreamId};
-use dataverse_ceramic::event::Event;
+use chrono::{DateTime, Utc};
+use dataverse_ceramic::event::{Event, EventSigner, EventValue, Payload};
 use dataverse_ceramic::StreamState;
 use int_enum::IntEnum;
 use serde::{Deserialize, Serialize};
@@ -15,6 +19,11 @@ pub struct Stream {
 	pub model: Option<StreamId>,
 	#[serde(default = "content_default")]
 	pub content: serde_json::Value,
+	#[serde(default)]
+	pub pinned: bool,
+	/// `updatedAt` of content, set with `with_content`
+	#[serde(default)]
+	pub updated_at: Option<DateTime<Utc>>,
 }
 
 fn content_default() -> serde_json::Value {
@@ -36,9 +45,20 @@ impl Stream {
 			model,
 			account: None,
 			content: serde_json::Value::Null,
+			pinned: false,
+			updated_at: None,
 		})
 	}
 
+	/// copy of the stream holding `content`, its update time is taken from `updatedAt`
+	pub fn with_content(self, content: serde_json::Value) -> Stream {
+		Stream {
+			updated_at: content_updated_at(&content),
+			content,
+			..self
+		}
+	}
+
 	pub fn stream_id(&self) -> anyhow::Result<StreamId> {
 		Ok(StreamId {
 			r#type: IntEnum::from_int(self.r#type)?,
@@ -49,11 +69,357 @@ impl Stream {
 	pub async fn state(&self, commits: Vec<Event>) -> anyhow::Result<StreamState> {
 		StreamState::make(self.r#type, commits).await
 	}
+
+	/// Compact the stream into a new genesis event whose content is the current tip state.
+	///
+	/// The snapshot genesis starts a new stream with its own stream id, states before the
+	/// snapshot are no longer reachable from it and can only be loaded from the old event log.
+	/// The returned event can be published with `save_event` like any other genesis.
+	pub async fn compact(
+		&self,
+		events: Vec<Event>,
+		signer: &dyn EventSigner,
+	) -> anyhow::Result<(Stream, Event)> {
+		let header = match events.first().map(|event| &event.value) {
+			Some(EventValue::Signed(signed)) if signed.is_gensis() => signed.payload()?.header,
+			_ => None,
+		}
+		.context("missing genesis header in events")?;
+		let state = self.state(events).await?;
+
+		let payload = Payload {
+			data: Some(state.content.clone()),
+			header: Some(header),
+			prev: None,
+			id: None,
+		};
+		let genesis = signer.sign(payload).await?;
+
+		let stream = Stream {
+			account: self.account.clone(),
+			..Stream::new(&self.dapp_id, self.r#type, &genesis, self.model.clone())?
+		}
+		.with_content(state.content);
+		Ok((stream, genesis))
+	}
+
+	/// copy of the stream with indexed fields derived from its genesis event and content:
+	/// model from the genesis header, account from its first controller and update time
+	/// from `updatedAt` of content
+	pub fn derive_index(&self, genesis: &Event) -> anyhow::Result<Stream> {
+		if genesis.cid != self.genesis {
+			anyhow::bail!("event {} is not genesis of stream", genesis.cid);
+		}
+		let header = match &genesis.value {
+			EventValue::Signed(signed) => signed.payload()?.header,
+			EventValue::Anchor(_) => None,
+		}
+		.context("missing header in genesis event")?;
+		Ok(Stream {
+			model: Some(header.model),
+			account: header.controllers.first().cloned(),
+			updated_at: content_updated_at(&self.content),
+			..self.clone()
+		})
+	}
 }
 
+fn content_updated_at(content: &serde_json::Value) -> Option<DateTime<Utc>> {
+	content.get("updatedAt")?.as_str()?.parse().ok()
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MigrationReport {
+	pub applied: Vec<String>,
+	pub skipped: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ImportReport {
+	pub inserted: usize,
+	pub skipped: usize,
+	pub errors: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReindexReport {
+	pub total: u64,
+	pub reindexed: u64,
+	pub errors: u64,
+}
+
+/// number of records reindexed between yields to the runtime
+const REINDEX_BATCH: u64 = 100;
+
 #[async_trait::async_trait]
 pub trait StreamStore: Sync + Send {
 	async fn save_stream(&self, stream: &Stream) -> anyhow::Result<()>;
 	async fn load_stream(&self, stream_id: &StreamId) -> anyhow::Result<Option<Stream>>;
 	async fn list_all_streams(&self) -> anyhow::Result<Vec<Stream>>;
+
+	/// apply pending schema migrations of the backing store
+	async fn migrate(&self) -> anyhow::Result<MigrationReport> {
+		Ok(MigrationReport::default())
+	}
+
+	/// index cids of events in the log of stream for `find_stream_by_event`, stores already
+	/// indexing the events they keep can ignore it
+	async fn index_events(&self, _stream_id: &StreamId, _cids: &[Cid]) -> anyhow::Result<()> {
+		Ok(())
+	}
+
+	/// Stream whose log contains the event, looked up by any event cid of the log.
+	///
+	/// The default scans all streams and only matches genesis and tip, stores should
+	/// override it with an indexed lookup.
+	async fn find_stream_by_event(&self, cid: &Cid) -> anyhow::Result<Option<StreamId>> {
+		for stream in self.list_all_streams().await? {
+			if stream.genesis == *cid || stream.tip == *cid {
+				return Ok(Some(stream.stream_id()?));
+			}
+		}
+		Ok(None)
+	}
+
+	/// load genesis event of the stream from raw event data, None if store keeps no events
+	async fn load_genesis_event(&self, _stream: &Stream) -> anyhow::Result<Option<Event>> {
+		Ok(None)
+	}
+
+	/// Rebuild indexed fields of every stream from its genesis event and content, see
+	/// `Stream::derive_index`, and save it back.
+	///
+	/// Streams without a stored genesis event are left unchanged. Runs inline and yields to
+	/// the runtime every 100 records, use `spawn_reindex` to run it next to normal traffic.
+	async fn reindex(&self) -> anyhow::Result<ReindexReport> {
+		let streams = self.list_all_streams().await?;
+		let mut report = ReindexReport::default();
+		for stream in streams {
+			report.total += 1;
+			if report.total % REINDEX_BATCH == 0 {
+				tokio::task::yield_now().await;
+			}
+			let genesis = match self.load_genesis_event(&stream).await {
+				Ok(Some(genesis)) => genesis,
+				Ok(None) => continue,
+				Err(err) => {
+					log::warn!("failed to load genesis {}: {}", stream.genesis, err);
+					report.errors += 1;
+					continue;
+				}
+			};
+			let result = match stream.derive_index(&genesis) {
+				Ok(stream) => self.save_stream(&stream).await,
+				Err(err) => Err(err),
+			};
+			match result {
+				Ok(_) => report.reindexed += 1,
+				Err(err) => {
+					log::warn!("failed to reindex stream {}: {}", stream.genesis, err);
+					report.errors += 1;
+				}
+			}
+		}
+		Ok(report)
+	}
+
+	/// export all streams as a json array for backup
+	async fn export_to_json(&self) -> anyhow::Result<serde_json::Value> {
+		let streams = self.list_all_streams().await?;
+		Ok(serde_json::to_value(streams)?)
+	}
+
+	/// Import streams exported by `export_to_json`, streams already in store are skipped.
+	///
+	/// The store is migrated before importing, fields missing in older exports take their
+	/// default values.
+	async fn import_from_json(&self, data: serde_json::Value) -> anyhow::Result<ImportReport> {
+		let items = match data {
+			serde_json::Value::Array(items) => items,
+			_ => anyhow::bail!("expected a json array of streams"),
+		};
+		self.migrate().await?;
+
+		let mut report = ImportReport::default();
+		for (idx, item) in items.into_iter().enumerate() {
+			let stream = match serde_json::from_value::<Stream>(item) {
+				Ok(stream) => stream,
+				Err(err) => {
+					report.errors.push(format!("stream {}: {}", idx, err));
+					continue;
+				}
+			};
+			let result = match stream.stream_id() {
+				Ok(stream_id) => match self.load_stream(&stream_id).await {
+					Ok(Some(_)) => {
+						report.skipped += 1;
+						continue;
+					}
+					Ok(None) => self.save_stream(&stream).await,
+					Err(err) => Err(err),
+				},
+				Err(err) => Err(err),
+			};
+			match result {
+				Ok(_) => report.inserted += 1,
+				Err(err) => report.errors.push(format!("stream {}: {}", idx, err)),
+			}
+		}
+		Ok(report)
+	}
+}
+
+/// run `StreamStore::reindex` of store in a background task
+pub fn spawn_reindex(
+	store: Arc<dyn StreamStore>,
+) -> tokio::task::JoinHandle<anyhow::Result<ReindexReport>> {
+	tokio::spawn(async move { store.reindex().await })
+}
+
+#[cfg(test)]
+mod tests {
+	use dataverse_ceramic::commit::example;
+	use dataverse_ceramic::event::KeySigner;
+
+	use super::*;
+	use crate::store::memory::MemoryStreamStore;
+
+	#[tokio::test]
+	async fn compact_into_snapshot_genesis() -> anyhow::Result<()> {
+		let commit = example::genesis();
+		let header = commit.genesis.payload()?.header.unwrap();
+		let r#type = commit.r#type;
+		let genesis: Event = commit.genesis.try_into()?;
+		let data: Event = example::data().commit.try_into()?;
+		let dapp_id = uuid::Uuid::new_v4();
+		let stream = Stream {
+			tip: data.cid,
+			account: header.controllers.first().cloned(),
+			..Stream::new(&dapp_id, r#type, &genesis, Some(header.model.clone()))?
+		};
+		let events = vec![genesis, data.clone()];
+		let tip_state = stream.state(events.clone()).await?;
+		let signer =
+			KeySigner::new("d160c4553ba7547cd5d66993d99329379a0c299a1bb1058abc5b874e0ba56375")
+				.await?;
+
+		let (snapshot, snapshot_genesis) = stream.compact(events, &signer).await?;
+		assert_ne!(snapshot.stream_id()?, stream.stream_id()?);
+		assert_eq!(snapshot.genesis, snapshot_genesis.cid);
+		assert_eq!(snapshot.tip, snapshot_genesis.cid);
+		assert_eq!(snapshot.account, stream.account);
+		assert_eq!(snapshot.model, stream.model);
+		assert_eq!(snapshot.content, tip_state.content);
+		let payload = match &snapshot_genesis.value {
+			EventValue::Signed(signed) => signed.payload()?,
+			_ => anyhow::bail!("snapshot genesis should be signed"),
+		};
+		assert_eq!(payload.header, Some(header));
+		assert_eq!(payload.data, Some(tip_state.content.clone()));
+		let state = snapshot.state(vec![snapshot_genesis]).await?;
+		assert_eq!(state.content, tip_state.content);
+
+		assert!(stream.compact(vec![data], &signer).await.is_err());
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn import_exported_streams() -> anyhow::Result<()> {
+		let commit = example::genesis();
+		let r#type = commit.r#type;
+		let genesis: Event = commit.genesis.try_into()?;
+		let dapp_id = uuid::Uuid::new_v4();
+		let stream = Stream {
+			content: serde_json::json!({ "title": "hello" }),
+			pinned: true,
+			..Stream::new(&dapp_id, r#type, &genesis, None)?
+		};
+		let store = MemoryStreamStore::new();
+		store.save_stream(&stream).await?;
+		let exported = store.export_to_json().await?;
+
+		let copy = MemoryStreamStore::new();
+		let report = copy.import_from_json(exported.clone()).await?;
+		assert_eq!(
+			report,
+			ImportReport {
+				inserted: 1,
+				skipped: 0,
+				errors: vec![]
+			}
+		);
+		let imported = copy.load_stream(&stream.stream_id()?).await?.unwrap();
+		assert_eq!(imported.content, stream.content);
+		assert!(imported.pinned);
+		// streams already in store are skipped
+		assert_eq!(copy.import_from_json(exported).await?.skipped, 1);
+
+		// older exports have no content and pinned fields
+		let data: Event = example::data().commit.try_into()?;
+		let old = serde_json::json!([
+			{
+				"type": r#type,
+				"dapp_id": dapp_id,
+				"genesis": data.cid,
+				"tip": data.cid,
+				"account": null,
+				"model": null,
+			},
+			{ "type": r#type },
+		]);
+		let report = copy.import_from_json(old).await?;
+		assert_eq!(report.inserted, 1);
+		assert_eq!(report.errors.len(), 1);
+		assert!(report.errors[0].starts_with("stream 1:"));
+		let stream_id = StreamId {
+			r#type: IntEnum::from_int(r#type)?,
+			cid: data.cid,
+		};
+		let imported = copy.load_stream(&stream_id).await?.unwrap();
+		assert_eq!(imported.content, serde_json::Value::Null);
+		assert!(!imported.pinned);
+
+		assert!(copy
+			.import_from_json(serde_json::json!({ "streams": [] }))
+			.await
+			.is_err());
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn reindex_repairs_corrupted_index() -> anyhow::Result<()> {
+		let commit = example::genesis();
+		let stream_id = commit.stream_id()?;
+		let header = commit.genesis.payload()?.header.unwrap();
+		let r#type = commit.r#type;
+		let genesis: Event = commit.genesis.try_into()?;
+
+		let store = Arc::new(MemoryStreamStore::new());
+		store.save_genesis_event(genesis.clone()).await;
+		let dapp_id = uuid::Uuid::new_v4();
+		let stream = Stream {
+			model: Some("kjzl6hvfrbw6c763ubdhowzao0m4yp84cxzbfnlh4hdi5alqo4yrebmc0qpjdi5".parse()?),
+			content: serde_json::json!({ "updatedAt": "2024-03-04T11:09:46.769Z" }),
+			..Stream::new(&dapp_id, r#type, &genesis, None)?
+		};
+		store.save_stream(&stream).await?;
+
+		let report = spawn_reindex(store.clone()).await??;
+		assert_eq!(
+			report,
+			ReindexReport {
+				total: 1,
+				reindexed: 1,
+				errors: 0
+			}
+		);
+		let stream = store.load_stream(&stream_id).await?.unwrap();
+		assert_eq!(stream.model, Some(header.model));
+		assert_eq!(stream.account, header.controllers.first().cloned());
+		assert_eq!(
+			stream.updated_at,
+			Some("2024-03-04T11:09:46.769Z".parse::<DateTime<Utc>>()?)
+		);
+		Ok(())
+	}
 }
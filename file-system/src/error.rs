@@ -1,3 +1,6 @@
+/// error kinds of ceramic streams, returned by `Client` inside `anyhow::Error`
+pub use dataverse_ceramic::error::DataverseError;
+
 pub struct IllegalError {
     pub code: i64,
     pub message: String,
@@ -1,9 +1,47 @@
 use std::str::FromStr;
 
 use base64::Engine;
-use dataverse_ceramic::StreamId;
+use ceramic_core::Base64String;
+use dataverse_ceramic::{StreamId, StreamState};
 use serde::Deserialize;
 
+/// decide whether `caller` may read a stream, denied streams are loaded with
+/// `Status::AccessDenied` instead of failing
+pub trait AccessControlValidator: Send + Sync {
+	fn validate(&self, state: &StreamState, caller: Option<&str>) -> anyhow::Result<()>;
+}
+
+/// Check the `accessControl` field of stream content.
+///
+/// Streams without access control or encryption provider are public. Encrypted streams
+/// are readable by their controllers only, decryption conditions are not evaluated.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ControllerAccessValidator;
+
+impl AccessControlValidator for ControllerAccessValidator {
+	fn validate(&self, state: &StreamState, caller: Option<&str>) -> anyhow::Result<()> {
+		let access_control = match AccessControl::from_content(&state.content)? {
+			Some(access_control) => access_control,
+			None => return Ok(()),
+		};
+		if access_control.encryption_provider.is_none() {
+			return Ok(());
+		}
+		let caller = match caller {
+			Some(caller) => caller,
+			None => anyhow::bail!("stream is encrypted, caller is unknown"),
+		};
+		let is_controller = state
+			.controllers()
+			.iter()
+			.any(|controller| controller.eq_ignore_ascii_case(caller));
+		if !is_controller {
+			anyhow::bail!("stream is encrypted, {} is not its controller", caller);
+		}
+		Ok(())
+	}
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AccessControl {
@@ -11,6 +49,18 @@ pub struct AccessControl {
 	pub monetization_provider: Option<MonetizationProvider>,
 }
 
+impl AccessControl {
+	/// access control in the `accessControl` field of content, a base64 json, none when absent
+	pub fn from_content(content: &serde_json::Value) -> anyhow::Result<Option<Self>> {
+		let access_control = match content.get("accessControl") {
+			None | Some(serde_json::Value::Null) => return Ok(None),
+			Some(access_control) => access_control,
+		};
+		let access_control = serde_json::from_value::<Base64String>(access_control.clone())?;
+		Ok(Some(serde_json::from_slice(&access_control.to_vec()?)?))
+	}
+}
+
 impl FromStr for AccessControl {
 	type Err = anyhow::Error;
 
@@ -53,6 +103,54 @@ impl EncryptionProvider {
 	}
 }
 
+/// Encryption hints found in content of an encrypted stream, see
+/// [`StreamFile::encryption_metadata`].
+///
+/// [`StreamFile::encryption_metadata`]: super::StreamFile::encryption_metadata
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncryptionMetadata {
+	pub provider: String,
+	pub key_id: Option<String>,
+	pub algorithm: Option<String>,
+}
+
+impl EncryptionMetadata {
+	/// Read encryption hints of content json, `None` when content is not encrypted.
+	///
+	/// `encryptionProvider` is either the provider name or an object naming it in `protocol`,
+	/// `keyId` and `algorithm` are read from that object or from content itself.
+	/// Content with only `encryptedSymmetricKey` is encrypted by Lit.
+	///
+	/// ```json
+	/// {
+	///   "encryptionProvider": { "protocol": "Lit", "keyId": "key-1", "algorithm": "AES-GCM" },
+	///   "encryptedSymmetricKey": "a1b2..."
+	/// }
+	/// ```
+	pub fn from_content(content: &serde_json::Value) -> Option<Self> {
+		let text = |value: &serde_json::Value, key: &str| {
+			value
+				.get(key)
+				.and_then(serde_json::Value::as_str)
+				.map(String::from)
+		};
+		let (provider, hints) = match content.get("encryptionProvider") {
+			Some(serde_json::Value::String(provider)) => (provider.clone(), content),
+			Some(provider @ serde_json::Value::Object(_)) => (
+				text(provider, "protocol").unwrap_or_else(|| "unknown".into()),
+				provider,
+			),
+			_ if content.get("encryptedSymmetricKey").is_some() => ("Lit".into(), content),
+			_ => return None,
+		};
+		Some(Self {
+			provider,
+			key_id: text(hints, "keyId").or_else(|| text(content, "keyId")),
+			algorithm: text(hints, "algorithm").or_else(|| text(content, "algorithm")),
+		})
+	}
+}
+
 #[derive(Debug, Deserialize)]
 pub enum DecryptionConditionsTypes {
 	AccessControlCondition,
@@ -160,6 +258,33 @@ mod tests {
 
 	use super::*;
 
+	#[test]
+	fn validate_controller_access() -> anyhow::Result<()> {
+		let controller = "did:pkh:eip155:1:0x312eA852726E3A9f633A0377c0ea882086d66666";
+		let mut state = StreamState::default();
+		state.set_metadata(serde_json::json!({ "controllers": [controller] }));
+		let validator = ControllerAccessValidator;
+		assert!(validator.validate(&state, None).is_ok());
+
+		let access_control = serde_json::json!({
+			"encryptionProvider": { "protocol": "Lit", "encryptedSymmetricKey": "key" }
+		});
+		let access_control = Base64String::from(serde_json::to_vec(&access_control)?);
+		state.content = serde_json::json!({ "accessControl": access_control });
+		assert!(validator.validate(&state, None).is_err());
+		assert!(validator
+			.validate(&state, Some("did:pkh:eip155:1:0x0"))
+			.is_err());
+		assert!(validator
+			.validate(&state, Some(&controller.to_lowercase()))
+			.is_ok());
+
+		let access_control = Base64String::from(b"{}".to_vec());
+		state.content = serde_json::json!({ "accessControl": access_control });
+		assert!(validator.validate(&state, None).is_ok());
+		Ok(())
+	}
+
 	#[test]
 	fn decode_access_control_decryption_condition() {
 		// case AccessControlCondition
@@ -2,6 +2,7 @@ use ceramic_core::{Base64String, StreamId};
 use chrono::{DateTime, Utc};
 use dataverse_core::store::dapp;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
 use crate::policy::Policy;
 
@@ -49,6 +50,51 @@ pub enum ActionType {
 	Receive,
 }
 
+/// content used to create an action file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActionFileContent {
+	pub action_type: String,
+	pub target_id: String,
+	pub payload: Value,
+}
+
+impl ActionFileContent {
+	/// check action_type is one of the registered action types
+	pub fn validate(&self) -> anyhow::Result<ActionType> {
+		if self.action_type.is_empty() {
+			anyhow::bail!("action type is empty");
+		}
+		serde_json::from_value(Value::String(self.action_type.clone()))
+			.map_err(|_| anyhow::anyhow!("unsupported action type {}", self.action_type))
+	}
+
+	pub fn to_action_file(&self, fs_version: &str) -> anyhow::Result<ActionFile> {
+		self.validate()?;
+		let mut action = match &self.payload {
+			Value::Object(payload) => payload.clone(),
+			Value::Null => Default::default(),
+			_ => anyhow::bail!("action payload must be an object"),
+		};
+		action.insert("actionType".into(), Value::String(self.action_type.clone()));
+		let action = serde_json::to_vec(&action)?;
+
+		let now = Utc::now();
+		Ok(ActionFile {
+			file_name: self.action_type.to_lowercase(),
+			file_type: 0,
+			fs_version: fs_version.into(),
+			created_at: now,
+			updated_at: now,
+			access_control: None,
+			deleted: None,
+			reserved: None,
+			action: Base64String::from(action),
+			relation_id: self.target_id.parse()?,
+		})
+	}
+}
+
 struct ActionFileProcessor {}
 
 #[async_trait::async_trait]
@@ -96,6 +142,27 @@ mod tests {
 		assert!(action.is_ok());
 	}
 
+	#[test]
+	fn validate_action_file_content() -> anyhow::Result<()> {
+		let mut content = ActionFileContent {
+			action_type: "LIKE".into(),
+			target_id: "kjzl6kcym7w8yaejed4nbzi4lisljvo1bklovqr4251l93x04064fozndciadha".into(),
+			payload: serde_json::json!({"comment": "I like it!"}),
+		};
+		assert_eq!(content.validate()?, ActionType::Like);
+
+		let action_file = content.to_action_file("0.11")?;
+		let action = action_file.action()?;
+		assert_eq!(action.action_type, ActionType::Like);
+		assert_eq!(action.comment, Some("I like it!".to_string()));
+
+		content.action_type = "".into();
+		assert!(content.validate().is_err());
+		content.action_type = "DISLIKE".into();
+		assert!(content.validate().is_err());
+		Ok(())
+	}
+
 	#[test]
 	fn test_deserialize_action() {
 		let content = "eyJhY3Rpb25UeXBlIjoiTElLRSIsImNvbW1lbnQiOiJJIGxpa2UgaXQhIiwiaXNSZWxhdGlvbklkRW5jcnlwdGVkIjpmYWxzZSwiaXNDb21tZW50RW5jcnlwdGVkIjpmYWxzZX0";
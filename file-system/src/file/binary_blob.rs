@@ -0,0 +1,54 @@
+use base64::{engine::general_purpose, Engine};
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+
+/// content of a binary blob stream, `data` is stored as a base64 string
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BinaryBlobFile {
+	pub content_type: String,
+	#[serde(with = "base64_bytes")]
+	pub data: Bytes,
+}
+
+mod base64_bytes {
+	use super::*;
+
+	pub fn serialize<S>(data: &Bytes, serializer: S) -> Result<S::Ok, S::Error>
+	where
+		S: serde::Serializer,
+	{
+		serializer.serialize_str(&general_purpose::STANDARD.encode(data))
+	}
+
+	pub fn deserialize<'de, D>(deserializer: D) -> Result<Bytes, D::Error>
+	where
+		D: serde::Deserializer<'de>,
+	{
+		let data = String::deserialize(deserializer)?;
+		let data = general_purpose::STANDARD
+			.decode(data)
+			.map_err(|err| serde::de::Error::custom(format!("invalid base64 data: {}", err)))?;
+		Ok(data.into())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parse_binary_blob_file() -> anyhow::Result<()> {
+		let blob = serde_json::json!({
+			"contentType": "image/png",
+			"data": "iVBORw0KGgo="
+		});
+		let blob = serde_json::from_value::<BinaryBlobFile>(blob)?;
+		assert_eq!(blob.content_type, "image/png");
+		assert_eq!(&blob.data[..], b"\x89PNG\r\n\x1a\n");
+
+		let value = serde_json::to_value(&blob)?;
+		assert_eq!(value["data"], "iVBORw0KGgo=");
+		Ok(())
+	}
+}
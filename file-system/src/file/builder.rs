@@ -0,0 +1,172 @@
+use ceramic_core::StreamId;
+use dataverse_ceramic::StreamState;
+use serde_json::Value;
+
+use super::status::Status;
+use super::StreamFile;
+
+/// Build a [`StreamFile`] from its states and ids, checked once in `build`.
+///
+/// ```ignore
+/// let file = StreamFileBuilder::new()
+///     .content(content_state)
+///     .file(index_file_state)
+///     .build()?;
+/// ```
+#[derive(Default)]
+pub struct StreamFileBuilder {
+	content: Option<StreamState>,
+	file: Option<StreamState>,
+	status: Option<(Status, String)>,
+	content_id: Option<String>,
+	file_id: Option<StreamId>,
+	file_model_id: Option<StreamId>,
+}
+
+impl StreamFileBuilder {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// content stream state, sets content, content id, model and controller
+	pub fn content(mut self, state: StreamState) -> Self {
+		self.content = Some(state);
+		self
+	}
+
+	/// index file state, sets file, file id, file model and controller
+	pub fn file(mut self, state: StreamState) -> Self {
+		self.file = Some(state);
+		self
+	}
+
+	pub fn status(mut self, status: Status, desc: String) -> Self {
+		self.status = Some((status, desc));
+		self
+	}
+
+	/// content id of a file without content state, must match the content state otherwise
+	pub fn content_id(mut self, content_id: String) -> Self {
+		self.content_id = Some(content_id);
+		self
+	}
+
+	/// file id of a file without file state, must match the file state otherwise
+	pub fn file_id(mut self, file_id: StreamId) -> Self {
+		self.file_id = Some(file_id);
+		self
+	}
+
+	/// file model of a file without file state, must match the file state otherwise
+	pub fn file_model_id(mut self, file_model_id: StreamId) -> Self {
+		self.file_model_id = Some(file_model_id);
+		self
+	}
+
+	/// fails when ids do not match the given states or `contentId` of the index file
+	/// is another stream than the content
+	pub fn build(self) -> anyhow::Result<StreamFile> {
+		let mut stream_file = StreamFile::default();
+		let content_id = match (&self.content, self.content_id) {
+			(Some(content), Some(content_id)) => {
+				let state_id = content.stream_id()?.to_string();
+				if state_id != content_id {
+					anyhow::bail!("content id {} is not content {}", content_id, state_id);
+				}
+				Some(content_id)
+			}
+			(Some(content), None) => Some(content.stream_id()?.to_string()),
+			(None, content_id) => content_id,
+		};
+
+		if let Some(file) = self.file {
+			let file_id = file.stream_id()?;
+			if let Some(content_id) = &content_id {
+				let file_content_id = file.content.get("contentId").and_then(Value::as_str);
+				if file_content_id != Some(content_id.as_str()) {
+					anyhow::bail!(
+						"index file {} points to content {}, not {}",
+						file_id,
+						file_content_id.unwrap_or("none"),
+						content_id
+					);
+				}
+			}
+			stream_file.set_file(file)?;
+			if let Some(id) = self.file_id.filter(|id| *id != file_id) {
+				anyhow::bail!("file id {} is not index file {}", id, file_id);
+			}
+			if let Some(model) = self
+				.file_model_id
+				.filter(|model| Some(model) != stream_file.file_model_id.as_ref())
+			{
+				anyhow::bail!(
+					"file model {} is not model of index file {}",
+					model,
+					file_id
+				);
+			}
+		} else {
+			stream_file.file_id = self.file_id;
+			stream_file.file_model_id = self.file_model_id;
+		}
+
+		match self.content {
+			Some(content) => stream_file.set_content(content)?,
+			None => stream_file.content_id = content_id,
+		}
+		if let Some((status, desc)) = self.status {
+			stream_file.write_status(status, desc);
+		}
+		Ok(stream_file)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_helpers::example_state;
+
+	#[tokio::test]
+	async fn build_file() -> anyhow::Result<()> {
+		let (stream_id, content) = example_state().await?;
+		let other: StreamId =
+			"kjzl6hvfrbw6c763ubdhowzao0m4yp84cxzbfnlh4hdi5alqo4yrebmc0qpjdi5".parse()?;
+		let mut file = content.clone();
+		file.content = serde_json::json!({ "contentId": stream_id.to_string() });
+
+		let built = StreamFileBuilder::new()
+			.content(content.clone())
+			.file(file.clone())
+			.status(Status::Validated, "checked".to_string())
+			.build()?;
+		assert_eq!(built.content_id, Some(stream_id.to_string()));
+		assert_eq!(built.file_id, Some(stream_id.clone()));
+		assert_eq!(built.content, Some(content.content.clone().into()));
+		assert_eq!(built.verified_status, Status::Validated);
+
+		let naked = StreamFileBuilder::new()
+			.content_id("content".to_string())
+			.file_id(stream_id.clone())
+			.build()?;
+		assert_eq!(naked.content_id, Some("content".to_string()));
+		assert_eq!(naked.file_id, Some(stream_id.clone()));
+		assert_eq!(naked.content, None);
+
+		let res = StreamFileBuilder::new()
+			.content(content.clone())
+			.content_id("other".to_string())
+			.build();
+		assert!(res.is_err());
+
+		let res = StreamFileBuilder::new()
+			.file(file.clone())
+			.content_id("other".to_string())
+			.build();
+		assert!(res.is_err());
+
+		let res = StreamFileBuilder::new().file(file).file_id(other).build();
+		assert!(res.is_err());
+		Ok(())
+	}
+}
@@ -1,23 +1,64 @@
-use std::{collections::HashMap, sync::Arc};
+use std::collections::{HashMap, HashSet};
+use std::{sync::Arc, time::Duration};
 
-use anyhow::Result;
-use chrono::Utc;
-use dataverse_ceramic::event::{Event, EventValue, VerifyOption};
-use dataverse_ceramic::{StreamId, StreamState};
+use anyhow::{Context, Result};
+use ceramic_core::{Base64String, Cid, StreamIdType};
+use chrono::{DateTime, Utc};
+use dataverse_ceramic::event::{Event, EventSigner, EventValue, Header, Payload, VerifyOption};
+use dataverse_ceramic::http::{self, AnchorRequestStatus};
+use dataverse_ceramic::kubo::CidPinner;
+use dataverse_ceramic::{AnchorStatus, CacheInvalidator, Ceramic, FeeSchedule, LogType};
+use dataverse_ceramic::{DataverseError, PageOptions, StreamId, StreamState};
 use dataverse_core::store::dapp;
 use dataverse_core::stream::{Stream, StreamStore};
+use futures::{future::BoxFuture, StreamExt};
 use int_enum::IntEnum;
+use tokio::sync::Mutex;
 
 use crate::file::status::Status;
 
-use super::index_file::IndexFile;
+use super::access_control::{AccessControl, AccessControlValidator};
+use super::action_file::ActionFileContent;
+use super::compatibility::CompatibilityResult;
+use super::content_type::{ContentType, ContentTypeResourceType};
+use super::index_file::{IndexFile, IndexFileType};
 use super::index_folder::IndexFolder;
-use super::FileModel;
-use super::{operator::StreamFileLoader, StreamFile};
+use super::{operator::PreloadedContext, operator::StreamFileLoader, StreamFile};
+use super::{FileContent, FileModel, HistoricalStreamFile, StreamFileDiff, StreamTree};
+
+const FS_VERSION: &str = "0.11";
+/// warn when anchoring saved events is estimated to cost more than this, in fee currency
+const ANCHOR_COST_WARN_THRESHOLD: f64 = 1.0;
+/// default number of content streams loaded in parallel by `load_files`
+const DEFAULT_MAX_CONCURRENT: usize = 16;
+/// streams of a page loaded by `load_files_stream`
+const STREAM_PAGE_SIZE: usize = 100;
+/// interval between anchor status polls of `await_anchor`
+const ANCHOR_POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Default)]
+pub struct PinReport {
+	pub pinned: usize,
+	pub failed: usize,
+	pub errors: Vec<(StreamId, anyhow::Error)>,
+}
 
 pub struct Client {
 	pub operator: Arc<dyn StreamFileLoader>,
 	pub stream_store: Arc<dyn StreamStore>,
+	pub pinner: Option<Arc<dyn CidPinner>>,
+	/// cache of operator, filled with the new state after saving events
+	pub cache: Option<Arc<dyn CacheInvalidator>>,
+	/// access check of files loaded by `load_file_as` and `load_files`, none allows every caller
+	pub access_validator: Option<Arc<dyn AccessControlValidator>>,
+	/// max content streams loaded in parallel by `load_files`
+	pub max_concurrent: usize,
+	/// pool verifying signatures of `save_events`, the global rayon pool if none
+	pub rayon_pool: Option<Arc<rayon::ThreadPool>>,
+	/// fees of the anchor service, saved events costing more than
+	/// `ANCHOR_COST_WARN_THRESHOLD` are warned about, none when anchoring is free
+	pub fee_schedule: Option<FeeSchedule>,
+	stream_ids: Arc<Mutex<HashMap<Cid, StreamId>>>,
 }
 
 impl Client {
@@ -25,11 +66,71 @@ impl Client {
 		Self {
 			operator,
 			stream_store,
+			pinner: None,
+			cache: None,
+			access_validator: None,
+			max_concurrent: DEFAULT_MAX_CONCURRENT,
+			rayon_pool: None,
+			fee_schedule: None,
+			stream_ids: Default::default(),
 		}
 	}
+
+	pub fn with_pinner(mut self, pinner: Arc<dyn CidPinner>) -> Self {
+		self.pinner = Some(pinner);
+		self
+	}
+
+	/// write saved states through to the cache, usually the `CachedStreamLoader` used as operator
+	pub fn with_cache(mut self, cache: Arc<dyn CacheInvalidator>) -> Self {
+		self.cache = Some(cache);
+		self
+	}
+
+	/// check access of streams before returning them, see `ControllerAccessValidator`
+	pub fn with_access_validator(mut self, validator: Arc<dyn AccessControlValidator>) -> Self {
+		self.access_validator = Some(validator);
+		self
+	}
+
+	pub fn with_max_concurrent(mut self, max_concurrent: usize) -> Self {
+		self.max_concurrent = max_concurrent;
+		self
+	}
+
+	/// verify signatures on an existing pool instead of the global one, the pool can be
+	/// shared with other users
+	pub fn with_rayon_pool(mut self, pool: Arc<rayon::ThreadPool>) -> Self {
+		self.rayon_pool = Some(pool);
+		self
+	}
+
+	/// estimate anchor costs of saved events with fees of the anchor service,
+	/// ceramic anchor service exposes no fees so they are configured here
+	pub fn with_fee_schedule(mut self, fee_schedule: FeeSchedule) -> Self {
+		self.fee_schedule = Some(fee_schedule);
+		self
+	}
+
+	/// estimated cost of anchoring events, none without a fee schedule
+	pub fn estimated_anchor_cost(&self, events: &[Event]) -> Option<f64> {
+		let fee_schedule = self.fee_schedule.as_ref()?;
+		Some(events.iter().map(|event| fee_schedule.cost(event)).sum())
+	}
 }
 
 impl Client {
+	/// compute stream id of genesis event without publishing it
+	pub fn generate_stream_id(stream_type: u64, genesis_event: &Event) -> anyhow::Result<StreamId> {
+		match &genesis_event.value {
+			EventValue::Signed(signed) if signed.is_gensis() => Ok(StreamId {
+				r#type: StreamIdType::from_int(stream_type)?,
+				cid: genesis_event.cid,
+			}),
+			_ => anyhow::bail!("event {} is not a genesis event", genesis_event.cid),
+		}
+	}
+
 	pub async fn get_file_model(
 		&self,
 		app_id: &uuid::Uuid,
@@ -61,303 +162,2019 @@ impl Client {
 			.load_stream_states(&ceramic, account, model_id)
 			.await
 	}
-}
 
-#[async_trait::async_trait]
-pub trait StreamFileTrait {
-	async fn load_file(&self, dapp_id: &uuid::Uuid, stream_id: &StreamId) -> Result<StreamFile>;
+	/// create an action file stream, signed by signer and saved as a new stream
+	pub async fn create_action_file(
+		&self,
+		dapp_id: &uuid::Uuid,
+		content: ActionFileContent,
+		signer: &dyn EventSigner,
+	) -> anyhow::Result<StreamFile> {
+		let action_file = content.to_action_file(FS_VERSION)?;
+		let model = self.get_file_model(dapp_id, FileModel::ActionFile).await?;
 
-	async fn load_stream(&self, dapp_id: &uuid::Uuid, stream_id: &StreamId) -> Result<StreamState>;
+		let payload = Payload {
+			data: Some(serde_json::to_value(&action_file)?),
+			header: Some(Header {
+				model: model.id,
+				controllers: vec![signer.controller()],
+				unique: uuid::Uuid::new_v4().as_bytes().to_vec(),
+			}),
+			prev: None,
+			id: None,
+		};
+		let genesis = signer.sign(payload).await?;
+		let stream_id =
+			Self::generate_stream_id(StreamIdType::ModelInstanceDocument.int_value(), &genesis)?;
 
-	async fn load_files(
-		&self,
-		account: Option<String>,
-		model_id: &StreamId,
-		options: Vec<LoadFilesOption>,
-	) -> anyhow::Result<Vec<StreamFile>>;
-}
+		let state = self.save_event(dapp_id, &stream_id, &genesis).await?;
+		StreamFile::new_with_file(state)
+	}
 
-pub enum LoadFilesOption {
-	Signal(serde_json::Value),
-	None,
-}
+	/// Create an index file in target dapp pointing to a content stream of source dapp.
+	///
+	/// `metadata` is stored in the `reserved` field of index file along with the source dapp,
+	/// `fileName` of metadata is used as file name. Both dapps must be on the same network.
+	pub async fn cross_dapp_link(
+		&self,
+		source_dapp_id: &uuid::Uuid,
+		target_dapp_id: &uuid::Uuid,
+		content_id: &StreamId,
+		metadata: serde_json::Value,
+		signer: &dyn EventSigner,
+	) -> anyhow::Result<StreamFile> {
+		let source_ceramic = dapp::get_dapp_ceramic(source_dapp_id).await?;
+		let target_ceramic = dapp::get_dapp_ceramic(target_dapp_id).await?;
+		if source_ceramic.network.pubsub_topic() != target_ceramic.network.pubsub_topic() {
+			anyhow::bail!(
+				"cross dapp link not supported: dapp {} is on {:?} but dapp {} is on {:?}",
+				source_dapp_id,
+				source_ceramic.network,
+				target_dapp_id,
+				target_ceramic.network
+			);
+		}
 
-#[async_trait::async_trait]
-impl StreamFileTrait for Client {
-	async fn load_file(&self, dapp_id: &uuid::Uuid, stream_id: &StreamId) -> Result<StreamFile> {
-		let ceramic = dapp::get_dapp_ceramic(dapp_id).await?;
-		let stream_state = self
+		let content = self
 			.operator
-			.load_stream_state(&ceramic, &stream_id, None)
+			.load_stream_state(&source_ceramic, content_id, None)
 			.await?;
-		let model_id = &stream_state.must_model()?;
-		let model = dapp::get_model(model_id).await?;
-		if model.dapp_id != dapp_id.clone() {
+		let content_model_id = content.must_model()?;
+		if dapp::get_model(&content_model_id).await?.dapp_id != *source_dapp_id {
 			anyhow::bail!(
-				"stream_id {} with model_id {} not belong to dapp {}",
-				stream_id,
-				model_id,
-				dapp_id
+				"stream {} not belong to dapp {}",
+				content_id,
+				source_dapp_id
 			);
 		}
-		match model.name.as_str() {
-			"indexFile" => {
-				let index_file = serde_json::from_value::<IndexFile>(stream_state.content.clone())?;
-				let mut file = StreamFile::new_with_file(stream_state)?;
-				if let Ok(content_id) = &index_file.content_id.parse() {
-					let content_state = self
-						.operator
-						.load_stream_state(&ceramic, &content_id, None)
-						.await?;
-					file.write_content(content_state)?;
-				}
-				Ok(file)
-			}
-			"actionFile" => StreamFile::new_with_file(stream_state),
-			"indexFolder" | "contentFolder" => StreamFile::new_with_content(stream_state),
-			_ => {
-				let mut file = StreamFile::new_with_content(stream_state)?;
-				let index_file_model_id = self
-					.get_file_model(&dapp_id, FileModel::IndexFile)
-					.await?
-					.id;
 
-				let index_file = self
-					.operator
-					.load_index_file_by_content_id(
-						&ceramic,
-						&index_file_model_id,
-						&stream_id.to_string(),
-					)
-					.await;
+		let mut metadata = match metadata {
+			serde_json::Value::Object(metadata) => metadata,
+			serde_json::Value::Null => Default::default(),
+			_ => anyhow::bail!("metadata must be a json object"),
+		};
+		metadata.insert(
+			"crossDappOrigin".to_string(),
+			serde_json::Value::String(source_dapp_id.to_string()),
+		);
+		let file_name = metadata
+			.get("fileName")
+			.and_then(serde_json::Value::as_str)
+			.map_or_else(|| content_id.to_string(), str::to_string);
+		let content_type = ContentType {
+			resource: ContentTypeResourceType::CERAMIC,
+			resource_id: Some(content_model_id.to_string()),
+		};
+		let now = Utc::now();
+		let index_file = IndexFile {
+			file_name,
+			file_type: IndexFileType::Public as u64,
+			content_id: content_id.to_string(),
+			created_at: now,
+			updated_at: now,
+			fs_version: Some(FS_VERSION.to_string()),
+			content_type: Base64String::from(serde_json::to_vec(&content_type)?),
+			reserved: Some(serde_json::Value::Object(metadata).to_string()),
+			..Default::default()
+		};
 
-				match index_file {
-					Ok((file_state, _)) => {
-						file.write_file(file_state)?;
-					}
-					Err(err) => {
-						tracing::error!(
-							model_id = index_file_model_id.to_string(),
-							stream_id = stream_id.to_string(),
-							"failed load index file model: {}",
-							err
-						);
-						let desc = format!("failed load index file model: {}", err);
-						file.write_status(Status::NakedStream, desc);
-					}
-				}
-				Ok(file)
-			}
-		}
+		let model = self
+			.get_file_model(target_dapp_id, FileModel::IndexFile)
+			.await?;
+		let payload = Payload {
+			data: Some(serde_json::to_value(&index_file)?),
+			header: Some(Header {
+				model: model.id,
+				controllers: vec![signer.controller()],
+				unique: uuid::Uuid::new_v4().as_bytes().to_vec(),
+			}),
+			prev: None,
+			id: None,
+		};
+		let genesis = signer.sign(payload).await?;
+		let stream_id =
+			Self::generate_stream_id(StreamIdType::ModelInstanceDocument.int_value(), &genesis)?;
+
+		let state = self
+			.save_event(target_dapp_id, &stream_id, &genesis)
+			.await?;
+		StreamFile::merge(content, state)
 	}
 
-	async fn load_stream(
+	/// file models registered for the dapp, models of other names are skipped
+	pub async fn list_available_stream_types(
 		&self,
 		dapp_id: &uuid::Uuid,
-		stream_id: &StreamId,
-	) -> anyhow::Result<StreamState> {
-		let ceramic = dapp::get_dapp_ceramic(dapp_id).await?;
-		self.operator
-			.load_stream_state(&ceramic, stream_id, None)
-			.await
+	) -> anyhow::Result<Vec<FileModel>> {
+		let mut file_models = vec![];
+		for model in dapp::get_models(dapp_id, true).await? {
+			match model.name.parse::<FileModel>() {
+				Ok(file_model) if !file_models.contains(&file_model) => {
+					file_models.push(file_model)
+				}
+				Ok(_) => {}
+				Err(err) => tracing::debug!(model = model.name, "skip model: {}", err),
+			}
+		}
+		Ok(file_models)
 	}
 
-	async fn load_files(
+	/// Load files like `load_files`, grouped by the id of index folder mirroring them.
+	///
+	/// Files without a folder are grouped under `None`, a file mirrored by several folders
+	/// appears in each of them.
+	pub async fn load_files_grouped(
 		&self,
 		account: Option<String>,
 		model_id: &StreamId,
 		options: Vec<LoadFilesOption>,
-	) -> Result<Vec<StreamFile>> {
-		let model = dapp::get_model(&model_id).await?;
-		let app_id = model.dapp_id;
+	) -> anyhow::Result<HashMap<Option<StreamId>, Vec<StreamFile>>> {
+		let (files, _) = self.load_files(account, model_id, options).await?;
+		let model = dapp::get_model(model_id).await?;
 		let ceramic = model.ceramic().await?;
-
-		let stream_states = self
+		let content_folder_model = self
+			.get_file_model(&model.dapp_id, FileModel::ContentFolder)
+			.await?;
+		let content_folders = self
 			.operator
-			.load_stream_states(&ceramic, account.clone(), &model_id)
+			.load_stream_states(&ceramic, None, &content_folder_model.id)
 			.await?;
 
-		match model.name.as_str() {
-			"indexFile" => {
-				let mut files: Vec<StreamFile> = vec![];
-				for state in stream_states {
-					let index_file: IndexFile = serde_json::from_value(state.content.clone())?;
-					let mut file = StreamFile::new_with_file(state)?;
-					file.content_id = Some(index_file.content_id.clone());
+		let mut file_folders: HashMap<String, Vec<StreamId>> = HashMap::new();
+		for state in content_folders {
+			let folder_id = state
+				.content
+				.get("indexFolderId")
+				.and_then(serde_json::Value::as_str)
+				.and_then(|id| id.parse::<StreamId>().ok());
+			let (folder_id, file_ids) = match (folder_id, state.content.get("mirrorFileIds")) {
+				(Some(folder_id), Some(serde_json::Value::Array(file_ids))) => {
+					(folder_id, file_ids)
+				}
+				_ => continue,
+			};
+			for file_id in file_ids.iter().filter_map(serde_json::Value::as_str) {
+				file_folders
+					.entry(file_id.to_string())
+					.or_default()
+					.push(folder_id.clone());
+			}
+		}
 
-					if let Ok(stream_id) = &index_file.content_id.parse() {
-						let content_state = self
-							.operator
-							.load_stream_state(&ceramic, stream_id, None)
-							.await?;
-						if let Err(err) = file.write_content(content_state) {
-							let desc = format!("failed load content file model {}", err);
-							file.write_status(Status::BrokenContent, desc);
-						};
+		let mut grouped: HashMap<Option<StreamId>, Vec<StreamFile>> = HashMap::new();
+		for file in files {
+			let folder_ids = file
+				.file_id
+				.as_ref()
+				.and_then(|file_id| file_folders.get(&file_id.to_string()));
+			match folder_ids {
+				Some(folder_ids) => {
+					for folder_id in folder_ids {
+						grouped
+							.entry(Some(folder_id.clone()))
+							.or_default()
+							.push(file.clone());
 					}
-					files.push(file);
 				}
-
-				Ok(files)
+				None => grouped.entry(None).or_default().push(file),
 			}
-			"actionFile" => stream_states
-				.into_iter()
-				.map(StreamFile::new_with_file)
-				.collect(),
-			"indexFolder" => {
-				let files = stream_states
-					.into_iter()
-					.filter_map(|state| {
-						let mut file = StreamFile::new_with_content(state.clone()).ok()?;
-						let index_folder =
-							match serde_json::from_value::<IndexFolder>(state.content.clone()) {
-								Err(err) => {
-									file.write_status(
-										Status::BrokenFolder,
-										format!("Failed to asset content as index_folder: {}", err),
-									);
-									return Some(file);
-								}
-								Ok(index_folder) => index_folder,
-							};
+		}
+		Ok(grouped)
+	}
 
-						let maybe_options = match index_folder.options() {
-							Ok(options) => options,
-							Err(err) => {
-								file.write_status(
-									Status::BrokenFolder,
-									format!("Failed to decode folder options: {}", err),
-								);
-								return Some(file);
-							}
-						};
+	/// check if moving folder under `proposed_parent` creates a cycle,
+	/// that is `proposed_parent` is the folder itself or one of its descendants
+	pub async fn detect_folder_cycle(
+		&self,
+		dapp_id: &uuid::Uuid,
+		folder_id: &StreamId,
+		proposed_parent: &StreamId,
+	) -> anyhow::Result<bool> {
+		let ceramic = dapp::get_dapp_ceramic(dapp_id).await?;
+		let mut visited = HashSet::new();
+		let mut current = Some(proposed_parent.clone());
+		while let Some(ancestor) = current {
+			if ancestor == *folder_id {
+				return Ok(true);
+			}
+			// ancestors already in a cycle
+			if !visited.insert(ancestor.to_string()) {
+				return Ok(true);
+			}
+			let state = self
+				.operator
+				.load_stream_state(&ceramic, &ancestor, None)
+				.await?;
+			let folder = serde_json::from_value::<IndexFolder>(state.content)?;
+			current = folder
+				.parent_folder_id
+				.map(|parent| parent.parse::<StreamId>())
+				.transpose()?;
+		}
+		Ok(false)
+	}
 
-						// check if index_folder access control is valid
-						if let Err(err) = index_folder.access_control() {
-							file.write_status(
-								Status::BrokenFolder,
-								format!("access control error: {}", err),
-							);
-							return Some(file);
-						}
+	/// load index file from streams preloaded by `preload_related_streams` as `load_file`
+	/// does, falls back to `load_file` when the context is absent or for another stream.
+	///
+	/// Action files and content folders of the context are left to the caller to render.
+	pub async fn load_file_with_context(
+		&self,
+		dapp_id: &uuid::Uuid,
+		stream_id: &StreamId,
+		ctx: Option<PreloadedContext>,
+	) -> anyhow::Result<StreamFile> {
+		let ctx = match ctx {
+			Some(ctx) if ctx.index_file.stream_id()? == *stream_id => ctx,
+			_ => return self.load_file(dapp_id, stream_id).await,
+		};
+		let model_id = ctx.index_file.must_model()?;
+		let model = dapp::get_model(&model_id).await?;
+		if model.dapp_id != *dapp_id {
+			return Err(DataverseError::ModelMismatch {
+				stream_id: stream_id.clone(),
+				model_id,
+			}
+			.into());
+		}
+		let index_file = serde_json::from_value::<IndexFile>(ctx.index_file.content.clone())?;
+		let mut file = StreamFile::new_with_file(ctx.index_file.clone())?;
+		file.content_id = Some(index_file.content_id);
+		if let Some(content) = ctx.content {
+			if self.check_access(&mut file, &ctx.index_file, None) {
+				file = StreamFile::merge(content, ctx.index_file)?;
+			}
+		}
+		check_deleted(&mut file);
+		check_deprecated(&mut file, &model);
+		Ok(file)
+	}
 
-						// check if index_folder options contains every signals
-						let required_signals: Vec<_> = options
-							.iter()
-							.filter_map(|option| match option {
-								LoadFilesOption::Signal(signal) => Some(signal.clone()),
-								_ => None,
-							})
-							.collect();
+	/// load every state of the stream file in chronological order, one for each event
+	pub async fn get_file_history(
+		&self,
+		dapp_id: &uuid::Uuid,
+		stream_id: &StreamId,
+	) -> anyhow::Result<Vec<HistoricalStreamFile>> {
+		let ceramic = dapp::get_dapp_ceramic(dapp_id).await?;
+		let tip = self
+			.stream_store
+			.load_stream(stream_id)
+			.await?
+			.map(|stream| stream.tip);
+		let events = self.operator.load_events(&ceramic, stream_id, tip).await?;
 
-						let all_signals_present = required_signals.iter().all(|signal| {
-							maybe_options
-								.as_ref()
-								.map_or(false, |options| options.signals.contains(signal))
-						});
+		let mut state = StreamState {
+			r#type: stream_id.r#type.int_value(),
+			..Default::default()
+		};
+		let mut model_name = None;
+		let mut history = vec![];
+		for event in events {
+			event.apply_to(&mut state).await?;
+			let model_id = state.must_model()?;
+			event.verify_signature(vec![VerifyOption::ResourceModelsContain(model_id.clone())])?;
 
-						if !all_signals_present {
-							return None;
-						}
-						Some(file)
-					})
-					.collect();
-				Ok(files)
+			if model_name.is_none() {
+				model_name = Some(dapp::get_model(&model_id).await?.name);
 			}
-			"contentFolder" => stream_states
-				.into_iter()
-				.map(StreamFile::new_with_content)
-				.collect(),
-			_ => {
-				let model_index_file = self.get_file_model(&app_id, FileModel::IndexFile).await?;
+			let file = match model_name.as_deref() {
+				Some("indexFile") | Some("actionFile") => StreamFile::new_with_file(state.clone())?,
+				_ => StreamFile::new_with_content(state.clone())?,
+			};
+			let timestamp = match &event.value {
+				EventValue::Signed(signed) => match signed.cacao()? {
+					Some(cacao) => Some(cacao.p.issued_at()?),
+					None => None,
+				},
+				EventValue::Anchor(_) => None,
+			};
+			history.push(HistoricalStreamFile {
+				state: file,
+				event_cid: event.cid,
+				timestamp,
+				event_type: format!("{:?}", event.log_type()),
+			});
+		}
+		Ok(history)
+	}
 
-				let file_query_edges = self
-					.operator
-					.load_stream_states(&ceramic, account, &model_index_file.id)
-					.await?;
+	/// Load file as it was at commit `tip`, events of the stream are loaded and replayed
+	/// up to `tip`. Fails when `tip` is not an event of the stream.
+	pub async fn load_file_at_commit(
+		&self,
+		dapp_id: &uuid::Uuid,
+		stream_id: &StreamId,
+		tip: Cid,
+	) -> Result<StreamFile> {
+		let ceramic = dapp::get_dapp_ceramic(dapp_id).await?;
+		// streams not in store are loaded up to `tip`, loaders without tip query need one
+		let latest = self
+			.stream_store
+			.load_stream(stream_id)
+			.await?
+			.map_or(tip, |stream| stream.tip);
+		let events = self
+			.operator
+			.load_events(&ceramic, stream_id, Some(latest))
+			.await?;
+		let state = StreamState::rollback(stream_id.r#type.int_value(), tip, &events).await?;
 
-				let mut file_map: HashMap<String, StreamFile> = HashMap::new();
-				for state in stream_states {
-					let content_id = state.stream_id()?;
-					let file = StreamFile::new_with_content(state)?;
-					file_map.insert(content_id.to_string(), file);
-				}
+		let model = dapp::get_model(&state.must_model()?).await?;
+		if model.dapp_id != *dapp_id {
+			anyhow::bail!("stream {} not belong to dapp {}", stream_id, dapp_id);
+		}
+		match model.name.as_str() {
+			"indexFile" | "actionFile" => StreamFile::new_with_file(state),
+			_ => StreamFile::new_with_content(state),
+		}
+	}
 
-				for node in file_query_edges {
-					let index_file = serde_json::from_value::<IndexFile>(node.content.clone());
-					if let Ok(index_file) = index_file {
-						if let Some(stream_file) = file_map.get_mut(&index_file.content_id) {
-							stream_file.file_model_id = Some(model_index_file.id.clone());
-							stream_file.file_id = Some(node.stream_id()?);
-							stream_file.file = Some(node.content);
-						}
-					}
-				}
+	/// check content of stream against the json schema of target model,
+	/// see [`CompatibilityResult`] for the meaning of fields
+	pub async fn is_model_compatible(
+		&self,
+		dapp_id: &uuid::Uuid,
+		content_id: &StreamId,
+		target_model_id: &StreamId,
+	) -> anyhow::Result<CompatibilityResult> {
+		let ceramic = dapp::get_dapp_ceramic(dapp_id).await?;
+		let content = self
+			.operator
+			.load_stream_state(&ceramic, content_id, None)
+			.await?;
+		let model = self
+			.operator
+			.load_stream_state(&ceramic, target_model_id, None)
+			.await?;
+		let schema = match model.content.get("schema") {
+			Some(schema) => schema,
+			None => anyhow::bail!("model {} has no schema", target_model_id),
+		};
+		CompatibilityResult::check(schema, &content.content)
+	}
 
-				// set verified_status to -1 if file_id is None (illegal file)
-				let files = file_map
-					.into_iter()
-					.map(|(_, mut file)| {
-						if file.file_id.is_none() {
-							if let Some(content_id) = file.content_id.clone() {
-								let desc = format!("file_id is None, content_id: {}", content_id);
-								file.write_status(Status::NakedStream, desc);
-							}
-						}
-						file
-					})
-					.collect();
+	/// Load file and check its content against the json schema of its model.
+	///
+	/// Content not matching the schema does not fail loading, the file is returned marked
+	/// `Status::SchemaMismatch` with the validation errors in its status desc.
+	pub async fn load_file_validated(
+		&self,
+		dapp_id: &uuid::Uuid,
+		stream_id: &StreamId,
+		schema: &serde_json::Value,
+	) -> anyhow::Result<StreamFile> {
+		let mut file = self.load_file(dapp_id, stream_id).await?;
+		file.check_schema(schema)?;
+		Ok(file)
+	}
 
-				Ok(files)
+	/// Wait until the tip of stream is anchored, polling the stream state every `poll_interval`.
+	///
+	/// Ceramic Anchor Service anchors requests in batches, it usually takes from a few minutes
+	/// up to around half an hour for a tip to be anchored, so `timeout` should be generous.
+	/// Fails with `DataverseError::AnchorTimeout` on timeout, the wait can be retried.
+	pub async fn force_anchor(
+		&self,
+		dapp_id: &uuid::Uuid,
+		stream_id: &StreamId,
+		poll_interval: Duration,
+		timeout: Duration,
+	) -> anyhow::Result<AnchorStatus> {
+		let ceramic = &dapp::get_dapp_ceramic(dapp_id).await?;
+		wait_anchor(stream_id, poll_interval, timeout, || async move {
+			let state = self
+				.operator
+				.load_stream_state(ceramic, stream_id, None)
+				.await?;
+			let tip_anchored = state
+				.log
+				.last()
+				.map_or(false, |log| log.r#type == LogType::Anchor.int_value());
+			match state.anchor_status {
+				AnchorStatus::Anchored => Ok(Some(AnchorStatus::Anchored)),
+				_ if tip_anchored => Ok(Some(AnchorStatus::Anchored)),
+				AnchorStatus::Failed => anyhow::bail!("anchor of stream {} failed", stream_id),
+				_ => Ok(None),
 			}
-		}
+		})
+		.await
 	}
-}
 
-#[async_trait::async_trait]
-pub trait StreamEventSaver {
-	async fn save_event(
+	/// Wait until the latest anchor request of stream is anchored or failed, polling its status
+	/// over ceramic http every 30 seconds. Fails with `DataverseError::AnchorTimeout` on timeout.
+	pub async fn await_anchor(
 		&self,
 		dapp_id: &uuid::Uuid,
 		stream_id: &StreamId,
-		event: &Event,
-	) -> Result<StreamState>;
-}
+		timeout: Duration,
+	) -> Result<AnchorRequestStatus> {
+		let ceramic = &dapp::get_dapp_ceramic(dapp_id).await?;
+		let http_client = &http::Client::new();
+		wait_anchor(stream_id, ANCHOR_POLL_INTERVAL, timeout, || async move {
+			match http_client
+				.anchor_request_status(ceramic, stream_id)
+				.await?
+			{
+				AnchorRequestStatus::Pending | AnchorRequestStatus::Processing => Ok(None),
+				status => Ok(Some(status)),
+			}
+		})
+		.await
+	}
 
-#[async_trait::async_trait]
-impl StreamEventSaver for Client {
-	async fn save_event(
+	/// pin all events of stream on kubo, so they are not garbage-collected
+	pub async fn pin_file(&self, dapp_id: &uuid::Uuid, stream_id: &StreamId) -> anyhow::Result<()> {
+		self.set_pinned(dapp_id, stream_id, true).await
+	}
+
+	pub async fn unpin_file(
 		&self,
 		dapp_id: &uuid::Uuid,
 		stream_id: &StreamId,
-		event: &Event,
-	) -> Result<StreamState> {
-		let ceramic = dapp::get_dapp_ceramic(dapp_id).await?;
-		match &event.value {
-			EventValue::Signed(signed) => {
-				let (mut stream, mut commits) = {
-					let stream = self.stream_store.load_stream(&stream_id).await;
-					match stream.ok().flatten() {
-						Some(stream) => (
-							stream.clone(),
-							self.operator
-								.load_events(&ceramic, stream_id, Some(stream.tip))
-								.await?,
-						),
-						None => {
-							if !signed.is_gensis() {
-								anyhow::bail!(
-									"publishing commit with stream_id {} not found in store",
-									stream_id
-								);
-							}
-							(
-								Stream::new(dapp_id, stream_id.r#type.int_value(), event, None)?,
-								vec![],
-							)
+	) -> anyhow::Result<()> {
+		self.set_pinned(dapp_id, stream_id, false).await
+	}
+
+	pub async fn list_pinned_files(&self, dapp_id: &uuid::Uuid) -> anyhow::Result<Vec<StreamId>> {
+		self.stream_store
+			.list_all_streams()
+			.await?
+			.into_iter()
+			.filter(|stream| stream.dapp_id == *dapp_id && stream.pinned)
+			.map(|stream| stream.stream_id())
+			.collect()
+	}
+
+	/// Pin the folder, its content folders, the files they mirror and the contents of files,
+	/// `concurrency` streams are pinned at the same time.
+	///
+	/// Streams already pinned are counted as pinned without requesting kubo again.
+	/// Streams failed to load or pin are reported in `errors`, only a folder failed
+	/// to load fails the whole call.
+	pub async fn pin_folder_recursively(
+		&self,
+		dapp_id: &uuid::Uuid,
+		folder_id: &StreamId,
+		concurrency: usize,
+	) -> anyhow::Result<PinReport> {
+		if concurrency == 0 {
+			anyhow::bail!("{} is not a valid concurrency", concurrency);
+		}
+		let ceramic = dapp::get_dapp_ceramic(dapp_id).await?;
+		let (stream_ids, errors) = self.collect_folder_streams(&ceramic, folder_id).await?;
+
+		let results: Vec<(StreamId, anyhow::Result<()>)> = futures::stream::iter(stream_ids)
+			.map(|stream_id| async move {
+				let result = match self.stream_store.load_stream(&stream_id).await {
+					Ok(Some(stream)) if stream.pinned => Ok(()),
+					_ => self.pin_file(dapp_id, &stream_id).await,
+				};
+				(stream_id, result)
+			})
+			.buffer_unordered(concurrency)
+			.collect()
+			.await;
+
+		let mut report = PinReport {
+			failed: errors.len(),
+			errors,
+			..Default::default()
+		};
+		for (stream_id, result) in results {
+			match result {
+				Ok(_) => report.pinned += 1,
+				Err(err) => {
+					report.failed += 1;
+					report.errors.push((stream_id, err));
+				}
+			}
+		}
+		Ok(report)
+	}
+
+	/// Index files of dapp whose `folderId` is `folder_id`.
+	///
+	/// Fails when the folder stream is not an index folder of dapp. Index files are
+	/// filtered by folder before their content is loaded.
+	pub async fn load_files_in_folder(
+		&self,
+		dapp_id: &uuid::Uuid,
+		folder_id: &StreamId,
+	) -> Result<Vec<StreamFile>> {
+		let ceramic = dapp::get_dapp_ceramic(dapp_id).await?;
+		self.check_index_folder(dapp_id, &ceramic, folder_id)
+			.await?;
+
+		let model = self.get_file_model(dapp_id, FileModel::IndexFile).await?;
+		let mut states = self
+			.operator
+			.load_stream_states(&ceramic, None, &model.id)
+			.await?;
+		states.retain(|state| {
+			let file_folder_id = state
+				.content
+				.get("folderId")
+				.and_then(serde_json::Value::as_str)
+				.and_then(|id| id.parse::<StreamId>().ok());
+			file_folder_id.as_ref() == Some(folder_id)
+		});
+		self.build_files(&model, &ceramic, None, states, &[]).await
+	}
+
+	/// Move index file into `target_folder_id`, a folder of the same dapp.
+	///
+	/// Only a data event updating `folderId` of index file is saved, the content stream is
+	/// not modified. Fails when the file is already in the target folder.
+	pub async fn move_file(
+		&self,
+		dapp_id: &uuid::Uuid,
+		stream_id: &StreamId,
+		target_folder_id: &StreamId,
+		signer: &dyn EventSigner,
+	) -> Result<StreamFile> {
+		let ceramic = dapp::get_dapp_ceramic(dapp_id).await?;
+		let file_state = self
+			.load_index_file_state(dapp_id, &ceramic, stream_id)
+			.await?;
+		self.check_index_folder(dapp_id, &ceramic, target_folder_id)
+			.await?;
+
+		let mut index_file = serde_json::from_value::<IndexFile>(file_state.content.clone())?;
+		let event = index_file
+			.move_to(&file_state, target_folder_id, signer)
+			.await?;
+		let file_state = self.save_event(dapp_id, stream_id, &event).await?;
+		match index_file.content_id.parse::<StreamId>() {
+			Ok(content_id) => {
+				let content_state = self
+					.operator
+					.load_stream_state(&ceramic, &content_id, None)
+					.await?;
+				StreamFile::merge(content_state, file_state)
+			}
+			Err(_) => StreamFile::new_with_file(file_state),
+		}
+	}
+
+	/// Copy file into `target_folder_id` as a new content stream and a new index file.
+	///
+	/// Content of `source_id`, an index file, is saved as the genesis of another stream,
+	/// the two index files never share a content stream. File name is kept unless `new_name`
+	/// is given.
+	///
+	/// Both genesis events are signed before anything is saved. Streams can't be removed
+	/// from ceramic, so when saving the index file fails the copied content is left without
+	/// index file and loads as `Status::NakedStream`, the error names that content stream.
+	pub async fn copy_file(
+		&self,
+		dapp_id: &uuid::Uuid,
+		source_id: &StreamId,
+		target_folder_id: &StreamId,
+		new_name: Option<String>,
+		signer: &dyn EventSigner,
+	) -> Result<StreamFile> {
+		let ceramic = dapp::get_dapp_ceramic(dapp_id).await?;
+		let file_state = self
+			.load_index_file_state(dapp_id, &ceramic, source_id)
+			.await?;
+		self.check_index_folder(dapp_id, &ceramic, target_folder_id)
+			.await?;
+		let index_file = serde_json::from_value::<IndexFile>(file_state.content.clone())?;
+		let content_id = index_file
+			.content_id
+			.parse::<StreamId>()
+			.with_context(|| format!("content of file {} is not a stream", source_id))?;
+		let content_state = self
+			.operator
+			.load_stream_state(&ceramic, &content_id, None)
+			.await?;
+
+		let payload = Payload {
+			data: Some(content_state.content.clone()),
+			header: Some(Header {
+				model: content_state.must_model()?,
+				controllers: vec![signer.controller()],
+				unique: uuid::Uuid::new_v4().as_bytes().to_vec(),
+			}),
+			prev: None,
+			id: None,
+		};
+		let content_genesis = signer.sign(payload).await?;
+		let copy_content_id = Self::generate_stream_id(
+			StreamIdType::ModelInstanceDocument.int_value(),
+			&content_genesis,
+		)?;
+
+		let mut copy = index_file.copy_as(&copy_content_id, target_folder_id, new_name, Utc::now());
+		copy.fs_version = Some(FS_VERSION.to_string());
+		let payload = Payload {
+			data: Some(serde_json::to_value(&copy)?),
+			header: Some(Header {
+				model: file_state.must_model()?,
+				controllers: vec![signer.controller()],
+				unique: uuid::Uuid::new_v4().as_bytes().to_vec(),
+			}),
+			prev: None,
+			id: None,
+		};
+		let file_genesis = signer.sign(payload).await?;
+		let copy_file_id = Self::generate_stream_id(
+			StreamIdType::ModelInstanceDocument.int_value(),
+			&file_genesis,
+		)?;
+
+		let copy_content = self
+			.save_event(dapp_id, &copy_content_id, &content_genesis)
+			.await?;
+		let copy_file = self
+			.save_event(dapp_id, &copy_file_id, &file_genesis)
+			.await
+			.with_context(|| {
+				format!(
+					"content {} copied from file {} is left without index file",
+					copy_content_id, source_id
+				)
+			})?;
+		StreamFile::merge(copy_content, copy_file)
+	}
+
+	/// Delete index file by saving a tombstone event with `deleted` and `deletedAt`.
+	///
+	/// Events of the stream are kept, loading the file afterwards marks it `Status::Deleted`
+	/// and `load_files` skips it unless `LoadFilesOption::IncludeDeleted` is given.
+	pub async fn delete_file(
+		&self,
+		dapp_id: &uuid::Uuid,
+		stream_id: &StreamId,
+		signer: &dyn EventSigner,
+	) -> Result<()> {
+		let ceramic = dapp::get_dapp_ceramic(dapp_id).await?;
+		let file_state = self
+			.load_index_file_state(dapp_id, &ceramic, stream_id)
+			.await?;
+		let mut index_file = serde_json::from_value::<IndexFile>(file_state.content.clone())?;
+		let event = index_file.delete(&file_state, signer).await?;
+		self.save_event(dapp_id, stream_id, &event).await?;
+		Ok(())
+	}
+
+	/// state of an index file stream, fails when its model is not index file of dapp
+	async fn load_index_file_state(
+		&self,
+		dapp_id: &uuid::Uuid,
+		ceramic: &Ceramic,
+		stream_id: &StreamId,
+	) -> Result<StreamState> {
+		let state = self
+			.operator
+			.load_stream_state(ceramic, stream_id, None)
+			.await?;
+		let model = dapp::get_model(&state.must_model()?).await?;
+		if model.dapp_id != *dapp_id || model.name != FileModel::IndexFile.to_string() {
+			anyhow::bail!(
+				"stream {} is not an index file of dapp {}",
+				stream_id,
+				dapp_id
+			);
+		}
+		Ok(state)
+	}
+
+	/// fails when `folder_id` is not an index folder of dapp
+	async fn check_index_folder(
+		&self,
+		dapp_id: &uuid::Uuid,
+		ceramic: &Ceramic,
+		folder_id: &StreamId,
+	) -> Result<()> {
+		let state = self
+			.operator
+			.load_stream_state(ceramic, folder_id, None)
+			.await
+			.with_context(|| format!("folder {} not found", folder_id))?;
+		let model = dapp::get_model(&state.must_model()?).await?;
+		if model.dapp_id != *dapp_id || model.name != FileModel::IndexFolder.to_string() {
+			anyhow::bail!(
+				"stream {} is not an index folder of dapp {}",
+				folder_id,
+				dapp_id
+			);
+		}
+		Ok(())
+	}
+
+	/// stream ids under index folder, including the folder itself,
+	/// and the errors of streams under it failed to load
+	async fn collect_folder_streams(
+		&self,
+		ceramic: &Ceramic,
+		folder_id: &StreamId,
+	) -> anyhow::Result<(Vec<StreamId>, Vec<(StreamId, anyhow::Error)>)> {
+		let folder = self
+			.operator
+			.load_stream_state(ceramic, folder_id, None)
+			.await?;
+		let folder = serde_json::from_value::<IndexFolder>(folder.content)?;
+
+		let mut stream_ids = vec![folder_id.clone()];
+		let mut errors = vec![];
+		for content_folder_id in folder.content_folder_ids {
+			let content_folder_id = match content_folder_id.parse::<StreamId>() {
+				Ok(content_folder_id) => content_folder_id,
+				Err(err) => {
+					let err =
+						anyhow::anyhow!("invalid content folder id {}: {}", content_folder_id, err);
+					errors.push((folder_id.clone(), err));
+					continue;
+				}
+			};
+			let content_folder = match self
+				.operator
+				.load_stream_state(ceramic, &content_folder_id, None)
+				.await
+			{
+				Ok(content_folder) => content_folder,
+				Err(err) => {
+					errors.push((content_folder_id, err));
+					continue;
+				}
+			};
+			stream_ids.push(content_folder_id.clone());
+
+			let file_ids = match content_folder.content.get("mirrorFileIds") {
+				Some(serde_json::Value::Array(file_ids)) => file_ids.clone(),
+				_ => continue,
+			};
+			for file_id in file_ids.iter().filter_map(serde_json::Value::as_str) {
+				let file_id = match file_id.parse::<StreamId>() {
+					Ok(file_id) => file_id,
+					Err(err) => {
+						let err = anyhow::anyhow!("invalid mirror file id {}: {}", file_id, err);
+						errors.push((content_folder_id.clone(), err));
+						continue;
+					}
+				};
+				let index_file = self
+					.operator
+					.load_stream_state(ceramic, &file_id, None)
+					.await
+					.and_then(|file| Ok(serde_json::from_value::<IndexFile>(file.content)?));
+				let index_file = match index_file {
+					Ok(index_file) => index_file,
+					Err(err) => {
+						errors.push((file_id, err));
+						continue;
+					}
+				};
+				stream_ids.push(file_id);
+				// content of file may be an ipfs cid instead of a stream
+				if let Ok(content_id) = index_file.content_id.parse::<StreamId>() {
+					stream_ids.push(content_id);
+				}
+			}
+		}
+
+		let mut seen = HashSet::new();
+		stream_ids.retain(|stream_id| seen.insert(stream_id.to_string()));
+		Ok((stream_ids, errors))
+	}
+
+	async fn set_pinned(
+		&self,
+		dapp_id: &uuid::Uuid,
+		stream_id: &StreamId,
+		pinned: bool,
+	) -> anyhow::Result<()> {
+		let pinner = match &self.pinner {
+			Some(pinner) => pinner,
+			None => anyhow::bail!("pinner not configured"),
+		};
+		let mut stream = match self.stream_store.load_stream(stream_id).await? {
+			Some(stream) if stream.dapp_id == *dapp_id => stream,
+			_ => anyhow::bail!("stream {} not found in dapp {}", stream_id, dapp_id),
+		};
+
+		let ceramic = dapp::get_dapp_ceramic(dapp_id).await?;
+		let events = self
+			.operator
+			.load_events(&ceramic, stream_id, Some(stream.tip))
+			.await?;
+		for event in events {
+			match pinned {
+				true => pinner.pin_cid(&event.cid).await?,
+				false => pinner.unpin_cid(&event.cid).await?,
+			}
+		}
+
+		stream.pinned = pinned;
+		self.stream_store.save_stream(&stream).await
+	}
+
+	/// Load file like `load_file`, checking access of `caller` with the access validator.
+	///
+	/// Access is checked against the index file of content, before content is loaded.
+	/// A denied file is returned without content and marked `Status::AccessDenied`.
+	#[cfg_attr(
+		feature = "opentelemetry",
+		tracing::instrument(
+			skip_all,
+			fields(
+				dapp_id = %dapp_id,
+				stream_id = %stream_id,
+				ceramic.url = tracing::field::Empty
+			)
+		)
+	)]
+	pub async fn load_file_as(
+		&self,
+		dapp_id: &uuid::Uuid,
+		stream_id: &StreamId,
+		caller: Option<&str>,
+	) -> Result<StreamFile> {
+		let ceramic = dapp::get_dapp_ceramic(dapp_id).await?;
+		#[cfg(feature = "opentelemetry")]
+		tracing::Span::current().record("ceramic.url", ceramic.endpoint.as_str());
+		let stream_state = self
+			.operator
+			.load_stream_state(&ceramic, &stream_id, None)
+			.await?;
+		let model_id = &stream_state.must_model()?;
+		let model = dapp::get_model(model_id).await?;
+		if model.dapp_id != dapp_id.clone() {
+			return Err(DataverseError::ModelMismatch {
+				stream_id: stream_id.clone(),
+				model_id: model_id.clone(),
+			}
+			.into());
+		}
+		let mut file = match model.name.as_str() {
+			"indexFile" => {
+				let index_file = serde_json::from_value::<IndexFile>(stream_state.content.clone())?;
+				let mut file = StreamFile::new_with_file(stream_state.clone())?;
+				file.content_id = Some(index_file.content_id.clone());
+				// content is only loaded for callers allowed by the index file
+				match index_file.content_id.parse::<StreamId>() {
+					Ok(content_id) if self.check_access(&mut file, &stream_state, caller) => {
+						let content_state = self
+							.operator
+							.load_stream_state(&ceramic, &content_id, None)
+							.await?;
+						StreamFile::merge(content_state, stream_state)
+					}
+					_ => Ok(file),
+				}
+			}
+			"actionFile" => StreamFile::new_with_file(stream_state.clone()).map(|mut file| {
+				self.check_access(&mut file, &stream_state, caller);
+				file
+			}),
+			"indexFolder" | "contentFolder" => StreamFile::new_with_content(stream_state.clone())
+				.map(|mut file| {
+					self.check_access(&mut file, &stream_state, caller);
+					file
+				}),
+			"binaryBlob" => {
+				let mut file = self
+					.load_content_file(dapp_id, &ceramic, stream_id, stream_state, caller)
+					.await?;
+				// a broken blob is marked as `load_files` does
+				if let Err(err) = file.decode_binary_content() {
+					file.write_status(Status::BrokenContent, err.to_string());
+				}
+				Ok(file)
+			}
+			_ => {
+				self.load_content_file(dapp_id, &ceramic, stream_id, stream_state, caller)
+					.await
+			}
+		}?;
+		check_deleted(&mut file);
+		check_deprecated(&mut file, &model);
+		Ok(file)
+	}
+
+	/// file of content stream with its index file, marked `NakedStream` without index file,
+	/// access of caller is checked against the index file
+	async fn load_content_file(
+		&self,
+		dapp_id: &uuid::Uuid,
+		ceramic: &Ceramic,
+		stream_id: &StreamId,
+		stream_state: StreamState,
+		caller: Option<&str>,
+	) -> Result<StreamFile> {
+		let index_file_model_id = self.get_file_model(dapp_id, FileModel::IndexFile).await?.id;
+
+		let index_file = self
+			.operator
+			.load_index_file_by_content_id(ceramic, &index_file_model_id, &stream_id.to_string())
+			.await;
+
+		match index_file {
+			Ok((file_state, _)) => {
+				let mut file = StreamFile::merge(stream_state, file_state.clone())?;
+				self.check_access(&mut file, &file_state, caller);
+				Ok(file)
+			}
+			Err(err) => {
+				tracing::error!(
+					model_id = index_file_model_id.to_string(),
+					stream_id = stream_id.to_string(),
+					"failed load index file model: {}",
+					err
+				);
+				let mut file = StreamFile::new_with_content(stream_state)?;
+				let desc = format!("failed load index file model: {}", err);
+				file.write_status(Status::NakedStream, desc);
+				Ok(file)
+			}
+		}
+	}
+
+	/// Check access of caller against the `accessControl` of state with the access validator,
+	/// returns whether the file is readable.
+	///
+	/// An unreadable file loses its content, it's marked `Status::AccessDenied`, or
+	/// `Status::BrokenContent` when the access control is malformed.
+	fn check_access(
+		&self,
+		file: &mut StreamFile,
+		state: &StreamState,
+		caller: Option<&str>,
+	) -> bool {
+		let validator = match &self.access_validator {
+			Some(validator) => validator,
+			None => return true,
+		};
+		let (status, desc) = match AccessControl::from_content(&state.content) {
+			Err(err) => (
+				Status::BrokenContent,
+				format!("malformed access control: {}", err),
+			),
+			Ok(_) => match validator.validate(state, caller) {
+				Ok(_) => return true,
+				Err(err) => (Status::AccessDenied, err.to_string()),
+			},
+		};
+		file.replace_content(None);
+		file.write_status(status, desc);
+		false
+	}
+
+	/// Load content of index files, at most `max_concurrent` at a time.
+	/// Files keep their order, files without content id are returned as is.
+	async fn load_index_file_contents(
+		&self,
+		ceramic: &Ceramic,
+		files: Vec<(StreamFile, Option<(StreamState, StreamId)>)>,
+		strict: bool,
+		include_empty: bool,
+	) -> anyhow::Result<Vec<StreamFile>> {
+		let mut loaded = futures::stream::iter(files)
+			.map(|(mut file, index_file)| async move {
+				if let Some((file_state, stream_id)) = index_file {
+					let merged = self
+						.operator
+						.load_stream_state(ceramic, &stream_id, None)
+						.await
+						.and_then(|content_state| StreamFile::merge(content_state, file_state));
+					match merged {
+						Err(err) if strict => return Err(err),
+						Err(err) => {
+							let desc = format!("failed load content file model {}", err);
+							file.write_status(Status::BrokenContent, desc);
+						}
+						Ok(merged) => {
+							file = merged;
+							check_empty_content(&mut file, include_empty);
+						}
+					}
+				}
+				Ok(file)
+			})
+			.buffered(self.max_concurrent.max(1));
+
+		let mut result = vec![];
+		while let Some(file) = loaded.next().await {
+			result.push(file?);
+		}
+		Ok(result)
+	}
+
+	/// Load stream and streams linked from its content, up to `max_depth` levels below root.
+	///
+	/// Stream ids in content are linked directly, cids are resolved with
+	/// `resolve_stream_id_from_cid`. Links failed to resolve or load are skipped,
+	/// streams already in the tree are not loaded again.
+	pub async fn load_stream_tree(
+		&self,
+		dapp_id: &uuid::Uuid,
+		root_stream_id: &StreamId,
+		max_depth: usize,
+	) -> anyhow::Result<StreamTree> {
+		let mut visited = HashSet::new();
+		visited.insert(root_stream_id.clone());
+		self.load_stream_subtree(dapp_id, root_stream_id, max_depth, &mut visited)
+			.await
+	}
+
+	fn load_stream_subtree<'a>(
+		&'a self,
+		dapp_id: &'a uuid::Uuid,
+		stream_id: &'a StreamId,
+		depth: usize,
+		visited: &'a mut HashSet<StreamId>,
+	) -> BoxFuture<'a, anyhow::Result<StreamTree>> {
+		Box::pin(async move {
+			let root = self.load_file(dapp_id, stream_id).await?;
+			let mut tree = StreamTree {
+				root,
+				children: vec![],
+			};
+			if depth == 0 {
+				return Ok(tree);
+			}
+
+			let (stream_ids, cids) = match tree.root.content.as_ref().and_then(FileContent::as_json)
+			{
+				Some(content) => content_links(content),
+				None => return Ok(tree),
+			};
+			let mut children = stream_ids;
+			for cid in cids {
+				match self.resolve_stream_id_from_cid(&cid).await {
+					Ok(stream_id) => children.push(stream_id),
+					Err(err) => tracing::debug!(cid = cid.to_string(), "skip link: {}", err),
+				}
+			}
+			for child in children {
+				if !visited.insert(child.clone()) {
+					continue;
+				}
+				match self
+					.load_stream_subtree(dapp_id, &child, depth - 1, visited)
+					.await
+				{
+					Ok(subtree) => tree.children.push(subtree),
+					Err(err) => {
+						tracing::warn!(stream_id = child.to_string(), "skip linked stream: {}", err)
+					}
+				}
+			}
+			Ok(tree)
+		})
+	}
+
+	/// find the stream which the event cid belongs to,
+	/// from events saved by this client or the event index of the store
+	pub async fn resolve_stream_id_from_cid(&self, cid: &Cid) -> anyhow::Result<StreamId> {
+		if let Some(stream_id) = self.stream_ids.lock().await.get(cid) {
+			return Ok(stream_id.clone());
+		}
+
+		match self.stream_store.find_stream_by_event(cid).await? {
+			Some(stream_id) => {
+				self.stream_ids.lock().await.insert(*cid, stream_id.clone());
+				Ok(stream_id)
+			}
+			None => Err(DataverseError::UnknownCid(*cid).into()),
+		}
+	}
+
+	async fn remember_stream_ids(&self, stream_id: &StreamId, events: &[Event]) {
+		let cids: Vec<Cid> = events.iter().map(|event| event.cid).collect();
+		if let Err(err) = self.stream_store.index_events(stream_id, &cids).await {
+			tracing::warn!(
+				stream_id = stream_id.to_string(),
+				?err,
+				"failed to index events"
+			);
+		}
+		let mut stream_ids = self.stream_ids.lock().await;
+		for cid in cids {
+			stream_ids.insert(cid, stream_id.clone());
+		}
+	}
+}
+
+#[async_trait::async_trait]
+pub trait StreamFileTrait {
+	async fn load_file(&self, dapp_id: &uuid::Uuid, stream_id: &StreamId) -> Result<StreamFile>;
+
+	async fn load_stream(&self, dapp_id: &uuid::Uuid, stream_id: &StreamId) -> Result<StreamState>;
+
+	/// Load the index file of stream without loading its content stream.
+	///
+	/// Enough for listings showing file name, type or content id, use `load_file`
+	/// when the content itself is needed.
+	async fn load_file_metadata(
+		&self,
+		dapp_id: &uuid::Uuid,
+		stream_id: &StreamId,
+	) -> Result<IndexFile>
+	where
+		Self: Sync,
+	{
+		let state = self.load_stream(dapp_id, stream_id).await?;
+		serde_json::from_value(state.content)
+			.with_context(|| format!("stream {} is not an index file", stream_id))
+	}
+
+	/// Byte size of content of file, see [`StreamFile::content_size`].
+	///
+	/// Loads the whole file, implementations knowing sizes from metadata should override it.
+	async fn content_size(
+		&self,
+		dapp_id: &uuid::Uuid,
+		stream_id: &StreamId,
+	) -> Result<Option<usize>>
+	where
+		Self: Sync,
+	{
+		Ok(self.load_file(dapp_id, stream_id).await?.content_size())
+	}
+
+	/// Byte size of index file of file, see [`StreamFile::file_size`].
+	async fn file_size(&self, dapp_id: &uuid::Uuid, stream_id: &StreamId) -> Result<Option<usize>>
+	where
+		Self: Sync,
+	{
+		Ok(self.load_file(dapp_id, stream_id).await?.file_size())
+	}
+
+	/// files of model with the cursor of the next page, the cursor is only returned when
+	/// loading a `LoadFilesOption::Page` with streams left after it
+	async fn load_files(
+		&self,
+		account: Option<String>,
+		model_id: &StreamId,
+		options: Vec<LoadFilesOption>,
+	) -> anyhow::Result<(Vec<StreamFile>, Option<String>)>;
+
+	/// changed fields between two versions of a file, see [`StreamFile::diff`]
+	fn diff(&self, old: &StreamFile, new: &StreamFile) -> StreamFileDiff;
+}
+
+pub enum LoadFilesOption {
+	Signal(serde_json::Value),
+	/// fail the whole batch on the first broken file instead of writing its status
+	StrictMode,
+	/// mark files with empty content as `EmptyContent` instead of `BrokenContent`
+	IncludeEmpty,
+	/// only keep folders containing all the tags, compared case-insensitively
+	FolderTagFilter(Vec<String>),
+	/// load nothing when the model is deprecated
+	ExcludeDeprecatedModels,
+	/// load a page of streams, the cursor of the next page is returned with the files
+	Page(PageOptions),
+	/// exclude files with any of the statuses
+	StatusFilter(Vec<Status>),
+	/// only keep files with one of the statuses
+	OnlyStatus(Vec<Status>),
+	/// only keep streams created in `after <= created_at < before`, bounds are optional
+	DateRange {
+		after: Option<DateTime<Utc>>,
+		before: Option<DateTime<Utc>>,
+	},
+	/// order files, they are in the order returned by ceramic otherwise
+	Sort(SortOption),
+	/// keep files deleted by `Client::delete_file`, marked `Status::Deleted`
+	IncludeDeleted,
+	/// check access of the caller with the access validator, the caller is unknown otherwise
+	Caller(String),
+	None,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortOption {
+	CreatedAsc,
+	CreatedDesc,
+	UpdatedAsc,
+	UpdatedDesc,
+	StreamIdAsc,
+}
+
+/// creation and update time of a loaded stream
+type StreamTimes = (Option<DateTime<Utc>>, Option<DateTime<Utc>>);
+
+impl SortOption {
+	fn stream_times(states: &[StreamState]) -> HashMap<String, StreamTimes> {
+		states
+			.iter()
+			.filter_map(|state| {
+				let stream_id = state.stream_id().ok()?.to_string();
+				Some((stream_id, (state.created_at(), state.updated_at())))
+			})
+			.collect()
+	}
+
+	/// sort files by time of their loaded stream, files of unknown time go last,
+	/// ties are ordered by stream id
+	fn sort_files(self, files: &mut Vec<StreamFile>, times: &HashMap<String, StreamTimes>) {
+		let mut keyed: Vec<_> = files
+			.drain(..)
+			.map(|file| {
+				let ids: Vec<String> = [
+					file.content_id.clone(),
+					file.file_id.as_ref().map(ToString::to_string),
+				]
+				.into_iter()
+				.flatten()
+				.collect();
+				let stream_id = ids
+					.iter()
+					.find(|id| times.contains_key(*id))
+					.or(ids.first())
+					.cloned()
+					.unwrap_or_default();
+				let time = times
+					.get(&stream_id)
+					.and_then(|(created, updated)| match self {
+						SortOption::CreatedAsc | SortOption::CreatedDesc => *created,
+						SortOption::UpdatedAsc | SortOption::UpdatedDesc => *updated,
+						SortOption::StreamIdAsc => None,
+					});
+				(stream_id, time, file)
+			})
+			.collect();
+		let descending = matches!(self, SortOption::CreatedDesc | SortOption::UpdatedDesc);
+		keyed.sort_by(|(a_id, a_time, _), (b_id, b_time, _)| {
+			let by_time = match (a_time, b_time) {
+				(Some(a), Some(b)) if descending => b.cmp(a),
+				(Some(a), Some(b)) => a.cmp(b),
+				(Some(_), None) => std::cmp::Ordering::Less,
+				(None, Some(_)) => std::cmp::Ordering::Greater,
+				(None, None) => std::cmp::Ordering::Equal,
+			};
+			by_time.then_with(|| a_id.cmp(b_id))
+		});
+		files.extend(keyed.into_iter().map(|(_, _, file)| file));
+	}
+}
+
+impl LoadFilesOption {
+	fn strict_mode(options: &[LoadFilesOption]) -> bool {
+		options
+			.iter()
+			.any(|option| matches!(option, LoadFilesOption::StrictMode))
+	}
+
+	fn include_empty(options: &[LoadFilesOption]) -> bool {
+		options
+			.iter()
+			.any(|option| matches!(option, LoadFilesOption::IncludeEmpty))
+	}
+
+	fn include_deleted(options: &[LoadFilesOption]) -> bool {
+		options
+			.iter()
+			.any(|option| matches!(option, LoadFilesOption::IncludeDeleted))
+	}
+
+	fn caller(options: &[LoadFilesOption]) -> Option<&str> {
+		options.iter().find_map(|option| match option {
+			LoadFilesOption::Caller(caller) => Some(caller.as_str()),
+			_ => None,
+		})
+	}
+
+	fn status_allowed(options: &[LoadFilesOption], status: Status) -> bool {
+		options.iter().all(|option| match option {
+			LoadFilesOption::StatusFilter(excluded) => !excluded.contains(&status),
+			LoadFilesOption::OnlyStatus(included) => included.contains(&status),
+			_ => true,
+		})
+	}
+
+	/// whether creation time is in every date range of options,
+	/// streams of unknown creation time are only kept without range
+	fn in_date_range(options: &[LoadFilesOption], created_at: Option<DateTime<Utc>>) -> bool {
+		options.iter().all(|option| match option {
+			LoadFilesOption::DateRange { after, before } => match created_at {
+				Some(created_at) => {
+					after.map_or(true, |after| created_at >= after)
+						&& before.map_or(true, |before| created_at < before)
+				}
+				None => false,
+			},
+			_ => true,
+		})
+	}
+
+	fn sort(options: &[LoadFilesOption]) -> Option<SortOption> {
+		options.iter().find_map(|option| match option {
+			LoadFilesOption::Sort(sort) => Some(*sort),
+			_ => None,
+		})
+	}
+
+	fn page(options: &[LoadFilesOption]) -> Option<&PageOptions> {
+		options.iter().find_map(|option| match option {
+			LoadFilesOption::Page(page) => Some(page),
+			_ => None,
+		})
+	}
+}
+
+/// write status of a file whose index file is tombstoned, errors already written are kept
+fn check_deleted(file: &mut StreamFile) {
+	if file.is_deleted() && file.verified_status.int_value() >= 0 {
+		let deleted_at = file
+			.file
+			.as_ref()
+			.and_then(|file| file.get("deletedAt"))
+			.and_then(serde_json::Value::as_str)
+			.unwrap_or("unknown time");
+		file.write_status(Status::Deleted, format!("deleted at {}", deleted_at));
+	}
+}
+
+/// mark a file of a deprecated model unless it already has a status
+fn check_deprecated(file: &mut StreamFile, model: &dapp::Model) {
+	if model.is_deprecated && file.verified_status == Status::None {
+		file.write_status(
+			Status::DeprecatedModel,
+			format!("model {} is deprecated", model.id),
+		);
+	}
+}
+
+/// write status of a file whose content is empty, it's broken unless empty files are included
+fn check_empty_content(file: &mut StreamFile, include_empty: bool) {
+	if !file.is_empty() || file.verified_status != Status::None {
+		return;
+	}
+	let status = match include_empty {
+		true => Status::EmptyContent,
+		false => Status::BrokenContent,
+	};
+	file.write_status(status, "content is empty".to_string());
+}
+
+/// stream ids and cids in string values of content, including dag-json links `{"/": cid}`
+fn content_links(content: &serde_json::Value) -> (Vec<StreamId>, Vec<Cid>) {
+	let mut stream_ids = vec![];
+	let mut cids = vec![];
+	let mut values = vec![content];
+	while let Some(value) = values.pop() {
+		match value {
+			serde_json::Value::String(str) => {
+				if let Ok(stream_id) = str.parse::<StreamId>() {
+					if !stream_ids.contains(&stream_id) {
+						stream_ids.push(stream_id);
+					}
+				} else if let Ok(cid) = str.parse::<Cid>() {
+					if !cids.contains(&cid) {
+						cids.push(cid);
+					}
+				}
+			}
+			serde_json::Value::Array(items) => values.extend(items.iter().rev()),
+			serde_json::Value::Object(map) => values.extend(map.values().rev()),
+			_ => {}
+		}
+	}
+	(stream_ids, cids)
+}
+
+/// collect files from results, broken ones are skipped unless in strict mode
+fn collect_files(
+	results: impl Iterator<Item = anyhow::Result<StreamFile>>,
+	strict: bool,
+) -> Result<Vec<StreamFile>> {
+	let mut files = vec![];
+	for result in results {
+		match result {
+			Ok(file) => files.push(file),
+			Err(err) if strict => return Err(err),
+			Err(err) => tracing::warn!("skip broken stream file: {}", err),
+		}
+	}
+	Ok(files)
+}
+
+impl Client {
+	/// Files of model yielded page by page, as soon as the streams of a page are turned into files.
+	///
+	/// Takes the same options as `load_files` except `Sort`, files come in the order of their
+	/// streams. Pages are loaded from the cursor and of the limit of `Page`, from the first
+	/// stream in pages of `STREAM_PAGE_SIZE` without it. A page failing to turn into files is
+	/// yielded as an error and the stream goes on with the next page.
+	pub fn load_files_stream<'a>(
+		&'a self,
+		account: Option<String>,
+		model_id: &'a StreamId,
+		options: Vec<LoadFilesOption>,
+	) -> impl futures::Stream<Item = Result<StreamFile>> + 'a {
+		async_stream::stream! {
+			let (model, ceramic) = match self.load_model(model_id, &options).await {
+				Ok(Some(loaded)) => loaded,
+				Ok(None) => return,
+				Err(err) => {
+					yield Err(err);
+					return;
+				}
+			};
+			let mut page = LoadFilesOption::page(&options).cloned().unwrap_or(PageOptions {
+				after: None,
+				limit: STREAM_PAGE_SIZE,
+			});
+			loop {
+				let loaded = self
+					.operator
+					.load_stream_states_paged(&ceramic, account.clone(), model_id, &page)
+					.await;
+				let (mut stream_states, next) = match loaded {
+					Ok(loaded) => loaded,
+					Err(err) => {
+						yield Err(err);
+						return;
+					}
+				};
+				stream_states
+					.retain(|state| LoadFilesOption::in_date_range(&options, state.created_at()));
+				let files = self
+					.build_files(&model, &ceramic, account.clone(), stream_states, &options)
+					.await;
+				match files {
+					Ok(files) => {
+						for file in files {
+							yield Ok(file);
 						}
 					}
+					Err(err) => yield Err(err),
+				}
+				match next {
+					Some(cursor) => page.after = Some(cursor),
+					None => return,
+				}
+			}
+		}
+	}
+
+	/// model of `model_id` with its ceramic, `None` when the model is deprecated and excluded
+	/// by options
+	async fn load_model(
+		&self,
+		model_id: &StreamId,
+		options: &[LoadFilesOption],
+	) -> Result<Option<(dapp::Model, Ceramic)>> {
+		let model = dapp::get_model(model_id).await?;
+		let exclude_deprecated = options
+			.iter()
+			.any(|option| matches!(option, LoadFilesOption::ExcludeDeprecatedModels));
+		if model.is_deprecated && exclude_deprecated {
+			return Ok(None);
+		}
+		let ceramic = model.ceramic().await?;
+		#[cfg(feature = "opentelemetry")]
+		tracing::Span::current()
+			.record("dapp_id", tracing::field::display(model.dapp_id))
+			.record("ceramic.url", ceramic.endpoint.as_str());
+		Ok(Some((model, ceramic)))
+	}
+
+	/// model of `model_id` with its stream states in the page and date range of options,
+	/// `None` when the model is deprecated and excluded by options
+	async fn load_model_states(
+		&self,
+		account: Option<String>,
+		model_id: &StreamId,
+		options: &[LoadFilesOption],
+	) -> Result<Option<(dapp::Model, Ceramic, Vec<StreamState>, Option<String>)>> {
+		let (model, ceramic) = match self.load_model(model_id, options).await? {
+			Some(loaded) => loaded,
+			None => return Ok(None),
+		};
+		let (mut stream_states, cursor) = match LoadFilesOption::page(options) {
+			Some(page) => {
+				self.operator
+					.load_stream_states_paged(&ceramic, account, model_id, page)
+					.await?
+			}
+			None => {
+				let states = self
+					.operator
+					.load_stream_states(&ceramic, account, model_id)
+					.await?;
+				(states, None)
+			}
+		};
+		stream_states.retain(|state| LoadFilesOption::in_date_range(options, state.created_at()));
+		Ok(Some((model, ceramic, stream_states, cursor)))
+	}
+
+	/// files of stream states of model, filtered by options,
+	/// index files of content are looked up among those controlled by account
+	async fn build_files(
+		&self,
+		model: &dapp::Model,
+		ceramic: &Ceramic,
+		account: Option<String>,
+		stream_states: Vec<StreamState>,
+		options: &[LoadFilesOption],
+	) -> Result<Vec<StreamFile>> {
+		let app_id = model.dapp_id;
+		let caller = LoadFilesOption::caller(options);
+		let strict = LoadFilesOption::strict_mode(options);
+		let include_empty = LoadFilesOption::include_empty(options);
+
+		let mut files = match model.name.as_str() {
+			"indexFile" => {
+				let mut files = vec![];
+				for state in stream_states {
+					let index_file = serde_json::from_value::<IndexFile>(state.content.clone());
+					let mut file = match StreamFile::new_with_file(state.clone()) {
+						Ok(file) => file,
+						Err(err) if strict => return Err(err),
+						Err(err) => {
+							tracing::warn!("skip broken index file: {}", err);
+							continue;
+						}
+					};
+					let index_file = match index_file {
+						Ok(index_file) => index_file,
+						Err(err) if strict => return Err(err.into()),
+						Err(err) => {
+							let desc = format!("failed decode index file {}", err);
+							file.write_status(Status::BrokenContent, desc);
+							files.push((file, None));
+							continue;
+						}
+					};
+					file.content_id = Some(index_file.content_id.clone());
+					let content_id = match self.check_access(&mut file, &state, caller) {
+						true => index_file.content_id.parse().ok(),
+						false => None,
+					};
+					files.push((file, content_id.map(|content_id| (state, content_id))));
+				}
+
+				self.load_index_file_contents(&ceramic, files, strict, include_empty)
+					.await
+			}
+			"actionFile" => collect_files(
+				stream_states.into_iter().map(|state| {
+					let mut file = StreamFile::new_with_file(state.clone())?;
+					self.check_access(&mut file, &state, caller);
+					Ok(file)
+				}),
+				strict,
+			),
+			"indexFolder" => {
+				let files = stream_states
+					.into_iter()
+					.filter_map(|state| {
+						let mut file = StreamFile::new_with_content(state.clone()).ok()?;
+						let index_folder =
+							match serde_json::from_value::<IndexFolder>(state.content.clone()) {
+								Err(err) => {
+									file.write_status(
+										Status::BrokenFolder,
+										format!("Failed to asset content as index_folder: {}", err),
+									);
+									return Some(file);
+								}
+								Ok(index_folder) => index_folder,
+							};
+
+						let maybe_options = match index_folder.options() {
+							Ok(options) => options,
+							Err(err) => {
+								file.write_status(
+									Status::BrokenFolder,
+									format!("Failed to decode folder options: {}", err),
+								);
+								return Some(file);
+							}
+						};
+
+						if let Err(err) = index_folder.validate() {
+							file.write_status(Status::BrokenFolder, format!("{}", err));
+							return Some(file);
+						}
+
+						// check if index_folder access control is valid
+						if let Err(err) = index_folder.access_control() {
+							file.write_status(
+								Status::BrokenFolder,
+								format!("access control error: {}", err),
+							);
+							return Some(file);
+						}
+
+						self.check_access(&mut file, &state, caller);
+
+						// check if index_folder options contains every signals
+						let required_signals: Vec<_> = options
+							.iter()
+							.filter_map(|option| match option {
+								LoadFilesOption::Signal(signal) => Some(signal.clone()),
+								_ => None,
+							})
+							.collect();
+
+						let all_signals_present = required_signals.iter().all(|signal| {
+							maybe_options
+								.as_ref()
+								.map_or(false, |options| options.signals.contains(signal))
+						});
+
+						if !all_signals_present {
+							return None;
+						}
+
+						let all_tags_present = options.iter().all(|option| match option {
+							LoadFilesOption::FolderTagFilter(tags) => {
+								tags.iter().all(|tag| index_folder.has_tag(tag))
+							}
+							_ => true,
+						});
+						if !all_tags_present {
+							return None;
+						}
+						Some(file)
+					})
+					.collect();
+				Ok(files)
+			}
+			"contentFolder" => collect_files(
+				stream_states.into_iter().map(|state| {
+					let mut file = StreamFile::new_with_content(state.clone())?;
+					self.check_access(&mut file, &state, caller);
+					Ok(file)
+				}),
+				strict,
+			),
+			_ => {
+				let model_index_file = self.get_file_model(&app_id, FileModel::IndexFile).await?;
+
+				let mut content_states: HashMap<String, StreamState> = HashMap::new();
+				for state in stream_states {
+					match state.stream_id() {
+						Ok(content_id) => {
+							content_states.insert(content_id.to_string(), state);
+						}
+						Err(err) if strict => return Err(err),
+						Err(err) => tracing::warn!("skip broken stream file: {}", err),
+					}
+				}
+
+				let content_ids: Vec<String> = content_states.keys().cloned().collect();
+				let index_files = self
+					.operator
+					.load_index_file_batch(&ceramic, account, &model_index_file.id, &content_ids)
+					.await;
+				// without index files every content loads as a naked stream naming the error
+				let (mut index_files, lookup_err) = match index_files {
+					Ok(index_files) => (index_files, None),
+					Err(err) if strict => return Err(err),
+					Err(err) => {
+						tracing::warn!(
+							model_id = model.id.to_string(),
+							"failed to load index files: {}",
+							err
+						);
+						(HashMap::new(), Some(err.to_string()))
+					}
 				};
+				let binary = model.name == FileModel::BinaryBlob.to_string();
+				// content without index file is an illegal file
+				let files = content_states
+					.into_iter()
+					.map(|(content_id, content_state)| {
+						let naked = |content_state, desc| {
+							let mut file = StreamFile::new_with_content(content_state)?;
+							file.write_status(Status::NakedStream, desc);
+							Ok::<_, anyhow::Error>(file)
+						};
+						let mut file = match index_files.remove(&content_id) {
+							Some((file_state, _)) => {
+								match StreamFile::merge(content_state.clone(), file_state.clone()) {
+									Ok(mut file) => {
+										self.check_access(&mut file, &file_state, caller);
+										file
+									}
+									Err(err) if strict => return Err(err),
+									Err(err) => {
+										naked(content_state, format!("broken index file: {}", err))?
+									}
+								}
+							}
+							None => {
+								let desc = match &lookup_err {
+									Some(err) => format!("failed to load index file: {}", err),
+									None => format!("file_id is None, content_id: {}", content_id),
+								};
+								naked(content_state, desc)?
+							}
+						};
+						if binary {
+							match file.decode_binary_content() {
+								Err(err) if strict => return Err(err),
+								Err(err) => {
+									file.write_status(Status::BrokenContent, err.to_string())
+								}
+								Ok(_) => {}
+							}
+						}
+						check_empty_content(&mut file, include_empty);
+						Ok(file)
+					});
+
+				collect_files(files, strict)
+			}
+		}?;
+		files.iter_mut().for_each(check_deleted);
+		if !LoadFilesOption::include_deleted(options) {
+			files.retain(|file| !file.is_deleted());
+		}
+		files.retain(|file| LoadFilesOption::status_allowed(options, file.verified_status));
+		Ok(files)
+	}
+}
+
+#[async_trait::async_trait]
+impl StreamFileTrait for Client {
+	fn diff(&self, old: &StreamFile, new: &StreamFile) -> StreamFileDiff {
+		StreamFile::diff(old, new)
+	}
+
+	async fn load_file(&self, dapp_id: &uuid::Uuid, stream_id: &StreamId) -> Result<StreamFile> {
+		self.load_file_as(dapp_id, stream_id, None).await
+	}
+
+	async fn load_stream(
+		&self,
+		dapp_id: &uuid::Uuid,
+		stream_id: &StreamId,
+	) -> anyhow::Result<StreamState> {
+		let ceramic = dapp::get_dapp_ceramic(dapp_id).await?;
+		self.operator
+			.load_stream_state(&ceramic, stream_id, None)
+			.await
+	}
+
+	#[cfg_attr(
+		feature = "opentelemetry",
+		tracing::instrument(
+			skip_all,
+			fields(
+				model_id = %model_id,
+				dapp_id = tracing::field::Empty,
+				ceramic.url = tracing::field::Empty
+			)
+		)
+	)]
+	async fn load_files(
+		&self,
+		account: Option<String>,
+		model_id: &StreamId,
+		options: Vec<LoadFilesOption>,
+	) -> Result<(Vec<StreamFile>, Option<String>)> {
+		let (model, ceramic, stream_states, cursor) = match self
+			.load_model_states(account.clone(), model_id, &options)
+			.await?
+		{
+			Some(loaded) => loaded,
+			None => return Ok((vec![], None)),
+		};
+		let sort = LoadFilesOption::sort(&options)
+			.map(|sort| (sort, SortOption::stream_times(&stream_states)));
+
+		let mut files = self
+			.build_files(&model, &ceramic, account, stream_states, &options)
+			.await?;
+		if let Some((sort, times)) = sort {
+			sort.sort_files(&mut files, &times);
+		}
+		Ok((files, cursor))
+	}
+}
+
+#[async_trait::async_trait]
+pub trait StreamEventSaver {
+	async fn save_event(
+		&self,
+		dapp_id: &uuid::Uuid,
+		stream_id: &StreamId,
+		event: &Event,
+	) -> Result<StreamState>;
+
+	/// save events of a stream in order, each event's prev must be the event before it
+	async fn save_events(
+		&self,
+		dapp_id: &uuid::Uuid,
+		stream_id: &StreamId,
+		events: &[Event],
+	) -> Result<StreamState>;
+}
+
+/// check events form a chain, each prev is the event before it, the first event's prev is not checked
+pub fn verify_chain(events: &[Event]) -> anyhow::Result<()> {
+	match events.first() {
+		Some(first) => check_event_order(first.prev()?, events),
+		None => Ok(()),
+	}
+}
+
+/// check events are ordered as a chain starting after `tip`, each prev is the event before it
+fn check_event_order(tip: Option<Cid>, events: &[Event]) -> anyhow::Result<()> {
+	let mut expected = tip;
+	for (idx, event) in events.iter().enumerate() {
+		let prev = event.prev()?;
+		if prev != expected {
+			anyhow::bail!(
+				"events not in order: event {} at {} has prev {:?}, expected {:?}",
+				event.cid,
+				idx,
+				prev.map(|cid| cid.to_string()),
+				expected.map(|cid| cid.to_string()),
+			);
+		}
+		expected = Some(event.cid);
+	}
+	Ok(())
+}
+
+/// call `poll` every `interval` until it yields a status, failing with
+/// `DataverseError::AnchorTimeout` after `timeout`
+async fn wait_anchor<T, F, Fut>(
+	stream_id: &StreamId,
+	interval: Duration,
+	timeout: Duration,
+	mut poll: F,
+) -> Result<T>
+where
+	F: FnMut() -> Fut,
+	Fut: std::future::Future<Output = Result<Option<T>>>,
+{
+	let wait = async {
+		loop {
+			match poll().await? {
+				Some(status) => return Ok(status),
+				None => tokio::time::sleep(interval).await,
+			}
+		}
+	};
+	match tokio::time::timeout(timeout, wait).await {
+		Ok(status) => status,
+		Err(_) => Err(DataverseError::AnchorTimeout {
+			stream_id: stream_id.clone(),
+			timeout,
+		}
+		.into()),
+	}
+}
+
+/// genesis event must create `stream_id`, checked before it is saved or published
+fn check_genesis(stream_id: &StreamId, genesis: &Event) -> anyhow::Result<()> {
+	let genesis_stream_id = Client::generate_stream_id(stream_id.r#type.int_value(), genesis)?;
+	if &genesis_stream_id != stream_id {
+		return Err(DataverseError::GenesisMismatch {
+			stream_id: stream_id.clone(),
+			genesis_stream_id,
+		}
+		.into());
+	}
+	Ok(())
+}
+
+impl Client {
+	/// stored stream with its events, a stream is created when `first` is a genesis event
+	/// not stored yet
+	async fn load_saved_stream(
+		&self,
+		ceramic: &Ceramic,
+		dapp_id: &uuid::Uuid,
+		stream_id: &StreamId,
+		first: &Event,
+	) -> Result<(Stream, Vec<Event>)> {
+		match self.stream_store.load_stream(stream_id).await? {
+			Some(stream) => {
+				let commits = self
+					.operator
+					.load_events(ceramic, stream_id, Some(stream.tip))
+					.await?;
+				Ok((stream, commits))
+			}
+			None => match &first.value {
+				EventValue::Signed(signed) if signed.is_gensis() => Ok((
+					Stream::new(dapp_id, stream_id.r#type.int_value(), first, None)?,
+					vec![],
+				)),
+				_ => Err(DataverseError::StreamNotFound(stream_id.clone()).into()),
+			},
+		}
+	}
+
+	/// upload verified events to ceramic, then move the stored stream to the last event,
+	/// the store is left unchanged if an upload fails
+	async fn commit_events(
+		&self,
+		ceramic: &Ceramic,
+		stream_id: &StreamId,
+		stream: Stream,
+		state: StreamState,
+		events: &[Event],
+	) -> Result<StreamState> {
+		let tip = events.last().context("no events to save")?.cid;
+		if let Some(cost) = self.estimated_anchor_cost(events) {
+			if cost > ANCHOR_COST_WARN_THRESHOLD {
+				tracing::warn!(
+					stream_id = stream_id.to_string(),
+					cost,
+					currency = self
+						.fee_schedule
+						.as_ref()
+						.map(|fees| fees.currency.as_str()),
+					"anchor cost of events exceeds threshold"
+				);
+			}
+		}
+		for event in events {
+			match &event.value {
+				EventValue::Signed(signed) if signed.is_gensis() => {
+					self.operator
+						.publish_genesis(ceramic, stream_id.r#type.int_value(), event)
+						.await?;
+				}
+				_ => {
+					self.operator
+						.upload_event(ceramic, stream_id, event.clone())
+						.await?
+				}
+			}
+		}
+
+		let stream = Stream {
+			model: Some(state.must_model()?),
+			account: state.account().map(str::to_string),
+			tip,
+			..stream
+		}
+		.with_content(state.content.clone());
+		self.stream_store.save_stream(&stream).await?;
+		self.remember_stream_ids(stream_id, events).await;
+		if let Some(cache) = &self.cache {
+			cache.populate(stream_id, state.clone()).await;
+		}
+		Ok(state)
+	}
+}
+
+#[async_trait::async_trait]
+impl StreamEventSaver for Client {
+	#[cfg_attr(
+		feature = "opentelemetry",
+		tracing::instrument(
+			skip_all,
+			fields(
+				dapp_id = %dapp_id,
+				stream_id = %stream_id,
+				event_count = 1,
+				ceramic.url = tracing::field::Empty
+			)
+		)
+	)]
+	async fn save_event(
+		&self,
+		dapp_id: &uuid::Uuid,
+		stream_id: &StreamId,
+		event: &Event,
+	) -> Result<StreamState> {
+		let ceramic = dapp::get_dapp_ceramic(dapp_id).await?;
+		#[cfg(feature = "opentelemetry")]
+		tracing::Span::current().record("ceramic.url", ceramic.endpoint.as_str());
+		match &event.value {
+			EventValue::Signed(signed) => {
+				if signed.is_gensis() {
+					check_genesis(stream_id, event)?;
+				}
+				let (stream, mut commits) = self
+					.load_saved_stream(&ceramic, dapp_id, stream_id, event)
+					.await?;
 				// check if commit already exists
 				if commits.iter().any(|ele| ele.cid == event.cid) {
 					return stream.state(commits).await;
@@ -365,37 +2182,1508 @@ impl StreamEventSaver for Client {
 
 				if let Some(prev) = event.prev()? {
 					if commits.iter().all(|ele| ele.cid != prev) {
-						anyhow::bail!("donot have prev commit");
+						tracing::warn!(
+							stream_id = stream_id.to_string(),
+							prev = prev.to_string(),
+							"prev commit missing from local store, loading from ceramic"
+						);
+						// usually only events after the stored tip are missing
+						match self
+							.operator
+							.load_events_since(&ceramic, stream_id, stream.tip)
+							.await
+						{
+							Ok(events) => commits.extend(events),
+							Err(err) => tracing::debug!(
+								stream_id = stream_id.to_string(),
+								?err,
+								"failed to load events after stored tip"
+							),
+						};
+						match commits.iter().position(|ele| ele.cid == prev) {
+							Some(idx) => commits.truncate(idx + 1),
+							// loaders unable to load the latest events, load the chain up to prev
+							None => {
+								commits = match self
+									.operator
+									.load_events(&ceramic, stream_id, Some(prev))
+									.await
+								{
+									Ok(events) if events.iter().any(|ele| ele.cid == prev) => {
+										events
+									}
+									Ok(_) => {
+										return Err(DataverseError::EventNotFound {
+											stream_id: stream_id.clone(),
+											cid: prev,
+										}
+										.into())
+									}
+									Err(err) => {
+										return Err(err.context(DataverseError::EventNotFound {
+											stream_id: stream_id.clone(),
+											cid: prev,
+										}))
+									}
+								}
+							}
+						}
 					}
 				}
 				commits.push(event.clone());
+				verify_chain(&commits)?;
 				let state = stream.state(commits).await?;
 
 				let model = state.must_model()?;
 				let opts = vec![
-					VerifyOption::ResourceModelsContain(model.clone()),
+					VerifyOption::ResourceModelsContain(model),
 					VerifyOption::ExpirationTimeBefore(Utc::now()),
 				];
-				event.verify_signature(opts)?;
+				event
+					.verify_signature(opts)
+					.map_err(|err| DataverseError::SignatureVerification(err.to_string()))?;
 
-				stream = Stream {
-					model: Some(model),
-					account: state.controllers().first().map(Clone::clone),
-					tip: event.cid,
-					content: state.content.clone(),
-					..stream
+				self.commit_events(
+					&ceramic,
+					stream_id,
+					stream,
+					state,
+					std::slice::from_ref(event),
+				)
+				.await
+			}
+			EventValue::Anchor(anchor) => {
+				if anchor.id != stream_id.cid {
+					anyhow::bail!("anchor {} is not of stream {}", event.cid, stream_id);
+				}
+				anchor.verify_proof()?;
+				let stream = match self.stream_store.load_stream(stream_id).await? {
+					Some(stream) => stream,
+					None => return Err(DataverseError::StreamNotFound(stream_id.clone()).into()),
 				};
+				let mut commits = self
+					.operator
+					.load_events(&ceramic, stream_id, Some(stream.tip))
+					.await?;
+				if commits.iter().any(|ele| ele.cid == event.cid) {
+					return stream.state(commits).await;
+				}
+				if commits.iter().all(|ele| ele.cid != anchor.prev) {
+					return Err(DataverseError::EventNotFound {
+						stream_id: stream_id.clone(),
+						cid: anchor.prev,
+					}
+					.into());
+				}
+				commits.push(event.clone());
+				verify_chain(&commits)?;
+				let state = stream.state(commits).await?;
 
+				let stream = Stream {
+					tip: event.cid,
+					..stream
+				}
+				.with_content(state.content.clone());
 				self.stream_store.save_stream(&stream).await?;
-				self.operator
-					.upload_event(&ceramic, &stream_id, event.clone())
-					.await?;
+				self.remember_stream_ids(stream_id, std::slice::from_ref(event))
+					.await;
+				if let Some(cache) = &self.cache {
+					cache.populate(stream_id, state.clone()).await;
+				}
 
 				Ok(state)
 			}
-			EventValue::Anchor(_) => {
-				anyhow::bail!("anchor commit not supported");
+		}
+	}
+
+	#[cfg_attr(
+		feature = "opentelemetry",
+		tracing::instrument(
+			skip_all,
+			fields(dapp_id = %dapp_id, stream_id = %stream_id, event_count = events.len())
+		)
+	)]
+	async fn save_events(
+		&self,
+		dapp_id: &uuid::Uuid,
+		stream_id: &StreamId,
+		events: &[Event],
+	) -> Result<StreamState> {
+		let first = events.first().context("no events to save")?;
+		if events
+			.iter()
+			.any(|event| matches!(event.value, EventValue::Anchor(_)))
+		{
+			return Err(DataverseError::AnchorNotSupported.into());
+		}
+		let ceramic = dapp::get_dapp_ceramic(dapp_id).await?;
+		let (stream, mut commits) = self
+			.load_saved_stream(&ceramic, dapp_id, stream_id, first)
+			.await?;
+
+		// events already saved are skipped, the rest must continue from tip
+		let saved = events
+			.iter()
+			.take_while(|event| commits.iter().any(|commit| commit.cid == event.cid))
+			.count();
+		let events = &events[saved..];
+		let tip = commits.last().map(|commit| commit.cid);
+		check_event_order(tip, events)?;
+		if let Some(genesis) = events.first().filter(|_| tip.is_none()) {
+			check_genesis(stream_id, genesis)?;
+		}
+		if events.is_empty() {
+			return stream.state(commits).await;
+		}
+
+		commits.extend(events.iter().cloned());
+		let state = stream.state(commits).await?;
+		let model = state.must_model()?;
+
+		let opts = vec![
+			VerifyOption::ResourceModelsContain(model),
+			VerifyOption::ExpirationTimeBefore(Utc::now()),
+		];
+		let (batch, pool) = (events.to_vec(), self.rayon_pool.clone());
+		let verified = tokio::task::spawn_blocking(move || match pool {
+			Some(pool) => pool.install(|| Event::verify_signatures_batch(&batch, opts)),
+			None => Event::verify_signatures_batch(&batch, opts),
+		})
+		.await?;
+		for result in verified {
+			result.map_err(|err| DataverseError::SignatureVerification(err.to_string()))?;
+		}
+
+		self.commit_events(&ceramic, stream_id, stream, state, events)
+			.await
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use dataverse_ceramic::commit::example;
+	use dataverse_ceramic::event::{anchor, EventsLoader, EventsUploader};
+	use dataverse_core::store::memory::MemoryStreamStore;
+
+	use super::*;
+	use crate::file::access_control::ControllerAccessValidator;
+	use crate::test_helpers::{
+		create_stream, example_state, genesis_state, seed_dapp, test_ceramic, test_client,
+		test_signer, MockStreamFileLoader, INDEX_FILE_MODEL_ID, INDEX_FOLDER_MODEL_ID,
+	};
+
+	#[tokio::test]
+	async fn load_file_metadata_only() -> anyhow::Result<()> {
+		let (stream_id, mut state) = example_state().await?;
+		let index_file = IndexFile {
+			file_name: "post".into(),
+			content_id: "kjzl6kcym7w8y5fhg4cl0xi8npfke3jmaaeeic9dwx64bgunnqa6amortdgdsym".into(),
+			..Default::default()
+		};
+		state.content = serde_json::to_value(&index_file)?;
+		let dapp_id = seed_dapp(&[]).await;
+		let client = |state: StreamState| {
+			let loader = MockStreamFileLoader::builder()
+				.with_stream(stream_id.clone(), state)
+				.build();
+			Client::new(Arc::new(loader), Arc::new(MemoryStreamStore::new()))
+		};
+
+		let metadata = client(state.clone())
+			.load_file_metadata(&dapp_id, &stream_id)
+			.await?;
+		assert_eq!(metadata.file_name, index_file.file_name);
+		assert_eq!(metadata.content_id, index_file.content_id);
+
+		state.content = serde_json::json!({ "title": "not an index file" });
+		assert!(client(state)
+			.load_file_metadata(&dapp_id, &stream_id)
+			.await
+			.is_err());
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn load_index_file_contents_concurrently() -> anyhow::Result<()> {
+		let (content_id, content) = example_state().await?;
+		let loader = MockStreamFileLoader::builder()
+			.with_stream(content_id.clone(), content.clone())
+			.with_load_delay(Duration::from_millis(10))
+			.build();
+		let loader = Arc::new(loader);
+		let client =
+			Client::new(loader.clone(), Arc::new(MemoryStreamStore::new())).with_max_concurrent(3);
+		let ceramic = test_ceramic();
+		let mut files = vec![];
+		for idx in 0..10 {
+			let mut file_state = content.clone();
+			file_state.content =
+				serde_json::json!({ "contentId": content_id.to_string(), "idx": idx });
+			let file = StreamFile::new_with_file(file_state.clone())?;
+			files.push((file, Some((file_state, content_id.clone()))));
+		}
+
+		let files = client
+			.load_index_file_contents(&ceramic, files, true, false)
+			.await?;
+		assert!(loader.max_in_flight() <= 3);
+		let order: Vec<_> = files
+			.iter()
+			.filter_map(|file| file.file.as_ref()?.get("idx").cloned())
+			.collect();
+		let expected: Vec<_> = (0..10).map(|idx| serde_json::json!(idx)).collect();
+		assert_eq!(order, expected);
+		assert!(files.iter().all(|file| file.content.is_some()));
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn load_index_file_contents_from_mock() -> anyhow::Result<()> {
+		let ceramic = test_ceramic();
+		let (content_id, content) = example_state().await?;
+		let missing_id: StreamId =
+			"kjzl6kcym7w8y5fhg4cl0xi8npfke3jmaaeeic9dwx64bgunnqa6amortdgdsym".parse()?;
+		let index_file = |content_id: &StreamId| {
+			let mut file_state = content.clone();
+			file_state.content = serde_json::to_value(IndexFile {
+				file_name: "post".into(),
+				content_id: content_id.to_string(),
+				..Default::default()
+			})?;
+			anyhow::Ok(file_state)
+		};
+		let loader = MockStreamFileLoader::builder()
+			.with_stream(content_id.clone(), content.clone())
+			.with_index_file(content_id.to_string(), index_file(&content_id)?)
+			.build();
+		let client = Client::new(Arc::new(loader), Arc::new(MemoryStreamStore::new()));
+
+		let model_id = content.must_model()?;
+		let (_, found) = client
+			.operator
+			.load_index_file_by_content_id(&ceramic, &model_id, &content_id.to_string())
+			.await?;
+		assert_eq!(found.file_name, "post");
+
+		let files = |ids: &[&StreamId]| -> anyhow::Result<Vec<_>> {
+			ids.iter()
+				.map(|id| {
+					let file_state = index_file(id)?;
+					let file = StreamFile::new_with_file(file_state.clone())?;
+					Ok((file, Some((file_state, (*id).clone()))))
+				})
+				.collect()
+		};
+		let loaded = client
+			.load_index_file_contents(&ceramic, files(&[&content_id, &missing_id])?, false, false)
+			.await?;
+		assert!(loaded[0].content.is_some());
+		assert_eq!(loaded[1].verified_status, Status::BrokenContent);
+		assert!(loaded[1].content.is_none());
+		assert!(client
+			.load_index_file_contents(&ceramic, files(&[&missing_id])?, true, false)
+			.await
+			.is_err());
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn load_files_when_index_file_lookup_fails() -> anyhow::Result<()> {
+		let (content_id, content) = example_state().await?;
+		let model_id = content.must_model()?;
+		let index_file_model_id: StreamId = INDEX_FILE_MODEL_ID.parse()?;
+		seed_dapp(&[("post", &model_id), ("indexFile", &index_file_model_id)]).await;
+
+		let loader = MockStreamFileLoader::builder()
+			.with_stream(content_id, content)
+			.with_failing_index_lookups()
+			.build();
+		let client = Client::new(Arc::new(loader), Arc::new(MemoryStreamStore::new()));
+
+		let (files, _) = client.load_files(None, &model_id, vec![]).await?;
+		assert_eq!(files.len(), 1);
+		assert_eq!(files[0].verified_status, Status::NakedStream);
+		assert!(client
+			.load_files(None, &model_id, vec![LoadFilesOption::StrictMode])
+			.await
+			.is_err());
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn load_files_with_index_file_of_account() -> anyhow::Result<()> {
+		let owner = "did:pkh:eip155:1:0x1111111111111111111111111111111111111111";
+		let other = "did:pkh:eip155:1:0x2222222222222222222222222222222222222222";
+		let (content_id, mut content) = example_state().await?;
+		content.metadata["controllers"] = serde_json::json!([owner]);
+		let model_id = content.must_model()?;
+		let index_file_model_id: StreamId = INDEX_FILE_MODEL_ID.parse()?;
+		let mut file_state = content.clone();
+		file_state.content = serde_json::to_value(IndexFile {
+			file_name: "post".into(),
+			content_id: content_id.to_string(),
+			..Default::default()
+		})?;
+		file_state.metadata["model"] = index_file_model_id.to_string().into();
+		file_state.metadata["controllers"] = serde_json::json!([other]);
+		seed_dapp(&[("post", &model_id), ("indexFile", &index_file_model_id)]).await;
+
+		let loader = MockStreamFileLoader::builder()
+			.with_stream(content_id.clone(), content)
+			.with_index_file(content_id.to_string(), file_state)
+			.build();
+		let client = Client::new(Arc::new(loader), Arc::new(MemoryStreamStore::new()));
+
+		// index file of another account is not the owner's file
+		let (files, _) = client
+			.load_files(Some(owner.to_string()), &model_id, vec![])
+			.await?;
+		assert_eq!(files.len(), 1);
+		assert_eq!(files[0].verified_status, Status::NakedStream);
+		assert_eq!(files[0].file_id, None);
+
+		let (files, _) = client.load_files(None, &model_id, vec![]).await?;
+		assert_eq!(files.len(), 1);
+		assert_eq!(files[0].file_id, Some(content_id));
+		assert!(files[0].file.is_some());
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn load_file_checks_access_of_index_file() -> anyhow::Result<()> {
+		let owner = "did:pkh:eip155:1:0x1111111111111111111111111111111111111111";
+		let other = "did:pkh:eip155:1:0x2222222222222222222222222222222222222222";
+		let (content_id, mut content) = example_state().await?;
+		content.metadata["controllers"] = serde_json::json!([owner]);
+		let model_id = content.must_model()?;
+		let index_file_model_id: StreamId = INDEX_FILE_MODEL_ID.parse()?;
+		let dapp_id = seed_dapp(&[("post", &model_id), ("indexFile", &index_file_model_id)]).await;
+
+		let index_file = |access_control: &[u8]| {
+			let mut file_state = content.clone();
+			file_state.log[0].cid =
+				"bafyreih3skzznsyro5i4bsfsyquhcewyqmsra6cx47nj2eb6aerm2nihuu".into();
+			file_state.metadata["model"] = index_file_model_id.to_string().into();
+			file_state.content = serde_json::to_value(IndexFile {
+				file_name: "post".into(),
+				content_id: content_id.to_string(),
+				access_control: Some(Base64String::from(access_control.to_vec())),
+				..Default::default()
+			})?;
+			anyhow::Ok(file_state)
+		};
+		let make_client = |file_state: StreamState, with_content: bool| {
+			let mut loader = MockStreamFileLoader::builder()
+				.with_stream(file_state.stream_id()?, file_state.clone())
+				.with_index_file(content_id.to_string(), file_state);
+			if with_content {
+				loader = loader.with_stream(content_id.clone(), content.clone());
 			}
+			let client = Client::new(Arc::new(loader.build()), Arc::new(MemoryStreamStore::new()))
+				.with_access_validator(Arc::new(ControllerAccessValidator));
+			anyhow::Ok(client)
+		};
+		let encrypted = serde_json::to_vec(&serde_json::json!({
+			"encryptionProvider": { "protocol": "Lit", "encryptedSymmetricKey": "key" }
+		}))?;
+		let file_state = index_file(&encrypted)?;
+		let file_id = file_state.stream_id()?;
+
+		// content streams are checked against their index file
+		let client = make_client(file_state.clone(), true)?;
+		let file = client
+			.load_file_as(&dapp_id, &content_id, Some(other))
+			.await?;
+		assert_eq!(file.verified_status, Status::AccessDenied);
+		assert!(file.content.is_none());
+		let file = client
+			.load_file_as(&dapp_id, &content_id, Some(owner))
+			.await?;
+		assert_eq!(file.verified_status, Status::None);
+		assert!(file.content.is_some());
+
+		let caller = |caller: &str| vec![LoadFilesOption::Caller(caller.to_string())];
+		let (files, _) = client.load_files(None, &model_id, caller(other)).await?;
+		assert_eq!(files[0].verified_status, Status::AccessDenied);
+		assert!(files[0].content.is_none());
+		let (files, _) = client.load_files(None, &model_id, caller(owner)).await?;
+		assert!(files[0].content.is_some());
+
+		// content of a denied index file is not loaded
+		let client = make_client(file_state, false)?;
+		let file = client.load_file_as(&dapp_id, &file_id, Some(other)).await?;
+		assert_eq!(file.verified_status, Status::AccessDenied);
+		assert_eq!(file.content_id, Some(content_id.to_string()));
+
+		let client = make_client(index_file(b"not json")?, true)?;
+		let file = client
+			.load_file_as(&dapp_id, &content_id, Some(owner))
+			.await?;
+		assert_eq!(file.verified_status, Status::BrokenContent);
+		assert!(file.content.is_none());
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn load_files_returns_cursor() -> anyhow::Result<()> {
+		let (stream_id, state) = example_state().await?;
+		let model_id: StreamId = INDEX_FILE_MODEL_ID.parse()?;
+		seed_dapp(&[("indexFile", &model_id)]).await;
+		let mut loader = MockStreamFileLoader::builder();
+		for cid in [
+			"bafyreih3skzznsyro5i4bsfsyquhcewyqmsra6cx47nj2eb6aerm2nihuu",
+			"bafyreidnbzsaplrdpjx3schac4fjhwqjzv3kbvdswi52npq3kpdzpbv5qa",
+		] {
+			let mut file_state = state.clone();
+			file_state.log[0].cid = cid.into();
+			file_state.metadata["model"] = model_id.to_string().into();
+			file_state.content = serde_json::to_value(IndexFile {
+				file_name: "post".into(),
+				content_id: "not a stream".into(),
+				..Default::default()
+			})?;
+			loader = loader.with_stream(file_state.stream_id()?, file_state);
+		}
+		let client = Client::new(Arc::new(loader.build()), Arc::new(MemoryStreamStore::new()));
+
+		let page =
+			|after: Option<String>| vec![LoadFilesOption::Page(PageOptions { after, limit: 1 })];
+		let (first, cursor) = client.load_files(None, &model_id, page(None)).await?;
+		assert_eq!(first.len(), 1);
+		assert!(cursor.is_some());
+		let (second, cursor) = client.load_files(None, &model_id, page(cursor)).await?;
+		assert_eq!(second.len(), 1);
+		assert_eq!(cursor, None);
+		assert_ne!(first[0].file_id, second[0].file_id);
+
+		let (files, cursor) = client.load_files(None, &model_id, vec![]).await?;
+		assert_eq!(files.len(), 2);
+		assert_eq!(cursor, None);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn load_files_stream_goes_on_after_error() -> anyhow::Result<()> {
+		let (stream_id, state) = example_state().await?;
+		let model_id: StreamId = INDEX_FILE_MODEL_ID.parse()?;
+		seed_dapp(&[("indexFile", &model_id)]).await;
+		let index_file = |cid: &str, content: serde_json::Value| {
+			let mut file_state = state.clone();
+			file_state.log[0].cid = cid.into();
+			file_state.metadata["model"] = model_id.to_string().into();
+			file_state.content = content;
+			anyhow::Ok((file_state.stream_id()?, file_state))
+		};
+		let (broken_id, broken) = index_file(
+			"bafyreih3skzznsyro5i4bsfsyquhcewyqmsra6cx47nj2eb6aerm2nihuu",
+			serde_json::json!({ "fileName": 1 }),
+		)?;
+		let (file_id, file) = index_file(
+			"bafyreidnbzsaplrdpjx3schac4fjhwqjzv3kbvdswi52npq3kpdzpbv5qa",
+			serde_json::to_value(IndexFile {
+				file_name: "post".into(),
+				content_id: "not a stream".into(),
+				..Default::default()
+			})?,
+		)?;
+		let loader = MockStreamFileLoader::builder()
+			.with_stream(broken_id, broken)
+			.with_stream(file_id.clone(), file)
+			.build();
+		let client = Client::new(Arc::new(loader), Arc::new(MemoryStreamStore::new()));
+
+		let options = vec![
+			LoadFilesOption::StrictMode,
+			LoadFilesOption::Page(PageOptions {
+				after: None,
+				limit: 1,
+			}),
+		];
+		let results: Vec<_> = client
+			.load_files_stream(None, &model_id, options)
+			.collect()
+			.await;
+		assert_eq!(results.len(), 2);
+		assert_eq!(results.iter().filter(|result| result.is_err()).count(), 1);
+		let file_ids: Vec<_> = results
+			.into_iter()
+			.filter_map(|result| result.ok()?.file_id)
+			.collect();
+		assert_eq!(file_ids, vec![file_id]);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn move_file_into_folder() -> anyhow::Result<()> {
+		let index_file_model_id: StreamId = INDEX_FILE_MODEL_ID.parse()?;
+		let index_folder_model_id: StreamId = INDEX_FOLDER_MODEL_ID.parse()?;
+		let dapp_id = seed_dapp(&[
+			("indexFile", &index_file_model_id),
+			("indexFolder", &index_folder_model_id),
+		])
+		.await;
+		let (content_id, content) = example_state().await?;
+		let loader = MockStreamFileLoader::builder()
+			.with_stream(content_id.clone(), content)
+			.build();
+		let client = Client::new(Arc::new(loader), Arc::new(MemoryStreamStore::new()));
+
+		let signer = test_signer().await?;
+		let index_file = IndexFile {
+			file_name: "post".into(),
+			content_id: content_id.to_string(),
+			..Default::default()
+		};
+		let file_id = create_stream(
+			&client,
+			&dapp_id,
+			&signer,
+			&index_file_model_id,
+			serde_json::to_value(&index_file)?,
+		)
+		.await?;
+		let folder_id = create_stream(
+			&client,
+			&dapp_id,
+			&signer,
+			&index_folder_model_id,
+			serde_json::json!({ "folderName": "docs" }),
+		)
+		.await?;
+
+		assert!(client
+			.load_files_in_folder(&dapp_id, &folder_id)
+			.await?
+			.is_empty());
+		let moved = client
+			.move_file(&dapp_id, &file_id, &folder_id, &signer)
+			.await?;
+		assert_eq!(moved.folder_id(), Some(&folder_id));
+		assert!(moved.content.is_some());
+		let files = client.load_files_in_folder(&dapp_id, &folder_id).await?;
+		assert_eq!(files.len(), 1);
+		assert_eq!(files[0].file_id.as_ref(), Some(&file_id));
+		assert!(files[0].content.is_some());
+		assert!(client
+			.load_files_in_folder(&dapp_id, &file_id)
+			.await
+			.is_err());
+		let events = client
+			.operator
+			.load_events(&test_ceramic(), &file_id, None)
+			.await?;
+		assert_eq!(events.len(), 2);
+
+		// target must be an index folder and source an index file
+		assert!(client
+			.move_file(&dapp_id, &file_id, &file_id, &signer)
+			.await
+			.is_err());
+		assert!(client
+			.move_file(&dapp_id, &folder_id, &folder_id, &signer)
+			.await
+			.is_err());
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn detect_folder_cycle() -> anyhow::Result<()> {
+		let dapp_id = seed_dapp(&[]).await;
+		let folder = |cid: &str, parent: Option<&StreamId>| {
+			let mut state = genesis_state(cid);
+			state.content = serde_json::json!({
+				"folderName": cid,
+				"folderType": 0,
+				"createdAt": "2023-09-01T07:03:23Z",
+				"updatedAt": "2023-09-01T07:03:23Z",
+				"fsVersion": "0.11",
+				"contentFolderIds": [],
+				"parentFolderId": parent.map(StreamId::to_string),
+			});
+			anyhow::Ok((state.stream_id()?, state))
+		};
+		let (a, a_state) = folder(
+			"bafyreih3skzznsyro5i4bsfsyquhcewyqmsra6cx47nj2eb6aerm2nihuu",
+			None,
+		)?;
+		let (b, b_state) = folder(
+			"bafyreidnbzsaplrdpjx3schac4fjhwqjzv3kbvdswi52npq3kpdzpbv5qa",
+			Some(&a),
+		)?;
+		// c and d are already parents of each other
+		let c = genesis_state("bafyreid43i4yornrup5nuiiu5bavu3k5se4z7wrokwd2oznvanp27eo7xe")
+			.stream_id()?;
+		let (d, d_state) = folder(
+			"bagcqcerayswtqarydm2rgeh37yir45ccvfkj3qhwhfmu4vdjjrtny5l4rpia",
+			Some(&c),
+		)?;
+		let (_, c_state) = folder(
+			"bafyreid43i4yornrup5nuiiu5bavu3k5se4z7wrokwd2oznvanp27eo7xe",
+			Some(&d),
+		)?;
+		let loader = MockStreamFileLoader::builder()
+			.with_stream(a.clone(), a_state)
+			.with_stream(b.clone(), b_state)
+			.with_stream(c.clone(), c_state)
+			.with_stream(d.clone(), d_state)
+			.build();
+		let client = Client::new(Arc::new(loader), Arc::new(MemoryStreamStore::new()));
+
+		// a -> b -> a
+		assert!(client.detect_folder_cycle(&dapp_id, &a, &b).await?);
+		assert!(client.detect_folder_cycle(&dapp_id, &a, &a).await?);
+		assert!(!client.detect_folder_cycle(&dapp_id, &b, &a).await?);
+		let other: StreamId =
+			"kjzl6kcym7w8y5fhg4cl0xi8npfke3jmaaeeic9dwx64bgunnqa6amortdgdsym".parse()?;
+		assert!(client.detect_folder_cycle(&dapp_id, &other, &c).await?);
+		Ok(())
+	}
+
+	#[derive(Default)]
+	struct MemoryPinner {
+		pinned: Mutex<Vec<Cid>>,
+	}
+
+	#[async_trait::async_trait]
+	impl CidPinner for MemoryPinner {
+		async fn pin_cid(&self, cid: &Cid) -> anyhow::Result<()> {
+			self.pinned.lock().await.push(*cid);
+			Ok(())
+		}
+
+		async fn unpin_cid(&self, cid: &Cid) -> anyhow::Result<()> {
+			self.pinned.lock().await.retain(|pinned| pinned != cid);
+			Ok(())
+		}
+	}
+
+	#[tokio::test]
+	async fn pin_folder_reports_missing_streams() -> anyhow::Result<()> {
+		let index_file_model_id: StreamId = INDEX_FILE_MODEL_ID.parse()?;
+		let index_folder_model_id: StreamId = INDEX_FOLDER_MODEL_ID.parse()?;
+		let dapp_id = seed_dapp(&[
+			("indexFile", &index_file_model_id),
+			("indexFolder", &index_folder_model_id),
+		])
+		.await;
+		let pinner = Arc::new(MemoryPinner::default());
+		let client = Client::new(
+			Arc::new(MockStreamFileLoader::builder().build()),
+			Arc::new(MemoryStreamStore::new()),
+		)
+		.with_pinner(pinner.clone());
+		let signer = test_signer().await?;
+		// neither stream is known to ceramic
+		let missing_folder_id = example::genesis().stream_id()?;
+		let missing_file_id =
+			genesis_state("bafyreid43i4yornrup5nuiiu5bavu3k5se4z7wrokwd2oznvanp27eo7xe")
+				.stream_id()?;
+
+		let index_file = IndexFile {
+			file_name: "post".into(),
+			content_id: "bafyreih3skzznsyro5i4bsfsyquhcewyqmsra6cx47nj2eb6aerm2nihuu".into(),
+			..Default::default()
+		};
+		let file_id = create_stream(
+			&client,
+			&dapp_id,
+			&signer,
+			&index_file_model_id,
+			serde_json::to_value(&index_file)?,
+		)
+		.await?;
+		let content_folder_id = create_stream(
+			&client,
+			&dapp_id,
+			&signer,
+			&index_folder_model_id,
+			serde_json::json!({
+				"folderName": "mirror",
+				"mirrorFileIds": [file_id.to_string(), missing_file_id.to_string()],
+			}),
+		)
+		.await?;
+		let folder_id = create_stream(
+			&client,
+			&dapp_id,
+			&signer,
+			&index_folder_model_id,
+			serde_json::json!({
+				"folderName": "docs",
+				"folderType": 0,
+				"createdAt": "2023-09-01T07:03:23Z",
+				"updatedAt": "2023-09-01T07:03:23Z",
+				"fsVersion": "0.11",
+				"contentFolderIds": [content_folder_id.to_string(), missing_folder_id.to_string()],
+			}),
+		)
+		.await?;
+
+		let report = client
+			.pin_folder_recursively(&dapp_id, &folder_id, 2)
+			.await?;
+		assert_eq!(report.pinned, 3);
+		assert_eq!(report.failed, 2);
+		let mut failed: Vec<_> = report.errors.iter().map(|(id, _)| id.to_string()).collect();
+		failed.sort();
+		let mut expected = vec![missing_folder_id.to_string(), missing_file_id.to_string()];
+		expected.sort();
+		assert_eq!(failed, expected);
+		assert_eq!(pinner.pinned.lock().await.len(), 3);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn copy_file_into_folder() -> anyhow::Result<()> {
+		let index_file_model_id: StreamId = INDEX_FILE_MODEL_ID.parse()?;
+		let index_folder_model_id: StreamId = INDEX_FOLDER_MODEL_ID.parse()?;
+		let dapp_id = seed_dapp(&[
+			("indexFile", &index_file_model_id),
+			("indexFolder", &index_folder_model_id),
+		])
+		.await;
+		let (content_id, content) = example_state().await?;
+		let loader = MockStreamFileLoader::builder()
+			.with_stream(content_id.clone(), content.clone())
+			.build();
+		let client = Client::new(Arc::new(loader), Arc::new(MemoryStreamStore::new()));
+		let signer = test_signer().await?;
+		let index_file = IndexFile {
+			file_name: "post".into(),
+			content_id: content_id.to_string(),
+			..Default::default()
+		};
+		let file_id = create_stream(
+			&client,
+			&dapp_id,
+			&signer,
+			&index_file_model_id,
+			serde_json::to_value(&index_file)?,
+		)
+		.await?;
+		let folder_id = create_stream(
+			&client,
+			&dapp_id,
+			&signer,
+			&index_folder_model_id,
+			serde_json::json!({ "folderName": "docs" }),
+		)
+		.await?;
+
+		let copy = client
+			.copy_file(&dapp_id, &file_id, &folder_id, Some("copy".into()), &signer)
+			.await?;
+		let copy_file_id = copy.file_id.clone().context("copy without index file")?;
+		assert_ne!(copy_file_id, file_id);
+		assert_ne!(copy.content_id, Some(content_id.to_string()));
+		assert_eq!(copy.folder_id(), Some(&folder_id));
+		assert_eq!(
+			copy.file().and_then(|file| file.get("fileName")),
+			Some(&serde_json::json!("copy"))
+		);
+		assert_eq!(
+			copy.content().and_then(FileContent::as_json),
+			Some(&content.content)
+		);
+		let copied = client.load_file_metadata(&dapp_id, &copy_file_id).await?;
+		assert_eq!(
+			copied.content_id,
+			copy.content_id.clone().unwrap_or_default()
+		);
+
+		assert!(client
+			.copy_file(&dapp_id, &file_id, &file_id, None, &signer)
+			.await
+			.is_err());
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn load_file_with_preloaded_context() -> anyhow::Result<()> {
+		let index_file_model_id: StreamId = INDEX_FILE_MODEL_ID.parse()?;
+		let action_file_model_id: StreamId =
+			"kjzl6hvfrbw6cb2cjc4cprolj8vnykf41834r9chlay1582sjxleag1b0juy5kl".parse()?;
+		let content_folder_model_id: StreamId =
+			"kjzl6hvfrbw6c5m61z7cvgk4xwzx0aelqj4f9hmctn8ha64qtasd8e2779dswd5".parse()?;
+		let dapp_id = seed_dapp(&[
+			("indexFile", &index_file_model_id),
+			("actionFile", &action_file_model_id),
+			("contentFolder", &content_folder_model_id),
+		])
+		.await;
+		let (content_id, content) = example_state().await?;
+		let loader = MockStreamFileLoader::builder()
+			.with_stream(content_id.clone(), content.clone())
+			.build();
+		let client = Client::new(Arc::new(loader), Arc::new(MemoryStreamStore::new()));
+		let signer = test_signer().await?;
+		let index_file = IndexFile {
+			file_name: "post".into(),
+			content_id: content_id.to_string(),
+			..Default::default()
+		};
+		let file_id = create_stream(
+			&client,
+			&dapp_id,
+			&signer,
+			&index_file_model_id,
+			serde_json::to_value(&index_file)?,
+		)
+		.await?;
+		let action_id = create_stream(
+			&client,
+			&dapp_id,
+			&signer,
+			&action_file_model_id,
+			serde_json::json!({ "relationId": file_id.to_string() }),
+		)
+		.await?;
+		let folder_id = create_stream(
+			&client,
+			&dapp_id,
+			&signer,
+			&content_folder_model_id,
+			serde_json::json!({ "folderName": "mirror", "mirrorFileIds": [file_id.to_string()] }),
+		)
+		.await?;
+		// action file of another stream is not related
+		create_stream(
+			&client,
+			&dapp_id,
+			&signer,
+			&action_file_model_id,
+			serde_json::json!({ "relationId": folder_id.to_string() }),
+		)
+		.await?;
+
+		let ctx = client
+			.operator
+			.preload_related_streams(&test_ceramic(), &file_id)
+			.await?;
+		assert_eq!(ctx.index_file.stream_id()?, file_id);
+		assert_eq!(
+			ctx.content.as_ref().map(|state| &state.content),
+			Some(&content.content)
+		);
+		let ids = |states: &[StreamState]| -> anyhow::Result<Vec<StreamId>> {
+			states.iter().map(StreamState::stream_id).collect()
+		};
+		assert_eq!(ids(&ctx.action_files)?, vec![action_id]);
+		assert_eq!(ids(&ctx.content_folders)?, vec![folder_id.clone()]);
+
+		let file = client
+			.load_file_with_context(&dapp_id, &file_id, Some(ctx.clone()))
+			.await?;
+		assert_eq!(file, client.load_file(&dapp_id, &file_id).await?);
+		assert_eq!(
+			file.content().and_then(FileContent::as_json),
+			Some(&content.content)
+		);
+		// context of another stream is ignored
+		let folder = client
+			.load_file_with_context(&dapp_id, &folder_id, Some(ctx))
+			.await?;
+		assert_eq!(folder.content_id, Some(folder_id.to_string()));
+		Ok(())
+	}
+
+	#[test]
+	fn estimated_anchor_cost_of_events() -> anyhow::Result<()> {
+		let event: Event = example::genesis().genesis.try_into()?;
+		let events = vec![event.clone(), event.clone()];
+		assert_eq!(test_client().estimated_anchor_cost(&events), None);
+
+		let client = test_client().with_fee_schedule(FeeSchedule {
+			per_byte: 0.5,
+			flat_fee: 2.0,
+			currency: "USD".into(),
+		});
+		let cost = 2.0 + event.payload_size() as f64 * 0.5;
+		assert_eq!(client.estimated_anchor_cost(&events), Some(cost * 2.0));
+		assert!(cost > ANCHOR_COST_WARN_THRESHOLD);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn force_anchor_times_out() -> anyhow::Result<()> {
+		let (stream_id, state) = example_state().await?;
+		let dapp_id = seed_dapp(&[]).await;
+		let loader = MockStreamFileLoader::builder()
+			.with_stream(stream_id.clone(), state)
+			.build();
+		let client = Client::new(Arc::new(loader), Arc::new(MemoryStreamStore::new()));
+
+		let timeout = Duration::from_millis(50);
+		let err = client
+			.force_anchor(&dapp_id, &stream_id, Duration::from_millis(10), timeout)
+			.await
+			.unwrap_err();
+		assert_eq!(
+			err.downcast_ref::<DataverseError>(),
+			Some(&DataverseError::AnchorTimeout { stream_id, timeout })
+		);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn save_anchor_event() -> anyhow::Result<()> {
+		let client = test_client();
+		let dapp_id = seed_dapp(&[]).await;
+		let genesis = example::genesis();
+		let stream_id = genesis.stream_id()?;
+		let model_id = genesis.model_id()?;
+		let commit: Event = genesis.genesis.try_into()?;
+		client
+			.operator
+			.upload_event(&test_ceramic(), &stream_id, commit.clone())
+			.await?;
+		let stream = Stream::new(
+			&dapp_id,
+			stream_id.r#type.int_value(),
+			&commit,
+			Some(model_id),
+		)?;
+		client.stream_store.save_stream(&stream).await?;
+		let tip = || async {
+			let stream = client.stream_store.load_stream(&stream_id).await?;
+			anyhow::Ok(stream.map(|stream| stream.tip))
+		};
+
+		// anchor of another stream
+		let other = anchor::example::anchor(Cid::default(), commit.cid)?;
+		assert!(client
+			.save_event(&dapp_id, &stream_id, &other)
+			.await
+			.is_err());
+
+		let mut unproven = anchor::example::anchor(commit.cid, commit.cid)?;
+		if let EventValue::Anchor(anchor) = &mut unproven.value {
+			anchor.proof_block = None;
+		}
+		assert!(client
+			.save_event(&dapp_id, &stream_id, &unproven)
+			.await
+			.is_err());
+		assert_eq!(tip().await?, Some(commit.cid));
+
+		let anchor = anchor::example::anchor(commit.cid, commit.cid)?;
+		let state = client.save_event(&dapp_id, &stream_id, &anchor).await?;
+		assert_eq!(state.log.len(), 2);
+		assert_eq!(
+			state.anchor_proof.map(|proof| proof.chain_id),
+			Some("eip155:1".into())
+		);
+		assert_eq!(tip().await?, Some(anchor.cid));
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn reject_genesis_of_another_stream() -> anyhow::Result<()> {
+		let client = test_client();
+		let dapp_id = seed_dapp(&[]).await;
+		let genesis = example::genesis();
+		let genesis_stream_id = genesis.stream_id()?;
+		let commit: Event = genesis.genesis.try_into()?;
+		let stream_id = StreamId {
+			r#type: genesis_stream_id.r#type,
+			cid: "bafyreih3skzznsyro5i4bsfsyquhcewyqmsra6cx47nj2eb6aerm2nihuu".parse()?,
+		};
+
+		let err = client
+			.save_event(&dapp_id, &stream_id, &commit)
+			.await
+			.unwrap_err();
+		assert!(matches!(
+			err.downcast_ref::<DataverseError>(),
+			Some(DataverseError::GenesisMismatch { .. })
+		));
+		let err = client
+			.save_events(&dapp_id, &stream_id, &[commit])
+			.await
+			.unwrap_err();
+		assert!(matches!(
+			err.downcast_ref::<DataverseError>(),
+			Some(DataverseError::GenesisMismatch { .. })
+		));
+
+		// neither saved nor published
+		for id in [&stream_id, &genesis_stream_id] {
+			assert!(client.stream_store.load_stream(id).await?.is_none());
+			assert!(client
+				.operator
+				.load_events(&test_ceramic(), id, None)
+				.await
+				.is_err());
+		}
+		Ok(())
+	}
+
+	/// store failing every call
+	struct FailingStore;
+
+	#[async_trait::async_trait]
+	impl StreamStore for FailingStore {
+		async fn save_stream(&self, stream: &Stream) -> anyhow::Result<()> {
+			anyhow::bail!("failed to save stream {}", stream.stream_id()?)
+		}
+
+		async fn load_stream(&self, stream_id: &StreamId) -> anyhow::Result<Option<Stream>> {
+			anyhow::bail!("failed to load stream {}", stream_id)
+		}
+
+		async fn list_all_streams(&self) -> anyhow::Result<Vec<Stream>> {
+			anyhow::bail!("failed to list streams")
+		}
+	}
+
+	#[tokio::test]
+	async fn save_events_uploads_before_store() -> anyhow::Result<()> {
+		let dapp_id = seed_dapp(&[]).await;
+		let genesis = example::genesis();
+		let stream_id = genesis.stream_id()?;
+		let mut commit: Event = genesis.genesis.try_into()?;
+		// without cacao the signature check doesn't depend on capability expiration
+		if let EventValue::Signed(signed) = &mut commit.value {
+			signed.cacao_block = None;
+		}
+		let commits = [commit];
+
+		// failed upload leaves the store unchanged
+		let client = Client::new(
+			Arc::new(
+				MockStreamFileLoader::builder()
+					.with_failing_uploads()
+					.build(),
+			),
+			Arc::new(MemoryStreamStore::new()),
+		);
+		assert!(client
+			.save_events(&dapp_id, &stream_id, &commits)
+			.await
+			.is_err());
+		assert!(client.stream_store.load_stream(&stream_id).await?.is_none());
+
+		// store errors are returned before anything is published
+		let client = Client::new(
+			Arc::new(MockStreamFileLoader::default()),
+			Arc::new(FailingStore),
+		);
+		assert!(client
+			.save_events(&dapp_id, &stream_id, &commits)
+			.await
+			.is_err());
+		assert!(client
+			.operator
+			.load_events(&test_ceramic(), &stream_id, None)
+			.await
+			.is_err());
+
+		let client = test_client();
+		let state = client.save_events(&dapp_id, &stream_id, &commits).await?;
+		assert_eq!(state.stream_id()?, stream_id);
+		let stored = client.stream_store.load_stream(&stream_id).await?;
+		assert_eq!(stored.map(|stream| stream.tip), Some(commits[0].cid));
+		Ok(())
+	}
+
+	#[test]
+	fn extract_content_links() -> anyhow::Result<()> {
+		let stream_id = "kjzl6kcym7w8y50i8sgcbbr4ev2x55bzv88srrg1dmlt9tv8g0x7nnj9soli1t5";
+		let cid = "bafyreid43i4yornrup5nuiiu5bavu3k5se4z7wrokwd2oznvanp27eo7xe";
+		let content = serde_json::json!({
+			"title": "hello",
+			"contentId": stream_id,
+			"assets": [{"/": cid}, {"link": stream_id}],
+			"count": 2
+		});
+		let (stream_ids, cids) = content_links(&content);
+		assert_eq!(stream_ids, vec![stream_id.parse::<StreamId>()?]);
+		assert_eq!(cids, vec![cid.parse::<Cid>()?]);
+		Ok(())
+	}
+
+	#[test]
+	fn deleted_file_status() {
+		let mut file = StreamFile {
+			file: Some(serde_json::json!({"deleted": true, "deletedAt": "2023-09-01T07:03:23Z"})),
+			..Default::default()
+		};
+		check_deleted(&mut file);
+		assert_eq!(file.verified_status, Status::Deleted);
+		assert!(LoadFilesOption::include_deleted(&[
+			LoadFilesOption::IncludeDeleted
+		]));
+		assert!(!LoadFilesOption::include_deleted(&[]));
+
+		let mut file = StreamFile {
+			file: Some(serde_json::json!({"deleted": false})),
+			..Default::default()
+		};
+		check_deleted(&mut file);
+		assert_eq!(file.verified_status, Status::None);
+
+		let mut file = StreamFile {
+			file: Some(serde_json::json!({"deleted": true})),
+			..Default::default()
+		};
+		file.write_status(Status::BrokenContent, "content not found".into());
+		check_deleted(&mut file);
+		assert_eq!(file.verified_status, Status::BrokenContent);
+	}
+
+	#[tokio::test]
+	async fn resolve_stream_id_from_cid() -> anyhow::Result<()> {
+		let index_file_model_id: StreamId = INDEX_FILE_MODEL_ID.parse()?;
+		let dapp_id = seed_dapp(&[("indexFile", &index_file_model_id)]).await;
+		let store = Arc::new(MemoryStreamStore::new());
+		let loader = Arc::new(MockStreamFileLoader::builder().build());
+		let client = Client::new(loader.clone(), store.clone());
+		let signer = test_signer().await?;
+		let file_id = create_stream(
+			&client,
+			&dapp_id,
+			&signer,
+			&index_file_model_id,
+			serde_json::to_value(IndexFile::default())?,
+		)
+		.await?;
+		let genesis = store
+			.load_stream(&file_id)
+			.await?
+			.context("stream not saved")?
+			.genesis;
+
+		let mut prev = genesis;
+		let mut updates = vec![];
+		for file_name in ["first", "second"] {
+			let update = signer
+				.sign(Payload {
+					data: Some(serde_json::json!([
+						{"op": "add", "path": "/fileName", "value": file_name}
+					])),
+					header: None,
+					prev: Some(prev),
+					id: Some(genesis),
+				})
+				.await?;
+			client.save_event(&dapp_id, &file_id, &update).await?;
+			prev = update.cid;
+			updates.push(update.cid);
 		}
+
+		// cached when saved
+		assert_eq!(client.resolve_stream_id_from_cid(&genesis).await?, file_id);
+		// found in the store index by a client not saved it, events between genesis and
+		// tip included
+		let other = Client::new(loader, store);
+		assert_eq!(other.resolve_stream_id_from_cid(&genesis).await?, file_id);
+		assert_eq!(
+			other.resolve_stream_id_from_cid(&updates[0]).await?,
+			file_id
+		);
+
+		let unknown: Cid = "bafyreih3skzznsyro5i4bsfsyquhcewyqmsra6cx47nj2eb6aerm2nihuu".parse()?;
+		let err = client
+			.resolve_stream_id_from_cid(&unknown)
+			.await
+			.unwrap_err();
+		assert_eq!(
+			err.downcast_ref::<DataverseError>(),
+			Some(&DataverseError::UnknownCid(unknown))
+		);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn file_history_in_event_order() -> anyhow::Result<()> {
+		let index_file_model_id: StreamId = INDEX_FILE_MODEL_ID.parse()?;
+		let dapp_id = seed_dapp(&[("indexFile", &index_file_model_id)]).await;
+		let client = Client::new(
+			Arc::new(MockStreamFileLoader::builder().build()),
+			Arc::new(MemoryStreamStore::new()),
+		);
+		let signer = test_signer().await?;
+		let index_file = IndexFile {
+			file_name: "post".into(),
+			content_id: "bafyreih3skzznsyro5i4bsfsyquhcewyqmsra6cx47nj2eb6aerm2nihuu".into(),
+			..Default::default()
+		};
+		let file_id = create_stream(
+			&client,
+			&dapp_id,
+			&signer,
+			&index_file_model_id,
+			serde_json::to_value(&index_file)?,
+		)
+		.await?;
+		let rename = signer
+			.sign(Payload {
+				data: Some(serde_json::json!([
+					{"op": "replace", "path": "/fileName", "value": "renamed"}
+				])),
+				header: None,
+				prev: Some(file_id.cid),
+				id: Some(file_id.cid),
+			})
+			.await?;
+		client.save_event(&dapp_id, &file_id, &rename).await?;
+
+		let history = client.get_file_history(&dapp_id, &file_id).await?;
+		assert_eq!(history.len(), 2);
+		assert_eq!(history[0].event_cid, file_id.cid);
+		assert_eq!(history[0].event_type, "Genesis");
+		assert_eq!(history[1].event_cid, rename.cid);
+		assert_eq!(history[1].event_type, "Signed");
+		let file_names: Vec<_> = history
+			.iter()
+			.map(|file| {
+				file.state
+					.file()
+					.and_then(|file| file.get("fileName"))
+					.cloned()
+			})
+			.collect();
+		assert_eq!(
+			file_names,
+			vec![
+				Some(serde_json::json!("post")),
+				Some(serde_json::json!("renamed"))
+			]
+		);
+		// events signed without cacao carry no issued time
+		assert!(history.iter().all(|file| file.timestamp.is_none()));
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn cross_dapp_link() -> anyhow::Result<()> {
+		let index_file_model_id: StreamId = INDEX_FILE_MODEL_ID.parse()?;
+		let target_dapp_id = seed_dapp(&[("indexFile", &index_file_model_id)]).await;
+		let (content_id, content) = example_state().await?;
+		let source_dapp_id = uuid::Uuid::from_u128(0x50c);
+		let content_model = dapp::Model {
+			id: content.must_model()?,
+			name: "post".into(),
+			dapp_id: source_dapp_id,
+			encryptable: vec![],
+			version: 0,
+			latest: true,
+			is_deprecated: false,
+		};
+		dapp::cache_dapp(&source_dapp_id, test_ceramic(), vec![content_model]).await;
+		let loader = MockStreamFileLoader::builder()
+			.with_stream(content_id.clone(), content.clone())
+			.build();
+		let client = Client::new(Arc::new(loader), Arc::new(MemoryStreamStore::new()));
+		let signer = test_signer().await?;
+
+		let link = client
+			.cross_dapp_link(
+				&source_dapp_id,
+				&target_dapp_id,
+				&content_id,
+				serde_json::json!({ "fileName": "linked" }),
+				&signer,
+			)
+			.await?;
+		assert_eq!(link.content_id, Some(content_id.to_string()));
+		assert_eq!(
+			link.content().and_then(FileContent::as_json),
+			Some(&content.content)
+		);
+		let file_id = link.file_id.clone().context("link without index file")?;
+		let index_file = client.load_file_metadata(&target_dapp_id, &file_id).await?;
+		assert_eq!(index_file.file_name, "linked");
+		assert_eq!(index_file.content_id, content_id.to_string());
+		let reserved: serde_json::Value =
+			serde_json::from_str(&index_file.reserved.context("missing reserved metadata")?)?;
+		assert_eq!(
+			reserved.get("crossDappOrigin"),
+			Some(&serde_json::json!(source_dapp_id.to_string()))
+		);
+
+		// content does not belong to the source dapp
+		assert!(client
+			.cross_dapp_link(
+				&target_dapp_id,
+				&source_dapp_id,
+				&content_id,
+				serde_json::Value::Null,
+				&signer,
+			)
+			.await
+			.is_err());
+		assert!(client
+			.cross_dapp_link(
+				&source_dapp_id,
+				&target_dapp_id,
+				&content_id,
+				serde_json::json!("linked"),
+				&signer,
+			)
+			.await
+			.is_err());
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn delete_file_hides_it_from_load_files() -> anyhow::Result<()> {
+		let index_file_model_id: StreamId = INDEX_FILE_MODEL_ID.parse()?;
+		let dapp_id = seed_dapp(&[("indexFile", &index_file_model_id)]).await;
+		let (content_id, content) = example_state().await?;
+		let loader = MockStreamFileLoader::builder()
+			.with_stream(content_id.clone(), content)
+			.build();
+		let client = Client::new(Arc::new(loader), Arc::new(MemoryStreamStore::new()));
+		let signer = test_signer().await?;
+		let index_file = IndexFile {
+			file_name: "post".into(),
+			content_id: content_id.to_string(),
+			..Default::default()
+		};
+		let file_id = create_stream(
+			&client,
+			&dapp_id,
+			&signer,
+			&index_file_model_id,
+			serde_json::to_value(&index_file)?,
+		)
+		.await?;
+
+		client.delete_file(&dapp_id, &file_id, &signer).await?;
+		let (files, _) = client
+			.load_files(None, &index_file_model_id, vec![])
+			.await?;
+		assert!(files.is_empty());
+
+		let (files, _) = client
+			.load_files(
+				None,
+				&index_file_model_id,
+				vec![LoadFilesOption::IncludeDeleted],
+			)
+			.await?;
+		assert_eq!(files.len(), 1);
+		assert_eq!(files[0].file_id.as_ref(), Some(&file_id));
+		assert_eq!(files[0].verified_status, Status::Deleted);
+		assert!(client
+			.delete_file(&dapp_id, &file_id, &signer)
+			.await
+			.is_err());
+		Ok(())
+	}
+
+	#[test]
+	fn filter_status() {
+		let broken = vec![Status::BrokenContent, Status::NakedStream];
+		let exclude = vec![LoadFilesOption::StatusFilter(broken.clone())];
+		assert!(LoadFilesOption::status_allowed(&exclude, Status::None));
+		assert!(!LoadFilesOption::status_allowed(
+			&exclude,
+			Status::NakedStream
+		));
+
+		let only = vec![LoadFilesOption::OnlyStatus(broken)];
+		assert!(!LoadFilesOption::status_allowed(&only, Status::None));
+		assert!(LoadFilesOption::status_allowed(
+			&only,
+			Status::BrokenContent
+		));
+
+		assert!(LoadFilesOption::status_allowed(&[], Status::BrokenFolder));
+	}
+
+	#[test]
+	fn sort_files() -> anyhow::Result<()> {
+		let day = |day: u32| format!("2024-01-{:02}T00:00:00Z", day).parse::<DateTime<Utc>>();
+		let mut times = HashMap::new();
+		times.insert("a".to_string(), (Some(day(3)?), Some(day(5)?)));
+		times.insert("b".to_string(), (Some(day(1)?), Some(day(9)?)));
+		times.insert("c".to_string(), (Some(day(2)?), None));
+		let sorted = |sort: SortOption| {
+			let mut files = ["c", "d", "a", "b"]
+				.into_iter()
+				.map(|id| StreamFile {
+					content_id: Some(id.to_string()),
+					..Default::default()
+				})
+				.collect();
+			sort.sort_files(&mut files, &times);
+			files
+				.into_iter()
+				.filter_map(|file| file.content_id)
+				.collect::<Vec<_>>()
+		};
+
+		assert_eq!(sorted(SortOption::CreatedAsc), ["b", "c", "a", "d"]);
+		assert_eq!(sorted(SortOption::CreatedDesc), ["a", "c", "b", "d"]);
+		assert_eq!(sorted(SortOption::UpdatedAsc), ["a", "b", "c", "d"]);
+		assert_eq!(sorted(SortOption::UpdatedDesc), ["b", "a", "c", "d"]);
+		assert_eq!(sorted(SortOption::StreamIdAsc), ["a", "b", "c", "d"]);
+		Ok(())
+	}
+
+	#[test]
+	fn filter_date_range() -> anyhow::Result<()> {
+		let day = |day: u32| format!("2024-01-{:02}T00:00:00Z", day).parse::<DateTime<Utc>>();
+		let range = |after: Option<u32>, before: Option<u32>| -> anyhow::Result<_> {
+			Ok(vec![LoadFilesOption::DateRange {
+				after: after.map(day).transpose()?,
+				before: before.map(day).transpose()?,
+			}])
+		};
+
+		let closed = range(Some(10), Some(20))?;
+		assert!(!LoadFilesOption::in_date_range(&closed, Some(day(9)?)));
+		assert!(LoadFilesOption::in_date_range(&closed, Some(day(10)?)));
+		assert!(LoadFilesOption::in_date_range(&closed, Some(day(19)?)));
+		assert!(!LoadFilesOption::in_date_range(&closed, Some(day(20)?)));
+		assert!(!LoadFilesOption::in_date_range(&closed, None));
+
+		let after_only = range(Some(10), None)?;
+		assert!(!LoadFilesOption::in_date_range(&after_only, Some(day(9)?)));
+		assert!(LoadFilesOption::in_date_range(&after_only, Some(day(31)?)));
+
+		let before_only = range(None, Some(20))?;
+		assert!(LoadFilesOption::in_date_range(&before_only, Some(day(1)?)));
+		assert!(!LoadFilesOption::in_date_range(
+			&before_only,
+			Some(day(21)?)
+		));
+
+		assert!(LoadFilesOption::in_date_range(&[], None));
+		Ok(())
+	}
+
+	#[test]
+	fn event_order() -> anyhow::Result<()> {
+		let genesis: Event = example::genesis().genesis.try_into()?;
+		let data: Event = example::data().commit.try_into()?;
+		let data_prev = data.prev()?;
+
+		assert!(check_event_order(None, &[genesis.clone()]).is_ok());
+		assert!(check_event_order(data_prev, &[data.clone()]).is_ok());
+		assert!(check_event_order(None, &[data.clone()]).is_err());
+		assert!(check_event_order(None, &[genesis.clone(), data]).is_err());
+		assert!(check_event_order(Some(genesis.cid), &[genesis.clone()]).is_err());
+
+		assert!(verify_chain(&[]).is_ok());
+		assert!(verify_chain(&[data.clone()]).is_ok());
+		assert!(verify_chain(&[genesis, data.clone()]).is_err());
+		assert!(verify_chain(&[data.clone(), data]).is_err());
+		Ok(())
+	}
+
+	#[test]
+	fn generate_stream_id() -> anyhow::Result<()> {
+		let genesis = example::genesis();
+		let expected = genesis.stream_id()?;
+		let event: Event = genesis.genesis.try_into()?;
+		let stream_type = StreamIdType::ModelInstanceDocument.int_value();
+		assert_eq!(Client::generate_stream_id(stream_type, &event)?, expected);
+
+		let data: Event = example::data().commit.try_into()?;
+		assert!(Client::generate_stream_id(stream_type, &data).is_err());
+		Ok(())
 	}
 }
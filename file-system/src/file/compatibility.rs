@@ -0,0 +1,110 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Result of checking stream content against the json schema of a model.
+///
+/// `extra_fields` are content fields not declared in schema properties, json schema allows them
+/// unless `additionalProperties` is false, so they do not make the content incompatible alone.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompatibilityResult {
+	pub compatible: bool,
+	pub missing_fields: Vec<String>,
+	pub extra_fields: Vec<String>,
+}
+
+impl CompatibilityResult {
+	pub fn check(schema: &Value, content: &Value) -> anyhow::Result<Self> {
+		let compiled = jsonschema::JSONSchema::compile(schema)
+			.map_err(|err| anyhow::anyhow!("invalid model schema: {}", err))?;
+
+		let fields = content.as_object();
+		let has_field = |field: &String| fields.map_or(false, |fields| fields.contains_key(field));
+		let missing_fields = schema
+			.get("required")
+			.and_then(Value::as_array)
+			.into_iter()
+			.flatten()
+			.filter_map(|field| field.as_str().map(String::from))
+			.filter(|field| !has_field(field))
+			.collect();
+
+		let properties = schema.get("properties").and_then(Value::as_object);
+		let extra_fields = fields
+			.into_iter()
+			.flat_map(|fields| fields.keys())
+			.filter(|field| properties.map_or(true, |properties| !properties.contains_key(*field)))
+			.cloned()
+			.collect();
+
+		Ok(Self {
+			compatible: compiled.is_valid(content),
+			missing_fields,
+			extra_fields,
+		})
+	}
+}
+
+/// errors of validating content against json schema, empty when content is valid
+pub fn schema_errors(schema: &Value, content: &Value) -> anyhow::Result<Vec<String>> {
+	let compiled = jsonschema::JSONSchema::compile(schema)
+		.map_err(|err| anyhow::anyhow!("invalid model schema: {}", err))?;
+	let errors = match compiled.validate(content) {
+		Ok(()) => vec![],
+		Err(errors) => errors
+			.map(|err| format!("{}: {}", err.instance_path, err))
+			.collect(),
+	};
+	Ok(errors)
+}
+
+#[cfg(test)]
+mod tests {
+	use serde_json::json;
+
+	use super::*;
+
+	#[test]
+	fn check_compatibility() -> anyhow::Result<()> {
+		let schema = json!({
+			"type": "object",
+			"properties": {
+				"fileName": {"type": "string"},
+				"fileType": {"type": "integer"}
+			},
+			"required": ["fileName", "fileType"]
+		});
+
+		let result = CompatibilityResult::check(&schema, &json!({"fileName": "a", "fileType": 0}))?;
+		assert_eq!(
+			result,
+			CompatibilityResult {
+				compatible: true,
+				..Default::default()
+			}
+		);
+
+		let result = CompatibilityResult::check(&schema, &json!({"fileName": "a", "tags": []}))?;
+		assert!(!result.compatible);
+		assert_eq!(result.missing_fields, vec!["fileType".to_string()]);
+		assert_eq!(result.extra_fields, vec!["tags".to_string()]);
+		Ok(())
+	}
+
+	#[test]
+	fn schema_errors_of_content() -> anyhow::Result<()> {
+		let schema = json!({
+			"type": "object",
+			"properties": {"fileName": {"type": "string"}},
+			"required": ["fileName"]
+		});
+		assert!(schema_errors(&schema, &json!({"fileName": "a"}))?.is_empty());
+
+		let errors = schema_errors(&schema, &json!({"fileName": 1}))?;
+		assert_eq!(errors.len(), 1);
+		assert!(errors[0].starts_with("/fileName"));
+
+		assert!(schema_errors(&json!({"type": 1}), &json!({})).is_err());
+		Ok(())
+	}
+}
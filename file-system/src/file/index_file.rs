@@ -1,10 +1,11 @@
 use std::str::FromStr;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use async_std::task;
 use ceramic_core::{Base64String, Cid};
 use chrono::{DateTime, Utc};
-use dataverse_ceramic::{self as ceramic, StreamId};
+use dataverse_ceramic::event::{EventSigner, Payload};
+use dataverse_ceramic::{self as ceramic, Event, StreamId, StreamState};
 use dataverse_core::store::dapp;
 use int_enum::IntEnum;
 use serde::{Deserialize, Serialize};
@@ -31,7 +32,12 @@ pub struct IndexFile {
 	pub content_type: Base64String,
 	pub access_control: Option<Base64String>,
 	pub deleted: Option<bool>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub deleted_at: Option<DateTime<Utc>>,
 	pub reserved: Option<String>,
+	/// index folder containing the file
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub folder_id: Option<String>,
 }
 
 impl Default for IndexFile {
@@ -46,7 +52,9 @@ impl Default for IndexFile {
 			content_type: Base64String::from(vec![]),
 			access_control: None,
 			deleted: None,
+			deleted_at: None,
 			reserved: None,
+			folder_id: None,
 		}
 	}
 }
@@ -62,6 +70,104 @@ impl IndexFile {
 			None => Ok(None),
 		}
 	}
+
+	/// Create a signed data event setting `folderId` of file to `folder_id`.
+	///
+	/// `state` is the current state of index file stream, only the index file is changed,
+	/// its content stream is left as is, `updatedAt` is set to now. Moving a file into its
+	/// current folder is rejected.
+	pub async fn move_to(
+		&mut self,
+		state: &StreamState,
+		folder_id: &StreamId,
+		signer: &dyn EventSigner,
+	) -> anyhow::Result<Event> {
+		let folder = folder_id.to_string();
+		if self.folder_id.as_ref() == Some(&folder) {
+			anyhow::bail!("file is already in folder {}", folder_id);
+		}
+		let tip: Cid = state.log.last().context("file has no event")?.cid.parse()?;
+		let updated_at = Utc::now();
+		let payload = Payload {
+			data: Some(serde_json::json!([
+				{"op": "add", "path": "/folderId", "value": folder},
+				{"op": "add", "path": "/updatedAt", "value": updated_at},
+			])),
+			header: None,
+			prev: Some(tip),
+			id: Some(state.stream_id()?.cid),
+		};
+		let event = signer.sign(payload).await?;
+		self.folder_id = Some(folder);
+		self.updated_at = updated_at;
+		Ok(event)
+	}
+
+	/// index file of a copy, pointing to `content_id` in `folder_id`, created at `now`
+	pub fn copy_as(
+		&self,
+		content_id: &StreamId,
+		folder_id: &StreamId,
+		file_name: Option<String>,
+		now: DateTime<Utc>,
+	) -> IndexFile {
+		IndexFile {
+			file_name: file_name.unwrap_or_else(|| self.file_name.clone()),
+			content_id: content_id.to_string(),
+			created_at: now,
+			updated_at: now,
+			deleted: None,
+			deleted_at: None,
+			folder_id: Some(folder_id.to_string()),
+			..self.clone()
+		}
+	}
+
+	pub fn is_deleted(&self) -> bool {
+		self.deleted == Some(true)
+	}
+
+	/// Create a signed data event marking file as deleted, a tombstone of the stream.
+	///
+	/// Streams are immutable, `deleted` and `deletedAt` are set on index file instead,
+	/// `updatedAt` is set to the same time as `deletedAt`.
+	pub async fn delete(
+		&mut self,
+		state: &StreamState,
+		signer: &dyn EventSigner,
+	) -> anyhow::Result<Event> {
+		if self.is_deleted() {
+			anyhow::bail!("file {} is already deleted", state.stream_id()?);
+		}
+		let tip: Cid = state.log.last().context("file has no event")?.cid.parse()?;
+		let deleted_at = Utc::now();
+		let payload = Payload {
+			data: Some(serde_json::json!([
+				{"op": "add", "path": "/deleted", "value": true},
+				{"op": "add", "path": "/deletedAt", "value": deleted_at},
+				{"op": "add", "path": "/updatedAt", "value": deleted_at},
+			])),
+			header: None,
+			prev: Some(tip),
+			id: Some(state.stream_id()?.cid),
+		};
+		let event = signer.sign(payload).await?;
+		self.deleted = Some(true);
+		self.deleted_at = Some(deleted_at);
+		self.updated_at = deleted_at;
+		Ok(event)
+	}
+
+	pub fn validate(&self) -> anyhow::Result<()> {
+		if self.updated_at < self.created_at {
+			anyhow::bail!(
+				"updated_at {} is before created_at {}",
+				self.updated_at,
+				self.created_at
+			);
+		}
+		Ok(())
+	}
 }
 
 #[repr(u64)]
@@ -95,6 +201,7 @@ impl Policy for IndexFileProcessor {
 		data: serde_json::Value,
 	) -> Result<()> {
 		let content: IndexFile = serde_json::from_value(data)?;
+		content.validate()?;
 		let content_type = content.content_type()?;
 
 		// validate content id
@@ -202,6 +309,7 @@ mod tests {
 	use serde_json::json;
 
 	use super::*;
+	use crate::test_helpers::{genesis_state, signed_payload, test_signer};
 
 	#[test]
 	fn test_decode_index_file() -> anyhow::Result<()> {
@@ -254,4 +362,113 @@ mod tests {
 			"lfcMzQrSOjIdBDupp2Or9Gdp1qrnrcQcCov2t9m34ec"
 		);
 	}
+
+	#[tokio::test]
+	async fn move_file() -> anyhow::Result<()> {
+		let genesis = "bagcqcerayswtqarydm2rgeh37yir45ccvfkj3qhwhfmu4vdjjrtny5l4rpia";
+		let state = genesis_state(genesis);
+		let signer = test_signer().await?;
+		let folder_id: StreamId =
+			"kjzl6kcym7w8y50i8sgcbbr4ev2x55bzv88srrg1dmlt9tv8g0x7nnj9soli1t5".parse()?;
+		let mut index_file = IndexFile {
+			folder_id: Some(folder_id.to_string()),
+			..Default::default()
+		};
+		assert!(index_file
+			.move_to(&state, &folder_id, &signer)
+			.await
+			.is_err());
+
+		index_file.folder_id = None;
+		let event = index_file.move_to(&state, &folder_id, &signer).await?;
+		assert_eq!(index_file.folder_id, Some(folder_id.to_string()));
+		let payload = signed_payload(&event)?;
+		assert_eq!(payload.id, Some(state.stream_id()?.cid));
+		assert_eq!(payload.prev, Some(genesis.parse::<Cid>()?));
+		assert_eq!(
+			payload.data,
+			Some(json!([
+				{"op": "add", "path": "/folderId", "value": folder_id.to_string()},
+				{"op": "add", "path": "/updatedAt", "value": index_file.updated_at},
+			]))
+		);
+		Ok(())
+	}
+
+	#[test]
+	fn copy_file() -> anyhow::Result<()> {
+		let content_id: StreamId =
+			"kjzl6kcym7w8y5fhg4cl0xi8npfke3jmaaeeic9dwx64bgunnqa6amortdgdsym".parse()?;
+		let folder_id: StreamId =
+			"kjzl6kcym7w8y50i8sgcbbr4ev2x55bzv88srrg1dmlt9tv8g0x7nnj9soli1t5".parse()?;
+		let source = IndexFile {
+			file_name: "post".into(),
+			file_type: 2,
+			content_id: "bafyreie5ecnb6ptkqhlnvkbedz3uxv3x4fsbz4xgeb7imzy4dixvhhmdwq".into(),
+			deleted: Some(true),
+			reserved: Some("reserved".into()),
+			..Default::default()
+		};
+		let now = Utc::now();
+
+		let copy = source.copy_as(&content_id, &folder_id, None, now);
+		assert_eq!(copy.file_name, "post");
+		assert_eq!(copy.file_type, 2);
+		assert_eq!(copy.content_id, content_id.to_string());
+		assert_eq!(copy.folder_id, Some(folder_id.to_string()));
+		assert_eq!(copy.created_at, now);
+		assert_eq!(copy.reserved, source.reserved);
+		assert!(!copy.is_deleted());
+
+		let copy = source.copy_as(&content_id, &folder_id, Some("copy of post".into()), now);
+		assert_eq!(copy.file_name, "copy of post");
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn delete_file() -> anyhow::Result<()> {
+		let genesis = "bagcqcerayswtqarydm2rgeh37yir45ccvfkj3qhwhfmu4vdjjrtny5l4rpia";
+		let state = genesis_state(genesis);
+		let signer = test_signer().await?;
+		let mut index_file = IndexFile {
+			deleted: Some(true),
+			..Default::default()
+		};
+		assert!(index_file.delete(&state, &signer).await.is_err());
+
+		index_file.deleted = Some(false);
+		assert!(!index_file.is_deleted());
+		let event = index_file.delete(&state, &signer).await?;
+		assert!(index_file.is_deleted());
+		assert!(index_file.deleted_at.is_some());
+		let payload = signed_payload(&event)?;
+		assert_eq!(payload.id, Some(state.stream_id()?.cid));
+		assert_eq!(payload.prev, Some(genesis.parse::<Cid>()?));
+		let patch = payload.data.unwrap();
+		assert_eq!(
+			patch[0],
+			json!({"op": "add", "path": "/deleted", "value": true})
+		);
+		assert_eq!(patch[1]["path"], "/deletedAt");
+		assert_eq!(
+			patch[2],
+			json!({"op": "add", "path": "/updatedAt", "value": index_file.updated_at})
+		);
+		assert_eq!(Some(index_file.updated_at), index_file.deleted_at);
+		Ok(())
+	}
+
+	#[test]
+	fn validate_timestamps() {
+		let created_at = Utc::now();
+		let mut index_file = IndexFile {
+			created_at,
+			updated_at: created_at,
+			..Default::default()
+		};
+		assert!(index_file.validate().is_ok());
+
+		index_file.updated_at = created_at - chrono::Duration::seconds(1);
+		assert!(index_file.validate().is_err());
+	}
 }
@@ -1,5 +1,8 @@
-use ceramic_core::Base64String;
+use anyhow::Context;
+use ceramic_core::{Base64String, Cid};
 use chrono::{DateTime, Utc};
+use dataverse_ceramic::event::{EventSigner, Payload};
+use dataverse_ceramic::{Event, StreamId, StreamState};
 use int_enum::IntEnum;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
@@ -7,6 +10,9 @@ use serde_repr::*;
 
 use super::access_control::AccessControl;
 
+/// maximum number of tags on a folder
+pub const MAX_FOLDER_TAGS: usize = 20;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct IndexFolder {
@@ -22,9 +28,14 @@ pub struct IndexFolder {
 	pub options: Option<Base64String>,
 	pub deleted: Option<bool>,
 	pub reserved: Option<String>,
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub tags: Vec<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub parent_folder_id: Option<String>,
 }
 
 impl IndexFolder {
+	/// decode folder options as V1, fields only in V2 options are ignored, see `options_v2`
 	pub fn options(&self) -> anyhow::Result<Option<FolderOptions>> {
 		match &self.options {
 			Some(options) => Ok(serde_json::from_slice(options.to_vec()?.as_ref())?),
@@ -32,6 +43,186 @@ impl IndexFolder {
 		}
 	}
 
+	/// decode folder options, V1 options are converted into V2 with version 1
+	pub fn options_v2(&self) -> anyhow::Result<Option<FolderOptionsV2>> {
+		let value: Value = match &self.options {
+			Some(options) => serde_json::from_slice(options.to_vec()?.as_ref())?,
+			None => return Ok(None),
+		};
+		if value.is_null() {
+			return Ok(None);
+		}
+		let options = match value.get("version").and_then(Value::as_u64) {
+			Some(2) => serde_json::from_value::<FolderOptionsV2>(value)?,
+			_ => serde_json::from_value::<FolderOptions>(value)?.into(),
+		};
+		Ok(Some(options))
+	}
+
+	/// store the encryption key hint in folder options, upgrading them to V2,
+	/// fails on malformed options instead of replacing them
+	pub fn set_encryption_key(mut self, key: String) -> anyhow::Result<Self> {
+		let mut options = self.options_v2()?.unwrap_or_default();
+		options.version = 2;
+		options.encryption_key = Some(key);
+		self.write_options(&options)?;
+		Ok(self)
+	}
+
+	/// Append a signal to folder options, options are created when missing.
+	///
+	/// ```
+	/// use dataverse_file_system::file::index_folder::IndexFolder;
+	/// use serde_json::json;
+	///
+	/// # fn main() -> anyhow::Result<()> {
+	/// let mut folder: IndexFolder = serde_json::from_value(json!({
+	///     "folderName": "posts",
+	///     "folderType": 0,
+	///     "createdAt": "2024-01-01T00:00:00Z",
+	///     "updatedAt": "2024-01-01T00:00:00Z",
+	///     "fsVersion": "0.11",
+	///     "contentFolderIds": []
+	/// }))?;
+	/// folder
+	///     .add_signal(json!({"type": 1}))?
+	///     .add_signal(json!({"type": 2}))?;
+	///
+	/// let folder: IndexFolder = serde_json::from_value(serde_json::to_value(&folder)?)?;
+	/// assert_eq!(folder.signals_count()?, 2);
+	/// # Ok(())
+	/// # }
+	/// ```
+	pub fn add_signal(&mut self, signal: Value) -> anyhow::Result<&mut Self> {
+		let mut options = self
+			.options_v2()?
+			.unwrap_or_else(|| FolderOptions::default().into());
+		options.signals.push(signal);
+		self.write_options(&options)?;
+		Ok(self)
+	}
+
+	/// Remove every signal equal to `signal`, returns whether any was removed.
+	///
+	/// ```
+	/// use dataverse_file_system::file::index_folder::IndexFolder;
+	/// use serde_json::json;
+	///
+	/// # fn main() -> anyhow::Result<()> {
+	/// let mut folder: IndexFolder = serde_json::from_value(json!({
+	///     "folderName": "posts",
+	///     "folderType": 0,
+	///     "createdAt": "2024-01-01T00:00:00Z",
+	///     "updatedAt": "2024-01-01T00:00:00Z",
+	///     "fsVersion": "0.11",
+	///     "contentFolderIds": []
+	/// }))?;
+	/// folder.add_signal(json!({"type": 1}))?;
+	/// assert!(folder.remove_signal(&json!({"type": 1}))?);
+	/// assert!(!folder.remove_signal(&json!({"type": 1}))?);
+	///
+	/// let folder: IndexFolder = serde_json::from_value(serde_json::to_value(&folder)?)?;
+	/// assert_eq!(folder.signals_count()?, 0);
+	/// # Ok(())
+	/// # }
+	/// ```
+	pub fn remove_signal(&mut self, signal: &Value) -> anyhow::Result<bool> {
+		let mut options = match self.options_v2()? {
+			Some(options) => options,
+			None => return Ok(false),
+		};
+		let count = options.signals.len();
+		options.signals.retain(|ele| ele != signal);
+		if options.signals.len() == count {
+			return Ok(false);
+		}
+		self.write_options(&options)?;
+		Ok(true)
+	}
+
+	/// number of signals in folder options, 0 without options
+	pub fn signals_count(&self) -> anyhow::Result<usize> {
+		Ok(self
+			.options_v2()?
+			.map(|options| options.signals.len())
+			.unwrap_or_default())
+	}
+
+	fn write_options(&mut self, options: &FolderOptionsV2) -> anyhow::Result<()> {
+		let options = serde_json::to_vec(options).context("failed to encode folder options")?;
+		self.options = Some(Base64String::from(options));
+		Ok(())
+	}
+
+	pub fn validate(&self) -> anyhow::Result<()> {
+		if self.tags.len() > MAX_FOLDER_TAGS {
+			anyhow::bail!(
+				"folder has {} tags, at most {} are allowed",
+				self.tags.len(),
+				MAX_FOLDER_TAGS
+			);
+		}
+		Ok(())
+	}
+
+	/// add a tag to folder, tags are compared case-insensitively
+	pub fn add_tag(mut self, tag: &str) -> Self {
+		if !self.has_tag(tag) {
+			self.tags.push(tag.to_string());
+		}
+		self
+	}
+
+	pub fn remove_tag(mut self, tag: &str) -> Self {
+		self.tags.retain(|t| !t.eq_ignore_ascii_case(tag));
+		self
+	}
+
+	pub fn has_tag(&self, tag: &str) -> bool {
+		self.tags.iter().any(|t| t.eq_ignore_ascii_case(tag))
+	}
+
+	/// Create a signed data event moving folder under `new_parent`, `None` moves it to root.
+	///
+	/// `state` is the current state of folder stream. Moving a folder into itself or a root
+	/// folder to root is rejected, use `Client::detect_folder_cycle` to check its descendants
+	/// before moving.
+	pub async fn move_to(
+		&mut self,
+		state: &StreamState,
+		new_parent: Option<StreamId>,
+		signer: &dyn EventSigner,
+	) -> anyhow::Result<Event> {
+		let folder_id = state.stream_id()?;
+		if new_parent.as_ref() == Some(&folder_id) {
+			anyhow::bail!("folder {} cannot be moved into itself", folder_id);
+		}
+		if new_parent.is_none() && self.parent_folder_id.is_none() {
+			anyhow::bail!("folder {} is already at root", folder_id);
+		}
+		let tip: Cid = state
+			.log
+			.last()
+			.context("folder has no event")?
+			.cid
+			.parse()?;
+		let patch = match (&new_parent, &self.parent_folder_id) {
+			(Some(parent), _) => serde_json::json!([
+				{"op": "add", "path": "/parentFolderId", "value": parent.to_string()}
+			]),
+			(None, _) => serde_json::json!([{"op": "remove", "path": "/parentFolderId"}]),
+		};
+		let payload = Payload {
+			data: Some(patch),
+			header: None,
+			prev: Some(tip),
+			id: Some(folder_id.cid),
+		};
+		let event = signer.sign(payload).await?;
+		self.parent_folder_id = new_parent.map(|parent| parent.to_string());
+		Ok(event)
+	}
+
 	pub fn access_control(&self) -> anyhow::Result<Option<AccessControl>> {
 		match &self.access_control {
 			Some(access_control) => {
@@ -55,6 +246,29 @@ pub struct FolderOptions {
 	pub signals: Vec<Value>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct FolderOptionsV2 {
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub folder_description: Option<String>,
+	#[serde(default = "Vec::new")]
+	pub signals: Vec<Value>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub encryption_key: Option<String>,
+	pub version: u8,
+}
+
+impl From<FolderOptions> for FolderOptionsV2 {
+	fn from(options: FolderOptions) -> Self {
+		Self {
+			folder_description: options.folder_description,
+			signals: options.signals,
+			encryption_key: None,
+			version: 1,
+		}
+	}
+}
+
 #[repr(u64)]
 #[derive(Debug, Serialize_repr, Deserialize_repr, Clone, Copy, IntEnum, PartialEq, Eq, Hash)]
 pub enum FolderType {
@@ -66,6 +280,7 @@ pub enum FolderType {
 #[cfg(test)]
 mod tests {
 	use super::*;
+	use crate::test_helpers::{genesis_state, signed_payload, test_signer};
 	use base64::{engine::general_purpose, Engine};
 	use serde_json::json;
 
@@ -115,4 +330,127 @@ mod tests {
 			]
 		);
 	}
+
+	fn public_folder(options: Option<Value>) -> IndexFolder {
+		IndexFolder {
+			folder_name: "folder".into(),
+			folder_type: FolderType::PublicFolderType,
+			created_at: Utc::now(),
+			updated_at: Utc::now(),
+			fs_version: "0.11".into(),
+			access_control: None,
+			content_folder_ids: vec![],
+			options: options.map(|options| Base64String::from(options.to_string().into_bytes())),
+			deleted: None,
+			reserved: None,
+			tags: vec![],
+			parent_folder_id: None,
+		}
+	}
+
+	#[test]
+	fn decode_folder_options_versions() -> anyhow::Result<()> {
+		let folder = public_folder(Some(json!({"signals": [{"type": 2}]})));
+		let options = folder.options_v2()?.unwrap();
+		assert_eq!(options.version, 1);
+		assert_eq!(options.encryption_key, None);
+		assert_eq!(options.signals, vec![json!({"type": 2})]);
+
+		let folder = public_folder(Some(
+			json!({"version": 2, "encryptionKey": "key", "signals": [{"type": 2}]}),
+		));
+		let options = folder.options_v2()?.unwrap();
+		assert_eq!(options.version, 2);
+		assert_eq!(options.encryption_key, Some("key".to_string()));
+		// V1 accessor still reads V2 options
+		assert_eq!(folder.options()?.unwrap().signals, vec![json!({"type": 2})]);
+
+		assert!(public_folder(None).options_v2()?.is_none());
+		assert!(public_folder(None).options()?.is_none());
+		Ok(())
+	}
+
+	#[test]
+	fn set_folder_encryption_key() -> anyhow::Result<()> {
+		let folder = public_folder(Some(json!({"signals": [{"type": 2}]})))
+			.set_encryption_key("key".into())?;
+		let options = folder.options_v2()?.unwrap();
+		assert_eq!(options.version, 2);
+		assert_eq!(options.encryption_key, Some("key".to_string()));
+		assert_eq!(options.signals, vec![json!({"type": 2})]);
+
+		let mut broken = public_folder(None);
+		broken.options = Some(Base64String::from(b"not json".to_vec()));
+		assert!(broken.set_encryption_key("key".into()).is_err());
+		Ok(())
+	}
+
+	#[test]
+	fn folder_signals() -> anyhow::Result<()> {
+		let mut folder = public_folder(Some(json!({"signals": [{"type": 2}]})));
+		folder.add_signal(json!({"type": 3}))?;
+		assert_eq!(folder.signals_count()?, 2);
+		assert_eq!(folder.options_v2()?.unwrap().version, 1);
+		assert!(folder.remove_signal(&json!({"type": 2}))?);
+		assert_eq!(
+			folder.options_v2()?.unwrap().signals,
+			vec![json!({"type": 3})]
+		);
+
+		let mut broken = public_folder(None);
+		broken.options = Some(Base64String::from(b"not json".to_vec()));
+		assert!(broken.add_signal(json!({"type": 3})).is_err());
+		assert!(broken.remove_signal(&json!({"type": 3})).is_err());
+		assert!(broken.signals_count().is_err());
+		Ok(())
+	}
+
+	#[test]
+	fn folder_tags() {
+		let folder = public_folder(None)
+			.add_tag("Music")
+			.add_tag("music")
+			.add_tag("jazz");
+		assert_eq!(folder.tags, vec!["Music".to_string(), "jazz".to_string()]);
+		assert!(folder.has_tag("MUSIC"));
+		assert!(folder.validate().is_ok());
+
+		let folder = folder.remove_tag("JAZZ");
+		assert_eq!(folder.tags, vec!["Music".to_string()]);
+
+		let folder = (0..=MAX_FOLDER_TAGS).fold(public_folder(None), |folder, i| {
+			folder.add_tag(&i.to_string())
+		});
+		assert!(folder.validate().is_err());
+	}
+
+	#[tokio::test]
+	async fn move_folder() -> anyhow::Result<()> {
+		let genesis = "bagcqcerayswtqarydm2rgeh37yir45ccvfkj3qhwhfmu4vdjjrtny5l4rpia";
+		let state = genesis_state(genesis);
+		let signer = test_signer().await?;
+		let mut folder = public_folder(None);
+
+		let folder_id = state.stream_id()?;
+		assert!(folder
+			.move_to(&state, Some(folder_id.clone()), &signer)
+			.await
+			.is_err());
+		assert!(folder.move_to(&state, None, &signer).await.is_err());
+
+		let parent: StreamId =
+			"kjzl6kcym7w8y50i8sgcbbr4ev2x55bzv88srrg1dmlt9tv8g0x7nnj9soli1t5".parse()?;
+		let event = folder
+			.move_to(&state, Some(parent.clone()), &signer)
+			.await?;
+		assert_eq!(folder.parent_folder_id, Some(parent.to_string()));
+		let payload = signed_payload(&event)?;
+		assert_eq!(payload.id, Some(folder_id.cid));
+		assert_eq!(payload.prev, Some(genesis.parse::<Cid>()?));
+		assert_eq!(
+			payload.data,
+			Some(json!([{"op": "add", "path": "/parentFolderId", "value": parent.to_string()}]))
+		);
+		Ok(())
+	}
 }
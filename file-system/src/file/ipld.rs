@@ -0,0 +1,206 @@
+use std::collections::BTreeMap;
+
+use anyhow::Context;
+use ceramic_core::{StreamId, StreamIdType};
+use int_enum::IntEnum;
+use libipld::Ipld;
+use serde_json::{Number, Value};
+
+use super::{status::Status, FileContent, StreamFile};
+
+impl StreamFile {
+	/// Convert into ipld map, stream ids are stored as links of their cid.
+	///
+	/// `fileId` is always a model instance document and model ids are always models,
+	/// so the stream type is restored by `from_ipld` according to the key.
+	pub fn to_ipld(&self) -> Ipld {
+		let stream_id_link = |stream_id: &Option<StreamId>| match stream_id {
+			Some(stream_id) => Ipld::Link(stream_id.cid),
+			None => Ipld::Null,
+		};
+		let json = |value: &Option<Value>| match value {
+			Some(value) => json_to_ipld(value),
+			None => Ipld::Null,
+		};
+		let string = |value: &Option<String>| match value {
+			Some(value) => Ipld::String(value.clone()),
+			None => Ipld::Null,
+		};
+
+		let mut map = BTreeMap::new();
+		map.insert("fileId".into(), stream_id_link(&self.file_id));
+		map.insert("fileModelId".into(), stream_id_link(&self.file_model_id));
+		map.insert("file".into(), json(&self.file));
+		map.insert("contentId".into(), string(&self.content_id));
+		map.insert("modelId".into(), stream_id_link(&self.model_id));
+		let content = match &self.content {
+			Some(FileContent::Binary(data)) => Ipld::Bytes(data.to_vec()),
+			Some(FileContent::Json(value)) => json_to_ipld(value),
+			None => Ipld::Null,
+		};
+		map.insert("content".into(), content);
+		map.insert("controller".into(), Ipld::String(self.controller.clone()));
+		map.insert(
+			"verifiedStatus".into(),
+			Ipld::Integer(self.verified_status.int_value().into()),
+		);
+		map.insert(
+			"verifiedStatusDesc".into(),
+			string(&self.verified_status_desc),
+		);
+		map.insert("anchored".into(), Ipld::Bool(self.anchored));
+		Ipld::Map(map)
+	}
+
+	pub fn from_ipld(ipld: Ipld) -> anyhow::Result<StreamFile> {
+		let mut map = match ipld {
+			Ipld::Map(map) => map,
+			_ => anyhow::bail!("stream file ipld should be a map"),
+		};
+		let mut take = |key: &str| map.remove(key).unwrap_or(Ipld::Null);
+
+		let stream_id = |ipld: Ipld, r#type: StreamIdType| match ipld {
+			Ipld::Link(cid) => Ok(Some(StreamId { r#type, cid })),
+			Ipld::Null => Ok(None),
+			_ => anyhow::bail!("stream id should be a link"),
+		};
+		let json = |ipld: Ipld| match ipld {
+			Ipld::Null => Ok(None),
+			ipld => ipld_to_json(ipld).map(Some),
+		};
+		let string = |ipld: Ipld| match ipld {
+			Ipld::String(value) => Ok(Some(value)),
+			Ipld::Null => Ok(None),
+			_ => anyhow::bail!("expected string in stream file ipld"),
+		};
+
+		let verified_status = match take("verifiedStatus") {
+			Ipld::Integer(status) => Status::from_int(status.try_into()?)?,
+			_ => anyhow::bail!("verifiedStatus should be an integer"),
+		};
+		// maps written before `anchored` was stored have no such key
+		let anchored = match take("anchored") {
+			Ipld::Bool(anchored) => anchored,
+			Ipld::Null => false,
+			_ => anyhow::bail!("anchored should be a bool"),
+		};
+		Ok(StreamFile {
+			file_id: stream_id(take("fileId"), StreamIdType::ModelInstanceDocument)?,
+			file_model_id: stream_id(take("fileModelId"), StreamIdType::Model)?,
+			file: json(take("file"))?,
+			content_id: string(take("contentId"))?,
+			model_id: stream_id(take("modelId"), StreamIdType::Model)?,
+			content: match take("content") {
+				Ipld::Bytes(data) => Some(FileContent::Binary(data.into())),
+				ipld => json(ipld)?.map(FileContent::Json),
+			},
+			controller: string(take("controller"))?.context("missing controller")?,
+			verified_status,
+			verified_status_desc: string(take("verifiedStatusDesc"))?,
+			anchored,
+			..Default::default()
+		})
+	}
+}
+
+fn json_to_ipld(value: &Value) -> Ipld {
+	match value {
+		Value::Null => Ipld::Null,
+		Value::Bool(value) => Ipld::Bool(*value),
+		Value::Number(number) => match (number.as_i64(), number.as_u64()) {
+			(Some(value), _) => Ipld::Integer(value.into()),
+			(_, Some(value)) => Ipld::Integer(value.into()),
+			_ => Ipld::Float(number.as_f64().unwrap_or_default()),
+		},
+		Value::String(value) => Ipld::String(value.clone()),
+		Value::Array(values) => Ipld::List(values.iter().map(json_to_ipld).collect()),
+		Value::Object(map) => Ipld::Map(
+			map.iter()
+				.map(|(key, value)| (key.clone(), json_to_ipld(value)))
+				.collect(),
+		),
+	}
+}
+
+fn ipld_to_json(ipld: Ipld) -> anyhow::Result<Value> {
+	Ok(match ipld {
+		Ipld::Null => Value::Null,
+		Ipld::Bool(value) => Value::Bool(value),
+		Ipld::Integer(value) => match i64::try_from(value) {
+			Ok(value) => Value::Number(value.into()),
+			Err(_) => Value::Number(u64::try_from(value)?.into()),
+		},
+		Ipld::Float(value) => Value::Number(Number::from_f64(value).context("invalid float")?),
+		Ipld::String(value) => Value::String(value),
+		Ipld::Bytes(_) => anyhow::bail!("bytes cannot be converted to json"),
+		Ipld::List(values) => Value::Array(
+			values
+				.into_iter()
+				.map(ipld_to_json)
+				.collect::<anyhow::Result<_>>()?,
+		),
+		Ipld::Map(map) => Value::Object(
+			map.into_iter()
+				.map(|(key, value)| Ok((key, ipld_to_json(value)?)))
+				.collect::<anyhow::Result<_>>()?,
+		),
+		Ipld::Link(cid) => Value::String(cid.to_string()),
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use libipld::{cbor::DagCborCodec, prelude::Codec};
+	use serde_json::json;
+
+	use super::*;
+
+	#[test]
+	fn stream_file_ipld_round_trip() -> anyhow::Result<()> {
+		let file = StreamFile {
+			file_id: Some(
+				"kjzl6kcym7w8y9s94kcardbh5u0ao76bci07xnnxjw1ew3i4eackykj76uagqfk".parse()?,
+			),
+			file_model_id: Some(
+				"kjzl6hvfrbw6c763ubdhowzao0m4yp84cxzbfnlh4hdi5alqo4yrebmc0qpjdi5".parse()?,
+			),
+			file: Some(json!({
+				"fileName": "post",
+				"fileType": 0,
+				"tags": ["a", "b"],
+				"deleted": false,
+				"size": -1.5,
+			})),
+			content_id: Some(
+				"kjzl6kcym7w8y5fhg4cl0xi8npfke3jmaaeeic9dwx64bgunnqa6amortdgdsym".into(),
+			),
+			controller: "did:pkh:eip155:137:0x312eA852726E3A9f633A0377c0ea882086d66666".into(),
+			verified_status: Status::NakedStream,
+			anchored: true,
+			..Default::default()
+		};
+
+		let ipld = file.to_ipld();
+		let bytes = DagCborCodec.encode(&ipld)?;
+		let decoded: Ipld = DagCborCodec.decode(&bytes)?;
+		let decoded = StreamFile::from_ipld(decoded)?;
+
+		assert_eq!(decoded.file_id, file.file_id);
+		assert_eq!(decoded.file_model_id, file.file_model_id);
+		assert_eq!(decoded.file, file.file);
+		assert_eq!(decoded.content_id, file.content_id);
+		assert_eq!(decoded.model_id, None);
+		assert_eq!(decoded.content, None);
+		assert_eq!(decoded.controller, file.controller);
+		assert_eq!(decoded.verified_status, file.verified_status);
+		assert_eq!(decoded.verified_status_desc, None);
+		assert!(decoded.anchored);
+
+		let mut ipld = file.to_ipld();
+		if let Ipld::Map(map) = &mut ipld {
+			map.remove("anchored");
+		}
+		assert!(!StreamFile::from_ipld(ipld)?.anchored);
+		Ok(())
+	}
+}
@@ -4,47 +4,194 @@ pub mod status;
 
 pub mod access_control;
 pub mod action_file;
+pub mod binary_blob;
+pub mod builder;
+pub mod compatibility;
 pub mod content_folder;
 pub mod content_type;
 pub mod index_file;
 pub mod index_folder;
+#[cfg(feature = "ipld")]
+mod ipld;
 
+pub use builder::StreamFileBuilder;
 pub use index_file::*;
 
 use std::fmt::Display;
+use std::str::FromStr;
+use std::sync::OnceLock;
 
 use anyhow::Context;
+use base64::{engine::general_purpose, Engine};
+use bytes::Bytes;
+use ceramic_core::Cid;
+use chrono::{DateTime, Utc};
 pub use client::*;
-use dataverse_ceramic::StreamState;
+use dataverse_ceramic::{AnchorStatus, Event, StreamState};
+use int_enum::IntEnum;
 pub use operator::*;
 
 use ceramic_core::StreamId;
 use serde::{Deserialize, Serialize};
 use serde_json::value::Value;
 
+use self::access_control::EncryptionMetadata;
 use self::status::Status;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// File of an index file and its content, serialized as camelCase json with absent fields
+/// omitted and status as its integer value.
+///
+/// ```
+/// use dataverse_file_system::file::{status::Status, StreamFile};
+///
+/// let json = serde_json::json!({
+///     "contentId": "kjzl6kcym7w8y5fhg4cl0xi8npfke3jmaaeeic9dwx64bgunnqa6amortdgdsym",
+///     "modelId": "kjzl6hvfrbw6c763ubdhowzao0m4yp84cxzbfnlh4hdi5alqo4yrebmc0qpjdi5",
+///     "content": { "title": "hello" },
+///     "controller": "did:pkh:eip155:1:0x312eA852726E3A9f633A0377c0ea882086d66666",
+///     "verifiedStatus": -1,
+///     "verifiedStatusDesc": "NakedStream: file_id is None"
+/// });
+/// let file = StreamFile::from_json(&json.to_string()).unwrap();
+/// assert_eq!(file.verified_status, Status::NakedStream);
+/// let value: serde_json::Value = serde_json::from_str(&file.to_json().unwrap()).unwrap();
+/// assert_eq!(value, json);
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct StreamFile {
 	#[serde(skip_serializing_if = "Option::is_none")]
 	pub file_id: Option<StreamId>,
 	#[serde(skip_serializing_if = "Option::is_none")]
 	pub file_model_id: Option<StreamId>,
-	#[serde(skip_serializing_if = "Option::is_none")]
+	/// index file json, set it with [`StreamFile::replace_file`] to derive sizes and folder again
+	#[serde(
+		default,
+		skip_serializing_if = "Option::is_none",
+		deserialize_with = "deserialize_some"
+	)]
 	pub file: Option<Value>,
 
 	#[serde(skip_serializing_if = "Option::is_none")]
 	pub content_id: Option<String>,
 	#[serde(skip_serializing_if = "Option::is_none")]
 	pub model_id: Option<StreamId>,
-	#[serde(skip_serializing_if = "Option::is_none")]
-	pub content: Option<Value>,
+	/// content of file, set it with [`StreamFile::replace_content`] to derive its size again
+	#[serde(
+		default,
+		skip_serializing_if = "Option::is_none",
+		deserialize_with = "deserialize_some"
+	)]
+	pub content: Option<FileContent>,
 
 	pub controller: String,
 	pub verified_status: Status,
 	#[serde(skip_serializing_if = "Option::is_none")]
 	pub verified_status_desc: Option<String>,
+	/// content stream is anchored on chain, independent of `verified_status`
+	#[serde(default, skip_serializing_if = "std::ops::Not::not")]
+	pub anchored: bool,
+
+	#[serde(skip)]
+	cache: FileCache,
+}
+
+/// values derived from content and file on first access, ignored when comparing files,
+/// reset whenever content or file is replaced
+#[derive(Debug, Clone, Default)]
+struct FileCache {
+	content_size: OnceLock<usize>,
+	file_size: OnceLock<usize>,
+	folder_id: OnceLock<Option<StreamId>>,
+}
+
+impl PartialEq for FileCache {
+	fn eq(&self, _other: &Self) -> bool {
+		true
+	}
+}
+
+/// read a present `null` as `Some`, only an absent field is `None`
+fn deserialize_some<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+	D: serde::Deserializer<'de>,
+	T: Deserialize<'de>,
+{
+	T::deserialize(deserializer).map(Some)
+}
+
+/// Content of a stream file, json for most models and raw bytes for binary blobs.
+///
+/// Bytes are serialized as a dag-json bytes object `{"/": {"bytes": "<base64>"}}`. Content is
+/// always deserialized as json, since the model isn't known there, see [`FileContent::from_json`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum FileContent {
+	Json(Value),
+	Binary(Bytes),
+}
+
+impl FileContent {
+	pub fn as_json(&self) -> Option<&Value> {
+		match self {
+			FileContent::Json(value) => Some(value),
+			FileContent::Binary(_) => None,
+		}
+	}
+
+	pub fn to_json(&self) -> Value {
+		match self {
+			FileContent::Json(value) => value.clone(),
+			FileContent::Binary(data) => serde_json::json!({
+				"/": { "bytes": general_purpose::STANDARD_NO_PAD.encode(data) }
+			}),
+		}
+	}
+
+	/// content of a binary blob file from its json form, a dag-json bytes object is read as
+	/// bytes, anything else as json. Only for content known to be of the binaryBlob model,
+	/// json content of other models may have the same shape.
+	pub fn from_json(value: Value) -> Self {
+		let bytes = match &value {
+			Value::Object(map) if map.len() == 1 => match map.get("/") {
+				Some(Value::Object(link)) if link.len() == 1 => {
+					link.get("bytes").and_then(Value::as_str)
+				}
+				_ => None,
+			},
+			_ => None,
+		};
+		match bytes.and_then(|bytes| general_purpose::STANDARD_NO_PAD.decode(bytes).ok()) {
+			Some(data) => FileContent::Binary(data.into()),
+			None => FileContent::Json(value),
+		}
+	}
+}
+
+impl From<Value> for FileContent {
+	fn from(value: Value) -> Self {
+		FileContent::Json(value)
+	}
+}
+
+impl Serialize for FileContent {
+	fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+	where
+		S: serde::Serializer,
+	{
+		match self {
+			FileContent::Json(value) => value.serialize(serializer),
+			FileContent::Binary(_) => self.to_json().serialize(serializer),
+		}
+	}
+}
+
+impl<'de> Deserialize<'de> for FileContent {
+	fn deserialize<D>(deserializer: D) -> Result<FileContent, D::Error>
+	where
+		D: serde::Deserializer<'de>,
+	{
+		Value::deserialize(deserializer).map(FileContent::Json)
+	}
 }
 
 impl Default for StreamFile {
@@ -59,6 +206,8 @@ impl Default for StreamFile {
 			controller: Default::default(),
 			verified_status: Default::default(),
 			verified_status_desc: Default::default(),
+			anchored: false,
+			cache: Default::default(),
 		}
 	}
 }
@@ -66,12 +215,17 @@ impl Default for StreamFile {
 impl StreamFile {
 	pub fn new_with_file(state: StreamState) -> anyhow::Result<Self> {
 		let mut file = Self::default();
-		file.write_file(state)?;
+		file.set_file(state)?;
 		Ok(file)
 	}
 
+	#[deprecated(note = "use `StreamFileBuilder` to build a file with both states")]
 	pub fn write_file(&mut self, state: StreamState) -> anyhow::Result<()> {
-		self.file = Some(state.content.clone());
+		self.set_file(state)
+	}
+
+	fn set_file(&mut self, state: StreamState) -> anyhow::Result<()> {
+		self.replace_file(Some(state.content.clone()));
 		self.file_id = Some(state.stream_id()?);
 		self.file_model_id = Some(state.must_model()?);
 		self.controller = state
@@ -84,12 +238,17 @@ impl StreamFile {
 
 	pub fn new_with_content(state: StreamState) -> anyhow::Result<Self> {
 		let mut file = Self::default();
-		file.write_content(state)?;
+		file.set_content(state)?;
 		Ok(file)
 	}
 
+	#[deprecated(note = "use `StreamFileBuilder` to build a file with both states")]
 	pub fn write_content(&mut self, state: StreamState) -> anyhow::Result<()> {
-		self.content = Some(state.content.clone());
+		self.set_content(state)
+	}
+
+	fn set_content(&mut self, state: StreamState) -> anyhow::Result<()> {
+		self.replace_content(Some(FileContent::Json(state.content.clone())));
 		self.content_id = Some(state.stream_id()?.to_string());
 		self.model_id = Some(state.must_model()?);
 		self.controller = state
@@ -97,22 +256,283 @@ impl StreamFile {
 			.first()
 			.context("no controller")?
 			.clone();
+		self.anchored = state.anchor_status == AnchorStatus::Anchored;
+		Ok(())
+	}
+
+	/// index file json
+	pub fn file(&self) -> Option<&Value> {
+		self.file.as_ref()
+	}
+
+	/// replace index file json, sizes and folder derived from it are computed again
+	pub fn replace_file(&mut self, file: Option<Value>) {
+		self.file = file;
+		self.cache.file_size = OnceLock::new();
+		self.cache.folder_id = OnceLock::new();
+	}
+
+	pub fn content(&self) -> Option<&FileContent> {
+		self.content.as_ref()
+	}
+
+	/// content as json, binary content as a dag-json bytes object. Content used to be a json
+	/// value, this keeps that form for callers not handling binary blobs
+	pub fn content_json(&self) -> Option<Value> {
+		self.content.as_ref().map(FileContent::to_json)
+	}
+
+	/// replace content, its size is computed again
+	pub fn replace_content(&mut self, content: Option<FileContent>) {
+		self.content = content;
+		self.cache.content_size = OnceLock::new();
+	}
+
+	/// file of index file state and the content state it points to,
+	/// fails when `contentId` of the index file is another stream
+	pub fn merge(content: StreamState, file: StreamState) -> anyhow::Result<StreamFile> {
+		StreamFileBuilder::new().content(content).file(file).build()
+	}
+
+	pub fn to_json(&self) -> anyhow::Result<String> {
+		Ok(serde_json::to_string(self)?)
+	}
+
+	pub fn from_json(json: &str) -> anyhow::Result<StreamFile> {
+		Ok(serde_json::from_str(json)?)
+	}
+
+	/// decode json content of a binary blob stream into its bytes, see [`BinaryBlobFile`]
+	///
+	/// [`BinaryBlobFile`]: binary_blob::BinaryBlobFile
+	pub fn decode_binary_content(&mut self) -> anyhow::Result<()> {
+		if let Some(FileContent::Json(content)) = &self.content {
+			let blob = serde_json::from_value::<binary_blob::BinaryBlobFile>(content.clone())
+				.context("failed decode binary blob")?;
+			self.replace_content(Some(FileContent::Binary(blob.data)));
+		}
 		Ok(())
 	}
 
+	/// content json has an encryption marker, see [`EncryptionMetadata::from_content`]
+	pub fn is_encrypted(&self) -> bool {
+		self.encryption_metadata().is_some()
+	}
+
+	/// encryption hints of content, ask for a decryption key before displaying it when present
+	pub fn encryption_metadata(&self) -> Option<EncryptionMetadata> {
+		EncryptionMetadata::from_content(self.content.as_ref()?.as_json()?)
+	}
+
+	/// content is empty when it's missing, `null`, `{}` or no bytes
+	pub fn is_empty(&self) -> bool {
+		match &self.content {
+			None | Some(FileContent::Json(Value::Null)) => true,
+			Some(FileContent::Json(Value::Object(map))) => map.is_empty(),
+			Some(FileContent::Binary(data)) => data.is_empty(),
+			_ => false,
+		}
+	}
+
+	/// byte size of content serialized as compact json, see [`StreamState::compact_size`],
+	/// or the number of bytes of binary content
+	pub fn compact_size(&self) -> usize {
+		match &self.content {
+			Some(FileContent::Json(content)) => serde_json::to_vec(content)
+				.map(|content| content.len())
+				.unwrap_or_default(),
+			Some(FileContent::Binary(data)) => data.len(),
+			None => 0,
+		}
+	}
+
+	/// byte size of content as in [`compact_size`], `None` without content,
+	/// computed once until content is replaced
+	///
+	/// [`compact_size`]: StreamFile::compact_size
+	pub fn content_size(&self) -> Option<usize> {
+		self.content.as_ref()?;
+		Some(*self.cache.content_size.get_or_init(|| self.compact_size()))
+	}
+
+	/// byte size of file serialized as compact json, `None` without file.
+	/// computed once like `content_size`
+	pub fn file_size(&self) -> Option<usize> {
+		let file = self.file.as_ref()?;
+		let size = self.cache.file_size.get_or_init(|| {
+			serde_json::to_vec(file)
+				.map(|file| file.len())
+				.unwrap_or_default()
+		});
+		Some(*size)
+	}
+
+	/// folder of file, `folderId` of index file, parsed once like `content_size`
+	pub fn folder_id(&self) -> Option<&StreamId> {
+		self.cache
+			.folder_id
+			.get_or_init(|| self.file.as_ref()?.get("folderId")?.as_str()?.parse().ok())
+			.as_ref()
+	}
+
+	/// index file is tombstoned, `deleted` is true
+	pub fn is_deleted(&self) -> bool {
+		self.file
+			.as_ref()
+			.and_then(|file| file.get("deleted"))
+			.and_then(Value::as_bool)
+			.unwrap_or(false)
+	}
+
+	pub fn full_size(events: &[Event]) -> usize {
+		StreamState::full_size(events)
+	}
+
+	/// files built from states take their status with [`StreamFileBuilder::status`]
 	pub fn write_status(&mut self, status: Status, desc: String) {
 		self.verified_status = status.clone();
 		self.verified_status_desc = Some(format!("{:?}: {}", status, desc));
 	}
+
+	/// check json content against schema of model, a mismatch is written as
+	/// `Status::SchemaMismatch` unless an error status was written before
+	pub fn check_schema(&mut self, schema: &Value) -> anyhow::Result<()> {
+		let content = match self.content.as_ref().and_then(FileContent::as_json) {
+			Some(content) => content,
+			None => return Ok(()),
+		};
+		let errors = compatibility::schema_errors(schema, content)?;
+		if !errors.is_empty() && self.verified_status.int_value() >= 0 {
+			self.write_status(Status::SchemaMismatch, errors.join("; "));
+		}
+		Ok(())
+	}
+
+	/// fields changed from old to new file, keyed by `content.<field>` and `file.<field>`
+	pub fn diff(old: &StreamFile, new: &StreamFile) -> StreamFileDiff {
+		let mut diff = StreamFileDiff::default();
+		let content = |file: &StreamFile| file.content.as_ref().map(FileContent::to_json);
+		diff.merge(
+			"content",
+			StreamFileDiff::between(&content(old), &content(new)),
+		);
+		diff.merge("file", StreamFileDiff::between(&old.file, &new.file));
+		diff
+	}
+}
+
+/// fields added, removed and modified between two json values, nested objects are
+/// compared one level deep with keys joined by `.`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StreamFileDiff {
+	pub added: serde_json::Map<String, Value>,
+	pub removed: serde_json::Map<String, Value>,
+	pub modified: Vec<(String, Value, Value)>,
 }
 
+impl StreamFileDiff {
+	pub fn between(old: &Option<Value>, new: &Option<Value>) -> Self {
+		let empty = serde_json::Map::new();
+		let as_object = |value: &Option<Value>| match value {
+			Some(Value::Object(map)) => Some(map.clone()),
+			None | Some(Value::Null) => Some(empty.clone()),
+			_ => None,
+		};
+		let mut diff = Self::default();
+		match (as_object(old), as_object(new)) {
+			(Some(old), Some(new)) => diff.diff_objects("", &old, &new, 1),
+			_ if old != new => diff.modified.push((
+				String::new(),
+				old.clone().unwrap_or_default(),
+				new.clone().unwrap_or_default(),
+			)),
+			_ => {}
+		}
+		diff
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+	}
+
+	fn diff_objects(
+		&mut self,
+		prefix: &str,
+		old: &serde_json::Map<String, Value>,
+		new: &serde_json::Map<String, Value>,
+		depth: usize,
+	) {
+		for (key, old_value) in old {
+			let path = join_path(prefix, key);
+			match (new.get(key), old_value) {
+				(None, _) => {
+					self.removed.insert(path, old_value.clone());
+				}
+				(Some(Value::Object(new_map)), Value::Object(old_map)) if depth > 0 => {
+					self.diff_objects(&path, old_map, new_map, depth - 1)
+				}
+				(Some(new_value), _) if new_value != old_value => {
+					self.modified
+						.push((path, old_value.clone(), new_value.clone()));
+				}
+				_ => {}
+			}
+		}
+		for (key, new_value) in new {
+			if !old.contains_key(key) {
+				self.added.insert(join_path(prefix, key), new_value.clone());
+			}
+		}
+	}
+
+	fn merge(&mut self, prefix: &str, other: StreamFileDiff) {
+		for (key, value) in other.added {
+			self.added.insert(join_path(prefix, &key), value);
+		}
+		for (key, value) in other.removed {
+			self.removed.insert(join_path(prefix, &key), value);
+		}
+		for (key, old, new) in other.modified {
+			self.modified.push((join_path(prefix, &key), old, new));
+		}
+	}
+}
+
+fn join_path(prefix: &str, key: &str) -> String {
+	match (prefix.is_empty(), key.is_empty()) {
+		(true, _) => key.to_string(),
+		(_, true) => prefix.to_string(),
+		_ => format!("{}.{}", prefix, key),
+	}
+}
+
+/// state of stream file after applying the event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoricalStreamFile {
+	pub state: StreamFile,
+	pub event_cid: Cid,
+	/// issued time of signed event, anchor event has no timestamp without querying chain
+	pub timestamp: Option<DateTime<Utc>>,
+	pub event_type: String,
+}
+
+/// stream file with the streams linked from its content
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
+pub struct StreamTree {
+	pub root: StreamFile,
+	pub children: Vec<StreamTree>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub enum FileModel {
 	IndexFile,
 	ActionFile,
 	IndexFolder,
 	ContentFolder,
+	BinaryBlob,
 }
 
 impl Display for FileModel {
@@ -122,18 +542,330 @@ impl Display for FileModel {
 			FileModel::ActionFile => "actionFile",
 			FileModel::IndexFolder => "indexFolder",
 			FileModel::ContentFolder => "contentFolder",
+			FileModel::BinaryBlob => "binaryBlob",
 		};
 		write!(f, "{}", str)
 	}
 }
 
+impl FromStr for FileModel {
+	type Err = anyhow::Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"indexFile" => Ok(FileModel::IndexFile),
+			"actionFile" => Ok(FileModel::ActionFile),
+			"indexFolder" => Ok(FileModel::IndexFolder),
+			"contentFolder" => Ok(FileModel::ContentFolder),
+			"binaryBlob" => Ok(FileModel::BinaryBlob),
+			_ => anyhow::bail!("unknown file model {}", s),
+		}
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
+	use crate::test_helpers::example_state;
 
 	#[test]
 	fn file_model() -> anyhow::Result<()> {
 		assert_eq!(FileModel::IndexFile.to_string(), "indexFile".to_string());
+		assert_eq!(
+			"contentFolder".parse::<FileModel>()?,
+			FileModel::ContentFolder
+		);
+		assert_eq!("binaryBlob".parse::<FileModel>()?, FileModel::BinaryBlob);
+		assert!("post".parse::<FileModel>().is_err());
+		Ok(())
+	}
+
+	#[test]
+	fn stream_file_diff() {
+		let old = StreamFile {
+			content: Some(
+				serde_json::json!({
+					"title": "hello",
+					"tags": ["a"],
+					"meta": {"lang": "en", "draft": true, "extra": {"a": 1}}
+				})
+				.into(),
+			),
+			..Default::default()
+		};
+		let new = StreamFile {
+			content: Some(
+				serde_json::json!({
+					"title": "hello world",
+					"tags": ["a"],
+					"meta": {"lang": "en", "extra": {"a": 2}, "words": 2}
+				})
+				.into(),
+			),
+			file: Some(serde_json::json!({"fileName": "post"})),
+			..Default::default()
+		};
+
+		let diff = StreamFile::diff(&old, &new);
+		let keys = |map: &serde_json::Map<String, Value>| map.keys().cloned().collect::<Vec<_>>();
+		assert_eq!(
+			keys(&diff.added),
+			vec!["content.meta.words", "file.fileName"]
+		);
+		assert_eq!(keys(&diff.removed), vec!["content.meta.draft"]);
+		let modified: Vec<_> = diff
+			.modified
+			.iter()
+			.map(|(key, _, _)| key.as_str())
+			.collect();
+		assert_eq!(modified, vec!["content.meta.extra", "content.title"]);
+
+		assert!(StreamFile::diff(&new, &new).is_empty());
+	}
+
+	#[test]
+	fn stream_file_is_empty() {
+		let mut file = StreamFile::default();
+		assert!(file.is_empty());
+		file.replace_content(Some(Value::Null.into()));
+		assert!(file.is_empty());
+		file.replace_content(Some(serde_json::json!({}).into()));
+		assert!(file.is_empty());
+		file.replace_content(Some(FileContent::Binary(Bytes::new())));
+		assert!(file.is_empty());
+		file.replace_content(Some(serde_json::json!({"title": "hello"}).into()));
+		assert!(!file.is_empty());
+		file.replace_content(Some(FileContent::Binary(Bytes::from_static(b"hello"))));
+		assert!(!file.is_empty());
+		assert_eq!(file.compact_size(), 5);
+	}
+
+	#[tokio::test]
+	async fn anchored_content_keeps_status() -> anyhow::Result<()> {
+		let (_, mut state) = example_state().await?;
+		state.anchor_status = AnchorStatus::Anchored;
+
+		let file = StreamFileBuilder::new()
+			.content(state)
+			.status(Status::Validated, "checked".to_string())
+			.build()?;
+		assert!(file.anchored);
+		assert_eq!(file.verified_status, Status::Validated);
+		let json: Value = serde_json::from_str(&file.to_json()?)?;
+		assert_eq!(json["anchored"], Value::Bool(true));
+		assert!(!StreamFile::default().to_json()?.contains("anchored"));
+		Ok(())
+	}
+
+	#[test]
+	fn stream_file_sizes() {
+		let mut file = StreamFile {
+			file: Some(serde_json::json!({"fileName": "post"})),
+			..Default::default()
+		};
+		assert_eq!(file.content_size(), None);
+		assert_eq!(file.file_size(), Some(r#"{"fileName":"post"}"#.len()));
+
+		file.replace_content(Some(serde_json::json!({"title": "hello"}).into()));
+		let fresh = file.clone();
+		assert_eq!(file.content_size(), Some(r#"{"title":"hello"}"#.len()));
+		assert_eq!(file, fresh);
+		file.replace_content(Some(serde_json::json!({"title": "hello world"}).into()));
+		assert_eq!(
+			file.content_size(),
+			Some(r#"{"title":"hello world"}"#.len())
+		);
+		file.replace_file(None);
+		assert_eq!(file.file_size(), None);
+
+		let blob = StreamFile {
+			content: Some(FileContent::Binary(vec![0u8; 16].into())),
+			..Default::default()
+		};
+		assert_eq!(blob.content_size(), Some(16));
+	}
+
+	#[test]
+	fn stream_file_folder_id() -> anyhow::Result<()> {
+		let folder_id: StreamId =
+			"kjzl6kcym7w8y5fhg4cl0xi8npfke3jmaaeeic9dwx64bgunnqa6amortdgdsym".parse()?;
+		let index_file = IndexFile {
+			folder_id: Some(folder_id.to_string()),
+			..Default::default()
+		};
+		let mut file = StreamFile {
+			file: Some(serde_json::to_value(&index_file)?),
+			..Default::default()
+		};
+		assert_eq!(file.folder_id(), Some(&folder_id));
+
+		file.replace_file(Some(serde_json::to_value(IndexFile::default())?));
+		assert_eq!(file.folder_id(), None);
+		assert_eq!(StreamFile::default().folder_id(), None);
+		Ok(())
+	}
+
+	#[test]
+	fn stream_file_deleted() -> anyhow::Result<()> {
+		let file = |deleted| {
+			let index_file = IndexFile {
+				deleted,
+				..Default::default()
+			};
+			Ok::<_, anyhow::Error>(StreamFile {
+				file: Some(serde_json::to_value(index_file)?),
+				..Default::default()
+			})
+		};
+		assert!(file(Some(true))?.is_deleted());
+		assert!(!file(Some(false))?.is_deleted());
+		assert!(!file(None)?.is_deleted());
+		assert!(!StreamFile::default().is_deleted());
+		Ok(())
+	}
+
+	#[test]
+	fn stream_file_encryption() {
+		let file = |content: Value| StreamFile {
+			content: Some(content.into()),
+			..Default::default()
+		};
+		assert!(!file(serde_json::json!({"title": "hello"})).is_encrypted());
+		assert!(!StreamFile::default().is_encrypted());
+
+		let encrypted = file(serde_json::json!({
+			"encryptionProvider": { "protocol": "Lit", "keyId": "key-1", "algorithm": "AES-GCM" },
+			"encryptedSymmetricKey": "a1b2"
+		}));
+		assert!(encrypted.is_encrypted());
+		assert_eq!(
+			encrypted.encryption_metadata(),
+			Some(EncryptionMetadata {
+				provider: "Lit".into(),
+				key_id: Some("key-1".into()),
+				algorithm: Some("AES-GCM".into()),
+			})
+		);
+
+		let key_only =
+			file(serde_json::json!({ "encryptedSymmetricKey": "a1b2", "keyId": "key-2" }));
+		let metadata = key_only.encryption_metadata().unwrap();
+		assert_eq!(metadata.provider, "Lit");
+		assert_eq!(metadata.key_id, Some("key-2".into()));
+		assert_eq!(metadata.algorithm, None);
+	}
+
+	#[tokio::test]
+	async fn merge_content_and_file() -> anyhow::Result<()> {
+		let (stream_id, content) = example_state().await?;
+
+		let mut file = content.clone();
+		file.content = serde_json::json!({ "contentId": stream_id.to_string() });
+		let merged = StreamFile::merge(content.clone(), file.clone())?;
+		assert_eq!(merged.content_id, Some(stream_id.to_string()));
+		assert_eq!(merged.file_id, Some(stream_id));
+		assert_eq!(merged.content, Some(content.content.clone().into()));
+
+		file.content = serde_json::json!({ "contentId": "other" });
+		assert!(StreamFile::merge(content, file).is_err());
+		Ok(())
+	}
+
+	/// file with fields picked by a xorshift seed, each optional field is absent in some files
+	fn arbitrary_file(seed: &mut u64) -> StreamFile {
+		let mut next = || {
+			*seed ^= *seed << 13;
+			*seed ^= *seed >> 7;
+			*seed ^= *seed << 17;
+			*seed
+		};
+		let stream_ids = [
+			"kjzl6kcym7w8y5fhg4cl0xi8npfke3jmaaeeic9dwx64bgunnqa6amortdgdsym",
+			"kjzl6hvfrbw6c763ubdhowzao0m4yp84cxzbfnlh4hdi5alqo4yrebmc0qpjdi5",
+			"kjzl6hvfrbw6c86gt9j415yw2x8stmkotcrzpeutrbkp42i4z90gp5ibptz4sso",
+		];
+		let statuses = [-7, -6, -5, -4, -3, -2, -1, 0, 1];
+		let mut stream_id = |present: u64| -> Option<StreamId> {
+			let n = next();
+			(n % 4 < present).then(|| stream_ids[n as usize % stream_ids.len()].parse().unwrap())
+		};
+		let file_id = stream_id(3);
+		let file_model_id = stream_id(3);
+		let model_id = stream_id(3);
+		let value = |n: u64| match n % 5 {
+			0 => None,
+			1 => Some(Value::Null),
+			2 => Some(serde_json::json!({ "title": format!("title {}", n), "count": n })),
+			3 => Some(serde_json::json!({ "nested": { "tags": ["a", n.to_string()] } })),
+			_ => Some(serde_json::json!(n.to_string())),
+		};
+		StreamFile {
+			file_id,
+			file_model_id,
+			file: value(next()),
+			content_id: (next() % 3 > 0).then(|| format!("content {}", next())),
+			model_id,
+			content: match next() % 4 {
+				0 => Some(FileContent::Binary(next().to_be_bytes().to_vec().into())),
+				_ => value(next()).map(FileContent::from),
+			},
+			controller: format!("did:pkh:eip155:1:0x{:x}", next()),
+			verified_status: Status::from_int(statuses[next() as usize % statuses.len()]).unwrap(),
+			verified_status_desc: (next() % 2 == 0).then(|| format!("desc {}", next())),
+			anchored: next() % 2 == 0,
+			..Default::default()
+		}
+	}
+
+	#[test]
+	fn json_roundtrip() -> anyhow::Result<()> {
+		let mut seed = 0x2545_f491_4f6c_dd1d;
+		for _ in 0..256 {
+			let mut file = arbitrary_file(&mut seed);
+			let decoded = StreamFile::from_json(&file.to_json()?)?;
+			// binary content is read back as json, the model isn't known when deserializing
+			let content = file.content_json().map(FileContent::Json);
+			file.replace_content(content);
+			assert_eq!(decoded, file);
+		}
+		Ok(())
+	}
+
+	#[test]
+	fn bytes_shaped_json_stays_json() -> anyhow::Result<()> {
+		let content = serde_json::json!({"/": {"bytes": "aGVsbG8"}});
+		let file: StreamFile = serde_json::from_value(serde_json::json!({
+			"content": content,
+			"controller": "did:key:test",
+			"verifiedStatus": 0
+		}))?;
+		assert_eq!(file.content(), Some(&FileContent::Json(content.clone())));
+		assert_eq!(
+			FileContent::from_json(content),
+			FileContent::Binary(Bytes::from_static(b"hello"))
+		);
+		Ok(())
+	}
+
+	#[test]
+	fn check_schema_keeps_file() -> anyhow::Result<()> {
+		let schema = serde_json::json!({
+			"type": "object",
+			"properties": {"title": {"type": "string"}},
+			"required": ["title"]
+		});
+		let mut file = StreamFile {
+			content: Some(serde_json::json!({"title": "hello"}).into()),
+			..Default::default()
+		};
+		file.check_schema(&schema)?;
+		assert_eq!(file.verified_status, Status::None);
+
+		file.replace_content(Some(serde_json::json!({"title": 1}).into()));
+		file.check_schema(&schema)?;
+		assert_eq!(file.verified_status, Status::SchemaMismatch);
+		assert!(file.verified_status_desc.unwrap().contains("/title"));
+		assert!(file.content.is_some());
 		Ok(())
 	}
 }
@@ -1,9 +1,24 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use ceramic_http_client::{FilterQuery, OperationFilter};
-use dataverse_ceramic::{event::EventsUploader, Ceramic, StreamId, StreamState, StreamsLoader};
+use dataverse_ceramic::{event::EventsUploader, CachedStreamLoader, Ceramic, StreamId};
+use dataverse_ceramic::{StreamState, StreamsLoader};
+use dataverse_core::store::dapp;
+use serde_json::Value;
 
 use super::index_file::IndexFile;
+use super::FileModel;
+
+/// streams related to an index file, loaded ahead of rendering
+#[derive(Debug, Clone)]
+pub struct PreloadedContext {
+	pub index_file: StreamState,
+	pub content: Option<StreamState>,
+	/// action files whose relation is the index file
+	pub action_files: Vec<StreamState>,
+	/// content folders mirroring the index file
+	pub content_folders: Vec<StreamState>,
+}
 
 #[async_trait::async_trait]
 pub trait StreamFileLoader: StreamsLoader + EventsUploader + Send + Sync {
@@ -36,6 +51,112 @@ pub trait StreamFileLoader: StreamsLoader + EventsUploader + Send + Sync {
 		}
 		anyhow::bail!("index file with content_id {} not found", content_id)
 	}
+
+	/// load index files of content_ids controlled by account, keyed by content_id.
+	/// content without index file is absent from the result
+	async fn load_index_file_batch(
+		&self,
+		ceramic: &Ceramic,
+		account: Option<String>,
+		index_file_model_id: &StreamId,
+		content_ids: &[String],
+	) -> anyhow::Result<HashMap<String, (StreamState, IndexFile)>> {
+		let content_ids: HashSet<&String> = content_ids.iter().collect();
+		let stream_states = self
+			.load_stream_states(ceramic, account, index_file_model_id)
+			.await?;
+		let mut result = index_files_by_content_id(stream_states);
+		result.retain(|content_id, _| content_ids.contains(content_id));
+		Ok(result)
+	}
+
+	/// Load the content, action files and content folders of index file concurrently.
+	///
+	/// Related files of a model not registered in the dapp are left empty.
+	async fn preload_related_streams(
+		&self,
+		ceramic: &Ceramic,
+		index_file_id: &StreamId,
+	) -> anyhow::Result<PreloadedContext> {
+		let index_file = self.load_stream_state(ceramic, index_file_id, None).await?;
+		let content_id =
+			serde_json::from_value::<IndexFile>(index_file.content.clone())?.content_id;
+		let dapp_id = dapp::get_model(&index_file.must_model()?).await?.dapp_id;
+		let file_id = index_file_id.to_string();
+
+		let load_related = |file_model: FileModel, field: &'static str| {
+			let file_id = file_id.clone();
+			async move {
+				let model = dapp::get_model_by_name(&dapp_id, &file_model.to_string()).await?;
+				let states = self.load_stream_states(ceramic, None, &model.id).await?;
+				let related = states
+					.into_iter()
+					.filter(|state| match state.content.get(field) {
+						Some(Value::String(id)) => *id == file_id,
+						Some(Value::Array(ids)) => {
+							ids.iter().any(|id| id.as_str() == Some(&file_id))
+						}
+						_ => false,
+					})
+					.collect::<Vec<_>>();
+				Ok::<_, anyhow::Error>(related)
+			}
+		};
+		let load_content = async {
+			match content_id.parse::<StreamId>() {
+				Ok(content_id) => self
+					.load_stream_state(ceramic, &content_id, None)
+					.await
+					.map(Some),
+				// content is an ipfs cid
+				Err(_) => Ok(None),
+			}
+		};
+
+		let (content, action_files, content_folders) = futures::join!(
+			load_content,
+			load_related(FileModel::ActionFile, "relationId"),
+			load_related(FileModel::ContentFolder, "mirrorFileIds"),
+		);
+		let or_empty = |related: anyhow::Result<Vec<StreamState>>| {
+			related.unwrap_or_else(|err| {
+				tracing::warn!(file_id, "failed to preload related streams: {}", err);
+				vec![]
+			})
+		};
+		Ok(PreloadedContext {
+			index_file,
+			content: content?,
+			action_files: or_empty(action_files),
+			content_folders: or_empty(content_folders),
+		})
+	}
+}
+
+#[async_trait::async_trait]
+impl<T: StreamFileLoader> StreamFileLoader for CachedStreamLoader<T> {
+	async fn load_index_file_by_content_id(
+		&self,
+		ceramic: &Ceramic,
+		index_file_model_id: &StreamId,
+		content_id: &String,
+	) -> anyhow::Result<(StreamState, IndexFile)> {
+		self.inner()
+			.load_index_file_by_content_id(ceramic, index_file_model_id, content_id)
+			.await
+	}
+
+	async fn load_index_file_batch(
+		&self,
+		ceramic: &Ceramic,
+		account: Option<String>,
+		index_file_model_id: &StreamId,
+		content_ids: &[String],
+	) -> anyhow::Result<HashMap<String, (StreamState, IndexFile)>> {
+		self.inner()
+			.load_index_file_batch(ceramic, account, index_file_model_id, content_ids)
+			.await
+	}
 }
 
 #[async_trait::async_trait]
@@ -67,4 +188,102 @@ impl StreamFileLoader for dataverse_ceramic::http::Client {
 			serde_json::from_value::<IndexFile>(state.content.clone())?,
 		))
 	}
+
+	/// content ids are queried in chunks of `MAX_CONTENT_IDS_PER_QUERY`, a chunk whose query
+	/// fails falls back to a lookup per content id
+	async fn load_index_file_batch(
+		&self,
+		ceramic: &Ceramic,
+		account: Option<String>,
+		model_id: &StreamId,
+		content_ids: &[String],
+	) -> anyhow::Result<HashMap<String, (StreamState, IndexFile)>> {
+		let mut result = HashMap::new();
+		for chunk in content_ids.chunks(MAX_CONTENT_IDS_PER_QUERY) {
+			let mut where_filter = HashMap::new();
+			where_filter.insert(
+				"contentId".to_string(),
+				OperationFilter::In(chunk.iter().map(|id| id.clone().into()).collect()),
+			);
+
+			let query = Some(FilterQuery::Where(where_filter));
+			match self
+				.query_model(ceramic, account.clone(), model_id, query)
+				.await
+			{
+				Ok(streams) => result.extend(index_files_by_content_id(streams)),
+				Err(err) => {
+					tracing::warn!(
+						"batch query index files failed, fallback to sequential: {}",
+						err
+					);
+					for content_id in chunk {
+						if let Ok(index_file) = self
+							.load_index_file_by_content_id(ceramic, model_id, content_id)
+							.await
+						{
+							let controlled = match &account {
+								Some(account) => index_file.0.controllers().contains(account),
+								None => true,
+							};
+							if controlled {
+								result.insert(content_id.clone(), index_file);
+							}
+						}
+					}
+				}
+			}
+		}
+		Ok(result)
+	}
+}
+
+/// max content ids in the `contentId In` filter of one index file query
+pub const MAX_CONTENT_IDS_PER_QUERY: usize = 100;
+
+/// states parsed as index files keyed by content_id, states failed to parse are skipped
+fn index_files_by_content_id(
+	states: Vec<StreamState>,
+) -> HashMap<String, (StreamState, IndexFile)> {
+	let mut result = HashMap::new();
+	for state in states {
+		match serde_json::from_value::<IndexFile>(state.content.clone()) {
+			Ok(index_file) => {
+				result.insert(index_file.content_id.clone(), (state, index_file));
+			}
+			Err(err) => {
+				let stream_id = state.stream_id().map(|id| id.to_string()).ok();
+				tracing::warn!(
+					stream_id,
+					"skip stream failed to parse as index_file: {}",
+					err
+				);
+			}
+		}
+	}
+	result
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_helpers::genesis_state;
+
+	#[test]
+	fn skip_states_not_index_files() -> anyhow::Result<()> {
+		let mut file = genesis_state("bafyreih3skzznsyro5i4bsfsyquhcewyqmsra6cx47nj2eb6aerm2nihuu");
+		file.content = serde_json::to_value(IndexFile {
+			file_name: "post".into(),
+			content_id: "content".into(),
+			..Default::default()
+		})?;
+		let mut broken =
+			genesis_state("bafyreidnbzsaplrdpjx3schac4fjhwqjzv3kbvdswi52npq3kpdzpbv5qa");
+		broken.content = serde_json::json!({ "fileName": 1 });
+
+		let index_files = index_files_by_content_id(vec![broken, file]);
+		assert_eq!(index_files.len(), 1);
+		assert_eq!(index_files["content"].1.file_name, "post");
+		Ok(())
+	}
 }
@@ -11,6 +11,14 @@ pub enum Status {
 	CACAOExpired = -2,
 	BrokenContent = -3,
 	BrokenFolder = -4,
+	EmptyContent = -5,
+	DeprecatedModel = -6,
+	/// caller is not allowed to read the stream
+	AccessDenied = -7,
+	/// index file tombstoned by `Client::delete_file`
+	Deleted = -8,
+	/// content does not match the json schema of model, errors are in the status desc
+	SchemaMismatch = -9,
 }
 
 impl Default for Status {
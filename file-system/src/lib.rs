@@ -3,3 +3,5 @@ pub mod file;
 pub mod policy;
 pub mod schema;
 pub mod task;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_helpers;
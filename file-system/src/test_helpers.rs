@@ -0,0 +1,412 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use ceramic_core::Cid;
+use dataverse_ceramic::commit::example;
+use dataverse_ceramic::event::{Event, EventValue, EventsLoader, EventsUploader};
+use dataverse_ceramic::event::{EventSigner, Header, KeySigner, Payload};
+use dataverse_ceramic::network::Network;
+use dataverse_ceramic::{Ceramic, StreamId, StreamLoader, StreamState, StreamsLoader};
+use dataverse_core::store::dapp;
+use dataverse_core::store::memory::MemoryStreamStore;
+use int_enum::IntEnum;
+use tokio::sync::Mutex;
+
+use crate::file::index_file::IndexFile;
+use crate::file::{Client, StreamEventSaver, StreamFileLoader};
+
+/// client backed by a `MockStreamFileLoader` and a `MemoryStreamStore`, no ceramic node or
+/// database is needed, events are seeded with `client.operator.upload_event`
+pub fn test_client() -> Client {
+	Client::new(
+		Arc::new(MockStreamFileLoader::default()),
+		Arc::new(MemoryStreamStore::new()),
+	)
+}
+
+/// seed of the ed25519 key of `test_signer`
+const TEST_SIGNER_SEED: &str = "d160c4553ba7547cd5d66993d99329379a0c299a1bb1058abc5b874e0ba56375";
+
+/// signer of real jws events, events carry no cacao so they pass verification in tests
+pub async fn test_signer() -> anyhow::Result<KeySigner> {
+	KeySigner::new(TEST_SIGNER_SEED).await
+}
+
+/// payload of a signed event
+pub fn signed_payload(event: &Event) -> anyhow::Result<Payload> {
+	match &event.value {
+		EventValue::Signed(signed) => signed.payload(),
+		EventValue::Anchor(_) => anyhow::bail!("event {} is not signed", event.cid),
+	}
+}
+
+/// save the genesis of a new stream of `model` holding `data`, signed by `signer`
+pub async fn create_stream(
+	client: &Client,
+	dapp_id: &uuid::Uuid,
+	signer: &dyn EventSigner,
+	model: &StreamId,
+	data: serde_json::Value,
+) -> anyhow::Result<StreamId> {
+	let payload = Payload {
+		data: Some(data),
+		header: Some(Header {
+			model: model.clone(),
+			controllers: vec![signer.controller()],
+			unique: uuid::Uuid::new_v4().as_bytes().to_vec(),
+		}),
+		prev: None,
+		id: None,
+	};
+	let genesis = signer.sign(payload).await?;
+	let stream_type = ceramic_core::StreamIdType::ModelInstanceDocument.int_value();
+	let stream_id = Client::generate_stream_id(stream_type, &genesis)?;
+	client.save_event(dapp_id, &stream_id, &genesis).await?;
+	Ok(stream_id)
+}
+
+/// state of a model instance document whose only event is `genesis`
+pub fn genesis_state(genesis: &str) -> StreamState {
+	StreamState {
+		r#type: 3,
+		log: vec![ceramic_http_client::api::StateLog {
+			cid: genesis.into(),
+			r#type: 0,
+			timestamp: None,
+			expiration_time: None,
+		}],
+		..Default::default()
+	}
+}
+
+/// state of `commit::example::genesis`, a content stream of the model registered as "post"
+pub async fn example_state() -> anyhow::Result<(StreamId, StreamState)> {
+	let genesis = example::genesis();
+	let stream_id = genesis.stream_id()?;
+	let state = StreamState::make(
+		stream_id.r#type.int_value(),
+		vec![genesis.genesis.try_into()?],
+	)
+	.await?;
+	Ok((stream_id, state))
+}
+
+/// index file model of tests, registered as "indexFile" with `seed_dapp`
+pub const INDEX_FILE_MODEL_ID: &str =
+	"kjzl6hvfrbw6c5qdzwi9esxvt1v5mtt7od7hb2947624mn4u0rmq1rh9anjcnxx";
+
+/// index folder model of tests, registered as "indexFolder" with `seed_dapp`
+pub const INDEX_FOLDER_MODEL_ID: &str =
+	"kjzl6hvfrbw6c89f0p1lyd1e78tel33qebisfdsi0prhhapn4rye45j1uj72tju";
+
+/// dapp shared by tests, registered with `seed_dapp`
+pub const TEST_DAPP_ID: uuid::Uuid = uuid::Uuid::from_u128(0xda99);
+
+/// ceramic of `TEST_DAPP_ID`, never called over network
+pub fn test_ceramic() -> Ceramic {
+	Ceramic {
+		endpoint: "http://localhost:7007".into(),
+		network: Network::InMemory,
+		pool: None,
+	}
+}
+
+/// register models of `(name, model_id)` in `TEST_DAPP_ID` without querying the registry,
+/// a model id must keep the same name across tests
+pub async fn seed_dapp(models: &[(&str, &StreamId)]) -> uuid::Uuid {
+	let models = models
+		.iter()
+		.map(|(name, id)| dapp::Model {
+			id: (*id).clone(),
+			name: name.to_string(),
+			dapp_id: TEST_DAPP_ID,
+			encryptable: vec![],
+			version: 0,
+			latest: true,
+			is_deprecated: false,
+		})
+		.collect();
+	dapp::cache_dapp(&TEST_DAPP_ID, test_ceramic(), models).await;
+	TEST_DAPP_ID
+}
+
+/// Stream file loader serving streams from memory.
+///
+/// States given to the builder are served as is, uploaded events are kept per stream and
+/// serve the states of streams without a given state.
+#[derive(Default)]
+pub struct MockStreamFileLoader {
+	streams: HashMap<StreamId, StreamState>,
+	index_files: HashMap<String, StreamState>,
+	events: Mutex<HashMap<StreamId, Vec<Event>>>,
+	fail_uploads: bool,
+	fail_index_lookups: bool,
+	load_delay: Option<Duration>,
+	in_flight: AtomicUsize,
+	max_in_flight: AtomicUsize,
+}
+
+impl MockStreamFileLoader {
+	pub fn builder() -> MockStreamFileLoaderBuilder {
+		MockStreamFileLoaderBuilder::default()
+	}
+
+	/// max number of stream loads running at the same time so far
+	pub fn max_in_flight(&self) -> usize {
+		self.max_in_flight.load(Ordering::SeqCst)
+	}
+
+	async fn track<T>(&self, load: impl Future<Output = T>) -> T {
+		let in_flight = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+		self.max_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+		if let Some(delay) = self.load_delay {
+			tokio::time::sleep(delay).await;
+		}
+		let result = load.await;
+		self.in_flight.fetch_sub(1, Ordering::SeqCst);
+		result
+	}
+
+	async fn uploaded(&self, stream_id: &StreamId, tip: Option<Cid>) -> Option<Vec<Event>> {
+		let streams = self.events.lock().await;
+		let events = streams.get(stream_id)?;
+		let end = match tip {
+			Some(tip) => events.iter().position(|event| event.cid == tip)? + 1,
+			None => events.len(),
+		};
+		Some(events[..end].to_vec())
+	}
+}
+
+#[derive(Default)]
+pub struct MockStreamFileLoaderBuilder {
+	loader: MockStreamFileLoader,
+}
+
+impl MockStreamFileLoaderBuilder {
+	pub fn with_stream(mut self, stream_id: StreamId, state: StreamState) -> Self {
+		self.loader.streams.insert(stream_id, state);
+		self
+	}
+
+	/// index file returned by the index file lookups of content_id
+	pub fn with_index_file(
+		mut self,
+		content_id: impl Into<String>,
+		file_state: StreamState,
+	) -> Self {
+		self.loader
+			.index_files
+			.insert(content_id.into(), file_state);
+		self
+	}
+
+	/// every upload fails, as when ceramic is unreachable
+	pub fn with_failing_uploads(mut self) -> Self {
+		self.loader.fail_uploads = true;
+		self
+	}
+
+	/// every index file lookup by content_id fails, as when the index query is rejected
+	pub fn with_failing_index_lookups(mut self) -> Self {
+		self.loader.fail_index_lookups = true;
+		self
+	}
+
+	/// wait before serving every stream load
+	pub fn with_load_delay(mut self, delay: Duration) -> Self {
+		self.loader.load_delay = Some(delay);
+		self
+	}
+
+	pub fn build(self) -> MockStreamFileLoader {
+		self.loader
+	}
+}
+
+#[async_trait::async_trait]
+impl EventsLoader for MockStreamFileLoader {
+	async fn load_events(
+		&self,
+		_ceramic: &Ceramic,
+		stream_id: &StreamId,
+		tip: Option<Cid>,
+	) -> anyhow::Result<Vec<Event>> {
+		match self.track(self.uploaded(stream_id, tip)).await {
+			Some(events) => Ok(events),
+			None => anyhow::bail!("mock loader has no events of stream {}", stream_id),
+		}
+	}
+}
+
+/// given states are served ignoring `tip`
+#[async_trait::async_trait]
+impl StreamLoader for MockStreamFileLoader {
+	async fn load_stream_state(
+		&self,
+		_ceramic: &Ceramic,
+		stream_id: &StreamId,
+		tip: Option<Cid>,
+	) -> anyhow::Result<StreamState> {
+		if let Some(state) = self.streams.get(stream_id) {
+			return Ok(self.track(async { state.clone() }).await);
+		}
+		match self.track(self.uploaded(stream_id, tip)).await {
+			Some(events) => StreamState::make(stream_id.r#type.int_value(), events).await,
+			None => anyhow::bail!("stream {} not found", stream_id),
+		}
+	}
+
+	async fn load_stream_state_no_cache(
+		&self,
+		ceramic: &Ceramic,
+		stream_id: &StreamId,
+	) -> anyhow::Result<StreamState> {
+		self.load_stream_state(ceramic, stream_id, None).await
+	}
+}
+
+#[async_trait::async_trait]
+impl StreamsLoader for MockStreamFileLoader {
+	async fn load_stream_states(
+		&self,
+		_ceramic: &Ceramic,
+		account: Option<String>,
+		model_id: &StreamId,
+	) -> anyhow::Result<Vec<StreamState>> {
+		let mut states: Vec<_> = self.streams.values().cloned().collect();
+		let uploaded: Vec<_> = self.events.lock().await.clone().into_iter().collect();
+		for (stream_id, events) in uploaded {
+			if !self.streams.contains_key(&stream_id) {
+				states.push(StreamState::make(stream_id.r#type.int_value(), events).await?);
+			}
+		}
+		let mut result = vec![];
+		for state in states {
+			if state.model()?.as_ref() != Some(model_id) {
+				continue;
+			}
+			match &account {
+				Some(account) if !state.controllers().contains(account) => continue,
+				_ => result.push(state),
+			}
+		}
+		Ok(result)
+	}
+}
+
+#[async_trait::async_trait]
+impl EventsUploader for MockStreamFileLoader {
+	async fn upload_event(
+		&self,
+		_ceramic: &Ceramic,
+		stream_id: &StreamId,
+		event: Event,
+	) -> anyhow::Result<()> {
+		if self.fail_uploads {
+			anyhow::bail!(
+				"failed to upload event {} of stream {}",
+				event.cid,
+				stream_id
+			);
+		}
+		let mut streams = self.events.lock().await;
+		streams.entry(stream_id.clone()).or_default().push(event);
+		Ok(())
+	}
+}
+
+#[async_trait::async_trait]
+impl StreamFileLoader for MockStreamFileLoader {
+	async fn load_index_file_by_content_id(
+		&self,
+		_ceramic: &Ceramic,
+		_index_file_model_id: &StreamId,
+		content_id: &String,
+	) -> anyhow::Result<(StreamState, IndexFile)> {
+		if self.fail_index_lookups {
+			anyhow::bail!("failed to query index file of {}", content_id);
+		}
+		match self.index_files.get(content_id) {
+			Some(state) => Ok((
+				state.clone(),
+				serde_json::from_value(state.content.clone())?,
+			)),
+			None => anyhow::bail!("index file with content_id {} not found", content_id),
+		}
+	}
+
+	async fn load_index_file_batch(
+		&self,
+		_ceramic: &Ceramic,
+		account: Option<String>,
+		_index_file_model_id: &StreamId,
+		content_ids: &[String],
+	) -> anyhow::Result<HashMap<String, (StreamState, IndexFile)>> {
+		if self.fail_index_lookups {
+			anyhow::bail!("failed to query index files");
+		}
+		let mut result = HashMap::new();
+		for content_id in content_ids {
+			if let Some(state) = self.index_files.get(content_id) {
+				if let Some(account) = &account {
+					if !state.controllers().contains(account) {
+						continue;
+					}
+				}
+				if let Ok(index_file) = serde_json::from_value(state.content.clone()) {
+					result.insert(content_id.clone(), (state.clone(), index_file));
+				}
+			}
+		}
+		Ok(result)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use dataverse_core::stream::Stream;
+
+	use super::*;
+
+	#[tokio::test]
+	async fn test_client_without_backends() -> anyhow::Result<()> {
+		let client = test_client();
+		let ceramic = test_ceramic();
+		let genesis = example::genesis();
+		let stream_id = genesis.stream_id()?;
+		let r#type = genesis.r#type;
+		let commit: Event = genesis.genesis.try_into()?;
+		client
+			.operator
+			.upload_event(&ceramic, &stream_id, commit.clone())
+			.await?;
+
+		let state = client
+			.operator
+			.load_stream_state(&ceramic, &stream_id, None)
+			.await?;
+		let model_id = state.must_model()?;
+		let states = client
+			.operator
+			.load_stream_states(&ceramic, None, &model_id)
+			.await?;
+		assert_eq!(states.len(), 1);
+		let other_account = Some("did:pkh:eip155:1:0x0".to_string());
+		let states = client
+			.operator
+			.load_stream_states(&ceramic, other_account, &model_id)
+			.await?;
+		assert!(states.is_empty());
+
+		let dapp_id = uuid::Uuid::new_v4();
+		let stream = Stream::new(&dapp_id, r#type, &commit, Some(model_id))?;
+		client.stream_store.save_stream(&stream).await?;
+		let loaded = client.stream_store.load_stream(&stream_id).await?;
+		assert_eq!(loaded.map(|stream| stream.tip), Some(commit.cid));
+		Ok(())
+	}
+}
@@ -0,0 +1,3 @@
+-- Your SQL goes here
+alter table streams
+    add column pinned boolean not null default false;
@@ -0,0 +1,3 @@
+-- Your SQL goes here
+alter table streams
+    add column updated_at timestamptz;
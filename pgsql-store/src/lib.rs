@@ -9,12 +9,15 @@ use int_enum::IntEnum;
 use std::collections::HashMap;
 use std::sync::Arc;
 
-use ceramic_core::{Cid, StreamId};
+use ceramic_core::{Cid, StreamId, StreamIdType};
 use dataverse_ceramic::{kubo, Ceramic, Event, EventsUploader, StreamState};
 use dataverse_ceramic::{EventsLoader, StreamLoader, StreamOperator, StreamsLoader};
-use dataverse_core::stream::{Stream, StreamStore};
+use dataverse_core::stream::{MigrationReport, Stream, StreamStore};
 use diesel::prelude::*;
 use diesel::r2d2::{ConnectionManager, Pool};
+use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
+
+pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
 
 #[derive(Clone)]
 pub struct Client {
@@ -23,14 +26,17 @@ pub struct Client {
 }
 
 impl Client {
-	pub fn new(operator: Arc<dyn StreamOperator>, dsn: &str) -> anyhow::Result<Self> {
+	pub async fn new(operator: Arc<dyn StreamOperator>, dsn: &str) -> anyhow::Result<Self> {
 		let manager = ConnectionManager::<PgConnection>::new(dsn);
 
 		let pool = match Pool::builder().test_on_check_out(true).build(manager) {
 			Ok(it) => it,
 			Err(err) => anyhow::bail!("failed build connection pool: {}", err),
 		};
-		Ok(Self { operator, pool })
+		let client = Self { operator, pool };
+		let report = client.migrate().await?;
+		tracing::info!(?report, "database migrated");
+		Ok(client)
 	}
 
 	async fn load_events_from_db(
@@ -98,6 +104,21 @@ impl Client {
 	}
 }
 
+fn run_migrations(pool: &Pool<ConnectionManager<PgConnection>>) -> anyhow::Result<MigrationReport> {
+	let conn = &mut pool.get()?;
+	let skipped = conn
+		.applied_migrations()
+		.map_err(|err| anyhow::anyhow!("failed to list applied migrations: {}", err))?
+		.len();
+	let applied = conn
+		.run_pending_migrations(MIGRATIONS)
+		.map_err(|err| anyhow::anyhow!("failed to run migrations: {}", err))?
+		.into_iter()
+		.map(|version| version.to_string())
+		.collect();
+	Ok(MigrationReport { applied, skipped })
+}
+
 #[async_trait::async_trait]
 impl StreamStore for Client {
 	async fn list_all_streams(&self) -> anyhow::Result<Vec<Stream>> {
@@ -138,6 +159,57 @@ impl StreamStore for Client {
 		}
 		Ok(None)
 	}
+
+	async fn migrate(&self) -> anyhow::Result<MigrationReport> {
+		// diesel migrations are blocking, keep them off the async executor
+		let pool = self.pool.clone();
+		tokio::task::spawn_blocking(move || run_migrations(&pool)).await?
+	}
+
+	/// events saved through this store are indexed by cid in the events table,
+	/// their stream is found by its genesis
+	async fn find_stream_by_event(&self, cid: &Cid) -> anyhow::Result<Option<StreamId>> {
+		let conn = &mut self.pool.get()?;
+		let genesis: Option<String> = schema::events::table
+			.filter(schema::events::cid.eq(cid.to_string()))
+			.select(schema::events::genesis)
+			.first(conn)
+			.optional()?;
+		let genesis = match genesis {
+			Some(genesis) => Cid::try_from(genesis)?,
+			None => *cid,
+		};
+		// streams are keyed by stream id, which also encodes the stream type
+		let stream_ids: Vec<String> = [StreamIdType::ModelInstanceDocument, StreamIdType::Model]
+			.into_iter()
+			.map(|r#type| {
+				StreamId {
+					r#type,
+					cid: genesis,
+				}
+				.to_string()
+			})
+			.collect();
+		let stream: Option<models::Stream> = schema::streams::table
+			.filter(
+				schema::streams::stream_id
+					.eq_any(stream_ids)
+					.or(schema::streams::tip.eq(cid.to_string())),
+			)
+			.first(conn)
+			.optional()?;
+		stream.map(|stream| stream.stream_id()).transpose()
+	}
+
+	async fn load_genesis_event(&self, stream: &Stream) -> anyhow::Result<Option<Event>> {
+		let conn = &mut self.pool.get()?;
+		let event: Option<models::Event> = schema::events::table
+			.filter(schema::events::cid.eq(stream.genesis.to_string()))
+			.select(models::Event::as_select())
+			.first(conn)
+			.optional()?;
+		event.map(|event| event.try_into()).transpose()
+	}
 }
 
 #[async_trait::async_trait]
@@ -313,3 +385,56 @@ impl EventsUploader for Client {
 		self.operator.upload_event(ceramic, stream_id, event).await
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use dataverse_ceramic::http;
+
+	/// migration tests drop and recreate tables, so they only run against a dedicated database
+	fn test_dsn() -> Option<String> {
+		std::env::var("PGSQL_STORE_TEST_DSN").ok()
+	}
+
+	#[tokio::test]
+	async fn migrate_v1_streams_to_v2() -> anyhow::Result<()> {
+		let Some(dsn) = test_dsn() else {
+			return Ok(());
+		};
+		let client = Client::new(Arc::new(http::Client::new()), &dsn).await?;
+
+		// rewind to the V1 schema, which has no streams.pinned nor streams.updated_at column
+		{
+			let conn = &mut client.pool.get()?;
+			conn.revert_all_migrations(MIGRATIONS)
+				.map_err(|err| anyhow::anyhow!("{}", err))?;
+			for _ in 0..2 {
+				conn.run_next_migration(MIGRATIONS)
+					.map_err(|err| anyhow::anyhow!("{}", err))?;
+			}
+			diesel::sql_query(
+				"insert into streams (stream_id, dapp_id, tip, content) \
+				 values ('v1-stream', '00000000-0000-0000-0000-000000000000', 'v1-tip', '{}')",
+			)
+			.execute(conn)?;
+		}
+
+		let report = client.migrate().await?;
+		assert_eq!(report.skipped, 2);
+		assert_eq!(report.applied.len(), 2);
+
+		let conn = &mut client.pool.get()?;
+		let (pinned, updated_at): (bool, Option<chrono::DateTime<chrono::Utc>>) =
+			schema::streams::table
+				.filter(schema::streams::stream_id.eq("v1-stream"))
+				.select((schema::streams::pinned, schema::streams::updated_at))
+				.first(conn)?;
+		assert!(!pinned);
+		assert_eq!(updated_at, None);
+
+		let report = client.migrate().await?;
+		assert!(report.applied.is_empty());
+		assert_eq!(report.skipped, 4);
+		Ok(())
+	}
+}
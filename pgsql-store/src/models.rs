@@ -1,6 +1,7 @@
 use std::str::FromStr;
 
 use ceramic_core::{Cid, StreamId};
+use chrono::{DateTime, Utc};
 use dataverse_ceramic::{
 	event::{AnchorValue, SignedValue, ToCid},
 	EventValue,
@@ -76,6 +77,8 @@ pub struct Stream {
 	pub account: Option<String>,
 	pub model_id: Option<String>,
 	pub content: serde_json::Value,
+	pub pinned: bool,
+	pub updated_at: Option<DateTime<Utc>>,
 }
 
 impl Stream {
@@ -95,6 +98,8 @@ impl TryFrom<&dataverse_core::stream::Stream> for Stream {
 			account: value.account.clone(),
 			model_id: value.model.clone().map(|x| x.to_string()),
 			content: value.content.clone(),
+			pinned: value.pinned,
+			updated_at: value.updated_at,
 		})
 	}
 }
@@ -116,6 +121,8 @@ impl TryInto<dataverse_core::stream::Stream> for Stream {
 			account: self.account,
 			model,
 			content: self.content,
+			pinned: self.pinned,
+			updated_at: self.updated_at,
 		})
 	}
 }
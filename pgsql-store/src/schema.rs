@@ -49,6 +49,8 @@ diesel::table! {
         #[max_length = 70]
         model_id -> Nullable<Varchar>,
         content -> Jsonb,
+        pinned -> Bool,
+        updated_at -> Nullable<Timestamptz>,
     }
 }
 